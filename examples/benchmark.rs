@@ -0,0 +1,218 @@
+//! Minimal redis-benchmark, which measures the throughput and latency of the server
+//! end-to-end.
+//!
+//! ```sh
+//! cargo run --release --example benchmark -- -c 50 -n 100000 -P 16 -t ping,ping_bulk
+//! ```
+//!
+//! It is a separate example rather than a second binary, since `spawn_redis_server.sh`
+//! runs the crate with `cargo run`, which requires a single binary.
+
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use redis_starter_rust::testing::TestClient;
+use redis_starter_rust::RespValue;
+use tokio::task::JoinSet;
+
+const USAGE: &str = "Usage: benchmark [-h <host>] [-p <port>] [-c <clients>] [-n <requests>] \
+    [-P <pipeline>] [-t <tests>] [-d <size>]
+
+ -h <host>      Server hostname (default 127.0.0.1)
+ -p <port>      Server port (default 6379)
+ -c <clients>   Number of parallel connections (default 50)
+ -n <requests>  Total number of requests of each test (default 100000)
+ -P <pipeline>  Requests sent at once by each connection (default 1)
+ -t <tests>     Comma separated tests to run, of ping, ping_bulk and del (default all)
+ -d <size>      Size in bytes of the PING_BULK payload (default 3)";
+
+/// Requests of a test, which are sent in a loop.
+#[derive(Debug, Clone, Copy)]
+enum Test {
+    Ping,
+    /// PING with a payload, which is echoed as bulk string.
+    PingBulk,
+    Del,
+}
+
+impl Test {
+    fn name(self) -> &'static str {
+        match self {
+            Test::Ping => "PING",
+            Test::PingBulk => "PING_BULK",
+            Test::Del => "DEL",
+        }
+    }
+    /// Serialized request, where `n` makes keys differ between requests.
+    fn request(self, n: usize, payload: &str) -> String {
+        let args: Vec<String> = match self {
+            Test::Ping => vec!["PING".into()],
+            Test::PingBulk => vec!["PING".into(), payload.into()],
+            Test::Del => vec!["DEL".into(), format!("key:{n}")],
+        };
+        let args = args
+            .into_iter()
+            .map(|arg| RespValue::BulkString(arg.into()))
+            .collect();
+        RespValue::Array(args).to_string()
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    addr: SocketAddr,
+    clients: usize,
+    requests: usize,
+    pipeline: usize,
+    tests: Vec<Test>,
+    payload: String,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> anyhow::Result<Options> {
+    let (mut host, mut port) = (String::from("127.0.0.1"), 6379);
+    let (mut clients, mut requests, mut pipeline, mut size) = (50, 100_000, 1, 3);
+    let mut tests = vec![Test::Ping, Test::PingBulk, Test::Del];
+
+    let mut args = args.skip(1);
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("Missing value of {flag}\n\n{USAGE}"))?;
+        let number = || {
+            value
+                .parse::<usize>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| anyhow!("Invalid value of {flag}: {value:?}"))
+        };
+        match flag.as_str() {
+            "-h" => host = value.clone(),
+            "-p" => port = value.parse()?,
+            "-c" => clients = number()?,
+            "-n" => requests = number()?,
+            "-P" => pipeline = number()?,
+            "-d" => size = number()?,
+            "-t" => {
+                tests = value
+                    .split(',')
+                    .map(|test| match test.to_ascii_lowercase().as_str() {
+                        "ping" => Ok(Test::Ping),
+                        "ping_bulk" => Ok(Test::PingBulk),
+                        "del" => Ok(Test::Del),
+                        _ => Err(anyhow!("Unknown test {test:?}")),
+                    })
+                    .collect::<anyhow::Result<_>>()?
+            }
+            _ => return Err(anyhow!("Unknown option {flag}\n\n{USAGE}")),
+        }
+    }
+
+    let addr = (host.as_str(), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Failed to resolve {host}"))?;
+    Ok(Options {
+        addr,
+        clients,
+        requests,
+        pipeline,
+        tests,
+        payload: "x".repeat(size),
+    })
+}
+
+/// Sends `requests` requests in batches of `pipeline`, returning the latency of each.
+///
+/// Like in redis-benchmark, every request of a batch is attributed the time until
+/// the whole batch was answered.
+async fn run_client(
+    addr: SocketAddr,
+    test: Test,
+    requests: usize,
+    pipeline: usize,
+    payload: String,
+) -> anyhow::Result<Vec<Duration>> {
+    let mut client = TestClient::connect(addr).await?;
+    let mut latencies = Vec::with_capacity(requests);
+    let mut sent = 0;
+    while sent < requests {
+        let batch = pipeline.min(requests - sent);
+        let frames: String = (sent..sent + batch)
+            .map(|n| test.request(n, &payload))
+            .collect();
+        let start = Instant::now();
+        client.send_raw(frames.as_bytes()).await?;
+        for _ in 0..batch {
+            if let RespValue::SimpleError(e) = client.read_reply().await? {
+                return Err(anyhow!("{} failed: {e}", test.name()));
+            }
+        }
+        latencies.resize(latencies.len() + batch, start.elapsed());
+        sent += batch;
+    }
+    Ok(latencies)
+}
+
+async fn run_test(options: &Options, test: Test) -> anyhow::Result<()> {
+    let mut clients = JoinSet::new();
+    let start = Instant::now();
+    for i in 0..options.clients {
+        // NOTE: The requests are spread evenly, the first clients send the remainder.
+        let requests = options.requests / options.clients
+            + usize::from(i < options.requests % options.clients);
+        if requests == 0 {
+            continue;
+        }
+        clients.spawn(run_client(
+            options.addr,
+            test,
+            requests,
+            options.pipeline,
+            options.payload.clone(),
+        ));
+    }
+    let mut latencies = Vec::with_capacity(options.requests);
+    while let Some(result) = clients.join_next().await {
+        latencies.extend(result??);
+    }
+    let elapsed = start.elapsed();
+    latencies.sort_unstable();
+
+    let percentile = |p: f64| {
+        let index = ((latencies.len() as f64 * p / 100.0).ceil() as usize).max(1) - 1;
+        latencies[index.min(latencies.len() - 1)].as_secs_f64() * 1000.0
+    };
+    println!("====== {} ======", test.name());
+    println!(
+        "  {} requests completed in {:.2} seconds",
+        latencies.len(),
+        elapsed.as_secs_f64()
+    );
+    println!(
+        "  {} parallel clients, pipeline of {}",
+        options.clients, options.pipeline
+    );
+    println!(
+        "  throughput: {:.2} requests per second",
+        latencies.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "  latency (msec): p50={:.3} p95={:.3} p99={:.3} max={:.3}",
+        percentile(50.0),
+        percentile(95.0),
+        percentile(99.0),
+        percentile(100.0)
+    );
+    println!();
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let options = parse_args(std::env::args())?;
+    for &test in &options.tests {
+        run_test(&options, test).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! Prints the keys of an RDB file with their type, TTL and size, without starting a
+//! server.
+//!
+//! ```sh
+//! cargo run --example rdb-dump -- dump.rdb --json
+//! ```
+//!
+//! Like [benchmark](./benchmark.rs) it is an example rather than a second binary, so
+//! that `cargo run` keeps starting the server.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use redis_starter_rust::inspect_rdb;
+
+const USAGE: &str = "Usage: rdb-dump <file> [--json]";
+
+fn main() -> anyhow::Result<()> {
+    let mut path = None;
+    let mut json = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if arg.starts_with("--") => return Err(anyhow!("Unknown option {arg}\n\n{USAGE}")),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(anyhow!("Unexpected argument {arg:?}\n\n{USAGE}")),
+        }
+    }
+    let path = path.ok_or_else(|| anyhow!("Missing file\n\n{USAGE}"))?;
+
+    let bytes = std::fs::read(&path).map_err(|e| anyhow!("Failed to read {path}: {e}"))?;
+    let summary = inspect_rdb(&bytes).map_err(|e| anyhow!("Failed to parse {path}: {e}"))?;
+    if json {
+        println!("{}", summary.to_json());
+        return Ok(());
+    }
+
+    println!("# version {}", summary.version);
+    for (name, value) in &summary.aux_fields {
+        println!("# {name}={value}");
+    }
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+    for key in &summary.keys {
+        // NOTE: Same as PTTL, which is -1 for keys without an expiry.
+        let ttl = key
+            .expires_at
+            .map_or(-1, |ms| ms.saturating_sub(now_ms) as i64);
+        println!(
+            "db={} key={:?} type={} encoding={} ttl={ttl} len={} memory={}",
+            key.db, key.name, key.value_type, key.encoding, key.len, key.memory
+        );
+    }
+    println!("# {} keys", summary.keys.len());
+    Ok(())
+}
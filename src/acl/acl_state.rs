@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::acl::AclUser;
+use crate::command::{Command, RedisError};
+
+/// User every connection starts out as, which can't be deleted.
+pub const DEFAULT_USER: &str = "default";
+
+/// Users of the ACL, see [`AclUser`].
+#[derive(Debug)]
+pub struct AclState {
+    users: Mutex<BTreeMap<String, AclUser>>,
+}
+
+impl AclState {
+    /// Creates the ACL with the 'default' user, which may run every command and
+    /// accepts `requirepass`, or any password if it is empty.
+    pub fn new(requirepass: &str) -> Self {
+        let mut default = AclUser::new(DEFAULT_USER);
+        for rule in ["on", "allkeys", "allchannels", "allcommands"] {
+            default.apply_rule(rule).unwrap();
+        }
+        let acl = Self {
+            users: Mutex::new(BTreeMap::from([(String::from(DEFAULT_USER), default)])),
+        };
+        acl.set_default_password(requirepass);
+        acl
+    }
+    pub fn lock_users(&self) -> MutexGuard<'_, BTreeMap<String, AclUser>> {
+        self.users.lock().unwrap()
+    }
+    /// Applies the `rules` to the user, creating it if it doesn't exist. Either all
+    /// rules are applied or, if any is invalid, none.
+    pub fn set_user(&self, name: &str, rules: &[String]) -> Result<(), RedisError> {
+        let mut users = self.lock_users();
+        let mut user = users
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| AclUser::new(name));
+        for rule in rules {
+            user.apply_rule(rule).map_err(|e| {
+                RedisError::Err(format!("Error in ACL SETUSER modifier '{rule}': {e}"))
+            })?;
+        }
+        users.insert(name.to_string(), user);
+        Ok(())
+    }
+    /// Deletes the users, returning how many of them existed.
+    pub fn delete_users(&self, names: &[String]) -> Result<usize, RedisError> {
+        if names.iter().any(|name| name == DEFAULT_USER) {
+            return Err(RedisError::err("The 'default' user cannot be removed"));
+        }
+        let mut users = self.lock_users();
+        Ok(names
+            .iter()
+            .filter(|name| users.remove(name.as_str()).is_some())
+            .count())
+    }
+    /// Makes the 'default' user accept only `requirepass`, or any password if it is
+    /// empty, as CONFIG SET requirepass does.
+    pub fn set_default_password(&self, requirepass: &str) {
+        let mut users = self.lock_users();
+        let Some(default) = users.get_mut(DEFAULT_USER) else {
+            return;
+        };
+        if requirepass.is_empty() {
+            default.apply_rule("nopass").unwrap();
+        } else {
+            default.apply_rule("resetpass").unwrap();
+            default.apply_rule(&format!(">{requirepass}")).unwrap();
+        }
+    }
+    /// Whether the user exists, is enabled and accepts the password.
+    pub fn authenticate(&self, user: &str, password: &str) -> bool {
+        self.lock_users()
+            .get(user)
+            .is_some_and(|user| user.enabled && user.check_password(password))
+    }
+    /// Whether a connection, authenticated as `user` or not at all, has to AUTH before
+    /// running commands. Without AUTH connections are the 'default' user if it doesn't
+    /// require a password, and are logged out once their user is disabled or deleted.
+    pub fn requires_auth(&self, user: Option<&str>) -> bool {
+        let users = self.lock_users();
+        match user {
+            Some(user) => !users.get(user).is_some_and(|user| user.enabled),
+            None => !users
+                .get(DEFAULT_USER)
+                .is_some_and(|user| user.enabled && user.nopass),
+        }
+    }
+    /// Checks that the user may run the command and access its keys and channels,
+    /// returning the NOPERM error otherwise.
+    pub fn check(&self, user: &str, command: &Command) -> Result<(), RedisError> {
+        let users = self.lock_users();
+        let (name, subcommand) = command.name();
+        let categories = command.spec().categories;
+        let Some(acl_user) = users
+            .get(user)
+            .filter(|acl_user| acl_user.can_run(name, subcommand, categories))
+        else {
+            let name = match subcommand {
+                Some(subcommand) => format!("{name}|{subcommand}"),
+                None => name.to_string(),
+            };
+            return Err(RedisError::NoPerm(format!(
+                "User {user} has no permissions to run the '{name}' command"
+            )));
+        };
+
+        if !command
+            .keys()
+            .iter()
+            .all(|key| acl_user.can_access_key(key))
+        {
+            return Err(RedisError::NoPerm(String::from(
+                "No permissions to access a key",
+            )));
+        }
+        if !command
+            .channels()
+            .iter()
+            .all(|channel| acl_user.can_access_channel(channel))
+        {
+            return Err(RedisError::NoPerm(String::from(
+                "No permissions to access a channel",
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::command::find_command;
+use crate::util::{glob_match, sha256, to_hex};
+
+/// ACL categories in the order ACL CAT lists them.
+pub const ACL_CATEGORIES: &[&str] = &[
+    "keyspace",
+    "read",
+    "write",
+    "set",
+    "sortedset",
+    "list",
+    "hash",
+    "string",
+    "bitmap",
+    "hyperloglog",
+    "geo",
+    "stream",
+    "pubsub",
+    "admin",
+    "fast",
+    "slow",
+    "blocking",
+    "dangerous",
+    "connection",
+    "transaction",
+    "scripting",
+];
+
+/// Commands a `+` or `-` rule of ACL SETUSER applies to.
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum CommandRule {
+    /// Commands in the category, or every command for 'all'.
+    Category(&'static str),
+    Command(&'static str),
+    Subcommand(&'static str, &'static str),
+}
+
+impl CommandRule {
+    fn parse(s: &str) -> Option<Self> {
+        if let Some(category) = s.strip_prefix('@') {
+            if category.eq_ignore_ascii_case("all") {
+                return Some(CommandRule::Category("all"));
+            }
+            let category = ACL_CATEGORIES
+                .iter()
+                .find(|name| name.eq_ignore_ascii_case(category))?;
+            return Some(CommandRule::Category(category));
+        }
+
+        match s.split_once('|') {
+            Some((name, subcommand)) => {
+                let spec = find_command(name)?;
+                let subcommand = spec.subcommand(subcommand)?;
+                Some(CommandRule::Subcommand(spec.name, subcommand.name))
+            }
+            None => find_command(s).map(|spec| CommandRule::Command(spec.name)),
+        }
+    }
+    fn matches(&self, name: &str, subcommand: Option<&str>, categories: &[&str]) -> bool {
+        match *self {
+            CommandRule::Category(category) => category == "all" || categories.contains(&category),
+            CommandRule::Command(command) => command == name,
+            CommandRule::Subcommand(command, sub) => command == name && subcommand == Some(sub),
+        }
+    }
+}
+
+impl fmt::Display for CommandRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandRule::Category(category) => write!(f, "@{category}"),
+            CommandRule::Command(command) => write!(f, "{command}"),
+            CommandRule::Subcommand(command, sub) => write!(f, "{command}|{sub}"),
+        }
+    }
+}
+
+/// User of the ACL, which connections authenticate as with AUTH or HELLO.
+#[derive(Debug, Clone)]
+pub struct AclUser {
+    pub name: String,
+    pub enabled: bool,
+    /// Whether any password is accepted.
+    pub nopass: bool,
+    /// Hex encoded SHA-256 hashes of the accepted passwords.
+    pub passwords: BTreeSet<String>,
+    /// Rules allowing or denying commands in the order they were given, the last one
+    /// matching a command decides whether it may run.
+    commands: Vec<(bool, CommandRule)>,
+    /// Glob patterns of the keys the user may access.
+    pub keys: Vec<String>,
+    /// Glob patterns of the pub/sub channels the user may access.
+    pub channels: Vec<String>,
+}
+
+impl AclUser {
+    /// Creates a disabled user without passwords, which may not run any command.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            enabled: false,
+            nopass: false,
+            passwords: BTreeSet::new(),
+            commands: Vec::new(),
+            keys: Vec::new(),
+            channels: Vec::new(),
+        }
+    }
+    /// Applies a single rule of ACL SETUSER, e.g. 'on', '>password', '~cache:*' or
+    /// '+@read', returning why it is invalid otherwise.
+    pub fn apply_rule(&mut self, rule: &str) -> Result<(), String> {
+        if let Some(password) = rule.strip_prefix('>') {
+            self.passwords.insert(hash_password(password));
+            self.nopass = false;
+        } else if let Some(password) = rule.strip_prefix('<') {
+            self.remove_password(&hash_password(password))?;
+        } else if let Some(hash) = rule.strip_prefix('#') {
+            self.passwords.insert(parse_hash(hash)?);
+            self.nopass = false;
+        } else if let Some(hash) = rule.strip_prefix('!') {
+            self.remove_password(&parse_hash(hash)?)?;
+        } else if let Some(pattern) = rule.strip_prefix('~') {
+            add_pattern(&mut self.keys, pattern, "allkeys")?;
+        } else if let Some(pattern) = rule.strip_prefix('&') {
+            add_pattern(&mut self.channels, pattern, "allchannels")?;
+        } else if let Some(command) = rule.strip_prefix('+') {
+            self.add_command_rule(true, command)?;
+        } else if let Some(command) = rule.strip_prefix('-') {
+            self.add_command_rule(false, command)?;
+        } else {
+            match rule.to_ascii_lowercase().as_str() {
+                "on" => self.enabled = true,
+                "off" => self.enabled = false,
+                "nopass" => {
+                    self.nopass = true;
+                    self.passwords.clear();
+                }
+                "resetpass" => {
+                    self.nopass = false;
+                    self.passwords.clear();
+                }
+                "allkeys" => self.keys = vec![String::from("*")],
+                "resetkeys" => self.keys.clear(),
+                "allchannels" => self.channels = vec![String::from("*")],
+                "resetchannels" => self.channels.clear(),
+                "allcommands" => self.add_command_rule(true, "@all")?,
+                "nocommands" => self.add_command_rule(false, "@all")?,
+                "reset" => *self = AclUser::new(&self.name),
+                _ => return Err(String::from("Syntax error")),
+            }
+        }
+        Ok(())
+    }
+    fn add_command_rule(&mut self, allow: bool, command: &str) -> Result<(), String> {
+        let rule = CommandRule::parse(command)
+            .ok_or_else(|| String::from("Unknown command or category name in ACL"))?;
+        if rule == CommandRule::Category("all") {
+            // NOTE: Every earlier rule is overridden, and denying everything is the same
+            //       as having no rules at all.
+            self.commands.clear();
+            if allow {
+                self.commands.push((allow, rule));
+            }
+            return Ok(());
+        }
+        // NOTE: An earlier identical rule can never decide anymore, since this one matches
+        //       the same commands and comes later.
+        self.commands.retain(|(_, existing)| *existing != rule);
+        self.commands.push((allow, rule));
+        Ok(())
+    }
+    fn remove_password(&mut self, hash: &str) -> Result<(), String> {
+        if !self.passwords.remove(hash) {
+            return Err(String::from(
+                "The password you are trying to remove from the user does not exist",
+            ));
+        }
+        Ok(())
+    }
+    pub fn check_password(&self, password: &str) -> bool {
+        self.nopass || self.passwords.contains(&hash_password(password))
+    }
+    /// Whether the user may run the command `name`, optionally with the subcommand,
+    /// which is in the ACL `categories`.
+    pub fn can_run(&self, name: &str, subcommand: Option<&str>, categories: &[&str]) -> bool {
+        self.commands
+            .iter()
+            .rev()
+            .find(|(_, rule)| rule.matches(name, subcommand, categories))
+            .is_some_and(|&(allow, _)| allow)
+    }
+    pub fn can_access_key(&self, key: &str) -> bool {
+        self.keys
+            .iter()
+            .any(|pattern| glob_match(pattern.as_bytes(), key.as_bytes(), false))
+    }
+    pub fn can_access_channel(&self, channel: &str) -> bool {
+        self.channels
+            .iter()
+            .any(|pattern| glob_match(pattern.as_bytes(), channel.as_bytes(), false))
+    }
+    pub fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec![if self.enabled { "on" } else { "off" }];
+        if self.nopass {
+            flags.push("nopass");
+        }
+        flags
+    }
+    /// Command rules recreating the ones of the user, e.g. '+@all -debug'.
+    pub fn describe_commands(&self) -> String {
+        let mut rules = Vec::new();
+        if self.commands.first() != Some(&(true, CommandRule::Category("all"))) {
+            rules.push(String::from("-@all"));
+        }
+        for (allow, rule) in &self.commands {
+            rules.push(format!("{}{rule}", if *allow { '+' } else { '-' }));
+        }
+        rules.join(" ")
+    }
+    pub fn describe_keys(&self) -> String {
+        let patterns: Vec<_> = self.keys.iter().map(|key| format!("~{key}")).collect();
+        patterns.join(" ")
+    }
+    pub fn describe_channels(&self) -> String {
+        let patterns: Vec<_> = self.channels.iter().map(|c| format!("&{c}")).collect();
+        patterns.join(" ")
+    }
+    /// Rules recreating the user, as listed by ACL LIST.
+    pub fn describe(&self) -> String {
+        let mut rules = vec![String::from("user"), self.name.clone()];
+        rules.extend(self.flags().into_iter().map(String::from));
+        rules.extend(self.passwords.iter().map(|hash| format!("#{hash}")));
+        if !self.keys.is_empty() {
+            rules.push(self.describe_keys());
+        }
+        if self.channels.is_empty() {
+            rules.push(String::from("resetchannels"));
+        } else {
+            rules.push(self.describe_channels());
+        }
+        rules.push(self.describe_commands());
+        rules.join(" ")
+    }
+}
+
+fn hash_password(password: &str) -> String {
+    to_hex(&sha256(password.as_bytes()))
+}
+
+fn parse_hash(hash: &str) -> Result<String, String> {
+    if hash.len() != 64 || !hash.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(String::from(
+            "The password hash must be exactly 64 characters and contain only lowercase \
+             hexadecimal characters",
+        ));
+    }
+    Ok(hash.to_ascii_lowercase())
+}
+
+/// Adds a key or channel pattern, unless the patterns already match everything.
+fn add_pattern(patterns: &mut Vec<String>, pattern: &str, all_flag: &str) -> Result<(), String> {
+    if patterns.iter().any(|existing| existing == "*") {
+        if pattern == "*" {
+            return Ok(());
+        }
+        return Err(format!(
+            "Adding a pattern after the * pattern (or the '{all_flag}' flag) is not valid and \
+             does not have any effect. Try 'reset{}' to start with an empty list of patterns",
+            all_flag.trim_start_matches("all")
+        ));
+    }
+    if pattern == "*" {
+        patterns.clear();
+    }
+    if !patterns.iter().any(|existing| existing == pattern) {
+        patterns.push(pattern.to_string());
+    }
+    Ok(())
+}
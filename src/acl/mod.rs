@@ -0,0 +1,5 @@
+mod acl_state;
+mod acl_user;
+
+pub use acl_state::{AclState, DEFAULT_USER};
+pub use acl_user::{AclUser, ACL_CATEGORIES};
@@ -0,0 +1,102 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use crate::command::dispatch;
+use crate::rdb::RdbReader;
+use crate::resp::{is_incomplete, parse_resp_value, RespValue};
+use crate::server::{ClientKind, ConnectionContext, ServerState};
+
+/// Loads the base file and replays every incremental file listed in the manifest.
+///
+/// A base file starting with an RDB header replaces the Database, everything else is
+/// replayed through the dispatcher as a fake client. A command failing to replay aborts
+/// loading.
+///
+/// The last file may end in the middle of a command, e.g. because the server crashed
+/// mid-write, in which case it is truncated to the last complete command if
+/// `aof-load-truncated` is enabled and rejected otherwise.
+///
+/// Returns the number of replayed commands.
+pub fn load_aof(state: &Arc<ServerState>) -> anyhow::Result<usize> {
+    let Some(aof) = &state.aof else {
+        return Ok(0);
+    };
+    let files = aof.files();
+
+    let mut ctx = ConnectionContext::new(ClientKind::AofLoader);
+    let mut num_commands = 0;
+
+    for (index, (path, _)) in files.iter().enumerate() {
+        let bytes = std::fs::read(path)?;
+
+        if bytes.starts_with(b"REDIS") {
+            let mut rdb = RdbReader::new(&bytes).read()?;
+            // NOTE: Only a single logical database is supported, so everything but 'db0' is dropped.
+            *state.db.lock().unwrap() = rdb.databases.remove(&0).unwrap_or_default();
+            continue;
+        }
+
+        let is_last = index + 1 == files.len();
+        num_commands += replay_commands(state, &mut ctx, path, &bytes, is_last)?;
+    }
+
+    Ok(num_commands)
+}
+
+fn replay_commands(
+    state: &Arc<ServerState>,
+    ctx: &mut ConnectionContext,
+    path: &Path,
+    bytes: &[u8],
+    is_last: bool,
+) -> anyhow::Result<usize> {
+    let mut input = bytes;
+    let mut num_commands = 0;
+
+    while !input.is_empty() {
+        let frame = input;
+        let value;
+        (input, value) = match parse_resp_value(input) {
+            Ok(x) => x,
+            Err(e) if is_incomplete(&e) => break,
+            Err(e) => {
+                let offset = bytes.len() - frame.len();
+                return Err(anyhow!(
+                    "Bad file format reading the AOF file {path:?} at offset {offset}: {e}"
+                ));
+            }
+        };
+        let frame = &frame[..frame.len() - input.len()];
+
+        // NOTE: Skipping a command that fails would silently drop the data it wrote.
+        if let RespValue::SimpleError(e) = dispatch(state, ctx, value, frame) {
+            let offset = bytes.len() - frame.len() - input.len();
+            return Err(anyhow!(
+                "Error replaying the command at offset {offset} of the AOF file {path:?}: {e}"
+            ));
+        }
+        num_commands += 1;
+    }
+
+    if !input.is_empty() {
+        let valid_len = bytes.len() - input.len();
+        if !is_last || !state.config().aof_load_truncated {
+            return Err(anyhow!(
+                "Unexpected end of file reading the AOF file {path:?} at offset {valid_len}"
+            ));
+        }
+        eprintln!(
+            "AOF was truncated, discarding the last {} bytes",
+            input.len()
+        );
+        // NOTE: Cut off through the writer, which already opened the file and has to
+        //       know its actual size to roll back failed appends.
+        if let Some(aof) = &state.aof {
+            aof.lock().truncate(valid_len as u64)?;
+        }
+    }
+
+    Ok(num_commands)
+}
@@ -0,0 +1,181 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AofManifestError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid manifest line {0}: {1:?}")]
+    InvalidLine(usize, String),
+    #[error("manifest contains more than one base file")]
+    MultipleBaseFiles,
+}
+
+/// Role of a file listed in the manifest.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum AofFileType {
+    /// Snapshot of the dataset, either an RDB file or rewritten commands.
+    Base,
+    /// Former base or incremental file which is about to be deleted.
+    History,
+    /// Commands appended after the base was written.
+    Incr,
+}
+
+impl From<AofFileType> for char {
+    fn from(value: AofFileType) -> Self {
+        match value {
+            AofFileType::Base => 'b',
+            AofFileType::History => 'h',
+            AofFileType::Incr => 'i',
+        }
+    }
+}
+
+impl TryFrom<char> for AofFileType {
+    type Error = ();
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            'b' => Ok(AofFileType::Base),
+            'h' => Ok(AofFileType::History),
+            'i' => Ok(AofFileType::Incr),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct AofInfo {
+    pub file_name: String,
+    pub seq: u64,
+    pub file_type: AofFileType,
+}
+
+/// List of files which make up the AOF, same format as used by Redis 7.
+///
+/// The dataset is restored by loading the base file followed by every incremental
+/// file in order. Each line describes one file:
+///
+/// ```text
+/// file appendonly.aof.1.base.rdb seq 1 type b
+/// file appendonly.aof.1.incr.aof seq 1 type i
+/// ```
+#[derive(PartialEq, Eq, Debug, Default, Clone)]
+pub struct AofManifest {
+    pub base: Option<AofInfo>,
+    pub incrs: Vec<AofInfo>,
+}
+
+impl AofManifest {
+    pub fn parse(input: &str) -> Result<Self, AofManifestError> {
+        let mut manifest = AofManifest::default();
+
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid_line = || AofManifestError::InvalidLine(index + 1, line.to_string());
+
+            // NOTE: Redis quotes file names containing spaces, which are not supported.
+            let mut file_name = None;
+            let mut seq = None;
+            let mut file_type = None;
+            let mut tokens = line.split_whitespace();
+            while let Some(key) = tokens.next() {
+                let value = tokens.next().ok_or_else(invalid_line)?;
+                match key {
+                    "file" => file_name = Some(value.to_string()),
+                    "seq" => seq = Some(value.parse().map_err(|_| invalid_line())?),
+                    "type" => {
+                        let mut chars = value.chars();
+                        file_type = match (chars.next(), chars.next()) {
+                            (Some(c), None) => AofFileType::try_from(c).ok(),
+                            _ => None,
+                        };
+                    }
+                    // NOTE: Unknown keys are skipped for forward compatibility.
+                    _ => {}
+                }
+            }
+
+            let (Some(file_name), Some(seq), Some(file_type)) = (file_name, seq, file_type) else {
+                return Err(invalid_line());
+            };
+            let info = AofInfo {
+                file_name,
+                seq,
+                file_type,
+            };
+            match file_type {
+                AofFileType::Base if manifest.base.is_some() => {
+                    return Err(AofManifestError::MultipleBaseFiles)
+                }
+                AofFileType::Base => manifest.base = Some(info),
+                AofFileType::Incr => manifest.incrs.push(info),
+                AofFileType::History => {}
+            }
+        }
+
+        Ok(manifest)
+    }
+    /// Reads the manifest of the AOF named `prefix` from `dir`.
+    ///
+    /// Returns [`None`] if there is no manifest yet.
+    pub fn load(dir: &Path, prefix: &str) -> Result<Option<Self>, AofManifestError> {
+        match std::fs::read_to_string(dir.join(manifest_name(prefix))) {
+            Ok(input) => Self::parse(&input).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+    /// Atomically replaces the manifest on disk.
+    pub fn save(&self, dir: &Path, prefix: &str) -> std::io::Result<()> {
+        let temp_path = dir.join(format!("temp-{}", manifest_name(prefix)));
+        std::fs::write(&temp_path, self.to_string())?;
+        std::fs::File::open(&temp_path)?.sync_all()?;
+        std::fs::rename(&temp_path, dir.join(manifest_name(prefix)))
+    }
+    /// Adds a new incremental file after the current ones.
+    pub fn add_incr(&mut self, prefix: &str) -> &AofInfo {
+        let seq = self.incrs.last().map_or(1, |info| info.seq + 1);
+        self.incrs.push(AofInfo {
+            file_name: format!("{prefix}.{seq}.incr.aof"),
+            seq,
+            file_type: AofFileType::Incr,
+        });
+        self.incrs.last().unwrap()
+    }
+    /// Name of the base file following the current one.
+    pub fn next_base(&self, prefix: &str, rdb_preamble: bool) -> AofInfo {
+        let seq = self.base.as_ref().map_or(1, |info| info.seq + 1);
+        let extension = if rdb_preamble { "rdb" } else { "aof" };
+        AofInfo {
+            file_name: format!("{prefix}.{seq}.base.{extension}"),
+            seq,
+            file_type: AofFileType::Base,
+        }
+    }
+}
+
+impl Display for AofManifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for info in self.base.iter().chain(&self.incrs) {
+            writeln!(
+                f,
+                "file {} seq {} type {}",
+                info.file_name,
+                info.seq,
+                char::from(info.file_type)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn manifest_name(prefix: &str) -> String {
+    format!("{prefix}.manifest")
+}
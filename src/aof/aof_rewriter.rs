@@ -0,0 +1,33 @@
+use crate::db::Database;
+use crate::rdb::{dump_value, RdbWriterError};
+use crate::resp::RespValue;
+use crate::util::to_hex;
+
+/// Builds a command sequence that recreates the Database, in RESP form.
+///
+/// Every key is written as `RESTORE key <unix-time-ms> <payload> REPLACE ABSTTL` with
+/// the DUMP payload of its value, so that any value the RDB format can hold is
+/// restored exactly, including its expiry.
+pub fn rewrite_commands(db: &Database) -> Result<Vec<u8>, RdbWriterError> {
+    let mut output = String::new();
+
+    for (key, slot) in db.iter() {
+        let payload = dump_value(slot.value())?;
+        // NOTE: A TTL of 0 means that the key does not expire.
+        let expires_ms = slot.expires_unix_ms().unwrap_or(0);
+        let args = [
+            "RESTORE",
+            key,
+            &expires_ms.to_string(),
+            &to_hex(&payload),
+            "REPLACE",
+            "ABSTTL",
+        ]
+        .into_iter()
+        .map(|arg| RespValue::BulkString(arg.to_owned().into()))
+        .collect();
+        output.push_str(&RespValue::Array(args).to_string());
+    }
+
+    Ok(output.into_bytes())
+}
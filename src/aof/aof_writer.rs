@@ -0,0 +1,313 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+
+use crate::aof::{AofFileType, AofInfo, AofManifest, AofManifestError};
+use crate::config::{AppendFsync, Config};
+use crate::server::LatencyMonitor;
+
+const FSYNC_INTERVAL: Duration = Duration::from_secs(1);
+/// Bytes of a new base file written at once, after each of which the progress is
+/// reported.
+const WRITE_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Appends write commands to the append-only file.
+///
+/// The AOF is a directory holding a base file, the incremental files appended to
+/// since and the [`AofManifest`] listing them. Every command is handed to the OS
+/// immediately, while the [`AppendFsync`] policy decides when the data is forced
+/// to disk:
+///
+/// * `always` syncs before [`AofFile::append`] returns,
+/// * `everysec` syncs once per second on a dedicated flush thread,
+/// * `no` leaves it to the OS.
+pub struct AofWriter {
+    file: Arc<Mutex<AofFile>>,
+}
+
+pub struct AofFile {
+    dir: PathBuf,
+    prefix: String,
+    manifest: AofManifest,
+    /// The last incremental file, which all commands are appended to.
+    file: File,
+    fsync: AppendFsync,
+    /// Sequence number of the incremental file opened by the running rewrite.
+    rewrite_incr_seq: Option<u64>,
+    /// Size of the base file after the last rewrite (or at startup).
+    pub base_size: u64,
+    /// Size of all files making up the AOF.
+    pub current_size: u64,
+    /// Size of the last incremental file.
+    incr_size: u64,
+    /// Error of the last write, while writes of clients are refused until retrying
+    /// [`AofFile::flush_pending`] succeeds.
+    pub last_write_error: Option<String>,
+    /// Commands which were applied but could not be written yet.
+    pending: Vec<u8>,
+    /// Records slow fsyncs as 'aof-fsync' events.
+    latency: Arc<LatencyMonitor>,
+}
+
+impl AofFile {
+    /// Appends the command `frame`, which is kept and written with the next attempt
+    /// if writing it fails.
+    pub fn append(&mut self, frame: &[u8]) -> std::io::Result<()> {
+        self.pending.extend_from_slice(frame);
+        self.flush_pending()
+    }
+    /// Writes the commands whose append failed before, which the flush thread retries
+    /// once per second.
+    pub fn flush_pending(&mut self) -> std::io::Result<()> {
+        let result = self.write_pending();
+        self.last_write_error = result.as_ref().err().map(ToString::to_string);
+        result
+    }
+    fn write_pending(&mut self) -> std::io::Result<()> {
+        if let Err(e) = self.file.write_all(&self.pending) {
+            // NOTE: A partly written command is cut off again, so that the retry doesn't
+            //       write it twice and the file stays loadable.
+            let _ = self.file.set_len(self.incr_size);
+            return Err(e);
+        }
+        self.incr_size += self.pending.len() as u64;
+        self.current_size += self.pending.len() as u64;
+        self.pending.clear();
+        if self.fsync == AppendFsync::Always {
+            let file = &self.file;
+            self.latency.time("aof-fsync", || file.sync_data())?;
+        }
+        Ok(())
+    }
+    /// Cuts the last incremental file off after `len` bytes, dropping an incomplete
+    /// command at its end.
+    pub fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        self.file.set_len(len)?;
+        self.current_size -= self.incr_size.saturating_sub(len);
+        self.incr_size = len;
+        Ok(())
+    }
+    /// Forces everything appended so far to disk, regardless of the policy.
+    pub fn sync(&self) -> std::io::Result<()> {
+        let file = &self.file;
+        self.latency.time("aof-fsync", || file.sync_data())
+    }
+    /// Changes the [`AppendFsync`] policy for all following appends.
+    pub fn set_fsync(&mut self, fsync: AppendFsync) {
+        self.fsync = fsync;
+    }
+    /// Switches appends to a new incremental file, so that everything written so far
+    /// can be replaced by the base file the rewrite creates.
+    pub fn start_rewrite(&mut self) -> std::io::Result<()> {
+        // NOTE: Pending commands belong into the previous file, which the snapshot of the
+        //       rewrite replaces.
+        self.flush_pending()?;
+        let mut manifest = self.manifest.clone();
+        let info = manifest.add_incr(&self.prefix).clone();
+        let file = open_incr(&self.dir, &info)?;
+        manifest.save(&self.dir, &self.prefix)?;
+
+        // NOTE: Everything in the previous file must be on disk before the rewrite can
+        //       make the file obsolete.
+        self.file.sync_data()?;
+        self.file = file;
+        self.incr_size = 0;
+        self.manifest = manifest;
+        self.rewrite_incr_seq = Some(info.seq);
+        Ok(())
+    }
+}
+
+impl AofWriter {
+    /// Opens the AOF in the configured directory, creating it if it does not exist.
+    ///
+    /// A single file AOF as written by Redis before 7.0 is moved into the directory
+    /// and used as base file.
+    pub fn open(config: &Config, latency: Arc<LatencyMonitor>) -> Result<Self, AofManifestError> {
+        let dir = config.aof_dir();
+        let prefix = config.appendfilename.clone();
+        std::fs::create_dir_all(&dir)?;
+
+        let mut manifest = match AofManifest::load(&dir, &prefix)? {
+            Some(manifest) => manifest,
+            None => {
+                let mut manifest = AofManifest::default();
+                let legacy_path = config.dir.join(&prefix);
+                if legacy_path.is_file() {
+                    std::fs::rename(&legacy_path, dir.join(&prefix))?;
+                    manifest.base = Some(AofInfo {
+                        file_name: prefix.clone(),
+                        seq: 1,
+                        file_type: AofFileType::Base,
+                    });
+                }
+                manifest
+            }
+        };
+        if manifest.incrs.is_empty() {
+            manifest.add_incr(&prefix);
+            manifest.save(&dir, &prefix)?;
+        }
+
+        let file = open_incr(&dir, manifest.incrs.last().unwrap())?;
+        let incr_size = file.metadata()?.len();
+        let mut base_size = 0;
+        let mut current_size = 0;
+        for info in manifest.base.iter().chain(&manifest.incrs) {
+            let size = std::fs::metadata(dir.join(&info.file_name))?.len();
+            if info.file_type == AofFileType::Base {
+                base_size = size;
+            }
+            current_size += size;
+        }
+
+        let file = Arc::new(Mutex::new(AofFile {
+            dir,
+            prefix,
+            manifest,
+            file,
+            fsync: config.appendfsync,
+            rewrite_incr_seq: None,
+            base_size,
+            current_size,
+            incr_size,
+            last_write_error: None,
+            pending: Vec::new(),
+            latency,
+        }));
+
+        // NOTE: The flush thread also runs for other policies, since CONFIG SET can
+        //       switch to 'everysec' at any time.
+        let weak = Arc::downgrade(&file);
+        std::thread::Builder::new()
+            .name("aof-fsync".into())
+            .spawn(move || fsync_loop(weak))?;
+
+        Ok(Self { file })
+    }
+    /// Locks the file, which also keeps other writers from appending until the guard
+    /// is dropped.
+    pub fn lock(&self) -> MutexGuard<'_, AofFile> {
+        self.file.lock().unwrap()
+    }
+    pub fn append(&self, frame: &[u8]) -> std::io::Result<()> {
+        self.lock().append(frame)
+    }
+    /// Installs `base` as the new base file, replacing all incremental files from
+    /// before [`AofFile::start_rewrite`] was called.
+    ///
+    /// `rdb_preamble` tells whether `base` is an RDB file or a list of commands, and
+    /// `progress` is called with the number of bytes whenever a part of it was written.
+    pub fn finish_rewrite(
+        &self,
+        base: &[u8],
+        rdb_preamble: bool,
+        progress: impl FnMut(u64),
+    ) -> std::io::Result<()> {
+        let (dir, prefix) = {
+            let file = self.lock();
+            (file.dir.clone(), file.prefix.clone())
+        };
+        let temp_path = dir.join(format!("temp-rewriteaof-bg-{}.aof", std::process::id()));
+
+        let result = write_synced(&temp_path, base, progress).and_then(|()| {
+            let mut file = self.lock();
+            let Some(rewrite_incr_seq) = file.rewrite_incr_seq.take() else {
+                return Err(std::io::Error::other("no AOF rewrite in progress"));
+            };
+
+            let mut manifest = file.manifest.clone();
+            let base_info = manifest.next_base(&prefix, rdb_preamble);
+            std::fs::rename(&temp_path, dir.join(&base_info.file_name))?;
+
+            let mut obsolete: Vec<AofInfo> = manifest.base.take().into_iter().collect();
+            obsolete.extend(
+                manifest
+                    .incrs
+                    .iter()
+                    .filter(|info| info.seq < rewrite_incr_seq)
+                    .cloned(),
+            );
+            manifest.incrs.retain(|info| info.seq >= rewrite_incr_seq);
+            manifest.base = Some(base_info);
+            manifest.save(&dir, &prefix)?;
+
+            for info in obsolete {
+                if let Err(e) = std::fs::remove_file(dir.join(&info.file_name)) {
+                    eprintln!("Error removing obsolete AOF file {:?}: {e}", info.file_name);
+                }
+            }
+
+            let incr_size: u64 = manifest
+                .incrs
+                .iter()
+                .filter_map(|info| std::fs::metadata(dir.join(&info.file_name)).ok())
+                .map(|metadata| metadata.len())
+                .sum();
+            file.manifest = manifest;
+            file.base_size = base.len() as u64;
+            file.current_size = file.base_size + incr_size;
+            Ok(())
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+
+        result
+    }
+    /// Files making up the AOF in the order they have to be loaded.
+    pub fn files(&self) -> Vec<(PathBuf, AofInfo)> {
+        let file = self.lock();
+        file.manifest
+            .base
+            .iter()
+            .chain(&file.manifest.incrs)
+            .map(|info| (file.dir.join(&info.file_name), info.clone()))
+            .collect()
+    }
+}
+
+fn open_incr(dir: &Path, info: &AofInfo) -> std::io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(&info.file_name))
+}
+
+fn write_synced(path: &Path, bytes: &[u8], mut progress: impl FnMut(u64)) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    for chunk in bytes.chunks(WRITE_CHUNK_SIZE) {
+        file.write_all(chunk)?;
+        progress(chunk.len() as u64);
+    }
+    file.sync_all()
+}
+
+fn fsync_loop(file: Weak<Mutex<AofFile>>) {
+    loop {
+        std::thread::sleep(FSYNC_INTERVAL);
+
+        let Some(file) = file.upgrade() else {
+            break;
+        };
+        // NOTE: Syncing a clone of the handle keeps 'append' from blocking on the fsync.
+        let (handle, latency) = {
+            let mut file = file.lock().unwrap();
+            if file.last_write_error.is_some() && file.flush_pending().is_ok() {
+                println!("AOF write error looks solved, Redis can write again.");
+            }
+            if file.fsync != AppendFsync::EverySec {
+                continue;
+            }
+            (file.file.try_clone(), file.latency.clone())
+        };
+        drop(file);
+
+        let synced = handle.and_then(|handle| latency.time("aof-fsync", || handle.sync_data()));
+        if let Err(e) = synced {
+            eprintln!("Error syncing the AOF: {e}");
+        }
+    }
+}
@@ -0,0 +1,9 @@
+mod aof_loader;
+mod aof_manifest;
+mod aof_rewriter;
+mod aof_writer;
+
+pub use aof_loader::load_aof;
+pub use aof_manifest::{AofFileType, AofInfo, AofManifest, AofManifestError};
+pub use aof_rewriter::rewrite_commands;
+pub use aof_writer::{AofFile, AofWriter};
@@ -0,0 +1,183 @@
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+use crate::cluster::{ClusterMessage, MessageType, CLUSTER_PORT_INCR};
+use crate::resp::{is_incomplete, parse_resp_value};
+use crate::server::ServerState;
+
+/// Interval at which failures are checked and due PINGs are sent.
+const CRON_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Accepts connections on the cluster bus and periodically pings every known node.
+///
+/// The bus listens on the client port plus [`CLUSTER_PORT_INCR`].
+pub async fn run_cluster_bus(state: Arc<ServerState>) -> anyhow::Result<()> {
+    let port = state.config().port.wrapping_add(CLUSTER_PORT_INCR);
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    // NOTE: The tasks are aborted along with the bus once the server shuts down.
+    let mut tasks = JoinSet::new();
+    tasks.spawn(run_cluster_cron(state.clone()));
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, addr) = accepted?;
+                let state = state.clone();
+                tasks.spawn(async move {
+                    if let Err(e) = handle_bus_connection(&state, stream, addr).await {
+                        eprintln!("Error on cluster bus connection from {addr}: {e}");
+                    }
+                });
+            }
+            Some(_) = tasks.join_next() => {}
+        }
+    }
+}
+
+async fn handle_bus_connection(
+    state: &ServerState,
+    mut stream: TcpStream,
+    addr: SocketAddr,
+) -> anyhow::Result<()> {
+    let Some(cluster) = &state.cluster else {
+        return Ok(());
+    };
+    let peer_ip = addr.ip().to_string();
+    let mut buffer = BytesMut::new();
+
+    while let Some(message) = read_message(&mut stream, &mut buffer).await? {
+        if let Some(reply) = cluster.process_message(&message, &peer_ip) {
+            stream.write_all(reply.to_frame().as_bytes()).await?;
+            cluster.messages_sent.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    Ok(())
+}
+
+async fn run_cluster_cron(state: Arc<ServerState>) {
+    let Some(cluster) = &state.cluster else {
+        return;
+    };
+    let mut interval = tokio::time::interval(CRON_INTERVAL);
+    // NOTE: PINGs in flight are aborted along with the cron.
+    let mut pings = JoinSet::new();
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            Some(_) = pings.join_next() => continue,
+        }
+
+        let due = cluster.due_pings();
+        for (id, ip, cport, kind) in due {
+            let state = state.clone();
+            pings.spawn(async move {
+                // NOTE: A node which does not reply is flagged once the PING times out, and
+                //       pinged again meanwhile, see 'due_pings'.
+                let _ = ping_node(&state, &id, &ip, cport, kind).await;
+            });
+        }
+
+        for id in cluster.check_failures() {
+            println!("Cluster node {id} is failing");
+            let Some(message) = cluster.build_fail_message(&id) else {
+                continue;
+            };
+            broadcast(&state, message);
+        }
+    }
+}
+
+async fn ping_node(
+    state: &ServerState,
+    id: &str,
+    ip: &str,
+    cport: u16,
+    kind: MessageType,
+) -> anyhow::Result<()> {
+    let Some(cluster) = &state.cluster else {
+        return Ok(());
+    };
+    let message = cluster.build_message(kind);
+    let reply = timeout(cluster.ping_timeout(), async {
+        let mut stream = TcpStream::connect((ip, cport)).await?;
+        stream.write_all(message.to_frame().as_bytes()).await?;
+        cluster.messages_sent.fetch_add(1, Ordering::Relaxed);
+        read_message(&mut stream, &mut BytesMut::new()).await
+    })
+    .await??;
+
+    match reply {
+        Some(reply) if reply.kind == MessageType::Pong => {
+            cluster.process_pong(id, &reply);
+            Ok(())
+        }
+        reply => Err(anyhow!(
+            "Unexpected reply from cluster node {id}: {reply:?}"
+        )),
+    }
+}
+
+/// Sends `message` to every other node without waiting for replies.
+fn broadcast(state: &Arc<ServerState>, message: ClusterMessage) {
+    let Some(cluster) = &state.cluster else {
+        return;
+    };
+    let targets: Vec<_> = cluster
+        .lock_topology()
+        .nodes
+        .values()
+        .filter(|node| node.id != cluster.myid && !node.handshake)
+        .map(|node| (node.ip.clone(), node.cport))
+        .collect();
+    let frame = message.to_frame();
+
+    for (ip, cport) in targets {
+        let state = state.clone();
+        let frame = frame.clone();
+        tokio::spawn(async move {
+            let Some(cluster) = &state.cluster else {
+                return;
+            };
+            let send = async {
+                let mut stream = TcpStream::connect((ip.as_str(), cport)).await?;
+                stream.write_all(frame.as_bytes()).await
+            };
+            if let Ok(Ok(())) = timeout(cluster.node_timeout, send).await {
+                cluster.messages_sent.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+/// Reads the next message, or [`None`] if the peer closed the connection.
+async fn read_message(
+    stream: &mut TcpStream,
+    buffer: &mut BytesMut,
+) -> anyhow::Result<Option<ClusterMessage>> {
+    loop {
+        match parse_resp_value(buffer) {
+            Ok((rest, value)) => {
+                let consumed = buffer.len() - rest.len();
+                let message = ClusterMessage::parse(value)
+                    .ok_or_else(|| anyhow!("Invalid cluster bus message"));
+                buffer.advance(consumed);
+                return message.map(Some);
+            }
+            Err(e) if is_incomplete(&e) => {
+                if stream.read_buf(buffer).await? == 0 {
+                    return Ok(None);
+                }
+            }
+            Err(e) => return Err(anyhow!("Invalid cluster bus message: {e}")),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::resp::RespValue;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum MessageType {
+    Ping,
+    Pong,
+    /// PING which also asks the receiver to add the sender to its nodes.
+    Meet,
+    /// Announces that the node in the first gossip entry is failing.
+    Fail,
+}
+
+impl MessageType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageType::Ping => "PING",
+            MessageType::Pong => "PONG",
+            MessageType::Meet => "MEET",
+            MessageType::Fail => "FAIL",
+        }
+    }
+}
+
+impl TryFrom<&str> for MessageType {
+    type Error = ();
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "PING" => Ok(MessageType::Ping),
+            "PONG" => Ok(MessageType::Pong),
+            "MEET" => Ok(MessageType::Meet),
+            "FAIL" => Ok(MessageType::Fail),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What the sender knows about another node.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct GossipEntry {
+    pub id: String,
+    pub ip: String,
+    pub port: u16,
+    /// Whether the sender considers the node failing.
+    pub failing: bool,
+}
+
+/// Message exchanged on the cluster bus.
+///
+/// NOTE: This is not the binary format of Redis' cluster bus, but an array of bulk
+///       strings: type, sender ID, sender port, current epoch, config epoch, slot
+///       ranges like `0-99,200`, followed by `id ip port flags` for every gossip entry.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ClusterMessage {
+    pub kind: MessageType,
+    pub sender: String,
+    pub port: u16,
+    pub current_epoch: u64,
+    pub config_epoch: u64,
+    /// Slots the sender serves, as `(start, end)`.
+    pub slots: Vec<(u16, u16)>,
+    pub gossip: Vec<GossipEntry>,
+}
+
+impl ClusterMessage {
+    pub fn to_frame(&self) -> String {
+        let slots = self
+            .slots
+            .iter()
+            .map(|&(start, end)| {
+                if start == end {
+                    start.to_string()
+                } else {
+                    format!("{start}-{end}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut args = vec![
+            self.kind.as_str().to_string(),
+            self.sender.clone(),
+            self.port.to_string(),
+            self.current_epoch.to_string(),
+            self.config_epoch.to_string(),
+            slots,
+        ];
+        for entry in &self.gossip {
+            args.push(entry.id.clone());
+            args.push(entry.ip.clone());
+            args.push(entry.port.to_string());
+            args.push(String::from(if entry.failing { "fail" } else { "-" }));
+        }
+
+        RespValue::Array(
+            args.into_iter()
+                .map(|arg| RespValue::BulkString(arg.into()))
+                .collect(),
+        )
+        .to_string()
+    }
+    pub fn parse(value: RespValue<'_>) -> Option<Self> {
+        let RespValue::Array(values) = value else {
+            return None;
+        };
+        let args: Vec<String> = values
+            .into_iter()
+            .map(|value| match value {
+                RespValue::BulkString(s) => Some(s.into_owned()),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        if args.len() < 6 {
+            return None;
+        }
+        let (header, gossip) = args.split_at(6);
+        let gossip = gossip.chunks_exact(4);
+        if !gossip.remainder().is_empty() {
+            return None;
+        }
+
+        let slots = header[5]
+            .split(',')
+            .filter(|range| !range.is_empty())
+            .map(|range| match range.split_once('-') {
+                Some((start, end)) => Some((start.parse().ok()?, end.parse().ok()?)),
+                None => range.parse().ok().map(|slot| (slot, slot)),
+            })
+            .collect::<Option<_>>()?;
+        let gossip = gossip
+            .map(|entry| {
+                Some(GossipEntry {
+                    id: entry[0].clone(),
+                    ip: entry[1].clone(),
+                    port: entry[2].parse().ok()?,
+                    failing: entry[3] == "fail",
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Self {
+            kind: MessageType::try_from(header[0].as_str()).ok()?,
+            sender: header[1].clone(),
+            port: header[2].parse().ok()?,
+            current_epoch: header[3].parse().ok()?,
+            config_epoch: header[4].parse().ok()?,
+            slots,
+            gossip,
+        })
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Offset of the cluster bus port from the client port.
+pub const CLUSTER_PORT_INCR: u16 = 10000;
+
+/// Node of the cluster, including this one.
+#[derive(Debug, Clone)]
+pub struct ClusterNode {
+    pub id: String,
+    pub ip: String,
+    pub port: u16,
+    /// Port of the cluster bus.
+    pub cport: u16,
+    /// Epoch of the node's slot claims, the highest one wins conflicting claims.
+    pub config_epoch: u64,
+    /// Met through CLUSTER MEET, but has not replied with its real ID yet.
+    pub handshake: bool,
+    /// Did not reply to a PING within the node timeout, as seen by this node.
+    pub pfail: bool,
+    /// Agreed to be failing by the majority of masters.
+    pub fail: bool,
+    pub fail_time: Option<Instant>,
+    /// When the first unanswered PING was sent.
+    pub ping_sent: Option<Instant>,
+    /// When the last PING was sent, which is repeated while `ping_sent` is unanswered.
+    pub last_ping: Option<Instant>,
+    pub pong_received: Option<Instant>,
+    /// Masters which reported the node as failing, with the time of their last report.
+    pub fail_reports: HashMap<String, Instant>,
+}
+
+impl ClusterNode {
+    pub fn new(id: String, ip: String, port: u16) -> Self {
+        Self {
+            id,
+            ip,
+            port,
+            cport: port.wrapping_add(CLUSTER_PORT_INCR),
+            config_epoch: 0,
+            handshake: false,
+            pfail: false,
+            fail: false,
+            fail_time: None,
+            ping_sent: None,
+            last_ping: None,
+            pong_received: None,
+            fail_reports: HashMap::new(),
+        }
+    }
+    /// Address clients are redirected to, in the form `ip:port`.
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.ip, self.port)
+    }
+    /// Flags as listed by CLUSTER NODES, without the ones depending on this node.
+    pub fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec!["master"];
+        if self.pfail && !self.fail {
+            flags.push("fail?");
+        }
+        if self.fail {
+            flags.push("fail");
+        }
+        if self.handshake {
+            flags.push("handshake");
+        }
+        flags
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::cluster::{key_hash_slot, ClusterNode, CLUSTER_SLOTS};
+use crate::command::RedisError;
+use crate::util::random_hex_id;
+
+/// Whether the cluster can serve queries, as seen by this node.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum ClusterHealth {
+    Ok,
+    /// Not every slot is served by a reachable node.
+    #[default]
+    Fail,
+}
+
+impl ClusterHealth {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClusterHealth::Ok => "ok",
+            ClusterHealth::Fail => "fail",
+        }
+    }
+}
+
+/// Change of a slot requested through CLUSTER SETSLOT.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum SetSlot {
+    /// Keys of the slot served by this node are being moved to the node.
+    Migrating(String),
+    /// Keys of the slot served by the node are being moved to this node.
+    Importing(String),
+    /// Stops migrating or importing the slot.
+    Stable,
+    /// Assigns the slot to the node, which ends a migration.
+    Node(String),
+}
+
+/// Nodes of the cluster and which of them serves each slot.
+#[derive(Debug)]
+pub struct ClusterTopology {
+    pub nodes: HashMap<String, ClusterNode>,
+    /// ID of the node serving each slot.
+    pub slots: Vec<Option<String>>,
+    /// Slots this node serves which are being moved to another node.
+    pub migrating: HashMap<u16, String>,
+    /// Slots another node serves which are being moved to this node.
+    pub importing: HashMap<u16, String>,
+}
+
+impl ClusterTopology {
+    pub fn assigned_slots(&self) -> usize {
+        self.slots.iter().filter(|owner| owner.is_some()).count()
+    }
+    /// Number of assigned slots whose node matches `filter`.
+    pub fn count_slots(&self, filter: impl Fn(&ClusterNode) -> bool) -> usize {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|owner| self.nodes.get(*owner).is_some_and(&filter))
+            .count()
+    }
+    /// Number of nodes serving at least one slot.
+    pub fn size(&self) -> usize {
+        self.slots.iter().flatten().collect::<HashSet<_>>().len()
+    }
+    /// Adds a node to start a handshake with, unless its address is already known.
+    pub fn add_handshake_node(&mut self, ip: String, port: u16) {
+        let known = self
+            .nodes
+            .values()
+            .any(|node| node.ip == ip && node.port == port);
+        if known {
+            return;
+        }
+        let mut node = ClusterNode::new(random_hex_id(40), ip, port);
+        node.handshake = true;
+        self.nodes.insert(node.id.clone(), node);
+    }
+    /// Ranges of slots served by the node `id`, as `(start, end)`.
+    pub fn node_slot_ranges(&self, id: &str) -> Vec<(u16, u16)> {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        for (slot, owner) in self.slots.iter().enumerate() {
+            if owner.as_deref() != Some(id) {
+                continue;
+            }
+            let slot = slot as u16;
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == slot => *end = slot,
+                _ => ranges.push((slot, slot)),
+            }
+        }
+        ranges
+    }
+}
+
+/// State of this node in cluster mode.
+#[derive(Debug)]
+pub struct ClusterState {
+    /// Identifies the node within the cluster, independent of its address.
+    pub myid: String,
+    /// Highest configuration epoch seen in the cluster.
+    pub current_epoch: AtomicU64,
+    /// Time after which an unreachable node is considered failing.
+    pub node_timeout: Duration,
+    pub messages_sent: AtomicU64,
+    pub messages_received: AtomicU64,
+    topology: Mutex<ClusterTopology>,
+}
+
+impl ClusterState {
+    pub fn new(port: u16, node_timeout: Duration) -> Self {
+        let myid = random_hex_id(40);
+        let myself = ClusterNode::new(myid.clone(), String::from("127.0.0.1"), port);
+        Self {
+            myid: myid.clone(),
+            current_epoch: AtomicU64::new(0),
+            node_timeout,
+            messages_sent: AtomicU64::new(0),
+            messages_received: AtomicU64::new(0),
+            topology: Mutex::new(ClusterTopology {
+                nodes: HashMap::from([(myid, myself)]),
+                slots: vec![None; usize::from(CLUSTER_SLOTS)],
+                migrating: HashMap::new(),
+                importing: HashMap::new(),
+            }),
+        }
+    }
+    pub fn lock_topology(&self) -> MutexGuard<'_, ClusterTopology> {
+        self.topology.lock().unwrap()
+    }
+    /// Configuration epoch of this node.
+    pub fn my_epoch(&self) -> u64 {
+        self.lock_topology().nodes[&self.myid].config_epoch
+    }
+    pub fn health(&self) -> ClusterHealth {
+        if self.lock_topology().count_slots(|node| !node.fail) == usize::from(CLUSTER_SLOTS) {
+            ClusterHealth::Ok
+        } else {
+            ClusterHealth::Fail
+        }
+    }
+    /// Assigns `slots` to this node, failing without changes if any is already served.
+    pub fn add_slots(&self, slots: &[u16]) -> Result<(), RedisError> {
+        let mut topology = self.lock_topology();
+        if let Some(slot) = slots
+            .iter()
+            .find(|&&slot| topology.slots[usize::from(slot)].is_some())
+        {
+            return Err(RedisError::Err(format!("Slot {slot} is already busy")));
+        }
+        for &slot in slots {
+            topology.slots[usize::from(slot)] = Some(self.myid.clone());
+        }
+        Ok(())
+    }
+    /// Unassigns `slots`, failing without changes if any is not served.
+    pub fn del_slots(&self, slots: &[u16]) -> Result<(), RedisError> {
+        let mut topology = self.lock_topology();
+        if let Some(slot) = slots
+            .iter()
+            .find(|&&slot| topology.slots[usize::from(slot)].is_none())
+        {
+            return Err(RedisError::Err(format!(
+                "Slot {slot} is already unassigned"
+            )));
+        }
+        for &slot in slots {
+            topology.slots[usize::from(slot)] = None;
+            topology.migrating.remove(&slot);
+            topology.importing.remove(&slot);
+        }
+        Ok(())
+    }
+    /// Applies CLUSTER SETSLOT to `slot`, where `has_keys` tells whether keys of the
+    /// slot are stored locally.
+    pub fn set_slot(&self, slot: u16, action: SetSlot, has_keys: bool) -> Result<(), RedisError> {
+        let mut topology = self.lock_topology();
+        let owner = topology.slots[usize::from(slot)].clone();
+        let is_mine = owner.as_deref() == Some(self.myid.as_str());
+        let check_node = |id: &str| {
+            if topology.nodes.contains_key(id) {
+                Ok(())
+            } else {
+                Err(RedisError::Err(format!("I don't know about node {id}")))
+            }
+        };
+
+        match action {
+            SetSlot::Migrating(id) => {
+                if !is_mine {
+                    return Err(RedisError::Err(format!(
+                        "I'm not the owner of hash slot {slot}"
+                    )));
+                }
+                check_node(&id)?;
+                if id == self.myid {
+                    return Err(RedisError::err("Target node is myself"));
+                }
+                topology.migrating.insert(slot, id);
+            }
+            SetSlot::Importing(id) => {
+                if is_mine {
+                    return Err(RedisError::Err(format!(
+                        "I'm already the owner of hash slot {slot}"
+                    )));
+                }
+                check_node(&id)?;
+                if id == self.myid {
+                    return Err(RedisError::err("Source node is myself"));
+                }
+                topology.importing.insert(slot, id);
+            }
+            SetSlot::Stable => {
+                topology.migrating.remove(&slot);
+                topology.importing.remove(&slot);
+            }
+            SetSlot::Node(id) => {
+                check_node(&id)?;
+                if is_mine && id != self.myid && has_keys {
+                    return Err(RedisError::Err(format!(
+                        "Can't assign hashslot {slot} to a different node while I still hold \
+                         keys for this hash slot."
+                    )));
+                }
+                topology.migrating.remove(&slot);
+                // NOTE: Taking over an imported slot needs a new config epoch, so that the
+                //       claim wins over the one of the former owner.
+                if topology.importing.remove(&slot).is_some() && id == self.myid {
+                    let epoch = self.current_epoch.fetch_add(1, Ordering::Relaxed) + 1;
+                    if let Some(myself) = topology.nodes.get_mut(&self.myid) {
+                        myself.config_epoch = epoch;
+                    }
+                }
+                topology.slots[usize::from(slot)] = Some(id);
+            }
+        }
+        Ok(())
+    }
+    /// Adds a node the cluster bus starts a handshake with, under a random ID until
+    /// it replies with its own.
+    pub fn meet(&self, ip: String, port: u16) {
+        self.lock_topology().add_handshake_node(ip, port);
+    }
+    /// Checks whether a command accessing `keys` can be served by this node, returning
+    /// the redirect or error to reply with otherwise.
+    ///
+    /// `asking` is set if the client sent ASKING before the command, and `exists`
+    /// tells whether a key is stored locally.
+    pub fn redirect(
+        &self,
+        keys: &[&str],
+        asking: bool,
+        exists: impl Fn(&str) -> bool,
+    ) -> Option<RedisError> {
+        let (first, rest) = keys.split_first()?;
+        let slot = key_hash_slot(first.as_bytes());
+        // NOTE: The dispatcher already refuses keys in different slots at the positions
+        //       of the command table before parsing. This check sees every parsed key,
+        //       so it is the authoritative one for "movablekeys" commands like MIGRATE.
+        if rest.iter().any(|key| key_hash_slot(key.as_bytes()) != slot) {
+            return Some(RedisError::CrossSlot);
+        }
+
+        let topology = self.lock_topology();
+        if topology.count_slots(|node| !node.fail) != usize::from(CLUSTER_SLOTS) {
+            return Some(RedisError::ClusterDown);
+        }
+        let Some(owner) = &topology.slots[usize::from(slot)] else {
+            return Some(RedisError::SlotUnbound(slot));
+        };
+        let addr = |id: &str| topology.nodes.get(id).map(ClusterNode::addr);
+
+        if *owner == self.myid {
+            let target = topology.migrating.get(&slot)?;
+            // NOTE: Keys which were already moved have to be asked for on the target,
+            //       while a mix of moved and local keys can only be retried later.
+            let missing = keys.iter().filter(|key| !exists(key)).count();
+            return match missing {
+                0 => None,
+                n if n == keys.len() => addr(target).map(|addr| RedisError::Ask(slot, addr)),
+                _ => Some(RedisError::TryAgain),
+            };
+        }
+        if asking && topology.importing.contains_key(&slot) {
+            return None;
+        }
+        addr(owner).map(|addr| RedisError::Moved(slot, addr))
+    }
+    /// Renders the reply to CLUSTER INFO.
+    pub fn info(&self) -> String {
+        let (assigned, pfail, fail, known_nodes, size) = {
+            let topology = self.lock_topology();
+            (
+                topology.assigned_slots(),
+                topology.count_slots(|node| node.pfail && !node.fail),
+                topology.count_slots(|node| node.fail),
+                topology.nodes.len(),
+                topology.size(),
+            )
+        };
+
+        let mut output = String::new();
+        let _ = write!(output, "cluster_state:{}\r\n", self.health().as_str());
+        let _ = write!(output, "cluster_slots_assigned:{assigned}\r\n");
+        let _ = write!(output, "cluster_slots_ok:{}\r\n", assigned - pfail - fail);
+        let _ = write!(output, "cluster_slots_pfail:{pfail}\r\n");
+        let _ = write!(output, "cluster_slots_fail:{fail}\r\n");
+        let _ = write!(output, "cluster_known_nodes:{known_nodes}\r\n");
+        let _ = write!(output, "cluster_size:{size}\r\n");
+        let _ = write!(
+            output,
+            "cluster_current_epoch:{}\r\n",
+            self.current_epoch.load(Ordering::Relaxed)
+        );
+        let _ = write!(output, "cluster_my_epoch:{}\r\n", self.my_epoch());
+        let _ = write!(
+            output,
+            "cluster_stats_messages_sent:{}\r\n",
+            self.messages_sent.load(Ordering::Relaxed)
+        );
+        let _ = write!(
+            output,
+            "cluster_stats_messages_received:{}\r\n",
+            self.messages_received.load(Ordering::Relaxed)
+        );
+        output
+    }
+    /// Renders the reply to CLUSTER NODES, one line per node.
+    pub fn nodes(&self) -> String {
+        let topology = self.lock_topology();
+        let unix_ms = |time: Option<Instant>| {
+            time.and_then(|time| {
+                let elapsed = time.elapsed();
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+                Some(now.saturating_sub(elapsed).as_millis())
+            })
+            .unwrap_or(0)
+        };
+
+        let mut output = String::new();
+        for node in topology.nodes.values() {
+            let mut flags = node.flags();
+            if node.id == self.myid {
+                flags.insert(0, "myself");
+            }
+            let connected = node.id == self.myid || (!node.handshake && node.ping_sent.is_none());
+            let _ = write!(
+                output,
+                "{} {}:{}@{} {} - {} {} {} {}",
+                node.id,
+                node.ip,
+                node.port,
+                node.cport,
+                flags.join(","),
+                unix_ms(node.ping_sent),
+                unix_ms(node.pong_received),
+                node.config_epoch,
+                if connected {
+                    "connected"
+                } else {
+                    "disconnected"
+                },
+            );
+            for (start, end) in topology.node_slot_ranges(&node.id) {
+                if start == end {
+                    let _ = write!(output, " {start}");
+                } else {
+                    let _ = write!(output, " {start}-{end}");
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
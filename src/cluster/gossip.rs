@@ -0,0 +1,261 @@
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::cluster::{
+    ClusterMessage, ClusterNode, ClusterState, ClusterTopology, GossipEntry, MessageType,
+};
+
+/// Multiple of the node timeout after which fail reports expire, and after which a
+/// failing node which serves slots is cleared once it is reachable again.
+const FAIL_UNDO_TIME_MULT: u32 = 2;
+/// Interval at which every node is pinged.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+impl ClusterState {
+    /// Builds a message announcing this node's view of the cluster.
+    pub fn build_message(&self, kind: MessageType) -> ClusterMessage {
+        let topology = self.lock_topology();
+        let gossip = topology
+            .nodes
+            .values()
+            .filter(|node| node.id != self.myid && !node.handshake)
+            .map(|node| GossipEntry {
+                id: node.id.clone(),
+                ip: node.ip.clone(),
+                port: node.port,
+                failing: node.pfail || node.fail,
+            })
+            .collect();
+        ClusterMessage {
+            kind,
+            sender: self.myid.clone(),
+            port: topology.nodes[&self.myid].port,
+            current_epoch: self.current_epoch.load(Ordering::Relaxed),
+            config_epoch: topology.nodes[&self.myid].config_epoch,
+            slots: topology.node_slot_ranges(&self.myid),
+            gossip,
+        }
+    }
+    /// Builds the message announcing that the node `id` is failing.
+    pub fn build_fail_message(&self, id: &str) -> Option<ClusterMessage> {
+        let mut message = self.build_message(MessageType::Fail);
+        let entry = message.gossip.iter().find(|entry| entry.id == id)?.clone();
+        message.gossip = vec![entry];
+        Some(message)
+    }
+    /// Marks the nodes which have to be pinged now as pinged, returning the ID, address
+    /// and cluster bus port of each with the kind of message to send.
+    ///
+    /// An unanswered PING is repeated after [`ClusterState::ping_timeout`], so that a
+    /// node which missed it, e.g. because it restarted, is found to be reachable again.
+    pub fn due_pings(&self) -> Vec<(String, String, u16, MessageType)> {
+        let now = Instant::now();
+        // NOTE: Nodes are pinged at least twice per node timeout, so that a single lost
+        //       PONG doesn't flag them.
+        let ping_interval = PING_INTERVAL.min(self.node_timeout / 2);
+        let mut topology = self.lock_topology();
+        topology
+            .nodes
+            .values_mut()
+            .filter(|node| node.id != self.myid)
+            .filter(
+                |node| match (node.ping_sent, node.last_ping, node.pong_received) {
+                    (Some(_), Some(time), _) => now.duration_since(time) >= self.ping_timeout(),
+                    (None, _, Some(time)) => now.duration_since(time) >= ping_interval,
+                    _ => true,
+                },
+            )
+            .map(|node| {
+                node.ping_sent.get_or_insert(now);
+                node.last_ping = Some(now);
+                let kind = if node.handshake {
+                    MessageType::Meet
+                } else {
+                    MessageType::Ping
+                };
+                (node.id.clone(), node.ip.clone(), node.cport, kind)
+            })
+            .collect()
+    }
+    /// Time after which a PING which was not answered is given up and repeated.
+    pub fn ping_timeout(&self) -> Duration {
+        self.node_timeout / 2
+    }
+    /// Handles a message received from `peer_ip`, returning the reply if one is due.
+    pub fn process_message(
+        &self,
+        message: &ClusterMessage,
+        peer_ip: &str,
+    ) -> Option<ClusterMessage> {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut topology = self.lock_topology();
+            if message.kind == MessageType::Meet && !topology.nodes.contains_key(&message.sender) {
+                let node =
+                    ClusterNode::new(message.sender.clone(), peer_ip.to_string(), message.port);
+                topology.nodes.insert(node.id.clone(), node);
+            }
+            // NOTE: Any message proves that the sender is reachable, not only a PONG.
+            self.mark_reachable(&mut topology, &message.sender);
+            self.apply_message(&mut topology, message);
+        }
+
+        match message.kind {
+            MessageType::Ping | MessageType::Meet => Some(self.build_message(MessageType::Pong)),
+            MessageType::Pong | MessageType::Fail => None,
+        }
+    }
+    /// Handles the reply of the node `id` to a PING or MEET.
+    ///
+    /// Nodes met through CLUSTER MEET are only known under their real ID from here on.
+    pub fn process_pong(&self, id: &str, message: &ClusterMessage) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        let mut topology = self.lock_topology();
+        let Some(pinged) = topology.nodes.get(id) else {
+            return;
+        };
+        if pinged.handshake {
+            let Some(mut node) = topology.nodes.remove(id) else {
+                return;
+            };
+            if message.sender == self.myid || topology.nodes.contains_key(&message.sender) {
+                return;
+            }
+            node.id = message.sender.clone();
+            node.handshake = false;
+            topology.nodes.insert(node.id.clone(), node);
+        } else if message.sender != id {
+            return;
+        }
+
+        if let Some(node) = topology.nodes.get_mut(&message.sender) {
+            node.pong_received = Some(Instant::now());
+        }
+        self.mark_reachable(&mut topology, &message.sender);
+        self.apply_message(&mut topology, message);
+    }
+    /// Clears the failure flags of the node `id`, which replied or sent a message.
+    fn mark_reachable(&self, topology: &mut ClusterTopology, id: &str) {
+        let fail_undo_time = self.node_timeout * FAIL_UNDO_TIME_MULT;
+        let serves_slots = topology.slots.iter().flatten().any(|owner| owner == id);
+        let Some(node) = topology.nodes.get_mut(id) else {
+            return;
+        };
+        if node.handshake {
+            return;
+        }
+        let now = Instant::now();
+        node.ping_sent = None;
+        node.last_ping = None;
+        node.pfail = false;
+        let undo = node
+            .fail_time
+            .is_some_and(|time| now.duration_since(time) >= fail_undo_time);
+        if node.fail && (!serves_slots || undo) {
+            node.fail = false;
+            node.fail_time = None;
+        }
+    }
+    /// Adopts the epochs, slot claims and gossip of a message from a known node.
+    fn apply_message(&self, topology: &mut ClusterTopology, message: &ClusterMessage) {
+        let Some(sender) = topology.nodes.get_mut(&message.sender) else {
+            return;
+        };
+        if sender.handshake {
+            return;
+        }
+        sender.config_epoch = message.config_epoch;
+        self.current_epoch
+            .fetch_max(message.current_epoch, Ordering::Relaxed);
+
+        // NOTE: A claim wins over the current one if its config epoch is greater,
+        //       which resolves conflicts once slots are moved between nodes.
+        for &(start, end) in &message.slots {
+            for slot in start..=end {
+                let Some(owner) = topology.slots.get(usize::from(slot)) else {
+                    continue;
+                };
+                let wins = match owner {
+                    None => true,
+                    Some(owner) if *owner == message.sender => false,
+                    Some(owner) => match topology.nodes.get(owner) {
+                        Some(owner) => owner.config_epoch < message.config_epoch,
+                        None => true,
+                    },
+                };
+                if wins && !topology.importing.contains_key(&slot) {
+                    topology.slots[usize::from(slot)] = Some(message.sender.clone());
+                    topology.migrating.remove(&slot);
+                }
+            }
+        }
+
+        if message.kind == MessageType::Fail {
+            for entry in &message.gossip {
+                if let Some(node) = topology.nodes.get_mut(&entry.id) {
+                    if node.id != self.myid && !node.fail {
+                        node.fail = true;
+                        node.fail_time = Some(Instant::now());
+                    }
+                }
+            }
+            return;
+        }
+
+        let now = Instant::now();
+        for entry in &message.gossip {
+            if entry.id == self.myid {
+                continue;
+            }
+            match topology.nodes.get_mut(&entry.id) {
+                Some(node) if entry.failing => {
+                    node.fail_reports.insert(message.sender.clone(), now);
+                }
+                Some(node) => {
+                    node.fail_reports.remove(&message.sender);
+                }
+                // NOTE: Nodes only have to be met by one node of the cluster, every
+                //       other node learns about them through gossip.
+                None if !entry.failing => {
+                    topology.add_handshake_node(entry.ip.clone(), entry.port);
+                }
+                None => {}
+            }
+        }
+    }
+    /// Flags nodes which did not reply in time, returning the ones which the majority
+    /// of masters now agrees are failing.
+    pub fn check_failures(&self) -> Vec<String> {
+        let now = Instant::now();
+        let report_validity = self.node_timeout * FAIL_UNDO_TIME_MULT;
+        let mut topology = self.lock_topology();
+        let quorum = topology.size() / 2 + 1;
+
+        let timed_out = |node: &ClusterNode| {
+            node.ping_sent
+                .is_some_and(|sent| now.duration_since(sent) >= self.node_timeout)
+        };
+        topology
+            .nodes
+            .retain(|_, node| !(node.handshake && timed_out(node)));
+
+        let mut failed = Vec::new();
+        for node in topology.nodes.values_mut() {
+            if node.id == self.myid {
+                continue;
+            }
+            if timed_out(node) {
+                node.pfail = true;
+            }
+            node.fail_reports
+                .retain(|_, time| now.duration_since(*time) < report_validity);
+            // NOTE: This node counts as one of the masters reporting the failure.
+            if node.pfail && !node.fail && node.fail_reports.len() + 1 >= quorum {
+                node.fail = true;
+                node.fail_time = Some(now);
+                failed.push(node.id.clone());
+            }
+        }
+        failed
+    }
+}
@@ -0,0 +1,14 @@
+mod cluster_bus;
+mod cluster_message;
+mod cluster_node;
+mod cluster_state;
+mod gossip;
+mod replies;
+mod slot;
+
+pub use cluster_bus::run_cluster_bus;
+pub use cluster_message::{ClusterMessage, GossipEntry, MessageType};
+pub use cluster_node::{ClusterNode, CLUSTER_PORT_INCR};
+pub use cluster_state::{ClusterHealth, ClusterState, ClusterTopology, SetSlot};
+pub use replies::{cluster_shards, cluster_slots};
+pub use slot::{key_hash_slot, CLUSTER_SLOTS};
@@ -0,0 +1,100 @@
+use crate::cluster::{ClusterNode, ClusterState, ClusterTopology};
+use crate::resp::RespValue;
+
+fn bulk(s: impl Into<String>) -> RespValue<'static> {
+    RespValue::BulkString(s.into().into())
+}
+
+/// Reply to CLUSTER SLOTS, listing every range of slots with the node serving it.
+pub fn cluster_slots(cluster: &ClusterState) -> RespValue<'static> {
+    let topology = cluster.lock_topology();
+    let ranges = slot_ranges(&topology)
+        .into_iter()
+        .filter_map(|(start, end, owner)| {
+            let node = topology.nodes.get(owner)?;
+            Some(RespValue::Array(vec![
+                RespValue::Integer(start.into()),
+                RespValue::Integer(end.into()),
+                RespValue::Array(vec![
+                    bulk(node.ip.as_str()),
+                    RespValue::Integer(node.port.into()),
+                    bulk(node.id.as_str()),
+                    RespValue::Array(vec![]),
+                ]),
+            ]))
+        })
+        .collect();
+    RespValue::Array(ranges)
+}
+
+/// Reply to CLUSTER SHARDS, listing every node serving slots with all of its ranges.
+///
+/// `repl_offset` is the replication offset of this node, the one of other nodes is
+/// not known.
+pub fn cluster_shards(cluster: &ClusterState, repl_offset: u64) -> RespValue<'static> {
+    let topology = cluster.lock_topology();
+    let mut shards: Vec<(&ClusterNode, Vec<RespValue<'static>>)> = Vec::new();
+    for (start, end, owner) in slot_ranges(&topology) {
+        let Some(node) = topology.nodes.get(owner) else {
+            continue;
+        };
+        let bounds = [
+            RespValue::Integer(start.into()),
+            RespValue::Integer(end.into()),
+        ];
+        match shards.iter_mut().find(|(shard, _)| shard.id == node.id) {
+            Some((_, slots)) => slots.extend(bounds),
+            None => shards.push((node, bounds.into())),
+        }
+    }
+
+    let shards = shards
+        .into_iter()
+        .map(|(node, slots)| {
+            let offset = if node.id == cluster.myid {
+                repl_offset
+            } else {
+                0
+            };
+            let node = RespValue::Array(vec![
+                bulk("id"),
+                bulk(node.id.as_str()),
+                bulk("port"),
+                RespValue::Integer(node.port.into()),
+                bulk("ip"),
+                bulk(node.ip.as_str()),
+                bulk("endpoint"),
+                bulk(node.ip.as_str()),
+                bulk("role"),
+                bulk("master"),
+                bulk("replication-offset"),
+                RespValue::Integer(offset as i64),
+                bulk("health"),
+                bulk("online"),
+            ]);
+            RespValue::Array(vec![
+                bulk("slots"),
+                RespValue::Array(slots),
+                bulk("nodes"),
+                RespValue::Array(vec![node]),
+            ])
+        })
+        .collect();
+    RespValue::Array(shards)
+}
+
+/// Contiguous ranges of slots served by the same node, as `(start, end, node ID)`.
+fn slot_ranges(topology: &ClusterTopology) -> Vec<(u16, u16, &str)> {
+    let mut ranges: Vec<(u16, u16, &str)> = Vec::new();
+    for (slot, owner) in topology.slots.iter().enumerate() {
+        let Some(owner) = owner.as_deref() else {
+            continue;
+        };
+        let slot = slot as u16;
+        match ranges.last_mut() {
+            Some((_, end, last)) if *last == owner && *end + 1 == slot => *end = slot,
+            _ => ranges.push((slot, slot, owner)),
+        }
+    }
+    ranges
+}
@@ -0,0 +1,22 @@
+use crate::util::crc16;
+
+/// Number of hash slots the keyspace is divided into.
+pub const CLUSTER_SLOTS: u16 = 16384;
+
+/// Hash slot of `key`.
+///
+/// If the key contains a non-empty hash tag like `{user1000}.following`, only the tag
+/// is hashed, so that related keys can be placed in the same slot.
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    let tag = key
+        .iter()
+        .position(|&b| b == b'{')
+        .and_then(|start| {
+            let rest = &key[start + 1..];
+            let end = rest.iter().position(|&b| b == b'}')?;
+            Some(&rest[..end])
+        })
+        .filter(|tag| !tag.is_empty());
+
+    crc16(tag.unwrap_or(key)) & (CLUSTER_SLOTS - 1)
+}
@@ -0,0 +1,71 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::command::CommandSpec;
+use crate::resp::RespValue;
+
+/// Reply of a [`CommandHandler`], which may wait for anything before it is known.
+pub type HandlerFuture = Pin<Box<dyn Future<Output = RespValue<'static>> + Send>>;
+
+/// Command added by users of the crate, which is dispatched like the built-in ones,
+/// see [`RedisServerBuilder::command`](crate::RedisServerBuilder::command).
+pub trait CommandHandler: Send + Sync {
+    /// Name the command is called by, which is matched case-insensitively.
+    fn name(&self) -> &'static str;
+    /// Number of arguments including the name, or the negated minimum if the command
+    /// takes a variable number of them.
+    fn arity(&self) -> i64;
+    /// Flags as listed by COMMAND INFO. "write" commands are persisted and replicated,
+    /// "fast" ones are reported as such by LATENCY and "stale" ones are served by
+    /// replicas without a link to their master.
+    fn flags(&self) -> &'static [&'static str] {
+        &[]
+    }
+    /// Runs the command with its arguments, not including the name.
+    fn call(&self, args: Vec<String>) -> HandlerFuture;
+}
+
+impl std::fmt::Debug for dyn CommandHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandHandler")
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+/// A registered [`CommandHandler`] together with its entry for the command table.
+#[derive(Debug)]
+pub struct CustomCommand {
+    pub spec: CommandSpec,
+    pub handler: Arc<dyn CommandHandler>,
+}
+
+impl CustomCommand {
+    pub fn new(handler: Arc<dyn CommandHandler>) -> Self {
+        let flags = handler.flags();
+        // NOTE: ACL rules can only refer to custom commands by name or by these basic
+        //       categories, which are derived from the flags.
+        let categories: &'static [&'static str] =
+            match (flags.contains(&"write"), flags.contains(&"fast")) {
+                (true, true) => &["write", "fast"],
+                (true, false) => &["write", "slow"],
+                (false, true) => &["read", "fast"],
+                (false, false) => &["read", "slow"],
+            };
+        let spec = CommandSpec {
+            name: handler.name(),
+            arity: handler.arity(),
+            flags,
+            categories,
+            first_key: 0,
+            last_key: 0,
+            key_step: 0,
+            group: "module",
+            since: "",
+            summary: "",
+            subcommands: &[],
+        };
+        Self { spec, handler }
+    }
+}
@@ -0,0 +1,706 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::resp::RespValue;
+
+/// Static information about a command, see [`COMMAND_TABLE`].
+#[derive(Debug)]
+pub struct CommandSpec {
+    /// Lowercase name, e.g. "config" or "get" for a subcommand.
+    pub name: &'static str,
+    /// Number of arguments including the name, or the negated minimum if the command
+    /// takes a variable number of them. Subcommands count the container name as well.
+    pub arity: i64,
+    /// Flags as listed by COMMAND INFO, e.g. "write" or "loading".
+    pub flags: &'static [&'static str],
+    /// ACL categories, without the leading '@'.
+    pub categories: &'static [&'static str],
+    /// Position of the first key argument, or 0 if the command takes no keys.
+    pub first_key: i64,
+    /// Position of the last key argument, negative positions count from the end.
+    pub last_key: i64,
+    /// Distance between two key arguments.
+    pub key_step: i64,
+    /// Group of the command in the documentation, e.g. "server".
+    pub group: &'static str,
+    /// Redis version which introduced the command.
+    pub since: &'static str,
+    pub summary: &'static str,
+    /// Subcommands of container commands like CONFIG, which have their own categories.
+    pub subcommands: &'static [CommandSpec],
+}
+
+const fn command(
+    name: &'static str,
+    arity: i64,
+    flags: &'static [&'static str],
+    categories: &'static [&'static str],
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        arity,
+        flags,
+        categories,
+        first_key: 0,
+        last_key: 0,
+        key_step: 0,
+        group: "",
+        since: "",
+        summary: "",
+        subcommands: &[],
+    }
+}
+
+const fn container(name: &'static str, subcommands: &'static [CommandSpec]) -> CommandSpec {
+    command(name, -2, &[], &["slow"]).subcommands(subcommands)
+}
+
+impl CommandSpec {
+    const fn keys(mut self, first_key: i64, last_key: i64, key_step: i64) -> Self {
+        self.first_key = first_key;
+        self.last_key = last_key;
+        self.key_step = key_step;
+        self
+    }
+    const fn doc(
+        mut self,
+        group: &'static str,
+        since: &'static str,
+        summary: &'static str,
+    ) -> Self {
+        self.group = group;
+        self.since = since;
+        self.summary = summary;
+        self
+    }
+    const fn subcommands(mut self, subcommands: &'static [CommandSpec]) -> Self {
+        self.subcommands = subcommands;
+        self
+    }
+}
+
+/// HELP subcommand of a container, whose reply lists the other subcommands.
+const fn help(group: &'static str, since: &'static str) -> CommandSpec {
+    command("help", 2, &["loading", "stale"], &["slow"]).doc(
+        group,
+        since,
+        "Returns helpful text about the different subcommands.",
+    )
+}
+
+const ADMIN: &[&str] = &["admin", "slow", "dangerous"];
+const CONNECTION: &[&str] = &["fast", "connection"];
+const CLIENT: &[&str] = &["slow", "connection"];
+const ADMIN_FLAGS: &[&str] = &["admin", "noscript", "loading", "stale"];
+const INFO_FLAGS: &[&str] = &["loading", "stale"];
+const NO_AUTH_FLAGS: &[&str] = &[
+    "noscript",
+    "loading",
+    "stale",
+    "fast",
+    "no_auth",
+    "allow_busy",
+];
+
+/// Every supported command, sorted by name.
+pub const COMMAND_TABLE: &[CommandSpec] = &[
+    container(
+        "acl",
+        &[
+            command("cat", -2, &["noscript", "loading", "stale"], &["slow"]).doc(
+                "server",
+                "6.0.0",
+                "Lists the ACL categories, or the commands inside a category.",
+            ),
+            command("deluser", -3, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "6.0.0",
+                "Deletes ACL users, and terminates their connections.",
+            ),
+            command("getuser", 3, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "6.0.0",
+                "Lists the ACL rules of a user.",
+            ),
+            help("server", "6.0.0"),
+            command("list", 2, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "6.0.0",
+                "Dumps the effective rules in ACL file format.",
+            ),
+            command("setuser", -3, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "6.0.0",
+                "Creates and modifies an ACL user and its rules.",
+            ),
+            command("whoami", 2, &["noscript", "loading", "stale"], &["slow"]).doc(
+                "server",
+                "6.0.0",
+                "Returns the authenticated username of the current connection.",
+            ),
+        ],
+    )
+    .doc(
+        "server",
+        "6.0.0",
+        "A container for Access List Control commands.",
+    ),
+    command("asking", 1, &["fast"], CONNECTION).doc(
+        "cluster",
+        "3.0.0",
+        "Signals that a cluster client is following an -ASK redirect.",
+    ),
+    command("auth", -2, NO_AUTH_FLAGS, CONNECTION).doc(
+        "connection",
+        "1.0.0",
+        "Authenticates the connection.",
+    ),
+    command("bgrewriteaof", 1, &["admin", "noasync", "noscript"], ADMIN).doc(
+        "server",
+        "1.0.0",
+        "Asynchronously rewrites the append-only file to disk.",
+    ),
+    command("bgsave", -1, &["admin", "noasync", "noscript"], ADMIN).doc(
+        "server",
+        "1.0.0",
+        "Asynchronously saves the database(s) to disk.",
+    ),
+    container(
+        "client",
+        &[
+            command("getname", 2, &["noscript", "loading", "stale"], CLIENT).doc(
+                "connection",
+                "2.6.9",
+                "Returns the name of the connection.",
+            ),
+            help("connection", "5.0.0"),
+            command("id", 2, &["noscript", "loading", "stale"], CLIENT).doc(
+                "connection",
+                "5.0.0",
+                "Returns the unique client ID of the connection.",
+            ),
+            command("info", 2, &["noscript", "loading", "stale"], CLIENT).doc(
+                "connection",
+                "6.2.0",
+                "Returns information about the connection.",
+            ),
+            command(
+                "list",
+                -2,
+                ADMIN_FLAGS,
+                &["admin", "slow", "dangerous", "connection"],
+            )
+            .doc("connection", "2.4.0", "Lists open connections."),
+            command("setname", 3, &["noscript", "loading", "stale"], CLIENT).doc(
+                "connection",
+                "2.6.9",
+                "Sets the connection name.",
+            ),
+        ],
+    )
+    .doc(
+        "connection",
+        "2.4.0",
+        "A container for client connection commands.",
+    ),
+    container(
+        "cluster",
+        &[
+            command("addslots", -3, &["admin", "stale", "noscript"], ADMIN).doc(
+                "cluster",
+                "3.0.0",
+                "Assigns new hash slots to a node.",
+            ),
+            command("countkeysinslot", 3, &["stale"], &["slow"]).doc(
+                "cluster",
+                "3.0.0",
+                "Returns the number of keys in a hash slot.",
+            ),
+            command("delslots", -3, &["admin", "stale", "noscript"], ADMIN).doc(
+                "cluster",
+                "3.0.0",
+                "Sets hash slots as unbound for a node.",
+            ),
+            command("getkeysinslot", 4, &["stale"], &["slow"]).doc(
+                "cluster",
+                "3.0.0",
+                "Returns the key names in a hash slot.",
+            ),
+            help("cluster", "5.0.0"),
+            command("info", 2, INFO_FLAGS, &["slow"]).doc(
+                "cluster",
+                "3.0.0",
+                "Returns information about the state of a node.",
+            ),
+            command("keyslot", 3, &["stale"], &["slow"]).doc(
+                "cluster",
+                "3.0.0",
+                "Returns the hash slot for a key.",
+            ),
+            command("meet", -4, &["admin", "stale", "noscript"], ADMIN).doc(
+                "cluster",
+                "3.0.0",
+                "Forces a node to handshake with another node.",
+            ),
+            command("myid", 2, INFO_FLAGS, &["slow"]).doc(
+                "cluster",
+                "3.0.0",
+                "Returns the ID of a node.",
+            ),
+            command("nodes", 2, INFO_FLAGS, &["slow"]).doc(
+                "cluster",
+                "3.0.0",
+                "Returns the cluster configuration for a node.",
+            ),
+            command("setslot", -4, &["admin", "stale", "noscript"], ADMIN).doc(
+                "cluster",
+                "3.0.0",
+                "Binds a hash slot to a node.",
+            ),
+            command("shards", 2, INFO_FLAGS, &["slow"]).doc(
+                "cluster",
+                "7.0.0",
+                "Returns the mapping of cluster slots to shards.",
+            ),
+            command("slots", 2, INFO_FLAGS, &["slow"]).doc(
+                "cluster",
+                "3.0.0",
+                "Returns the mapping of cluster slots to nodes.",
+            ),
+        ],
+    )
+    .doc(
+        "cluster",
+        "3.0.0",
+        "A container for Redis Cluster commands.",
+    ),
+    command("command", -1, INFO_FLAGS, &["slow", "connection"])
+        .doc(
+            "server",
+            "2.8.13",
+            "Returns detailed information about all commands.",
+        )
+        .subcommands(&[
+            command("count", 2, INFO_FLAGS, &["slow", "connection"]).doc(
+                "server",
+                "2.8.13",
+                "Returns a count of commands.",
+            ),
+            command("docs", -2, INFO_FLAGS, &["slow", "connection"]).doc(
+                "server",
+                "7.0.0",
+                "Returns documentary information about one, multiple or all commands.",
+            ),
+            help("server", "5.0.0"),
+            command("info", -2, INFO_FLAGS, &["slow", "connection"]).doc(
+                "server",
+                "2.8.13",
+                "Returns information about one, multiple or all commands.",
+            ),
+        ]),
+    container(
+        "config",
+        &[
+            command("get", -3, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "2.0.0",
+                "Returns the effective values of configuration parameters.",
+            ),
+            help("server", "5.0.0"),
+            command("set", -4, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "2.0.0",
+                "Sets configuration parameters in-flight.",
+            ),
+        ],
+    )
+    .doc(
+        "server",
+        "2.0.0",
+        "A container for server configuration commands.",
+    ),
+    command("debug", -2, ADMIN_FLAGS, ADMIN).doc(
+        "server",
+        "1.0.0",
+        "A container for debugging commands.",
+    ),
+    command("del", -2, &["write"], &["keyspace", "write", "slow"])
+        .keys(1, -1, 1)
+        .doc("generic", "1.0.0", "Deletes one or more keys."),
+    command("dump", 2, &["readonly"], &["keyspace", "read", "slow"])
+        .keys(1, 1, 1)
+        .doc(
+            "generic",
+            "2.6.0",
+            "Returns a serialized representation of the value stored at a key.",
+        ),
+    command("echo", 2, &["fast"], CONNECTION).doc(
+        "connection",
+        "1.0.0",
+        "Returns the given string.",
+    ),
+    command("hello", -1, NO_AUTH_FLAGS, CONNECTION).doc(
+        "connection",
+        "6.0.0",
+        "Handshakes with the Redis server.",
+    ),
+    command("info", -1, INFO_FLAGS, &["slow", "dangerous"]).doc(
+        "server",
+        "1.0.0",
+        "Returns information and statistics about the server.",
+    ),
+    command(
+        "lastsave",
+        1,
+        &["loading", "stale", "fast"],
+        &["admin", "fast", "dangerous"],
+    )
+    .doc(
+        "server",
+        "1.0.0",
+        "Returns the Unix timestamp of the last successful save to disk.",
+    ),
+    container(
+        "latency",
+        &[
+            command("doctor", 2, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "2.8.13",
+                "Returns a human-readable latency analysis report.",
+            ),
+            help("server", "2.8.13"),
+            command("histogram", -2, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "7.0.0",
+                "Returns the cumulative distribution of latencies of a subset or all commands.",
+            ),
+            command("history", 3, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "2.8.13",
+                "Returns timestamp-latency samples for an event.",
+            ),
+            command("latest", 2, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "2.8.13",
+                "Returns the latest latency samples for all events.",
+            ),
+            command("reset", -2, ADMIN_FLAGS, ADMIN).doc(
+                "server",
+                "2.8.13",
+                "Resets the latency data for one or more events.",
+            ),
+        ],
+    )
+    .doc(
+        "server",
+        "2.8.13",
+        "A container for latency diagnostics commands.",
+    ),
+    command("lolwut", -1, &["readonly", "fast"], &["read", "fast"]).doc(
+        "server",
+        "5.0.0",
+        "Displays computer art and the Redis version.",
+    ),
+    container(
+        "memory",
+        &[
+            command("doctor", 2, &[], &["slow"]).doc(
+                "server",
+                "4.0.0",
+                "Outputs a memory problems report.",
+            ),
+            help("server", "4.0.0"),
+            command("stats", 2, &[], &["slow"]).doc(
+                "server",
+                "4.0.0",
+                "Returns details about memory usage.",
+            ),
+        ],
+    )
+    .doc(
+        "server",
+        "4.0.0",
+        "A container for memory diagnostics commands.",
+    ),
+    command(
+        "migrate",
+        -6,
+        &["write", "movablekeys"],
+        &["keyspace", "write", "slow", "dangerous"],
+    )
+    .keys(3, 3, 1)
+    .doc(
+        "generic",
+        "2.6.0",
+        "Atomically transfers a key from one Redis instance to another.",
+    ),
+    command("ping", -1, &["fast", "stale"], CONNECTION).doc(
+        "connection",
+        "1.0.0",
+        "Returns the server's liveliness response.",
+    ),
+    command(
+        "psync",
+        -3,
+        &["admin", "noscript", "no_async_loading", "no_multi"],
+        ADMIN,
+    )
+    .doc(
+        "server",
+        "2.8.0",
+        "An internal command used in replication.",
+    ),
+    command("quit", -1, NO_AUTH_FLAGS, CONNECTION).doc(
+        "connection",
+        "1.0.0",
+        "Closes the connection.",
+    ),
+    command(
+        "replconf",
+        -1,
+        &["admin", "noscript", "loading", "stale", "allow_busy"],
+        ADMIN,
+    )
+    .doc(
+        "server",
+        "3.0.0",
+        "An internal command for configuring the replication stream.",
+    ),
+    command(
+        "replicaof",
+        3,
+        &["admin", "noscript", "stale", "no_async_loading"],
+        ADMIN,
+    )
+    .doc(
+        "server",
+        "5.0.0",
+        "Configures a server as replica of another, or promotes it to a master.",
+    ),
+    command(
+        "restore",
+        -4,
+        &["write", "denyoom"],
+        &["keyspace", "write", "slow", "dangerous"],
+    )
+    .keys(1, 1, 1)
+    .doc(
+        "generic",
+        "2.6.0",
+        "Creates a key from the serialized representation of a value.",
+    ),
+    command(
+        "save",
+        1,
+        &["admin", "noscript", "no_async_loading", "no_multi"],
+        ADMIN,
+    )
+    .doc(
+        "server",
+        "1.0.0",
+        "Synchronously saves the database(s) to disk.",
+    ),
+    command("scan", -2, &["readonly"], &["keyspace", "read", "slow"]).doc(
+        "generic",
+        "2.8.0",
+        "Iterates over the key names in the database.",
+    ),
+    command("wait", 3, &["noscript"], &["slow", "connection"]).doc(
+        "generic",
+        "3.0.0",
+        "Blocks until the asynchronous replication of all preceding write commands sent \
+         by the connection is completed.",
+    ),
+];
+
+/// Other names of commands, which are looked up like their actual name.
+const COMMAND_ALIASES: &[(&str, &str)] = &[("slaveof", "replicaof"), ("unlink", "del")];
+
+/// Longest name that is looked up, which fits every command and alias.
+const MAX_COMMAND_NAME_LEN: usize = 32;
+
+/// Commands of the [`COMMAND_TABLE`] by their name and aliases, built on first use.
+fn command_index() -> &'static HashMap<&'static str, &'static CommandSpec> {
+    static INDEX: OnceLock<HashMap<&'static str, &'static CommandSpec>> = OnceLock::new();
+    INDEX.get_or_init(|| {
+        let mut index: HashMap<_, _> = COMMAND_TABLE.iter().map(|spec| (spec.name, spec)).collect();
+        for &(alias, name) in COMMAND_ALIASES {
+            let spec = index[name];
+            index.insert(alias, spec);
+        }
+        index
+    })
+}
+
+/// Looks up a command by its case-insensitive name or alias.
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    // NOTE: Lowercased on the stack, since every request is looked up.
+    let mut buffer = [0; MAX_COMMAND_NAME_LEN];
+    let buffer = buffer.get_mut(..name.len())?;
+    buffer.copy_from_slice(name.as_bytes());
+    buffer.make_ascii_lowercase();
+    let name = std::str::from_utf8(buffer).ok()?;
+    command_index().get(name).copied()
+}
+
+impl CommandSpec {
+    /// Whether the command may be called with `num_args` arguments including its name.
+    pub fn accepts(&self, num_args: usize) -> bool {
+        if self.arity < 0 {
+            num_args as i64 >= -self.arity
+        } else {
+            num_args as i64 == self.arity
+        }
+    }
+    /// Whether the command has the flag, e.g. "write".
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+    /// Positions of the key arguments in a request with `num_args` arguments, following
+    /// the first, last and step key positions.
+    ///
+    /// NOTE: Commands flagged "movablekeys", e.g. MIGRATE, may take further keys at
+    ///       positions that depend on their other arguments.
+    pub fn key_positions(&self, num_args: usize) -> impl Iterator<Item = usize> {
+        let num_args = num_args as i64;
+        let last_key = if self.last_key < 0 {
+            num_args + self.last_key
+        } else {
+            self.last_key.min(num_args - 1)
+        };
+        let (first_key, last_key) = if self.first_key > 0 && self.key_step > 0 {
+            (self.first_key as usize, last_key.max(0) as usize)
+        } else {
+            (1, 0)
+        };
+        (first_key..=last_key).step_by(self.key_step.max(1) as usize)
+    }
+    /// Looks up a subcommand by its case-insensitive name.
+    pub fn subcommand(&self, name: &str) -> Option<&'static CommandSpec> {
+        self.subcommands
+            .iter()
+            .find(|spec| spec.name.eq_ignore_ascii_case(name))
+    }
+    /// Reply of HELP for container commands, which lists the subcommands with their
+    /// summary.
+    pub fn help(&self) -> RespValue<'static> {
+        let name = self.name.to_ascii_uppercase();
+        let mut lines = vec![format!(
+            "{name} <subcommand> [<arg> [value] [opt] ...]. Subcommands are:"
+        )];
+        for subcommand in self.subcommands {
+            lines.push(subcommand.name.to_ascii_uppercase());
+            lines.push(format!("    {}", subcommand.summary));
+        }
+        RespValue::Array(
+            lines
+                .into_iter()
+                .map(|line| RespValue::SimpleString(line.into()))
+                .collect(),
+        )
+    }
+    /// Reply of COMMAND INFO for the command, whose subcommands are named after
+    /// `parent` as in "config|get".
+    pub fn info(&self, parent: Option<&str>) -> RespValue<'static> {
+        let strings = |values: &[&'static str]| {
+            RespValue::Array(
+                values
+                    .iter()
+                    .map(|&value| RespValue::BulkString(value.into()))
+                    .collect(),
+            )
+        };
+        let categories: Vec<_> = self
+            .categories
+            .iter()
+            .map(|category| format!("@{category}"))
+            .map(|category| RespValue::BulkString(category.into()))
+            .collect();
+        let key_specs = if self.first_key > 0 {
+            vec![self.key_spec()]
+        } else {
+            Vec::new()
+        };
+
+        RespValue::Array(vec![
+            RespValue::BulkString(self.full_name(parent).into()),
+            RespValue::Integer(self.arity),
+            strings(self.flags),
+            RespValue::Integer(self.first_key),
+            RespValue::Integer(self.last_key),
+            RespValue::Integer(self.key_step),
+            RespValue::Array(categories),
+            RespValue::Array(Vec::new()),
+            RespValue::Array(key_specs),
+            RespValue::Array(
+                self.subcommands
+                    .iter()
+                    .map(|subcommand| subcommand.info(Some(self.name)))
+                    .collect(),
+            ),
+        ])
+    }
+    /// Reply of COMMAND DOCS for the command, a flat array of its fields.
+    pub fn docs(&self) -> RespValue<'static> {
+        let mut docs = vec![
+            RespValue::BulkString("summary".into()),
+            RespValue::BulkString(self.summary.into()),
+            RespValue::BulkString("since".into()),
+            RespValue::BulkString(self.since.into()),
+            RespValue::BulkString("group".into()),
+            RespValue::BulkString(self.group.into()),
+        ];
+        if !self.subcommands.is_empty() {
+            let mut subcommands = Vec::new();
+            for subcommand in self.subcommands {
+                subcommands.push(RespValue::BulkString(
+                    subcommand.full_name(Some(self.name)).into(),
+                ));
+                subcommands.push(subcommand.docs());
+            }
+            docs.push(RespValue::BulkString("subcommands".into()));
+            docs.push(RespValue::Array(subcommands));
+        }
+        RespValue::Array(docs)
+    }
+    /// Name including the container command, e.g. "config|get".
+    pub fn full_name(&self, parent: Option<&str>) -> String {
+        match parent {
+            Some(parent) => format!("{parent}|{}", self.name),
+            None => self.name.to_string(),
+        }
+    }
+    /// Key specification equivalent to the first, last and step key positions.
+    fn key_spec(&self) -> RespValue<'static> {
+        let string = |s: &'static str| RespValue::BulkString(s.into());
+        // NOTE: A non-negative last key is relative to the first key in key specs.
+        let last_key = if self.last_key < 0 {
+            self.last_key
+        } else {
+            self.last_key - self.first_key
+        };
+        RespValue::Array(vec![
+            string("begin_search"),
+            RespValue::Array(vec![
+                string("type"),
+                string("index"),
+                string("spec"),
+                RespValue::Array(vec![string("index"), RespValue::Integer(self.first_key)]),
+            ]),
+            string("find_keys"),
+            RespValue::Array(vec![
+                string("type"),
+                string("range"),
+                string("spec"),
+                RespValue::Array(vec![
+                    string("lastkey"),
+                    RespValue::Integer(last_key),
+                    string("keystep"),
+                    RespValue::Integer(self.key_step),
+                    string("limit"),
+                    RespValue::Integer(0),
+                ]),
+            ]),
+        ])
+    }
+}
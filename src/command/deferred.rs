@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+use crate::command::{CommandHandler, RedisError};
+use crate::rdb::dump_value;
+use crate::resp::{is_incomplete, parse_resp_value, RespValue};
+use crate::server::ServerState;
+use crate::util::to_hex;
+
+/// Reply that is only known after waiting for something outside the connection.
+///
+/// Commands store it in the [`ConnectionContext`] instead of replying, and the
+/// connection resolves it before handling the next request.
+///
+/// [`ConnectionContext`]: crate::server::ConnectionContext
+#[derive(Debug)]
+pub enum DeferredReply {
+    /// Waits until `num_replicas` replicas acknowledged `offset`, or the timeout
+    /// expires.
+    Wait {
+        num_replicas: usize,
+        timeout: Option<Duration>,
+        offset: u64,
+    },
+    /// Restores `entries` of key, expiry and DUMP payload on the node at `host` and
+    /// `port`, deleting them here afterwards unless `copy` is set.
+    Migrate {
+        host: String,
+        port: u16,
+        timeout: Duration,
+        entries: Vec<MigrateEntry>,
+        copy: bool,
+        replace: bool,
+    },
+    /// Replies with OK once `duration` elapsed, see DEBUG SLEEP.
+    Sleep(Duration),
+    /// Runs a custom command with its arguments, not including the name.
+    Custom {
+        handler: Arc<dyn CommandHandler>,
+        args: Vec<String>,
+    },
+}
+
+/// Key to migrate with its expiry and DUMP payload when MIGRATE ran.
+pub type MigrateEntry = (String, Option<std::time::Instant>, String);
+
+impl DeferredReply {
+    pub async fn resolve(self, state: &Arc<ServerState>) -> RespValue<'static> {
+        match self {
+            DeferredReply::Wait {
+                num_replicas,
+                timeout,
+                offset,
+            } => {
+                let acked = wait_for_acks(state, num_replicas, timeout, offset).await;
+                RespValue::Integer(acked as i64)
+            }
+            DeferredReply::Migrate {
+                host,
+                port,
+                timeout,
+                entries,
+                copy,
+                replace,
+            } => {
+                let mut num_migrated = 0;
+                let result =
+                    migrate_keys(&host, port, timeout, &entries, replace, &mut num_migrated).await;
+                // NOTE: Keys which reached the target are deleted even if a later one failed.
+                //       A key written by another client while waiting for the target is
+                //       kept though, as its new value was never migrated.
+                if !copy {
+                    let migrated: HashMap<&str, _> = entries[..num_migrated]
+                        .iter()
+                        .map(|(key, expires, payload)| (key.as_str(), (expires, payload)))
+                        .collect();
+                    state.delete_keys_if(migrated.keys().copied(), |key, slot| {
+                        let (expires, payload) = migrated[key];
+                        slot.expires() == *expires
+                            && dump_value(slot.value()).is_ok_and(|dump| to_hex(&dump) == *payload)
+                    });
+                }
+                match result {
+                    Ok(()) => RespValue::SimpleString("OK".into()),
+                    Err(e) => e.into(),
+                }
+            }
+            DeferredReply::Sleep(duration) => {
+                tokio::time::sleep(duration).await;
+                RespValue::SimpleString("OK".into())
+            }
+            DeferredReply::Custom { handler, args } => handler.call(args).await,
+        }
+    }
+}
+
+async fn wait_for_acks(
+    state: &Arc<ServerState>,
+    num_replicas: usize,
+    timeout: Option<Duration>,
+    offset: u64,
+) -> usize {
+    let replication = &state.replication;
+    let count_acked = || {
+        replication
+            .lock_replicas()
+            .iter()
+            .filter(|replica| replica.ack_offset >= offset)
+            .count()
+    };
+
+    let acked = count_acked();
+    if acked >= num_replicas {
+        return acked;
+    }
+
+    let mut timeout =
+        timeout.map(|timeout| state.blocking_timers.timeout_at(Instant::now() + timeout));
+    replication.request_acks();
+    loop {
+        // NOTE: Registering for the notification before counting ensures that no ACK
+        //       arriving in between is missed.
+        let notified = replication.ack_notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        let acked = count_acked();
+        if acked >= num_replicas {
+            return acked;
+        }
+        match &mut timeout {
+            Some(timeout) => {
+                tokio::select! {
+                    _ = notified => {}
+                    _ = timeout => return count_acked(),
+                }
+            }
+            None => notified.await,
+        }
+    }
+}
+
+async fn migrate_keys(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    entries: &[MigrateEntry],
+    replace: bool,
+    num_migrated: &mut usize,
+) -> Result<(), RedisError> {
+    let mut stream = tokio::time::timeout(timeout, TcpStream::connect((host, port)))
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .ok_or(RedisError::IoErr(
+            "error or timeout connecting to the client",
+        ))?;
+    let mut buffer = BytesMut::new();
+
+    for (key, expires, payload) in entries {
+        // NOTE: A TTL of 0 means no expiry, so a key about to expire keeps 1ms.
+        let ttl = expires.map_or(0, |expires| {
+            let remaining = expires.saturating_duration_since(std::time::Instant::now());
+            remaining.as_millis().max(1) as u64
+        });
+        let ttl = ttl.to_string();
+        let mut restore = vec!["RESTORE", key, &ttl, payload];
+        if replace {
+            restore.push("REPLACE");
+        }
+        // NOTE: ASKING lets the target accept keys of a slot it is still importing.
+        for args in [&["ASKING"][..], &restore] {
+            let reply = tokio::time::timeout(timeout, request(&mut stream, &mut buffer, args))
+                .await
+                .ok()
+                .and_then(Result::ok)
+                .ok_or(RedisError::IoErr(
+                    "error or timeout reading to target instance",
+                ))?;
+            reply
+                .map_err(|e| RedisError::Err(format!("Target instance replied with error: {e}")))?;
+        }
+        *num_migrated += 1;
+    }
+    Ok(())
+}
+
+/// Sends a command to another server and reads its reply, which is either a simple
+/// string or the error it replied with.
+async fn request(
+    stream: &mut TcpStream,
+    buffer: &mut BytesMut,
+    args: &[&str],
+) -> std::io::Result<Result<(), String>> {
+    let args = args
+        .iter()
+        .map(|arg| RespValue::BulkString((*arg).into()))
+        .collect();
+    stream
+        .write_all(RespValue::Array(args).to_string().as_bytes())
+        .await?;
+
+    loop {
+        match parse_resp_value(buffer) {
+            Ok((rest, value)) => {
+                let consumed = buffer.len() - rest.len();
+                let reply = match value {
+                    RespValue::SimpleString(_) => Ok(()),
+                    RespValue::SimpleError(e) => Err(e.to_string()),
+                    value => Err(format!("unexpected reply {value:?}")),
+                };
+                buffer.advance(consumed);
+                return Ok(reply);
+            }
+            Err(e) if is_incomplete(&e) => {
+                if stream.read_buf(buffer).await? == 0 {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+            Err(_) => return Err(std::io::ErrorKind::InvalidData.into()),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::acl::DEFAULT_USER;
+use crate::cluster::key_hash_slot;
+use crate::command::{find_command, Command, CustomCommand, RedisError};
+use crate::replication::MasterLinkState;
+use crate::resp::RespValue;
+use crate::server::{ClientKind, CommandName, ConnectionContext, RateLimitKey, ServerState};
+
+const RATELIMIT_ERROR: &str = "rate limit exceeded, try again later";
+
+/// Parses and executes a single request.
+///
+/// `frame` are the raw bytes `value` was parsed from, which are appended to the AOF
+/// and propagated to replicas verbatim if the command successfully modified the
+/// dataset, unless [`Command::propagated_frame`] rewrites it. Since only arrays are
+/// accepted, that is the RESP encoding of the command.
+pub fn dispatch(
+    state: &Arc<ServerState>,
+    ctx: &mut ConnectionContext,
+    value: RespValue<'_>,
+    frame: &[u8],
+) -> RespValue<'static> {
+    let RespValue::Array(args) = value else {
+        return reject(state, None, RedisError::err("command has to be Array"));
+    };
+    let custom = match args.first() {
+        Some(RespValue::BulkString(name)) => state.find_custom_command(name).cloned(),
+        _ => None,
+    };
+    let name = command_name(custom.as_deref(), &args);
+    // NOTE: ASKING only applies to the request right after it, also if that is refused.
+    let asking = std::mem::take(&mut ctx.asking);
+    // NOTE: Checked on the arguments before they are parsed, so that multi-key commands
+    //       are refused as a whole no matter how they store their keys.
+    let cross_slot = state.cluster.is_some() && is_cross_slot(&args);
+    let parsed = match custom {
+        Some(custom) => Command::custom(custom, args),
+        None => Command::try_from(args),
+    };
+    let command = match parsed {
+        Ok(command) => command,
+        Err(e) => return reject(state, name, e.into()),
+    };
+    state.clients.record_command(ctx, command.name());
+    if let Err(e) = check(state, ctx, &command, cross_slot, asking) {
+        return reject(state, Some(command.name()), e);
+    }
+
+    let persist = command.is_propagated() && ctx.kind != ClientKind::AofLoader;
+    let rewritten = if persist {
+        command.propagated_frame()
+    } else {
+        None
+    };
+    let frame = rewritten
+        .as_ref()
+        .map_or(frame, |rewritten| rewritten.as_bytes());
+
+    // NOTE: Writes hold the AOF and replica locks until they are appended and propagated,
+    //       so that neither a rewrite nor a new replica snapshots a write that is not
+    //       yet in the file or the replication stream.
+    let mut aof = match &state.aof {
+        Some(aof) if persist => Some(aof.lock()),
+        _ => None,
+    };
+    // NOTE: Replicas forward the stream of their master as is instead, see
+    //       'apply_commands', and do not propagate writes of their own clients.
+    let replicas =
+        (persist && !state.replication.is_replica()).then(|| state.replication.lock_replicas());
+
+    state
+        .stats
+        .total_commands_processed
+        .fetch_add(1, Ordering::Relaxed);
+    let name = command.name();
+    let event = if command.spec().has_flag("fast") {
+        "fast-command"
+    } else {
+        "command"
+    };
+    let start = Instant::now();
+    let response = command.execute(state, ctx);
+    let duration = start.elapsed();
+    state.latency.record(event, duration);
+
+    let error = match &response {
+        RespValue::SimpleError(e) => Some(e),
+        _ => None,
+    };
+    state.stats.record_call(name, duration, error.is_some());
+    if let Some(e) = error {
+        state.stats.record_error(e);
+    } else {
+        // NOTE: The write was applied anyway, so it is kept for the next attempt and
+        //       replicas still have to receive it, while 'check' refuses further writes.
+        if let Some(aof) = &mut aof {
+            if let Err(e) = aof.append(frame) {
+                eprintln!("Error writing to the AOF: {e}");
+            }
+        }
+        if let Some(replicas) = &replicas {
+            state.replication.propagate(replicas, frame);
+        }
+    }
+    drop(replicas);
+    if aof.take().is_some() {
+        state.rewrite_aof_if_grown();
+    }
+
+    response
+}
+
+/// Checks whether the connection may run the command in the current state of the
+/// server, returning the error to reply with otherwise.
+///
+/// `cross_slot` is set if the keys of the request hash to different slots, see
+/// [`is_cross_slot`], and `asking` if the previous request was ASKING.
+fn check(
+    state: &ServerState,
+    ctx: &ConnectionContext,
+    command: &Command,
+    cross_slot: bool,
+    asking: bool,
+) -> Result<(), RedisError> {
+    // NOTE: Checked first, so that it also limits attempts to guess passwords.
+    if ctx.kind == ClientKind::Normal && !acquire_rate_limit(state, ctx) {
+        return Err(RedisError::err(RATELIMIT_ERROR));
+    }
+    if ctx.kind == ClientKind::Normal && !command.is_allowed_unauthenticated() {
+        if state.requires_auth(ctx) {
+            return Err(RedisError::NoAuth);
+        }
+        let user = ctx.user.as_deref().unwrap_or(DEFAULT_USER);
+        state.acl.check(user, command)?;
+    }
+
+    if let (Some(cluster), ClientKind::Normal) = (&state.cluster, ctx.kind) {
+        if cross_slot {
+            return Err(RedisError::CrossSlot);
+        }
+        let redirect = cluster.redirect(&command.keys(), asking, |key| {
+            state.db.lock().unwrap().get(key).is_some()
+        });
+        if let Some(redirect) = redirect {
+            return Err(redirect);
+        }
+    }
+
+    if ctx.kind == ClientKind::Normal
+        && !command.is_allowed_when_stale()
+        && state.replication.is_replica()
+        && !state.config().replica_serve_stale_data
+        && state.replication.master_link() != MasterLinkState::Connected
+    {
+        return Err(RedisError::MasterDown);
+    }
+
+    // NOTE: Writes streamed from the master are always applied, only clients are rejected.
+    if command.is_write()
+        && ctx.kind == ClientKind::Normal
+        && state.replication.is_replica()
+        && state.config().replica_read_only
+    {
+        return Err(RedisError::ReadOnly);
+    }
+    if command.is_write()
+        && ctx.kind == ClientKind::Normal
+        && state.writes_stopped_by_bgsave_error()
+    {
+        return Err(RedisError::MisConf);
+    }
+    if command.is_write() && ctx.kind == ClientKind::Normal {
+        if let Some(e) = state.aof_write_error() {
+            return Err(RedisError::AofWrite(e));
+        }
+    }
+    Ok(())
+}
+
+/// Takes a token from the bucket of the connection, returning whether it may run a
+/// command or exceeded `rate-limit`.
+fn acquire_rate_limit(state: &ServerState, ctx: &ConnectionContext) -> bool {
+    let (rate, burst, by) = {
+        let config = state.config();
+        (
+            config.rate_limit,
+            config.rate_limit_burst,
+            config.rate_limit_by,
+        )
+    };
+    if rate == 0 {
+        return true;
+    }
+    // NOTE: Internal connections, which aren't registered, are never limited.
+    let ip = match state.clients.addr(ctx.id) {
+        Some(addr) => addr.ip(),
+        None => return true,
+    };
+    let key = RateLimitKey::new(by, ctx.id, ip);
+    state
+        .rate_limiter
+        .try_acquire(key, rate, burst, Instant::now())
+}
+
+/// Whether the keys of the request, at the positions given by the command table, hash
+/// to different slots.
+///
+/// NOTE: This doesn't see the keys of "movablekeys" commands outside of those positions,
+///       e.g. after KEYS of MIGRATE. [`ClusterState::redirect`] checks all parsed keys
+///       again and is authoritative for those.
+///
+/// [`ClusterState::redirect`]: crate::cluster::ClusterState::redirect
+fn is_cross_slot(args: &[RespValue]) -> bool {
+    let Some(RespValue::BulkString(name)) = args.first() else {
+        return false;
+    };
+    let Some(spec) = find_command(name) else {
+        return false;
+    };
+    let spec = match args.get(1) {
+        Some(RespValue::BulkString(subcommand)) => spec.subcommand(subcommand).unwrap_or(spec),
+        _ => spec,
+    };
+    let mut slots = spec
+        .key_positions(args.len())
+        .filter_map(|i| match &args[i] {
+            RespValue::BulkString(key) => Some(key_hash_slot(key.as_bytes())),
+            _ => None,
+        });
+    match slots.next() {
+        Some(first) => slots.any(|slot| slot != first),
+        None => false,
+    }
+}
+
+/// Counts a request refused before it ran, by the command if it is known.
+fn reject(state: &ServerState, name: Option<CommandName>, error: RedisError) -> RespValue<'static> {
+    if let Some(name) = name {
+        state.stats.record_rejected_call(name);
+    }
+    state.stats.record_error(&error.to_string());
+    error.into()
+}
+
+/// Name of the requested command, also if its arguments turn out to be invalid.
+///
+/// `custom` is the custom command the request names, if any.
+fn command_name(custom: Option<&CustomCommand>, args: &[RespValue]) -> Option<CommandName> {
+    if let Some(custom) = custom {
+        return Some((custom.spec.name, None));
+    }
+    let Some(RespValue::BulkString(name)) = args.first() else {
+        return None;
+    };
+    let spec = find_command(name)?;
+    let subcommand = match args.get(1) {
+        Some(RespValue::BulkString(subcommand)) => spec.subcommand(subcommand),
+        _ => None,
+    };
+    Some((spec.name, subcommand.map(|subcommand| subcommand.name)))
+}
@@ -0,0 +1,855 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::acl::{ACL_CATEGORIES, DEFAULT_USER};
+use crate::cluster::{cluster_shards, cluster_slots, key_hash_slot};
+use crate::command::{
+    find_command, lolwut, Command, CommandSpec, DeferredReply, RedisError, COMMAND_TABLE,
+};
+use crate::config::CONFIG_ENTRIES;
+use crate::db::{dump_json, load_json, unix_ms_to_instant, Database, DatabaseSlot};
+use crate::rdb::{dump_value, RdbReader};
+use crate::replication::{run_replica_link, MasterLinkState};
+use crate::resp::RespValue;
+use crate::server::{
+    info, memory_stats, ClientKind, CommandName, ConnectionContext, ServerState, REDIS_VERSION,
+};
+use crate::util::{from_hex, glob_match, glob_match_fuzz, to_hex};
+
+const INVALID_CLIENT_NAME_ERROR: &str =
+    "Client names cannot contain spaces, newlines or special characters.";
+
+/// Timeout of MIGRATE if none is given, same as in Redis.
+const DEFAULT_MIGRATE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+impl Command {
+    pub fn execute(
+        self,
+        state: &Arc<ServerState>,
+        ctx: &mut ConnectionContext,
+    ) -> RespValue<'static> {
+        match self {
+            Command::Command => {
+                RespValue::Array(COMMAND_TABLE.iter().map(|spec| spec.info(None)).collect())
+            }
+            Command::Help(name) => find_command(name)
+                .expect("every container is in the command table")
+                .help(),
+            Command::CommandCount => RespValue::Integer(COMMAND_TABLE.len() as i64),
+            Command::CommandInfo(names) if names.is_empty() => {
+                RespValue::Array(COMMAND_TABLE.iter().map(|spec| spec.info(None)).collect())
+            }
+            Command::CommandInfo(names) => RespValue::Array(
+                names
+                    .iter()
+                    .map(|name| match find_command_or_subcommand(name) {
+                        Some((spec, parent)) => spec.info(parent),
+                        None => RespValue::NullBulkString,
+                    })
+                    .collect(),
+            ),
+            Command::CommandDocs(names) => {
+                let specs: Vec<_> = if names.is_empty() {
+                    COMMAND_TABLE.iter().map(|spec| (spec, None)).collect()
+                } else {
+                    names
+                        .iter()
+                        .filter_map(|name| find_command_or_subcommand(name))
+                        .collect()
+                };
+                let mut docs = Vec::new();
+                for (spec, parent) in specs {
+                    docs.push(RespValue::BulkString(spec.full_name(parent).into()));
+                    docs.push(spec.docs());
+                }
+                RespValue::Array(docs)
+            }
+            Command::Echo(message) => RespValue::BulkString(message.into()),
+            Command::Ping(None) => RespValue::SimpleString("PONG".into()),
+            Command::Ping(Some(message)) => RespValue::BulkString(message.into()),
+            Command::Auth(user, password) => {
+                let nopass = state
+                    .acl
+                    .lock_users()
+                    .get(DEFAULT_USER)
+                    .is_some_and(|user| user.nopass);
+                if user.is_none() && nopass {
+                    return RedisError::err(
+                        "AUTH <password> called without any password configured for the default \
+                         user. Are you sure your configuration is correct?",
+                    )
+                    .into();
+                }
+                let user = user.unwrap_or_else(|| String::from(DEFAULT_USER));
+                if !state.acl.authenticate(&user, &password) {
+                    return RedisError::WrongPass.into();
+                }
+                ctx.user = Some(user);
+                RespValue::SimpleString("OK".into())
+            }
+            Command::Hello {
+                protover,
+                auth,
+                setname,
+            } => {
+                // NOTE: Replies are always RESP2, so clients asking for RESP3 are refused
+                //       and fall back to RESP2 instead of misreading the replies.
+                if protover.is_some_and(|protover| protover != 2) {
+                    return RedisError::NoProto.into();
+                }
+                if setname
+                    .as_deref()
+                    .is_some_and(|name| !is_valid_client_name(name))
+                {
+                    return RedisError::err(INVALID_CLIENT_NAME_ERROR).into();
+                }
+                match auth {
+                    Some((user, password)) if state.acl.authenticate(&user, &password) => {
+                        ctx.user = Some(user);
+                    }
+                    Some(_) => return RedisError::WrongPass.into(),
+                    None if state.requires_auth(ctx) => {
+                        return RedisError::HelloNoAuth.into();
+                    }
+                    None => {}
+                }
+                if let Some(name) = setname {
+                    state.clients.update(ctx.id, |client| client.name = name);
+                }
+                let mode = if state.cluster.is_some() {
+                    "cluster"
+                } else {
+                    "standalone"
+                };
+                let role = if state.replication.is_replica() {
+                    "replica"
+                } else {
+                    "master"
+                };
+                let fields = [
+                    ("server", RespValue::BulkString("redis".into())),
+                    ("version", RespValue::BulkString(REDIS_VERSION.into())),
+                    ("proto", RespValue::Integer(2)),
+                    ("id", RespValue::Integer(ctx.id as i64)),
+                    ("mode", RespValue::BulkString(mode.into())),
+                    ("role", RespValue::BulkString(role.into())),
+                    ("modules", RespValue::Array(vec![])),
+                ];
+                RespValue::Array(
+                    fields
+                        .into_iter()
+                        .flat_map(|(name, value)| [RespValue::BulkString(name.into()), value])
+                        .collect(),
+                )
+            }
+            Command::Quit => {
+                ctx.quit = true;
+                RespValue::SimpleString("OK".into())
+            }
+            Command::ClientId => RespValue::Integer(ctx.id as i64),
+            Command::ClientInfo => match state.clients.lock_clients().get(&ctx.id) {
+                Some(client) => RespValue::BulkString(format!("{}\n", client.render()).into()),
+                None => RespValue::NullBulkString,
+            },
+            Command::ClientList => {
+                let list: String = state
+                    .clients
+                    .lock_clients()
+                    .values()
+                    .map(|client| format!("{}\n", client.render()))
+                    .collect();
+                RespValue::BulkString(list.into())
+            }
+            Command::ClientGetName => match state.clients.lock_clients().get(&ctx.id) {
+                Some(client) if !client.name.is_empty() => {
+                    RespValue::BulkString(client.name.clone().into())
+                }
+                _ => RespValue::NullBulkString,
+            },
+            Command::ClientSetName(name) => {
+                if !is_valid_client_name(&name) {
+                    return RedisError::err(INVALID_CLIENT_NAME_ERROR).into();
+                }
+                state.clients.update(ctx.id, |client| client.name = name);
+                RespValue::SimpleString("OK".into())
+            }
+            Command::AclSetUser(user, rules) => match state.acl.set_user(&user, &rules) {
+                Ok(()) => RespValue::SimpleString("OK".into()),
+                Err(e) => e.into(),
+            },
+            Command::AclGetUser(name) => {
+                let users = state.acl.lock_users();
+                let Some(user) = users.get(&name) else {
+                    return RespValue::NullBulkString;
+                };
+                let strings = |strings: Vec<String>| {
+                    RespValue::Array(
+                        strings
+                            .into_iter()
+                            .map(|s| RespValue::BulkString(s.into()))
+                            .collect(),
+                    )
+                };
+                let flags = user.flags().into_iter().map(String::from).collect();
+                let fields = [
+                    ("flags", strings(flags)),
+                    (
+                        "passwords",
+                        strings(user.passwords.iter().cloned().collect()),
+                    ),
+                    (
+                        "commands",
+                        RespValue::BulkString(user.describe_commands().into()),
+                    ),
+                    ("keys", RespValue::BulkString(user.describe_keys().into())),
+                    (
+                        "channels",
+                        RespValue::BulkString(user.describe_channels().into()),
+                    ),
+                    ("selectors", RespValue::Array(vec![])),
+                ];
+                RespValue::Array(
+                    fields
+                        .into_iter()
+                        .flat_map(|(name, value)| [RespValue::BulkString(name.into()), value])
+                        .collect(),
+                )
+            }
+            Command::AclDelUser(users) => match state.acl.delete_users(&users) {
+                Ok(deleted) => RespValue::Integer(deleted as i64),
+                Err(e) => e.into(),
+            },
+            Command::AclList => RespValue::Array(
+                state
+                    .acl
+                    .lock_users()
+                    .values()
+                    .map(|user| RespValue::BulkString(user.describe().into()))
+                    .collect(),
+            ),
+            Command::AclWhoAmI => {
+                let user = ctx
+                    .user
+                    .clone()
+                    .unwrap_or_else(|| String::from(DEFAULT_USER));
+                RespValue::BulkString(user.into())
+            }
+            Command::AclCat(None) => RespValue::Array(
+                ACL_CATEGORIES
+                    .iter()
+                    .map(|&category| RespValue::BulkString(category.into()))
+                    .collect(),
+            ),
+            Command::AclCat(Some(category)) => {
+                let category = category.to_ascii_lowercase();
+                if !ACL_CATEGORIES.contains(&category.as_str()) {
+                    return RedisError::Err(format!("Unknown category '{category}'")).into();
+                }
+                let mut commands = Vec::new();
+                for spec in COMMAND_TABLE {
+                    if spec.categories.contains(&category.as_str()) {
+                        commands.push(RespValue::BulkString(spec.name.into()));
+                    }
+                    for subcommand in spec.subcommands {
+                        if subcommand.categories.contains(&category.as_str()) {
+                            let name = format!("{}|{}", spec.name, subcommand.name);
+                            commands.push(RespValue::BulkString(name.into()));
+                        }
+                    }
+                }
+                RespValue::Array(commands)
+            }
+            Command::Save => match state.save() {
+                Ok(()) => RespValue::SimpleString("OK".into()),
+                Err(e) => RedisError::err(e).into(),
+            },
+            Command::BgSave => match state.bgsave() {
+                Ok(true) => RespValue::SimpleString("Background saving started".into()),
+                Ok(false) => RedisError::err("Background save already in progress").into(),
+                Err(e) => RedisError::err(e).into(),
+            },
+            Command::LastSave => {
+                let last_save = state.rdb_last_save_time.load(Ordering::Relaxed);
+                RespValue::Integer(last_save as i64)
+            }
+            Command::BgRewriteAof => match state.bgrewriteaof() {
+                Ok(true) => {
+                    RespValue::SimpleString("Background append only file rewriting started".into())
+                }
+                Ok(false) => {
+                    RedisError::err("Background append only file rewriting already in progress")
+                        .into()
+                }
+                Err(e) => RedisError::err(e).into(),
+            },
+            Command::Info(sections) => RespValue::BulkString(info(state, &sections).into()),
+            Command::DebugDumpJson(path) => {
+                let json = dump_json(&state.db.lock().unwrap());
+                match std::fs::write(path, json) {
+                    Ok(()) => RespValue::SimpleString("OK".into()),
+                    Err(e) => RedisError::err(e).into(),
+                }
+            }
+            // NOTE: Like 'DEBUG RELOAD' this replaces the dataset without being persisted
+            //       to the AOF.
+            Command::DebugLoadJson(path) => {
+                let db = std::fs::read_to_string(path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| Ok(load_json(&json)?));
+                match db {
+                    Ok(db) => {
+                        *state.db.lock().unwrap() = db;
+                        RespValue::SimpleString("OK".into())
+                    }
+                    Err(e) => RedisError::err(e).into(),
+                }
+            }
+            // NOTE: Only the connection waits, like a blocked client, other clients and the
+            //       background tasks sharing its worker thread keep running.
+            Command::DebugSleep(duration) => {
+                ctx.deferred = Some(DeferredReply::Sleep(duration));
+                RespValue::Null
+            }
+            Command::DebugObject(key) => {
+                let limits = state.config().encoding_limits;
+                let db = state.db.lock().unwrap();
+                let Some(slot) = db.get(&key) else {
+                    return RedisError::err("no such key").into();
+                };
+                // NOTE: The serialized length excludes the type byte, RDB version and
+                //       checksum DUMP adds around the value.
+                let serialized_len = dump_value(slot.value()).map_or(0, |dump| dump.len() - 11);
+                RespValue::SimpleString(
+                    format!(
+                        "Value at:{:p} refcount:1 encoding:{} serializedlength:{serialized_len} \
+                         lru:0 lru_seconds_idle:0",
+                        slot,
+                        slot.value().encoding(&limits)
+                    )
+                    .into(),
+                )
+            }
+            Command::DebugJmap => match std::fs::read_to_string("/proc/self/maps") {
+                Ok(maps) => {
+                    println!("Memory mappings of the process:\n{maps}");
+                    RespValue::SimpleString("OK".into())
+                }
+                Err(e) => RedisError::err(e).into(),
+            },
+            Command::DebugSetActiveExpire(enabled) => {
+                state
+                    .active_expire_enabled
+                    .store(enabled, Ordering::Relaxed);
+                RespValue::SimpleString("OK".into())
+            }
+            Command::DebugStringMatchLen => {
+                glob_match_fuzz(1_000_000);
+                RespValue::SimpleString("Apparently Redis did not crash: test passed".into())
+            }
+            Command::DebugReload { save } => match state.reload_rdb(save) {
+                Ok(()) => {
+                    println!("DB reloaded by DEBUG RELOAD");
+                    RespValue::SimpleString("OK".into())
+                }
+                Err(e) => RedisError::err(e).into(),
+            },
+            Command::DebugChangeReplId => {
+                state.replication.change_replid();
+                println!("Changed replication IDs after receiving DEBUG change-repl-id");
+                RespValue::SimpleString("OK".into())
+            }
+            Command::DebugConfigReload => match state.reload_config() {
+                Ok(reload) => {
+                    let names = |names: Vec<&'static str>| {
+                        RespValue::Array(
+                            names
+                                .into_iter()
+                                .map(|name| RespValue::BulkString(name.into()))
+                                .collect(),
+                        )
+                    };
+                    RespValue::Array(vec![
+                        RespValue::BulkString("applied".into()),
+                        names(reload.applied),
+                        RespValue::BulkString("ignored".into()),
+                        names(reload.ignored),
+                    ])
+                }
+                Err(e) => RedisError::err(e).into(),
+            },
+            Command::ReplConf(options) => {
+                for (option, value) in options {
+                    if option.eq_ignore_ascii_case("listening-port") {
+                        let Ok(port) = value.parse() else {
+                            return RedisError::err("invalid listening-port").into();
+                        };
+                        ctx.listening_port = Some(port);
+                    } else if option.eq_ignore_ascii_case("getack") {
+                        let offset = state.replication.repl_offset.load(Ordering::Relaxed);
+                        ctx.force_reply = true;
+                        return RespValue::Array(vec![
+                            RespValue::BulkString("REPLCONF".into()),
+                            RespValue::BulkString("ACK".into()),
+                            RespValue::BulkString(offset.to_string().into()),
+                        ]);
+                    }
+                    // NOTE: Capabilities only matter once there are alternatives to PSYNC2,
+                    //       and ACKs are handled by the connection streaming to the replica.
+                }
+                RespValue::SimpleString("OK".into())
+            }
+            Command::LatencyLatest => RespValue::Array(
+                state
+                    .latency
+                    .lock_events()
+                    .iter()
+                    .filter_map(|(name, event)| {
+                        let last = event.samples.back()?;
+                        Some(RespValue::Array(vec![
+                            RespValue::BulkString((*name).into()),
+                            RespValue::Integer(last.time as i64),
+                            RespValue::Integer(last.latency_ms as i64),
+                            RespValue::Integer(event.max_ms as i64),
+                        ]))
+                    })
+                    .collect(),
+            ),
+            Command::LatencyHistory(name) => {
+                let events = state.latency.lock_events();
+                let samples = events
+                    .iter()
+                    .find(|(event, _)| event.eq_ignore_ascii_case(&name))
+                    .map(|(_, event)| &event.samples);
+                RespValue::Array(
+                    samples
+                        .into_iter()
+                        .flatten()
+                        .map(|sample| {
+                            RespValue::Array(vec![
+                                RespValue::Integer(sample.time as i64),
+                                RespValue::Integer(sample.latency_ms as i64),
+                            ])
+                        })
+                        .collect(),
+                )
+            }
+            Command::LatencyHistogram(names) => {
+                let matches = |(name, subcommand): CommandName| {
+                    names.is_empty()
+                        || names
+                            .iter()
+                            .any(|requested| match requested.split_once('|') {
+                                Some((requested, requested_subcommand)) => {
+                                    requested.eq_ignore_ascii_case(name)
+                                        && subcommand.is_some_and(|subcommand| {
+                                            requested_subcommand.eq_ignore_ascii_case(subcommand)
+                                        })
+                                }
+                                // NOTE: Containers stand for all of their subcommands.
+                                None => requested.eq_ignore_ascii_case(name),
+                            })
+                };
+                let mut reply = Vec::new();
+                for (&name, command) in state.stats.lock_commands().iter() {
+                    if command.calls == 0 || !matches(name) {
+                        continue;
+                    }
+                    let histogram = command
+                        .histogram
+                        .cumulative()
+                        .into_iter()
+                        .flat_map(|(usec, calls)| {
+                            [
+                                RespValue::Integer(usec as i64),
+                                RespValue::Integer(calls as i64),
+                            ]
+                        })
+                        .collect();
+                    let name = match name {
+                        (name, Some(subcommand)) => format!("{name}|{subcommand}"),
+                        (name, None) => name.to_string(),
+                    };
+                    reply.push(RespValue::BulkString(name.into()));
+                    reply.push(RespValue::Array(vec![
+                        RespValue::BulkString("calls".into()),
+                        RespValue::Integer(command.calls as i64),
+                        RespValue::BulkString("histogram_usec".into()),
+                        RespValue::Array(histogram),
+                    ]));
+                }
+                RespValue::Array(reply)
+            }
+            Command::LatencyReset(events) => {
+                RespValue::Integer(state.latency.reset(&events) as i64)
+            }
+            Command::LatencyDoctor => RespValue::BulkString(state.latency.doctor().into()),
+            Command::MemoryStats => {
+                let memory = memory_stats(state);
+                let integer = |name: &'static str, value: u64| {
+                    [
+                        RespValue::BulkString(name.into()),
+                        RespValue::Integer(value as i64),
+                    ]
+                };
+                let float = |name: &'static str, value: f64| {
+                    [
+                        RespValue::BulkString(name.into()),
+                        RespValue::BulkString(format!("{value:.6}").into()),
+                    ]
+                };
+                let db = RespValue::Array(
+                    [
+                        integer("overhead.hashtable.main", memory.db_hashtable_main),
+                        integer("overhead.hashtable.expires", memory.db_hashtable_expires),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+                );
+                let fragmentation_bytes = memory.rss.saturating_sub(memory.total_allocated);
+                RespValue::Array(
+                    [
+                        integer("peak.allocated", memory.peak_allocated),
+                        integer("total.allocated", memory.total_allocated),
+                        integer("startup.allocated", memory.startup_allocated),
+                        integer("replication.backlog", memory.replication_backlog),
+                        integer("clients.slaves", memory.clients_replicas),
+                        integer("clients.normal", memory.clients_normal),
+                        integer("aof.buffer", memory.aof_buffer),
+                        [RespValue::BulkString("db.0".into()), db],
+                        integer("overhead.total", memory.overhead_total),
+                        integer("keys.count", memory.keys_count),
+                        integer("keys.bytes-per-key", memory.bytes_per_key()),
+                        integer("dataset.bytes", memory.dataset_bytes),
+                        float("dataset.percentage", memory.dataset_percentage()),
+                        float("peak.percentage", memory.peak_percentage()),
+                        integer("allocator.resident", memory.rss),
+                        float("fragmentation", memory.fragmentation()),
+                        integer("fragmentation.bytes", fragmentation_bytes),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+                )
+            }
+            Command::MemoryDoctor => RespValue::BulkString(memory_stats(state).doctor().into()),
+            // NOTE: The connection replies with CONTINUE or FULLRESYNC once it took the
+            //       snapshot or the missing part of the backlog, see 'serve_replica'.
+            Command::Psync(replid, offset) => {
+                let replication = &state.replication;
+                if replication.is_replica()
+                    && replication.master_link() != MasterLinkState::Connected
+                {
+                    return RedisError::NoMasterLink.into();
+                }
+                ctx.kind = ClientKind::Replica;
+                let offset = u64::try_from(offset)
+                    .ok()
+                    .filter(|&offset| replication.can_continue(&replid, offset));
+                ctx.psync_offset = offset;
+                RespValue::Null
+            }
+            Command::ConfigGet(patterns) => {
+                let config = state.config();
+                let mut reply = Vec::new();
+                for entry in CONFIG_ENTRIES {
+                    let matches = patterns
+                        .iter()
+                        .any(|pattern| glob_match(pattern.as_bytes(), entry.name.as_bytes(), true));
+                    if matches {
+                        reply.push(RespValue::BulkString(entry.name.into()));
+                        reply.push(RespValue::BulkString((entry.get)(&config).into()));
+                    }
+                }
+                RespValue::Array(reply)
+            }
+            Command::ConfigSet(params) => match state.config_set(&params) {
+                Ok(()) => RespValue::SimpleString("OK".into()),
+                Err(e) => e.into(),
+            },
+            // NOTE: Handlers are async, so they run once the connection resolves the reply.
+            Command::Custom(custom, args) => {
+                ctx.deferred = Some(DeferredReply::Custom {
+                    handler: custom.handler.clone(),
+                    args,
+                });
+                RespValue::Null
+            }
+            Command::Wait(num_replicas, timeout_ms) => {
+                if state.replication.is_replica() {
+                    return RedisError::err("WAIT cannot be used with replica instances").into();
+                }
+                ctx.deferred = Some(DeferredReply::Wait {
+                    num_replicas,
+                    // NOTE: A timeout of 0 blocks forever.
+                    timeout: (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms)),
+                    offset: state.replication.repl_offset.load(Ordering::Relaxed),
+                });
+                RespValue::Null
+            }
+            Command::Del(keys) => {
+                let mut db = state.db.lock().unwrap();
+                let now = Instant::now();
+                let removed = keys
+                    .iter()
+                    .filter_map(|key| db.remove(key))
+                    .filter(|slot| !slot.is_expired(now))
+                    .count();
+                RespValue::Integer(removed as i64)
+            }
+            Command::Scan {
+                cursor,
+                pattern,
+                count,
+                kind,
+            } => {
+                let db = state.db.lock().unwrap();
+                let (cursor, entries) = db.scan(cursor, count);
+                // NOTE: Like in Redis the filters apply after the keys were selected, so
+                //       fewer than COUNT keys may be returned before the iteration ends.
+                let keys = entries
+                    .into_iter()
+                    .filter(|(key, _)| match &pattern {
+                        Some(pattern) => glob_match(pattern.as_bytes(), key.as_bytes(), false),
+                        None => true,
+                    })
+                    .filter(|(_, slot)| match &kind {
+                        Some(kind) => slot.value().type_name() == kind,
+                        None => true,
+                    })
+                    .map(|(key, _)| RespValue::BulkString(key.clone().into()))
+                    .collect();
+                RespValue::Array(vec![
+                    RespValue::BulkString(cursor.to_string().into()),
+                    RespValue::Array(keys),
+                ])
+            }
+            // NOTE: Replies are always RESP2, which has no verbatim strings.
+            Command::Lolwut { version, params } => {
+                RespValue::BulkString(lolwut(version, &params).into())
+            }
+            // NOTE: Bulk strings only hold UTF-8 here, so DUMP payloads are hex encoded,
+            //       which real Redis can't RESTORE. MIGRATE only works between these
+            //       servers for the same reason.
+            Command::Dump(key) => {
+                let db = state.db.lock().unwrap();
+                let slot = db.get(&key);
+                state.stats.record_lookup(slot.is_some());
+                match slot {
+                    Some(slot) => match dump_value(slot.value()) {
+                        Ok(payload) => RespValue::BulkString(to_hex(&payload).into()),
+                        Err(e) => RedisError::err(e).into(),
+                    },
+                    None => RespValue::NullBulkString,
+                }
+            }
+            Command::Restore {
+                key,
+                ttl_ms,
+                payload,
+                replace,
+                absttl,
+            } => {
+                let Some(value) = from_hex(&payload)
+                    .and_then(|payload| RdbReader::new(&payload).read_dump().ok())
+                else {
+                    return RedisError::err("DUMP payload version or checksum are wrong").into();
+                };
+                let mut db = state.db.lock().unwrap();
+                if !replace && db.get(&key).is_some() {
+                    return RedisError::BusyKey.into();
+                }
+                let expires = match (ttl_ms, absttl) {
+                    (0, _) => None,
+                    (ttl_ms, false) => Some(Instant::now() + Duration::from_millis(ttl_ms)),
+                    (expires_ms, true) => match unix_ms_to_instant(expires_ms) {
+                        Some(expires) => Some(expires),
+                        // NOTE: Like in Redis a key which already expired is not created,
+                        //       but still replaces the existing one.
+                        None => {
+                            db.remove(&key);
+                            return RespValue::SimpleString("OK".into());
+                        }
+                    },
+                };
+                let slot = match expires {
+                    Some(expires) => DatabaseSlot::Timed { expires, value },
+                    None => DatabaseSlot::Simple(value),
+                };
+                db.insert(key, slot);
+                RespValue::SimpleString("OK".into())
+            }
+            Command::Migrate {
+                host,
+                port,
+                keys,
+                timeout_ms,
+                copy,
+                replace,
+            } => {
+                let db = state.db.lock().unwrap();
+                let mut entries = Vec::new();
+                for key in keys {
+                    let Some(slot) = db.get(&key) else {
+                        continue;
+                    };
+                    let payload = match dump_value(slot.value()) {
+                        Ok(payload) => to_hex(&payload),
+                        Err(e) => return RedisError::err(e).into(),
+                    };
+                    entries.push((key, slot.expires(), payload));
+                }
+                if entries.is_empty() {
+                    return RespValue::SimpleString("NOKEY".into());
+                }
+
+                ctx.deferred = Some(DeferredReply::Migrate {
+                    host,
+                    port,
+                    timeout: match timeout_ms {
+                        0 => DEFAULT_MIGRATE_TIMEOUT,
+                        timeout_ms => Duration::from_millis(timeout_ms),
+                    },
+                    entries,
+                    copy,
+                    replace,
+                });
+                RespValue::Null
+            }
+            Command::ReplicaOf(None) => {
+                if state.replication.is_replica() {
+                    state.replication.promote();
+                    println!("MASTER MODE enabled");
+                }
+                RespValue::SimpleString("OK".into())
+            }
+            Command::ReplicaOf(Some((host, port))) => {
+                if state.replication.master() == Some((host.clone(), port)) {
+                    return RespValue::SimpleString(
+                        "OK Already connected to specified master".into(),
+                    );
+                }
+                println!("REPLICAOF {host}:{port} enabled");
+                let generation = state.replication.set_master(host, port);
+                state.spawn(run_replica_link(state.clone(), generation));
+                RespValue::SimpleString("OK".into())
+            }
+            Command::ClusterInfo => match &state.cluster {
+                Some(cluster) => RespValue::BulkString(cluster.info().into()),
+                None => cluster_disabled(),
+            },
+            Command::ClusterMyId => match &state.cluster {
+                Some(cluster) => RespValue::BulkString(cluster.myid.clone().into()),
+                None => cluster_disabled(),
+            },
+            Command::ClusterKeySlot(key) => match &state.cluster {
+                Some(_) => RespValue::Integer(key_hash_slot(key.as_bytes()).into()),
+                None => cluster_disabled(),
+            },
+            Command::ClusterSlots => match &state.cluster {
+                Some(cluster) => cluster_slots(cluster),
+                None => cluster_disabled(),
+            },
+            Command::ClusterShards => match &state.cluster {
+                Some(cluster) => {
+                    let offset = state.replication.repl_offset.load(Ordering::Relaxed);
+                    cluster_shards(cluster, offset)
+                }
+                None => cluster_disabled(),
+            },
+            Command::ClusterAddSlots(slots) => match &state.cluster {
+                Some(cluster) => match cluster.add_slots(&slots) {
+                    Ok(()) => RespValue::SimpleString("OK".into()),
+                    Err(e) => e.into(),
+                },
+                None => cluster_disabled(),
+            },
+            Command::ClusterDelSlots(slots) => match &state.cluster {
+                Some(cluster) => match cluster.del_slots(&slots) {
+                    Ok(()) => RespValue::SimpleString("OK".into()),
+                    Err(e) => e.into(),
+                },
+                None => cluster_disabled(),
+            },
+            Command::ClusterNodes => match &state.cluster {
+                Some(cluster) => RespValue::BulkString(cluster.nodes().into()),
+                None => cluster_disabled(),
+            },
+            Command::ClusterMeet(ip, port) => match &state.cluster {
+                Some(cluster) => {
+                    cluster.meet(ip, port);
+                    RespValue::SimpleString("OK".into())
+                }
+                None => cluster_disabled(),
+            },
+            Command::ClusterSetSlot(slot, action) => match &state.cluster {
+                Some(cluster) => {
+                    let has_keys = keys_in_slot(&state.db.lock().unwrap(), slot)
+                        .next()
+                        .is_some();
+                    match cluster.set_slot(slot, action, has_keys) {
+                        Ok(()) => RespValue::SimpleString("OK".into()),
+                        Err(e) => e.into(),
+                    }
+                }
+                None => cluster_disabled(),
+            },
+            Command::ClusterCountKeysInSlot(slot) => match &state.cluster {
+                Some(_) => {
+                    let count = keys_in_slot(&state.db.lock().unwrap(), slot).count();
+                    RespValue::Integer(count as i64)
+                }
+                None => cluster_disabled(),
+            },
+            Command::ClusterGetKeysInSlot(slot, count) => match &state.cluster {
+                Some(_) => RespValue::Array(
+                    keys_in_slot(&state.db.lock().unwrap(), slot)
+                        .take(count)
+                        .map(|key| RespValue::BulkString(key.clone().into()))
+                        .collect(),
+                ),
+                None => cluster_disabled(),
+            },
+            Command::Asking => match &state.cluster {
+                Some(_) => {
+                    ctx.asking = true;
+                    RespValue::SimpleString("OK".into())
+                }
+                None => cluster_disabled(),
+            },
+        }
+    }
+}
+
+/// Keys stored in `slot`, found by scanning every key.
+fn keys_in_slot(db: &Database, slot: u16) -> impl Iterator<Item = &String> {
+    let now = Instant::now();
+    db.iter()
+        .filter(move |(key, value)| !value.is_expired(now) && key_hash_slot(key.as_bytes()) == slot)
+        .map(|(key, _)| key)
+}
+
+/// Whether `name` may be set by CLIENT SETNAME or HELLO.
+///
+/// NOTE: Names are listed space separated by CLIENT LIST, so they must not contain
+///       spaces or anything that would break the line.
+fn is_valid_client_name(name: &str) -> bool {
+    name.bytes().all(|b| b.is_ascii_graphic())
+}
+
+fn cluster_disabled() -> RespValue<'static> {
+    RedisError::err("This instance has cluster support disabled").into()
+}
+
+/// Looks up a command, or a subcommand given as e.g. "config|get" together with the
+/// name of its container.
+fn find_command_or_subcommand(name: &str) -> Option<(&'static CommandSpec, Option<&'static str>)> {
+    match name.split_once('|') {
+        Some((name, subcommand)) => {
+            let spec = find_command(name)?;
+            Some((spec.subcommand(subcommand)?, Some(spec.name)))
+        }
+        None => find_command(name).map(|spec| (spec, None)),
+    }
+}
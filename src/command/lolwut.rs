@@ -0,0 +1,155 @@
+use std::f32::consts::PI;
+
+use crate::server::REDIS_VERSION;
+use crate::util::random_f32;
+
+/// Size of the drawing if none is given, same as in Redis.
+const DEFAULT_COLUMNS: i64 = 66;
+const DEFAULT_SQUARES_PER_ROW: i64 = 8;
+const DEFAULT_SQUARES_PER_COLUMN: i64 = 12;
+
+/// Output of LOLWUT, which is the art of `version` followed by the server version.
+///
+/// NOTE: Only the art of version 5 is drawn, which is also used if no version is given.
+///       Versions without art only print the server version, same as in Redis.
+pub fn lolwut(version: Option<i64>, params: &[i64]) -> String {
+    if version.is_some_and(|version| version != 5) {
+        return format!("Redis ver. {REDIS_VERSION}\n");
+    }
+    let param = |i: usize, default: i64, max: i64| {
+        params.get(i).copied().unwrap_or(default).clamp(1, max) as usize
+    };
+    let columns = param(0, DEFAULT_COLUMNS, 1000);
+    let squares_per_row = param(1, DEFAULT_SQUARES_PER_ROW, 200);
+    let squares_per_column = param(2, DEFAULT_SQUARES_PER_COLUMN, 200);
+
+    let mut output = schotter(columns, squares_per_row, squares_per_column).render();
+    output.push_str(&format!(
+        "\nGeorg Nees - schotter, plotter on paper, 1968. Redis ver. {REDIS_VERSION}\n"
+    ));
+    output
+}
+
+/// Draws Georg Nees' "Schotter", a grid of squares which get more disordered
+/// towards the bottom.
+fn schotter(columns: usize, squares_per_row: usize, squares_per_column: usize) -> Canvas {
+    // NOTE: Every character holds 2x4 pixels.
+    let width = columns * 2;
+    let padding = if width > 4 { 2 } else { 0 };
+    let side = (width - padding * 2) as f32 / squares_per_row as f32;
+    let height = (side * squares_per_column as f32) as usize + padding * 2;
+
+    let mut canvas = Canvas::new(width, height);
+    for y in 0..squares_per_column {
+        for x in 0..squares_per_row {
+            let mut center_x = x as f32 * side + side / 2.0 + padding as f32;
+            let mut center_y = y as f32 * side + side / 2.0 + padding as f32;
+            let mut angle = 0.0;
+            // NOTE: The first two rows stay in order.
+            if y > 1 {
+                let disorder = || {
+                    let offset = random_f32() / squares_per_column as f32 * y as f32;
+                    if random_f32() < 0.5 {
+                        -offset
+                    } else {
+                        offset
+                    }
+                };
+                angle = disorder();
+                center_x += disorder() * side / 3.0;
+                center_y += disorder() * side / 3.0;
+            }
+            canvas.draw_square(center_x, center_y, side, angle);
+        }
+    }
+    canvas
+}
+
+/// Monochrome image, which is rendered as Braille characters.
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+    fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+    /// Sets the pixel, unless it lies outside the canvas.
+    fn set(&mut self, x: i64, y: i64) {
+        if (0..self.width as i64).contains(&x) && (0..self.height as i64).contains(&y) {
+            self.pixels[y as usize * self.width + x as usize] = true;
+        }
+    }
+    /// Draws a line with Bresenham's algorithm.
+    fn draw_line(&mut self, (mut x, mut y): (i64, i64), (x2, y2): (i64, i64)) {
+        let dx = (x2 - x).abs();
+        let dy = (y2 - y).abs();
+        let step_x = if x < x2 { 1 } else { -1 };
+        let step_y = if y < y2 { 1 } else { -1 };
+        let mut error = dx - dy;
+        loop {
+            self.set(x, y);
+            if x == x2 && y == y2 {
+                break;
+            }
+            let doubled = error * 2;
+            if doubled > -dy {
+                error -= dy;
+                x += step_x;
+            }
+            if doubled < dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+    /// Draws the outline of a square centered at `x`, `y` and rotated by `angle`.
+    fn draw_square(&mut self, x: f32, y: f32, side: f32, angle: f32) {
+        // NOTE: The corners are half a diagonal away from the center.
+        let radius = (side / std::f32::consts::SQRT_2).round();
+        let corners: Vec<(i64, i64)> = (0..4)
+            .map(|i| {
+                let k = PI / 4.0 + angle + PI / 2.0 * i as f32;
+                ((k.sin() * radius + x) as i64, (k.cos() * radius + y) as i64)
+            })
+            .collect();
+        for i in 0..4 {
+            self.draw_line(corners[i], corners[(i + 1) % 4]);
+        }
+    }
+    /// Renders every 2x4 pixels as a Braille character, whose dots are numbered
+    /// column by column except for the bottom row.
+    fn render(&self) -> String {
+        const DOTS: [(usize, usize, u32); 8] = [
+            (0, 0, 0x01),
+            (0, 1, 0x02),
+            (0, 2, 0x04),
+            (1, 0, 0x08),
+            (1, 1, 0x10),
+            (1, 2, 0x20),
+            (0, 3, 0x40),
+            (1, 3, 0x80),
+        ];
+        let mut output = String::new();
+        for y in (0..self.height).step_by(4) {
+            for x in (0..self.width).step_by(2) {
+                let bits = DOTS
+                    .iter()
+                    .filter(|&&(dx, dy, _)| self.get(x + dx, y + dy))
+                    .fold(0, |bits, &(_, _, bit)| bits | bit);
+                output.push(char::from_u32(0x2800 + bits).unwrap_or(' '));
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
@@ -0,0 +1,18 @@
+mod command_handler;
+mod command_table;
+mod deferred;
+mod dispatch;
+mod execute;
+mod lolwut;
+mod redis_command;
+mod redis_error;
+mod reply_handle;
+
+pub use command_handler::{CommandHandler, CustomCommand, HandlerFuture};
+pub use command_table::{find_command, CommandSpec, COMMAND_TABLE};
+pub use deferred::DeferredReply;
+pub use dispatch::dispatch;
+pub use lolwut::lolwut;
+pub use redis_command::{Command, CommandParseError};
+pub use redis_error::RedisError;
+pub use reply_handle::{reply_later, PendingReply, ReplyHandle};
@@ -0,0 +1,804 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+use crate::cluster::{SetSlot, CLUSTER_SLOTS};
+use crate::command::{find_command, CommandSpec, CustomCommand};
+use crate::resp::RespValue;
+
+#[allow(clippy::enum_variant_names)]
+pub enum Command {
+    Command,
+    CommandCount,
+    /// Names of the commands, or subcommands like "config|get", to describe.
+    CommandInfo(Vec<String>),
+    /// Names of the commands to document, all of them if empty.
+    CommandDocs(Vec<String>),
+    Echo(String),
+    Ping(Option<String>),
+    /// User, 'default' if none is given, and password.
+    Auth(Option<String>, String),
+    Hello {
+        protover: Option<i64>,
+        /// User and password to authenticate with.
+        auth: Option<(String, String)>,
+        /// Name to set like CLIENT SETNAME.
+        setname: Option<String>,
+    },
+    Quit,
+    ClientId,
+    ClientInfo,
+    ClientList,
+    ClientGetName,
+    ClientSetName(String),
+    /// User name and the rules to apply to it.
+    AclSetUser(String, Vec<String>),
+    AclGetUser(String),
+    AclDelUser(Vec<String>),
+    AclList,
+    AclWhoAmI,
+    /// Lists the categories, or the commands in the given category.
+    AclCat(Option<String>),
+    Save,
+    BgSave,
+    LastSave,
+    BgRewriteAof,
+    Info(Vec<String>),
+    DebugDumpJson(String),
+    DebugLoadJson(String),
+    DebugConfigReload,
+    DebugSleep(Duration),
+    DebugObject(String),
+    /// Logs the memory mappings of the process.
+    DebugJmap,
+    DebugSetActiveExpire(bool),
+    /// Runs the glob pattern fuzz test.
+    DebugStringMatchLen,
+    /// Reloads the dataset from the RDB file, after saving it there unless NOSAVE is
+    /// given.
+    DebugReload {
+        save: bool,
+    },
+    DebugChangeReplId,
+    ReplConf(Vec<(String, String)>),
+    /// Patterns of the parameters to get.
+    ConfigGet(Vec<String>),
+    ConfigSet(Vec<(String, String)>),
+    LatencyLatest,
+    LatencyHistory(String),
+    /// Events to reset, all of them if empty.
+    LatencyReset(Vec<String>),
+    LatencyDoctor,
+    /// Commands to report, e.g. "config" or "config|get", all of them if empty.
+    LatencyHistogram(Vec<String>),
+    MemoryStats,
+    MemoryDoctor,
+    Lolwut {
+        version: Option<i64>,
+        /// Size of the drawing, which depends on the version.
+        params: Vec<i64>,
+    },
+    Psync(String, i64),
+    Wait(usize, u64),
+    Del(Vec<String>),
+    /// Host and port of the new master, or [`None`] for 'REPLICAOF NO ONE'.
+    ReplicaOf(Option<(String, u16)>),
+    ClusterInfo,
+    ClusterMyId,
+    ClusterKeySlot(String),
+    ClusterAddSlots(Vec<u16>),
+    ClusterDelSlots(Vec<u16>),
+    ClusterSlots,
+    ClusterShards,
+    ClusterNodes,
+    ClusterMeet(String, u16),
+    ClusterSetSlot(u16, SetSlot),
+    ClusterCountKeysInSlot(u16),
+    ClusterGetKeysInSlot(u16, usize),
+    Asking,
+    /// Replies with the serialized value of the key, hex encoded since bulk strings only
+    /// hold UTF-8 here. The payload is therefore only understood by RESTORE of this
+    /// server, not by real Redis, and vice versa.
+    Dump(String),
+    Scan {
+        cursor: u64,
+        pattern: Option<String>,
+        count: usize,
+        /// Only returns keys of this type, e.g. "string".
+        kind: Option<String>,
+    },
+    Restore {
+        key: String,
+        /// TTL in milliseconds or 0 for none, which is the Unix time in milliseconds the
+        /// key expires at if `absttl` is set.
+        ttl_ms: u64,
+        /// Hex encoded payload as returned by DUMP of this server. Raw payloads of real
+        /// Redis are rejected.
+        payload: String,
+        /// Replaces an existing key.
+        replace: bool,
+        absttl: bool,
+    },
+    Migrate {
+        host: String,
+        port: u16,
+        keys: Vec<String>,
+        timeout_ms: u64,
+        /// Keeps the keys on this node.
+        copy: bool,
+        /// Replaces existing keys on the target.
+        replace: bool,
+    },
+    /// HELP subcommand of the container with this name.
+    Help(&'static str),
+    /// Command registered through a [`CommandHandler`](crate::command::CommandHandler)
+    /// and its arguments, not including the name.
+    Custom(Arc<CustomCommand>, Vec<String>),
+}
+
+#[derive(Error, Debug)]
+pub enum CommandParseError {
+    #[error("empty command name")]
+    EmptyCommandName,
+    #[error("invalid arguments")]
+    InvalidArguments,
+    #[error("wrong argument type")]
+    WrongArgType,
+    #[error("command does not exist")]
+    CommandDoesNotExist,
+    #[error("too many arguments")]
+    TooManyArguments,
+    #[error("Invalid or out of range slot")]
+    InvalidSlot,
+    /// The number of arguments does not match the arity of the command, named like
+    /// "config|get" for subcommands.
+    #[error("wrong number of arguments for '{0}' command")]
+    WrongArity(String),
+}
+
+impl Command {
+    /// Whether the command modifies the dataset, which replicas refuse to clients.
+    pub fn is_write(&self) -> bool {
+        self.spec().has_flag("write")
+    }
+    /// Whether the command has to be appended to the AOF and propagated to replicas
+    /// as is once it succeeded.
+    ///
+    /// NOTE: MIGRATE propagates the deletion of the migrated keys itself instead.
+    pub fn is_propagated(&self) -> bool {
+        self.is_write() && !matches!(self, Command::Migrate { .. })
+    }
+    /// Request to append to the AOF and propagate to replicas in place of the received
+    /// one, if the effect of the command depends on when it runs.
+    ///
+    /// NOTE: A relative TTL is turned into an absolute one, so that replaying the AOF
+    ///       later or a lagging replica doesn't extend the lifetime of the key.
+    pub fn propagated_frame(&self) -> Option<String> {
+        match self {
+            Command::Restore {
+                key,
+                ttl_ms,
+                payload,
+                replace,
+                absttl: false,
+            } if *ttl_ms > 0 => {
+                let now_ms = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |now| now.as_millis() as u64);
+                let expires_ms = now_ms.saturating_add(*ttl_ms).to_string();
+                let mut args = vec!["RESTORE", key, &expires_ms, payload];
+                if *replace {
+                    args.push("REPLACE");
+                }
+                args.push("ABSTTL");
+                let args = args
+                    .into_iter()
+                    .map(|arg| RespValue::BulkString(arg.into()))
+                    .collect();
+                Some(RespValue::Array(args).to_string())
+            }
+            _ => None,
+        }
+    }
+    /// Keys the command accesses, which decide the node serving it in cluster mode.
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            Command::Del(keys) | Command::Migrate { keys, .. } => {
+                keys.iter().map(String::as_str).collect()
+            }
+            Command::Dump(key) | Command::Restore { key, .. } | Command::DebugObject(key) => {
+                vec![key.as_str()]
+            }
+            _ => Vec::new(),
+        }
+    }
+    /// Name of the command and of its subcommand for container commands like CONFIG, as
+    /// listed in the [`COMMAND_TABLE`](crate::command::COMMAND_TABLE).
+    pub fn name(&self) -> (&'static str, Option<&'static str>) {
+        match self {
+            Command::Command => ("command", None),
+            Command::CommandCount => ("command", Some("count")),
+            Command::CommandInfo(_) => ("command", Some("info")),
+            Command::CommandDocs(_) => ("command", Some("docs")),
+            Command::Echo(_) => ("echo", None),
+            Command::Ping(_) => ("ping", None),
+            Command::Auth(..) => ("auth", None),
+            Command::Hello { .. } => ("hello", None),
+            Command::Quit => ("quit", None),
+            Command::ClientId => ("client", Some("id")),
+            Command::ClientInfo => ("client", Some("info")),
+            Command::ClientList => ("client", Some("list")),
+            Command::ClientGetName => ("client", Some("getname")),
+            Command::ClientSetName(_) => ("client", Some("setname")),
+            Command::AclSetUser(..) => ("acl", Some("setuser")),
+            Command::AclGetUser(_) => ("acl", Some("getuser")),
+            Command::AclDelUser(_) => ("acl", Some("deluser")),
+            Command::AclList => ("acl", Some("list")),
+            Command::AclWhoAmI => ("acl", Some("whoami")),
+            Command::AclCat(_) => ("acl", Some("cat")),
+            Command::Save => ("save", None),
+            Command::BgSave => ("bgsave", None),
+            Command::LastSave => ("lastsave", None),
+            Command::BgRewriteAof => ("bgrewriteaof", None),
+            Command::Info(_) => ("info", None),
+            Command::DebugDumpJson(_)
+            | Command::DebugLoadJson(_)
+            | Command::DebugConfigReload
+            | Command::DebugSleep(_)
+            | Command::DebugObject(_)
+            | Command::DebugJmap
+            | Command::DebugSetActiveExpire(_)
+            | Command::DebugStringMatchLen
+            | Command::DebugReload { .. }
+            | Command::DebugChangeReplId => ("debug", None),
+            Command::ReplConf(_) => ("replconf", None),
+            Command::ConfigGet(_) => ("config", Some("get")),
+            Command::ConfigSet(_) => ("config", Some("set")),
+            Command::LatencyLatest => ("latency", Some("latest")),
+            Command::LatencyHistory(_) => ("latency", Some("history")),
+            Command::LatencyReset(_) => ("latency", Some("reset")),
+            Command::LatencyDoctor => ("latency", Some("doctor")),
+            Command::LatencyHistogram(_) => ("latency", Some("histogram")),
+            Command::MemoryStats => ("memory", Some("stats")),
+            Command::MemoryDoctor => ("memory", Some("doctor")),
+            Command::Lolwut { .. } => ("lolwut", None),
+            Command::Psync(..) => ("psync", None),
+            Command::Wait(..) => ("wait", None),
+            Command::Del(_) => ("del", None),
+            Command::ReplicaOf(_) => ("replicaof", None),
+            Command::ClusterInfo => ("cluster", Some("info")),
+            Command::ClusterMyId => ("cluster", Some("myid")),
+            Command::ClusterKeySlot(_) => ("cluster", Some("keyslot")),
+            Command::ClusterAddSlots(_) => ("cluster", Some("addslots")),
+            Command::ClusterDelSlots(_) => ("cluster", Some("delslots")),
+            Command::ClusterSlots => ("cluster", Some("slots")),
+            Command::ClusterShards => ("cluster", Some("shards")),
+            Command::ClusterNodes => ("cluster", Some("nodes")),
+            Command::ClusterMeet(..) => ("cluster", Some("meet")),
+            Command::ClusterSetSlot(..) => ("cluster", Some("setslot")),
+            Command::ClusterCountKeysInSlot(_) => ("cluster", Some("countkeysinslot")),
+            Command::ClusterGetKeysInSlot(..) => ("cluster", Some("getkeysinslot")),
+            Command::Asking => ("asking", None),
+            Command::Dump(_) => ("dump", None),
+            Command::Scan { .. } => ("scan", None),
+            Command::Restore { .. } => ("restore", None),
+            Command::Migrate { .. } => ("migrate", None),
+            Command::Help(name) => (name, Some("help")),
+            Command::Custom(custom, _) => (custom.spec.name, None),
+        }
+    }
+    /// Entry of the command, or of its subcommand, in the command table.
+    pub fn spec(&self) -> &CommandSpec {
+        if let Command::Custom(custom, _) = self {
+            return &custom.spec;
+        }
+        let (name, subcommand) = self.name();
+        let spec = find_command(name).expect("every command is in the command table");
+        match subcommand {
+            Some(subcommand) => spec
+                .subcommand(subcommand)
+                .expect("every subcommand is in the command table"),
+            None => spec,
+        }
+    }
+    /// Pub/sub channels the command accesses, which ACL channel patterns restrict.
+    pub fn channels(&self) -> Vec<&str> {
+        Vec::new()
+    }
+    /// Whether the command runs before the connection authenticated.
+    pub fn is_allowed_unauthenticated(&self) -> bool {
+        self.spec().has_flag("no_auth")
+    }
+    /// Whether a replica serves the command while it has no up to date dataset.
+    pub fn is_allowed_when_stale(&self) -> bool {
+        self.spec().has_flag("stale")
+    }
+    /// Parses the arguments of a custom command, including its name.
+    pub fn custom(
+        custom: Arc<CustomCommand>,
+        args: Vec<RespValue>,
+    ) -> Result<Self, CommandParseError> {
+        if !custom.spec.accepts(args.len()) {
+            return Err(CommandParseError::WrongArity(custom.spec.name.to_string()));
+        }
+        let args = bulk_strings(&args[1..])?;
+        Ok(Command::Custom(custom, args))
+    }
+}
+
+impl TryFrom<Vec<RespValue<'_>>> for Command {
+    type Error = CommandParseError;
+
+    fn try_from(values: Vec<RespValue>) -> Result<Self, Self::Error> {
+        let num_args = values.len();
+        let Some(RespValue::BulkString(cmd)) = values.first() else {
+            return Err(match values.first() {
+                None => CommandParseError::EmptyCommandName,
+                Some(_) => CommandParseError::WrongArgType,
+            });
+        };
+        let spec = find_command(cmd).ok_or(CommandParseError::CommandDoesNotExist)?;
+        // NOTE: The arity of every command and subcommand is validated against the
+        //       command table, so parsers only have to check what it leaves open.
+        let subcommand = match values.get(1) {
+            Some(RespValue::BulkString(subcommand)) => spec.subcommand(subcommand),
+            _ => None,
+        };
+        let (checked, parent) = match subcommand {
+            Some(subcommand) => (subcommand, Some(spec.name)),
+            None => (spec, None),
+        };
+        if !checked.accepts(num_args) {
+            return Err(CommandParseError::WrongArity(checked.full_name(parent)));
+        }
+        // NOTE: HELP is the same for every container, so it is parsed before the
+        //       subcommands of each.
+        if num_args == 2 && subcommand.is_some_and(|subcommand| subcommand.name == "help") {
+            return Ok(Command::Help(spec.name));
+        }
+        match spec.name {
+            "ping" => {
+                if values.len() > 2 {
+                    return Err(CommandParseError::TooManyArguments);
+                }
+                match values.get(1) {
+                    None => Ok(Command::Ping(None)),
+                    Some(RespValue::BulkString(string)) => {
+                        Ok(Command::Ping(Some(string.to_string())))
+                    }
+                    Some(_) => Err(CommandParseError::WrongArgType),
+                }
+            }
+            "echo" => {
+                let [message] = bulk_strings(&values[1..])?
+                    .try_into()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                Ok(Command::Echo(message))
+            }
+            "command" => {
+                let args = bulk_strings(&values[1..])?;
+                let Some((subcommand, args)) = args.split_first() else {
+                    return Ok(Command::Command);
+                };
+                match (subcommand.to_ascii_uppercase().as_str(), args) {
+                    ("COUNT", []) => Ok(Command::CommandCount),
+                    ("INFO", names) => Ok(Command::CommandInfo(names.to_vec())),
+                    ("DOCS", names) => Ok(Command::CommandDocs(names.to_vec())),
+                    _ => Err(CommandParseError::InvalidArguments),
+                }
+            }
+            "save" => Ok(Command::Save),
+            "bgsave" => {
+                if values.len() > 1 {
+                    return Err(CommandParseError::TooManyArguments);
+                }
+                Ok(Command::BgSave)
+            }
+            "lastsave" => Ok(Command::LastSave),
+            "bgrewriteaof" => Ok(Command::BgRewriteAof),
+            "info" => Ok(Command::Info(bulk_strings(&values[1..])?)),
+            "auth" => match bulk_strings(&values[1..])?.as_slice() {
+                [password] => Ok(Command::Auth(None, password.clone())),
+                [user, password] => Ok(Command::Auth(Some(user.clone()), password.clone())),
+                _ => Err(CommandParseError::InvalidArguments),
+            },
+            "hello" => {
+                let args = bulk_strings(&values[1..])?;
+                let mut args = args.iter();
+                let protover = match args.next() {
+                    Some(protover) => Some(
+                        protover
+                            .parse()
+                            .map_err(|_| CommandParseError::InvalidArguments)?,
+                    ),
+                    None => None,
+                };
+                let (mut auth, mut setname) = (None, None);
+                while let Some(option) = args.next() {
+                    match option.to_ascii_uppercase().as_str() {
+                        "AUTH" => {
+                            let (Some(user), Some(password)) = (args.next(), args.next()) else {
+                                return Err(CommandParseError::InvalidArguments);
+                            };
+                            auth = Some((user.clone(), password.clone()));
+                        }
+                        "SETNAME" => {
+                            let Some(name) = args.next() else {
+                                return Err(CommandParseError::InvalidArguments);
+                            };
+                            setname = Some(name.clone());
+                        }
+                        _ => return Err(CommandParseError::InvalidArguments),
+                    }
+                }
+                Ok(Command::Hello {
+                    protover,
+                    auth,
+                    setname,
+                })
+            }
+            "quit" => {
+                if num_args > 1 {
+                    return Err(CommandParseError::TooManyArguments);
+                }
+                Ok(Command::Quit)
+            }
+            "client" => {
+                let args = bulk_strings(&values[1..])?;
+                let Some((subcommand, args)) = args.split_first() else {
+                    return Err(CommandParseError::InvalidArguments);
+                };
+                match (subcommand.to_ascii_uppercase().as_str(), args) {
+                    ("ID", []) => Ok(Command::ClientId),
+                    ("INFO", []) => Ok(Command::ClientInfo),
+                    ("LIST", []) => Ok(Command::ClientList),
+                    ("GETNAME", []) => Ok(Command::ClientGetName),
+                    ("SETNAME", [name]) => Ok(Command::ClientSetName(name.clone())),
+                    _ => Err(CommandParseError::InvalidArguments),
+                }
+            }
+            "acl" => {
+                let args = bulk_strings(&values[1..])?;
+                let Some((subcommand, args)) = args.split_first() else {
+                    return Err(CommandParseError::InvalidArguments);
+                };
+                match (subcommand.to_ascii_uppercase().as_str(), args) {
+                    ("SETUSER", [user, rules @ ..]) => {
+                        Ok(Command::AclSetUser(user.clone(), rules.to_vec()))
+                    }
+                    ("GETUSER", [user]) => Ok(Command::AclGetUser(user.clone())),
+                    ("DELUSER", users) if !users.is_empty() => {
+                        Ok(Command::AclDelUser(users.to_vec()))
+                    }
+                    ("LIST", []) => Ok(Command::AclList),
+                    ("WHOAMI", []) => Ok(Command::AclWhoAmI),
+                    ("CAT", []) => Ok(Command::AclCat(None)),
+                    ("CAT", [category]) => Ok(Command::AclCat(Some(category.clone()))),
+                    _ => Err(CommandParseError::InvalidArguments),
+                }
+            }
+            "debug" => {
+                let args = bulk_strings(&values[1..])?;
+                let Some((subcommand, args)) = args.split_first() else {
+                    return Err(CommandParseError::InvalidArguments);
+                };
+                match (subcommand.to_ascii_uppercase().as_str(), args) {
+                    ("DUMP-JSON", [path]) => Ok(Command::DebugDumpJson(path.clone())),
+                    ("LOAD-JSON", [path]) => Ok(Command::DebugLoadJson(path.clone())),
+                    ("CONFIG-RELOAD", []) => Ok(Command::DebugConfigReload),
+                    ("SLEEP", [seconds]) => {
+                        let duration = seconds
+                            .parse()
+                            .ok()
+                            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                            .ok_or(CommandParseError::InvalidArguments)?;
+                        Ok(Command::DebugSleep(duration))
+                    }
+                    ("OBJECT", [key]) => Ok(Command::DebugObject(key.clone())),
+                    ("JMAP", []) => Ok(Command::DebugJmap),
+                    ("SET-ACTIVE-EXPIRE", [enabled]) => match enabled.as_str() {
+                        "0" => Ok(Command::DebugSetActiveExpire(false)),
+                        "1" => Ok(Command::DebugSetActiveExpire(true)),
+                        _ => Err(CommandParseError::InvalidArguments),
+                    },
+                    ("STRINGMATCH-LEN", []) => Ok(Command::DebugStringMatchLen),
+                    ("RELOAD", []) => Ok(Command::DebugReload { save: true }),
+                    ("RELOAD", [option]) if option.eq_ignore_ascii_case("NOSAVE") => {
+                        Ok(Command::DebugReload { save: false })
+                    }
+                    ("CHANGE-REPL-ID", []) => Ok(Command::DebugChangeReplId),
+                    _ => Err(CommandParseError::InvalidArguments),
+                }
+            }
+            "replconf" => {
+                let args = bulk_strings(&values[1..])?;
+                if args.is_empty() || args.len() % 2 != 0 {
+                    return Err(CommandParseError::InvalidArguments);
+                }
+                let options = args
+                    .chunks(2)
+                    .map(|pair| (pair[0].clone(), pair[1].clone()))
+                    .collect();
+                Ok(Command::ReplConf(options))
+            }
+            "psync" => {
+                let [replid, offset] = bulk_strings(&values[1..])?
+                    .try_into()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                let offset = offset
+                    .parse()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                Ok(Command::Psync(replid, offset))
+            }
+            "wait" => {
+                let [num_replicas, timeout] = bulk_strings(&values[1..])?
+                    .try_into()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                match (num_replicas.parse(), timeout.parse()) {
+                    (Ok(num_replicas), Ok(timeout)) => Ok(Command::Wait(num_replicas, timeout)),
+                    _ => Err(CommandParseError::InvalidArguments),
+                }
+            }
+            "del" => {
+                let keys = bulk_strings(&values[1..])?;
+                if keys.is_empty() {
+                    return Err(CommandParseError::InvalidArguments);
+                }
+                Ok(Command::Del(keys))
+            }
+            "replicaof" => {
+                let [host, port] = bulk_strings(&values[1..])?
+                    .try_into()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                if host.eq_ignore_ascii_case("NO") && port.eq_ignore_ascii_case("ONE") {
+                    return Ok(Command::ReplicaOf(None));
+                }
+                let port = port
+                    .parse()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                Ok(Command::ReplicaOf(Some((host, port))))
+            }
+            "cluster" => {
+                let args = bulk_strings(&values[1..])?;
+                let Some((subcommand, args)) = args.split_first() else {
+                    return Err(CommandParseError::InvalidArguments);
+                };
+                match (subcommand.to_ascii_uppercase().as_str(), args) {
+                    ("INFO", []) => Ok(Command::ClusterInfo),
+                    ("MYID", []) => Ok(Command::ClusterMyId),
+                    ("SLOTS", []) => Ok(Command::ClusterSlots),
+                    ("SHARDS", []) => Ok(Command::ClusterShards),
+                    ("NODES", []) => Ok(Command::ClusterNodes),
+                    ("SETSLOT", [slot, action, rest @ ..]) => {
+                        let slot = parse_slots(std::slice::from_ref(slot))?[0];
+                        let action = match (action.to_ascii_uppercase().as_str(), rest) {
+                            ("MIGRATING", [id]) => SetSlot::Migrating(id.clone()),
+                            ("IMPORTING", [id]) => SetSlot::Importing(id.clone()),
+                            ("STABLE", []) => SetSlot::Stable,
+                            ("NODE", [id]) => SetSlot::Node(id.clone()),
+                            _ => return Err(CommandParseError::InvalidArguments),
+                        };
+                        Ok(Command::ClusterSetSlot(slot, action))
+                    }
+                    ("COUNTKEYSINSLOT", [slot]) => {
+                        let slot = parse_slots(std::slice::from_ref(slot))?[0];
+                        Ok(Command::ClusterCountKeysInSlot(slot))
+                    }
+                    ("GETKEYSINSLOT", [slot, count]) => {
+                        let slot = parse_slots(std::slice::from_ref(slot))?[0];
+                        let count = count
+                            .parse()
+                            .map_err(|_| CommandParseError::InvalidArguments)?;
+                        Ok(Command::ClusterGetKeysInSlot(slot, count))
+                    }
+                    ("MEET", [ip, port]) => {
+                        let port = port
+                            .parse()
+                            .map_err(|_| CommandParseError::InvalidArguments)?;
+                        Ok(Command::ClusterMeet(ip.clone(), port))
+                    }
+                    ("KEYSLOT", [key]) => Ok(Command::ClusterKeySlot(key.clone())),
+                    ("ADDSLOTS", slots) if !slots.is_empty() => {
+                        Ok(Command::ClusterAddSlots(parse_slots(slots)?))
+                    }
+                    ("DELSLOTS", slots) if !slots.is_empty() => {
+                        Ok(Command::ClusterDelSlots(parse_slots(slots)?))
+                    }
+                    _ => Err(CommandParseError::InvalidArguments),
+                }
+            }
+            "asking" => Ok(Command::Asking),
+            "config" => {
+                let args = bulk_strings(&values[1..])?;
+                let Some((subcommand, args)) = args.split_first() else {
+                    return Err(CommandParseError::InvalidArguments);
+                };
+                match subcommand.to_ascii_uppercase().as_str() {
+                    "GET" if !args.is_empty() => Ok(Command::ConfigGet(args.to_vec())),
+                    "SET" => {
+                        let pairs = args.chunks_exact(2);
+                        if args.is_empty() || !pairs.remainder().is_empty() {
+                            return Err(CommandParseError::InvalidArguments);
+                        }
+                        Ok(Command::ConfigSet(
+                            pairs
+                                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                                .collect(),
+                        ))
+                    }
+                    _ => Err(CommandParseError::InvalidArguments),
+                }
+            }
+            "latency" => {
+                let args = bulk_strings(&values[1..])?;
+                let Some((subcommand, args)) = args.split_first() else {
+                    return Err(CommandParseError::InvalidArguments);
+                };
+                match (subcommand.to_ascii_uppercase().as_str(), args) {
+                    ("LATEST", []) => Ok(Command::LatencyLatest),
+                    ("HISTORY", [event]) => Ok(Command::LatencyHistory(event.clone())),
+                    ("RESET", events) => Ok(Command::LatencyReset(events.to_vec())),
+                    ("DOCTOR", []) => Ok(Command::LatencyDoctor),
+                    ("HISTOGRAM", commands) => Ok(Command::LatencyHistogram(commands.to_vec())),
+                    _ => Err(CommandParseError::InvalidArguments),
+                }
+            }
+            "memory" => {
+                let [subcommand] = bulk_strings(&values[1..])?
+                    .try_into()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                match subcommand.to_ascii_uppercase().as_str() {
+                    "STATS" => Ok(Command::MemoryStats),
+                    "DOCTOR" => Ok(Command::MemoryDoctor),
+                    _ => Err(CommandParseError::InvalidArguments),
+                }
+            }
+            "lolwut" => {
+                let args = bulk_strings(&values[1..])?;
+                let (version, params) = match args.as_slice() {
+                    [option, version, params @ ..] if option.eq_ignore_ascii_case("VERSION") => {
+                        (Some(version.as_str()), params)
+                    }
+                    params => (None, params),
+                };
+                let version = version
+                    .map(str::parse)
+                    .transpose()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                let params = params
+                    .iter()
+                    .map(|param| param.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                Ok(Command::Lolwut { version, params })
+            }
+            "dump" => {
+                let [key] = bulk_strings(&values[1..])?
+                    .try_into()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                Ok(Command::Dump(key))
+            }
+            "scan" => {
+                let args = bulk_strings(&values[1..])?;
+                let Some((cursor, mut options)) = args.split_first() else {
+                    return Err(CommandParseError::InvalidArguments);
+                };
+                let cursor = cursor
+                    .parse()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+
+                // NOTE: Same default as in Redis.
+                let (mut pattern, mut count, mut kind) = (None, 10, None);
+                while let [option, value, rest @ ..] = options {
+                    match option.to_ascii_uppercase().as_str() {
+                        "MATCH" => pattern = Some(value.clone()),
+                        "COUNT" => {
+                            count = value
+                                .parse()
+                                .ok()
+                                .filter(|&count| count > 0)
+                                .ok_or(CommandParseError::InvalidArguments)?
+                        }
+                        "TYPE" => kind = Some(value.to_ascii_lowercase()),
+                        _ => return Err(CommandParseError::InvalidArguments),
+                    }
+                    options = rest;
+                }
+                if !options.is_empty() {
+                    return Err(CommandParseError::InvalidArguments);
+                }
+                Ok(Command::Scan {
+                    cursor,
+                    pattern,
+                    count,
+                    kind,
+                })
+            }
+            "restore" => {
+                let args = bulk_strings(&values[1..])?;
+                let [key, ttl, payload, options @ ..] = args.as_slice() else {
+                    return Err(CommandParseError::InvalidArguments);
+                };
+                let ttl = ttl
+                    .parse()
+                    .map_err(|_| CommandParseError::InvalidArguments)?;
+                let (mut replace, mut absttl) = (false, false);
+                for option in options {
+                    match option.to_ascii_uppercase().as_str() {
+                        "REPLACE" => replace = true,
+                        "ABSTTL" => absttl = true,
+                        _ => return Err(CommandParseError::InvalidArguments),
+                    }
+                }
+                Ok(Command::Restore {
+                    key: key.clone(),
+                    ttl_ms: ttl,
+                    payload: payload.clone(),
+                    replace,
+                    absttl,
+                })
+            }
+            "migrate" => {
+                let args = bulk_strings(&values[1..])?;
+                let [host, port, key, db, timeout_ms, options @ ..] = args.as_slice() else {
+                    return Err(CommandParseError::InvalidArguments);
+                };
+                // NOTE: There is only a single database.
+                if db != "0" {
+                    return Err(CommandParseError::InvalidArguments);
+                }
+                let (Ok(port), Ok(timeout_ms)) = (port.parse(), timeout_ms.parse()) else {
+                    return Err(CommandParseError::InvalidArguments);
+                };
+
+                let (mut copy, mut replace, mut keys) = (false, false, None);
+                for (i, option) in options.iter().enumerate() {
+                    match option.to_ascii_uppercase().as_str() {
+                        "COPY" => copy = true,
+                        "REPLACE" => replace = true,
+                        "KEYS" if key.is_empty() => {
+                            keys = Some(options[i + 1..].to_vec());
+                            break;
+                        }
+                        _ => return Err(CommandParseError::InvalidArguments),
+                    }
+                }
+                let keys = match keys {
+                    Some(keys) if !keys.is_empty() => keys,
+                    None if !key.is_empty() => vec![key.clone()],
+                    _ => return Err(CommandParseError::InvalidArguments),
+                };
+                Ok(Command::Migrate {
+                    host: host.clone(),
+                    port,
+                    keys,
+                    timeout_ms,
+                    copy,
+                    replace,
+                })
+            }
+            _ => Err(CommandParseError::CommandDoesNotExist),
+        }
+    }
+}
+
+fn bulk_strings(values: &[RespValue]) -> Result<Vec<String>, CommandParseError> {
+    values
+        .iter()
+        .map(|value| match value {
+            RespValue::BulkString(s) => Ok(s.to_string()),
+            _ => Err(CommandParseError::WrongArgType),
+        })
+        .collect()
+}
+
+fn parse_slots(values: &[String]) -> Result<Vec<u16>, CommandParseError> {
+    values
+        .iter()
+        .map(|value| match value.parse() {
+            Ok(slot) if slot < CLUSTER_SLOTS => Ok(slot),
+            _ => Err(CommandParseError::InvalidSlot),
+        })
+        .collect()
+}
@@ -0,0 +1,97 @@
+use thiserror::Error;
+
+use crate::command::CommandParseError;
+use crate::resp::RespValue;
+
+/// Error reply of a command, whose message starts with the error code clients match
+/// on, e.g. "WRONGTYPE".
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RedisError {
+    /// Generic error with the message following the code.
+    #[error("ERR {0}")]
+    Err(String),
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("NOAUTH Authentication required.")]
+    NoAuth,
+    #[error(
+        "NOAUTH HELLO must be called with the client already authenticated, otherwise the \
+         HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and \
+         select the RESP protocol version at the same time"
+    )]
+    HelloNoAuth,
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+    #[error("NOPERM {0}")]
+    NoPerm(String),
+    #[error("NOPROTO sorry, this protocol version is not supported.")]
+    NoProto,
+    /// The slot is served by the node at the given address.
+    #[error("MOVED {0} {1}")]
+    Moved(u16, String),
+    /// The slot is being migrated and the keys have to be asked for at the given address.
+    #[error("ASK {0} {1}")]
+    Ask(u16, String),
+    /// The keys of a single command hash to different slots.
+    #[error("CROSSSLOT Keys in request don't hash to the same slot")]
+    CrossSlot,
+    /// The slot is being migrated and only some of the keys were moved yet.
+    #[error("TRYAGAIN Multiple keys request during rehashing of slot")]
+    TryAgain,
+    /// Not every slot is served.
+    #[error("CLUSTERDOWN The cluster is down")]
+    ClusterDown,
+    /// The slot is not served by any node.
+    #[error("CLUSTERDOWN Hash slot {0} not served")]
+    SlotUnbound(u16),
+    #[error(
+        "BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE."
+    )]
+    Busy,
+    #[error("BUSYKEY Target key name already exists.")]
+    BusyKey,
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
+    Oom,
+    #[error("EXECABORT Transaction discarded because of previous errors.")]
+    ExecAbort,
+    #[error("NOSCRIPT No matching script. Please use EVAL.")]
+    NoScript,
+    #[error("READONLY You can't write against a read only replica.")]
+    ReadOnly,
+    #[error(
+        "MISCONF Redis is configured to save RDB snapshots, but it's currently unable to \
+         persist to disk. Commands that may modify the data set are disabled, because this \
+         instance is configured to report errors during writes if RDB snapshotting fails \
+         (stop-writes-on-bgsave-error option). Please check the Redis logs for details about \
+         the RDB error."
+    )]
+    MisConf,
+    /// Writing to the AOF failed, so writes are refused until it succeeds again.
+    #[error("MISCONF Errors writing to the AOF file: {0}")]
+    AofWrite(String),
+    #[error("MASTERDOWN Link with MASTER is down and replica-serve-stale-data is set to 'no'.")]
+    MasterDown,
+    #[error("NOMASTERLINK Can't SYNC while not connected with my master")]
+    NoMasterLink,
+    #[error("IOERR {0}")]
+    IoErr(&'static str),
+}
+
+impl RedisError {
+    /// Generic error with the given message, e.g. of a failed I/O operation.
+    pub fn err(message: impl ToString) -> Self {
+        RedisError::Err(message.to_string())
+    }
+}
+
+impl From<CommandParseError> for RedisError {
+    fn from(e: CommandParseError) -> Self {
+        RedisError::err(e)
+    }
+}
+
+impl From<RedisError> for RespValue<'static> {
+    fn from(e: RedisError) -> Self {
+        RespValue::SimpleError(e.to_string().into())
+    }
+}
@@ -0,0 +1,60 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::sync::oneshot;
+
+use crate::command::RedisError;
+use crate::resp::RespValue;
+
+const DROPPED_REPLY_ERROR: &str = "the command was dropped without replying";
+
+/// Creates a reply that another task completes later, e.g. once a key is ready or a
+/// timer expired.
+///
+/// Command handlers return the [`PendingReply`] as their reply and hand the
+/// [`ReplyHandle`] to whoever will know it.
+pub fn reply_later() -> (ReplyHandle, PendingReply) {
+    let (sender, receiver) = oneshot::channel();
+    (ReplyHandle { sender }, PendingReply { receiver })
+}
+
+/// Completes a [`PendingReply`], see [`reply_later`].
+#[derive(Debug)]
+pub struct ReplyHandle {
+    sender: oneshot::Sender<RespValue<'static>>,
+}
+
+impl ReplyHandle {
+    /// Sends the reply, which is handed back if the client disconnected in the meantime.
+    pub fn reply(self, reply: RespValue<'static>) -> Result<(), RespValue<'static>> {
+        self.sender.send(reply)
+    }
+    /// Whether the client disconnected, so that nobody waits for the reply anymore.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+    /// Completes once the client disconnected, which lets the task giving the reply
+    /// stop waiting as well.
+    pub async fn closed(&mut self) {
+        self.sender.closed().await;
+    }
+}
+
+/// Reply of a blocked client, which completes once the [`ReplyHandle`] replied.
+///
+/// The connection drops it when the client disconnects, which closes the handle.
+#[derive(Debug)]
+pub struct PendingReply {
+    receiver: oneshot::Receiver<RespValue<'static>>,
+}
+
+impl Future for PendingReply {
+    type Output = RespValue<'static>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver)
+            .poll(cx)
+            .map(|reply| reply.unwrap_or_else(|_| RedisError::err(DROPPED_REPLY_ERROR).into()))
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+
+use crate::config::{find_config_entry, Config};
+
+/// Limits nested `include` directives, which would otherwise recurse forever on a
+/// file including itself.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+impl Config {
+    /// Applies the directives of a redis.conf style file at `path`.
+    ///
+    /// Every line holds a parameter name followed by its value, e.g. `port 6380`.
+    /// Blank lines and lines starting with `#` are ignored, and `include <path>`
+    /// applies the directives of another file at that point.
+    pub fn load_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.load_file_nested(path, 0)
+    }
+    fn load_file_nested(&mut self, path: &Path, depth: usize) -> anyhow::Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(anyhow!("Too many nested includes in {}", path.display()));
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |reason: &str| {
+                anyhow!(
+                    "Config file {} at line {}: '{line}': {reason}",
+                    path.display(),
+                    index + 1
+                )
+            };
+
+            let args = split_args(line).ok_or_else(|| error("Unbalanced quotes"))?;
+            let Some((name, values)) = args.split_first() else {
+                continue;
+            };
+            if name.eq_ignore_ascii_case("include") {
+                let [include] = values else {
+                    return Err(error("wrong number of arguments"));
+                };
+                self.load_file_nested(Path::new(include), depth + 1)?;
+                continue;
+            }
+
+            let entry = find_config_entry(name)
+                .filter(|_| !values.is_empty())
+                .ok_or_else(|| error("Bad directive or wrong number of arguments"))?;
+            // NOTE: Parameters taking several arguments, e.g. 'replicaof', get them as a
+            //       single space separated value, the same as on the command line.
+            (entry.set)(self, &values.join(" ")).map_err(|e| error(&e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits a config file line into its arguments, or returns `None` if a quoted
+/// argument isn't terminated.
+///
+/// Double quoted arguments support the escapes `\n`, `\r`, `\t`, `\b`, `\a`, `\xHH` and
+/// a backslash before any other character. Single quoted arguments only support `\'`.
+/// A closing quote has to be followed by whitespace or the end of the line.
+fn split_args(line: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            return Some(args);
+        };
+
+        let mut arg = String::new();
+        match first {
+            '"' => {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            'n' => arg.push('\n'),
+                            'r' => arg.push('\r'),
+                            't' => arg.push('\t'),
+                            'b' => arg.push('\u{8}'),
+                            'a' => arg.push('\u{7}'),
+                            'x' => {
+                                let hex: String = chars.clone().take(2).collect();
+                                let valid =
+                                    hex.len() == 2 && hex.chars().all(|c| c.is_ascii_hexdigit());
+                                match u8::from_str_radix(&hex, 16) {
+                                    Ok(byte) if valid => {
+                                        arg.push(char::from(byte));
+                                        chars.nth(1);
+                                    }
+                                    _ => arg.push('x'),
+                                }
+                            }
+                            c => arg.push(c),
+                        },
+                        c => arg.push(c),
+                    }
+                }
+            }
+            '\'' => {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        '\\' if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                            arg.push('\'');
+                        }
+                        c => arg.push(c),
+                    }
+                }
+            }
+            _ => {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    arg.push(c);
+                }
+            }
+        }
+
+        if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+            return None;
+        }
+        args.push(arg);
+    }
+}
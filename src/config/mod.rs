@@ -0,0 +1,288 @@
+mod file;
+mod registry;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use std::str::FromStr;
+
+use crate::db::EncodingLimits;
+
+pub use registry::{find_config_entry, ConfigEntry, CONFIG_ENTRIES};
+
+/// How often the append-only file is forced to disk.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum AppendFsync {
+    Always,
+    EverySec,
+    No,
+}
+
+impl AppendFsync {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AppendFsync::Always => "always",
+            AppendFsync::EverySec => "everysec",
+            AppendFsync::No => "no",
+        }
+    }
+}
+
+/// What the server does before exiting on a signal, see `shutdown-on-sigterm`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ShutdownMode {
+    /// Saves a final snapshot only if save points are configured.
+    Default,
+    Save,
+    NoSave,
+}
+
+impl ShutdownMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShutdownMode::Default => "default",
+            ShutdownMode::Save => "save",
+            ShutdownMode::NoSave => "nosave",
+        }
+    }
+}
+
+impl FromStr for ShutdownMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "default" => Ok(ShutdownMode::Default),
+            "save" => Ok(ShutdownMode::Save),
+            "nosave" => Ok(ShutdownMode::NoSave),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How the server reports its status to a process supervisor, see `supervised`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Supervised {
+    No,
+    Systemd,
+    /// Notifies systemd if the server was started by it, i.e. `NOTIFY_SOCKET` is set.
+    Auto,
+}
+
+impl Supervised {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Supervised::No => "no",
+            Supervised::Systemd => "systemd",
+            Supervised::Auto => "auto",
+        }
+    }
+}
+
+impl FromStr for Supervised {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "no" => Ok(Supervised::No),
+            "systemd" => Ok(Supervised::Systemd),
+            "auto" => Ok(Supervised::Auto),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What `rate-limit` applies to.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum RateLimitBy {
+    /// Every connection has its own limit.
+    Client,
+    /// Connections from the same address share a limit.
+    Ip,
+}
+
+impl RateLimitBy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitBy::Client => "client",
+            RateLimitBy::Ip => "ip",
+        }
+    }
+}
+
+impl FromStr for RateLimitBy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "client" => Ok(RateLimitBy::Client),
+            "ip" => Ok(RateLimitBy::Ip),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for AppendFsync {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "always" => Ok(AppendFsync::Always),
+            "everysec" => Ok(AppendFsync::EverySec),
+            "no" => Ok(AppendFsync::No),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub port: u16,
+    /// Host and port of the master to replicate from.
+    pub replicaof: Option<(String, u16)>,
+    /// Whether a replica rejects writes from its own clients.
+    pub replica_read_only: bool,
+    /// Whether a replica answers clients while its link to the master is down.
+    pub replica_serve_stale_data: bool,
+    /// Password to AUTH with at the master.
+    pub masterauth: String,
+    /// Number of bytes of the replication stream kept for partial resynchronizations.
+    pub repl_backlog_size: usize,
+    /// Bytes of the replication stream which may be queued for a replica before it is
+    /// disconnected, or zero for no limit.
+    pub replica_output_buffer_limit: usize,
+    pub cluster_enabled: bool,
+    /// Time after which an unreachable cluster node is considered failing.
+    pub cluster_node_timeout: Duration,
+    pub dir: PathBuf,
+    pub dbfilename: String,
+    /// Save points as seconds and number of changes, e.g. `(3600, 1)`.
+    ///
+    /// NOTE: Snapshots are not taken periodically, the save points only decide whether
+    ///       one is taken when shutting down.
+    pub save: Vec<(u64, u64)>,
+    pub shutdown_on_sigterm: ShutdownMode,
+    pub shutdown_on_sigint: ShutdownMode,
+    pub stop_writes_on_bgsave_error: bool,
+    pub appendonly: bool,
+    pub appendfilename: String,
+    pub appenddirname: String,
+    pub appendfsync: AppendFsync,
+    pub aof_load_truncated: bool,
+    pub aof_use_rdb_preamble: bool,
+    pub auto_aof_rewrite_percentage: u64,
+    pub auto_aof_rewrite_min_size: u64,
+    /// Time after which idle clients are disconnected, or zero to never disconnect them.
+    pub timeout: Duration,
+    /// Bytes of requests that may be buffered without being handled before the client is
+    /// disconnected, see 'handle_connection'.
+    pub client_query_buffer_limit: usize,
+    /// Password clients have to AUTH with, or empty if none is required.
+    pub requirepass: String,
+    /// Minimum duration of an event to be recorded by LATENCY, or zero to record none.
+    pub latency_monitor_threshold: Duration,
+    /// Commands per second a client may run, or zero for no limit.
+    pub rate_limit: u64,
+    /// Commands a client may run at once before `rate_limit` applies.
+    pub rate_limit_burst: u64,
+    pub rate_limit_by: RateLimitBy,
+    /// Port of the HTTP endpoint serving Prometheus metrics, or zero to not serve them.
+    pub metrics_port: u16,
+    pub supervised: Supervised,
+    /// Whether SIGUSR1 starts a background save after logging the diagnostics report.
+    pub sigusr1_bgsave: bool,
+    /// Thresholds of the compact encodings of aggregates, e.g. 'hash-max-listpack-entries'.
+    pub encoding_limits: EncodingLimits,
+    /// Config file the server was started with, which can be reloaded at runtime.
+    pub config_file: Option<PathBuf>,
+    /// Command line arguments the server was started with, which override the config
+    /// file again when it is reloaded.
+    pub args: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 6379,
+            replicaof: None,
+            replica_read_only: true,
+            replica_serve_stale_data: true,
+            masterauth: String::new(),
+            repl_backlog_size: 1024 * 1024,
+            replica_output_buffer_limit: 256 * 1024 * 1024,
+            cluster_enabled: false,
+            cluster_node_timeout: Duration::from_millis(15000),
+            dir: PathBuf::from("."),
+            dbfilename: String::from("dump.rdb"),
+            // NOTE: Same default as in Redis.
+            save: vec![(3600, 1), (300, 100), (60, 10000)],
+            shutdown_on_sigterm: ShutdownMode::Default,
+            shutdown_on_sigint: ShutdownMode::Default,
+            stop_writes_on_bgsave_error: true,
+            appendonly: false,
+            appendfilename: String::from("appendonly.aof"),
+            appenddirname: String::from("appendonlydir"),
+            appendfsync: AppendFsync::EverySec,
+            aof_load_truncated: true,
+            aof_use_rdb_preamble: true,
+            auto_aof_rewrite_percentage: 100,
+            auto_aof_rewrite_min_size: 64 * 1024 * 1024,
+            timeout: Duration::ZERO,
+            client_query_buffer_limit: 1024 * 1024 * 1024,
+            requirepass: String::new(),
+            latency_monitor_threshold: Duration::ZERO,
+            rate_limit: 0,
+            rate_limit_burst: 100,
+            rate_limit_by: RateLimitBy::Client,
+            metrics_port: 0,
+            supervised: Supervised::No,
+            sigusr1_bgsave: false,
+            encoding_limits: EncodingLimits::default(),
+            config_file: None,
+            args: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Builds the Config from command line arguments, e.g. `--port 6380`.
+    ///
+    /// A first argument not starting with `--` is the path of a config file, whose
+    /// parameters are overridden by the remaining arguments.
+    pub fn from_args<I>(args: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = Config {
+            args: args.into_iter().collect(),
+            ..Default::default()
+        };
+        let mut args = config.args.clone().into_iter().peekable();
+
+        if let Some(path) = args.next_if(|arg| !arg.starts_with("--")) {
+            config.load_file(Path::new(&path))?;
+            config.config_file = Some(PathBuf::from(path));
+        }
+
+        while let Some(arg) = args.next() {
+            let entry = arg
+                .strip_prefix("--")
+                .and_then(find_config_entry)
+                .ok_or_else(|| anyhow!("Unknown argument {arg:?}"))?;
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("Missing value for argument {arg:?}"))?;
+            (entry.set)(&mut config, &value).map_err(|e| anyhow!("Invalid {arg}: {e}"))?;
+        }
+
+        Ok(config)
+    }
+    pub fn rdb_path(&self) -> PathBuf {
+        self.dir.join(&self.dbfilename)
+    }
+    /// Directory holding the files which make up the AOF.
+    pub fn aof_dir(&self) -> PathBuf {
+        self.dir.join(&self.appenddirname)
+    }
+}
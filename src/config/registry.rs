@@ -0,0 +1,462 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::config::{AppendFsync, Config, RateLimitBy, ShutdownMode, Supervised};
+
+/// Parameter of the [`Config`], as read by CONFIG GET and written by CONFIG SET and
+/// command line arguments.
+pub struct ConfigEntry {
+    pub name: &'static str,
+    /// Whether CONFIG SET can change the parameter while the server runs.
+    pub mutable: bool,
+    pub get: fn(&Config) -> String,
+    /// Parses and stores a value, returning why it is invalid otherwise.
+    pub set: fn(&mut Config, &str) -> Result<(), String>,
+}
+
+/// Every known parameter, in the order CONFIG GET lists them.
+pub const CONFIG_ENTRIES: &[ConfigEntry] = &[
+    ConfigEntry {
+        name: "port",
+        mutable: false,
+        get: |config| config.port.to_string(),
+        set: |config, value| parse_number(value).map(|port| config.port = port),
+    },
+    ConfigEntry {
+        name: "replicaof",
+        mutable: false,
+        get: |config| match &config.replicaof {
+            Some((host, port)) => format!("{host} {port}"),
+            None => String::new(),
+        },
+        set: |config, value| {
+            // NOTE: Host and port are passed as a single argument, e.g. "localhost 6379".
+            let Some((host, port)) = value.split_once(' ') else {
+                return Err(String::from("argument must be '<host> <port>'"));
+            };
+            let port = parse_number(port.trim())?;
+            config.replicaof = Some((host.to_string(), port));
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "replica-read-only",
+        mutable: true,
+        get: |config| yes_no(config.replica_read_only),
+        set: |config, value| parse_yes_no(value).map(|v| config.replica_read_only = v),
+    },
+    ConfigEntry {
+        name: "replica-serve-stale-data",
+        mutable: true,
+        get: |config| yes_no(config.replica_serve_stale_data),
+        set: |config, value| parse_yes_no(value).map(|v| config.replica_serve_stale_data = v),
+    },
+    ConfigEntry {
+        name: "masterauth",
+        mutable: true,
+        get: |config| config.masterauth.clone(),
+        set: |config, value| {
+            config.masterauth = value.to_string();
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "repl-backlog-size",
+        mutable: true,
+        get: |config| config.repl_backlog_size.to_string(),
+        set: |config, value| {
+            let size = in_range(parse_memory(value)?, 1, i64::MAX as u64)?;
+            config.repl_backlog_size = usize::try_from(size).map_err(|e| e.to_string())?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "replica-output-buffer-limit",
+        mutable: true,
+        get: |config| config.replica_output_buffer_limit.to_string(),
+        set: |config, value| {
+            let limit = in_range(parse_memory(value)?, 0, i64::MAX as u64)?;
+            config.replica_output_buffer_limit =
+                usize::try_from(limit).map_err(|e| e.to_string())?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "cluster-enabled",
+        mutable: false,
+        get: |config| yes_no(config.cluster_enabled),
+        set: |config, value| parse_yes_no(value).map(|v| config.cluster_enabled = v),
+    },
+    ConfigEntry {
+        name: "cluster-node-timeout",
+        mutable: false,
+        get: |config| config.cluster_node_timeout.as_millis().to_string(),
+        set: |config, value| {
+            let ms = in_range(parse_number(value)?, 0, i64::MAX as u64)?;
+            config.cluster_node_timeout = Duration::from_millis(ms);
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "dir",
+        mutable: false,
+        get: |config| config.dir.display().to_string(),
+        set: |config, value| {
+            config.dir = PathBuf::from(value);
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "dbfilename",
+        mutable: true,
+        get: |config| config.dbfilename.clone(),
+        set: |config, value| {
+            config.dbfilename = value.to_string();
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "save",
+        mutable: true,
+        get: |config| {
+            let points: Vec<String> = config
+                .save
+                .iter()
+                .map(|(seconds, changes)| format!("{seconds} {changes}"))
+                .collect();
+            points.join(" ")
+        },
+        set: |config, value| {
+            let numbers = value
+                .split_whitespace()
+                .map(parse_number)
+                .collect::<Result<Vec<u64>, _>>()?;
+            if numbers.len() % 2 != 0 {
+                return Err(String::from("Invalid save parameters"));
+            }
+            config.save = numbers
+                .chunks(2)
+                .map(|point| (point[0], point[1]))
+                .collect();
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "shutdown-on-sigterm",
+        mutable: true,
+        get: |config| config.shutdown_on_sigterm.as_str().to_string(),
+        set: |config, value| {
+            config.shutdown_on_sigterm = parse_shutdown_mode(value)?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "shutdown-on-sigint",
+        mutable: true,
+        get: |config| config.shutdown_on_sigint.as_str().to_string(),
+        set: |config, value| {
+            config.shutdown_on_sigint = parse_shutdown_mode(value)?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "stop-writes-on-bgsave-error",
+        mutable: true,
+        get: |config| yes_no(config.stop_writes_on_bgsave_error),
+        set: |config, value| parse_yes_no(value).map(|v| config.stop_writes_on_bgsave_error = v),
+    },
+    ConfigEntry {
+        name: "appendonly",
+        mutable: false,
+        get: |config| yes_no(config.appendonly),
+        set: |config, value| parse_yes_no(value).map(|v| config.appendonly = v),
+    },
+    ConfigEntry {
+        name: "appendfilename",
+        mutable: false,
+        get: |config| config.appendfilename.clone(),
+        set: |config, value| {
+            config.appendfilename = value.to_string();
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "appenddirname",
+        mutable: false,
+        get: |config| config.appenddirname.clone(),
+        set: |config, value| {
+            config.appenddirname = value.to_string();
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "appendfsync",
+        mutable: true,
+        get: |config| config.appendfsync.as_str().to_string(),
+        set: |config, value| {
+            config.appendfsync = AppendFsync::from_str(value).map_err(|_| {
+                String::from("argument(s) must be one of the following: always, everysec, no")
+            })?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "aof-load-truncated",
+        mutable: true,
+        get: |config| yes_no(config.aof_load_truncated),
+        set: |config, value| parse_yes_no(value).map(|v| config.aof_load_truncated = v),
+    },
+    ConfigEntry {
+        name: "aof-use-rdb-preamble",
+        mutable: true,
+        get: |config| yes_no(config.aof_use_rdb_preamble),
+        set: |config, value| parse_yes_no(value).map(|v| config.aof_use_rdb_preamble = v),
+    },
+    ConfigEntry {
+        name: "auto-aof-rewrite-percentage",
+        mutable: true,
+        get: |config| config.auto_aof_rewrite_percentage.to_string(),
+        set: |config, value| {
+            let percentage = in_range(parse_number(value)?, 0, i32::MAX as u64)?;
+            config.auto_aof_rewrite_percentage = percentage;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "auto-aof-rewrite-min-size",
+        mutable: true,
+        get: |config| config.auto_aof_rewrite_min_size.to_string(),
+        set: |config, value| {
+            let size = in_range(parse_memory(value)?, 0, i64::MAX as u64)?;
+            config.auto_aof_rewrite_min_size = size;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "timeout",
+        mutable: true,
+        get: |config| config.timeout.as_secs().to_string(),
+        set: |config, value| {
+            let secs = in_range(parse_number(value)?, 0, i32::MAX as u64)?;
+            config.timeout = Duration::from_secs(secs);
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "client-query-buffer-limit",
+        mutable: true,
+        get: |config| config.client_query_buffer_limit.to_string(),
+        set: |config, value| {
+            let limit = in_range(parse_memory(value)?, 1024 * 1024, i64::MAX as u64)?;
+            config.client_query_buffer_limit = usize::try_from(limit).map_err(|e| e.to_string())?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "requirepass",
+        mutable: true,
+        get: |config| config.requirepass.clone(),
+        set: |config, value| {
+            config.requirepass = value.to_string();
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "latency-monitor-threshold",
+        mutable: true,
+        get: |config| config.latency_monitor_threshold.as_millis().to_string(),
+        set: |config, value| {
+            let ms = in_range(parse_number(value)?, 0, i64::MAX as u64)?;
+            config.latency_monitor_threshold = Duration::from_millis(ms);
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "rate-limit",
+        mutable: true,
+        get: |config| config.rate_limit.to_string(),
+        set: |config, value| parse_number(value).map(|rate| config.rate_limit = rate),
+    },
+    ConfigEntry {
+        name: "rate-limit-burst",
+        mutable: true,
+        get: |config| config.rate_limit_burst.to_string(),
+        set: |config, value| {
+            config.rate_limit_burst = in_range(parse_number(value)?, 1, i64::MAX as u64)?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "rate-limit-by",
+        mutable: true,
+        get: |config| config.rate_limit_by.as_str().to_string(),
+        set: |config, value| {
+            config.rate_limit_by = RateLimitBy::from_str(value).map_err(|_| {
+                String::from("argument(s) must be one of the following: client, ip")
+            })?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "metrics-port",
+        mutable: false,
+        get: |config| config.metrics_port.to_string(),
+        set: |config, value| parse_number(value).map(|port| config.metrics_port = port),
+    },
+    ConfigEntry {
+        name: "supervised",
+        mutable: false,
+        get: |config| config.supervised.as_str().to_string(),
+        set: |config, value| {
+            config.supervised = Supervised::from_str(value).map_err(|_| {
+                String::from("argument(s) must be one of the following: no, systemd, auto")
+            })?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "sigusr1-bgsave",
+        mutable: true,
+        get: |config| yes_no(config.sigusr1_bgsave),
+        set: |config, value| parse_yes_no(value).map(|v| config.sigusr1_bgsave = v),
+    },
+    ConfigEntry {
+        name: "hash-max-listpack-entries",
+        mutable: true,
+        get: |config| config.encoding_limits.hash_max_listpack_entries.to_string(),
+        set: |config, value| {
+            config.encoding_limits.hash_max_listpack_entries = parse_number(value)?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "hash-max-listpack-value",
+        mutable: true,
+        get: |config| config.encoding_limits.hash_max_listpack_value.to_string(),
+        set: |config, value| {
+            config.encoding_limits.hash_max_listpack_value = parse_number(value)?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "list-max-listpack-size",
+        mutable: true,
+        get: |config| config.encoding_limits.list_max_listpack_size.to_string(),
+        set: |config, value| {
+            // NOTE: Negative sizes select a size in bytes, of which -5 is the largest.
+            config.encoding_limits.list_max_listpack_size =
+                in_range(parse_number(value)?, -5, i64::MAX)?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "set-max-intset-entries",
+        mutable: true,
+        get: |config| config.encoding_limits.set_max_intset_entries.to_string(),
+        set: |config, value| {
+            config.encoding_limits.set_max_intset_entries = parse_number(value)?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "set-max-listpack-entries",
+        mutable: true,
+        get: |config| config.encoding_limits.set_max_listpack_entries.to_string(),
+        set: |config, value| {
+            config.encoding_limits.set_max_listpack_entries = parse_number(value)?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "set-max-listpack-value",
+        mutable: true,
+        get: |config| config.encoding_limits.set_max_listpack_value.to_string(),
+        set: |config, value| {
+            config.encoding_limits.set_max_listpack_value = parse_number(value)?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "zset-max-listpack-entries",
+        mutable: true,
+        get: |config| config.encoding_limits.zset_max_listpack_entries.to_string(),
+        set: |config, value| {
+            config.encoding_limits.zset_max_listpack_entries = parse_number(value)?;
+            Ok(())
+        },
+    },
+    ConfigEntry {
+        name: "zset-max-listpack-value",
+        mutable: true,
+        get: |config| config.encoding_limits.zset_max_listpack_value.to_string(),
+        set: |config, value| {
+            config.encoding_limits.zset_max_listpack_value = parse_number(value)?;
+            Ok(())
+        },
+    },
+];
+
+/// Looks up a parameter by its case-insensitive name.
+pub fn find_config_entry(name: &str) -> Option<&'static ConfigEntry> {
+    CONFIG_ENTRIES
+        .iter()
+        .find(|entry| entry.name.eq_ignore_ascii_case(name))
+}
+
+fn yes_no(value: bool) -> String {
+    String::from(if value { "yes" } else { "no" })
+}
+
+fn parse_yes_no(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(String::from("argument must be 'yes' or 'no'")),
+    }
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| String::from("argument couldn't be parsed into an integer"))
+}
+
+/// Parses a number of bytes, optionally followed by a unit: 'k', 'm' and 'g' are
+/// powers of 1000 while 'kb', 'mb' and 'gb' are powers of 1024, e.g. "64mb".
+fn parse_shutdown_mode(value: &str) -> Result<ShutdownMode, String> {
+    ShutdownMode::from_str(value).map_err(|_| {
+        String::from("argument(s) must be one of the following: default, save, nosave")
+    })
+}
+
+fn parse_memory(value: &str) -> Result<u64, String> {
+    let lowercase = value.to_ascii_lowercase();
+    let digits = lowercase.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match &lowercase[digits.len()..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err(String::from("argument must be a memory value")),
+    };
+    let number: u64 = digits
+        .parse()
+        .map_err(|_| String::from("argument must be a memory value"))?;
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| String::from("argument must be a memory value"))
+}
+
+fn in_range<T: PartialOrd + Display>(value: T, min: T, max: T) -> Result<T, String> {
+    if value < min || value > max {
+        return Err(format!(
+            "argument must be between {min} and {max} inclusive"
+        ));
+    }
+    Ok(value)
+}
@@ -1,6 +1,51 @@
-use std::collections::{HashMap, HashSet};
-use std::time::Instant;
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+const MAX_EMBSTR_LEN: usize = 44;
+/// Stale entries the expiry index may hold beyond twice the number of keys.
+const EXPIRIES_SLACK: usize = 1024;
+/// Node pointers and spare capacity per entry of the table of keys, roughly.
+const HASHTABLE_ENTRY_OVERHEAD: usize = 8;
+/// Keys a SCAN call visits at most per key it should return, which bounds the work
+/// spent skipping expired keys, same as in Redis.
+const SCAN_MAX_VISITS_PER_KEY: usize = 10;
+
+/// Sizes up to which Redis stores aggregates with a compact encoding, as configured by
+/// 'set-max-intset-entries' and the '*-max-listpack-*' parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingLimits {
+    pub hash_max_listpack_entries: usize,
+    pub hash_max_listpack_value: usize,
+    /// Maximum number of entries of a list, or if negative its maximum size from -1 for
+    /// 4 KiB to -5 for 64 KiB.
+    pub list_max_listpack_size: i64,
+    pub set_max_intset_entries: usize,
+    pub set_max_listpack_entries: usize,
+    pub set_max_listpack_value: usize,
+    pub zset_max_listpack_entries: usize,
+    pub zset_max_listpack_value: usize,
+}
+
+impl Default for EncodingLimits {
+    fn default() -> Self {
+        // NOTE: Same defaults as in Redis.
+        Self {
+            hash_max_listpack_entries: 128,
+            hash_max_listpack_value: 64,
+            list_max_listpack_size: -2,
+            set_max_intset_entries: 512,
+            set_max_listpack_entries: 128,
+            set_max_listpack_value: 64,
+            zset_max_listpack_entries: 128,
+            zset_max_listpack_value: 64,
+        }
+    }
+}
+
+#[derive(Debug)]
 pub enum DatabaseValue {
     Null,
     Boolean(bool),
@@ -11,8 +56,197 @@ pub enum DatabaseValue {
     Error(String),
     Set(HashSet<DatabaseValue>),
     Map(HashMap<DatabaseValue, DatabaseValue>),
+    SortedSet(HashMap<DatabaseValue, f64>),
+}
+
+impl DatabaseValue {
+    /// Type as reported by TYPE and filtered by 'SCAN ... TYPE'.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DatabaseValue::Array(_) => "list",
+            DatabaseValue::Set(_) => "set",
+            DatabaseValue::Map(_) => "hash",
+            DatabaseValue::SortedSet(_) => "zset",
+            _ => "string",
+        }
+    }
+    /// String form of a scalar value, which is how it is persisted.
+    ///
+    /// Returns [`None`] for values that only exist as aggregate members or replies.
+    pub fn to_scalar_string(&self) -> Option<String> {
+        match self {
+            DatabaseValue::String(s) => Some(s.clone()),
+            DatabaseValue::Integer(i) => Some(i.to_string()),
+            DatabaseValue::Double(d) => Some(d.to_string()),
+            DatabaseValue::Boolean(b) => Some(String::from(if *b { "1" } else { "0" })),
+            _ => None,
+        }
+    }
+    /// Estimated number of bytes the value occupies, including its members.
+    pub fn memory_usage(&self) -> usize {
+        let size = std::mem::size_of::<DatabaseValue>();
+        match self {
+            DatabaseValue::String(s) | DatabaseValue::Error(s) => size + s.capacity(),
+            DatabaseValue::Array(values) => {
+                size + values
+                    .iter()
+                    .map(DatabaseValue::memory_usage)
+                    .sum::<usize>()
+            }
+            DatabaseValue::Set(members) => {
+                size + members
+                    .iter()
+                    .map(|member| member.memory_usage() + HASHTABLE_ENTRY_OVERHEAD)
+                    .sum::<usize>()
+            }
+            DatabaseValue::Map(map) => {
+                size + map
+                    .iter()
+                    .map(|(k, v)| k.memory_usage() + v.memory_usage() + HASHTABLE_ENTRY_OVERHEAD)
+                    .sum::<usize>()
+            }
+            DatabaseValue::SortedSet(members) => {
+                size + members
+                    .keys()
+                    .map(|member| {
+                        member.memory_usage()
+                            + std::mem::size_of::<f64>()
+                            + HASHTABLE_ENTRY_OVERHEAD
+                    })
+                    .sum::<usize>()
+            }
+            _ => size,
+        }
+    }
+    /// Encoding Redis would store the value with given the `limits` of the compact
+    /// encodings, as reported by DEBUG OBJECT.
+    pub fn encoding(&self, limits: &EncodingLimits) -> &'static str {
+        match self {
+            DatabaseValue::String(s) if s.parse::<i64>().is_ok() => "int",
+            DatabaseValue::String(s) if s.len() <= MAX_EMBSTR_LEN => "embstr",
+            DatabaseValue::String(_) => "raw",
+            DatabaseValue::Array(values)
+                if is_list_listpack(values, limits.list_max_listpack_size) =>
+            {
+                "listpack"
+            }
+            DatabaseValue::Array(_) => "quicklist",
+            DatabaseValue::Set(members)
+                if members.len() <= limits.set_max_intset_entries
+                    && members
+                        .iter()
+                        .all(|m| matches!(m, DatabaseValue::Integer(_))) =>
+            {
+                "intset"
+            }
+            DatabaseValue::Set(members)
+                if is_listpack(
+                    members.len(),
+                    members.iter(),
+                    limits.set_max_listpack_entries,
+                    limits.set_max_listpack_value,
+                ) =>
+            {
+                "listpack"
+            }
+            DatabaseValue::Set(_) => "hashtable",
+            DatabaseValue::Map(map)
+                if is_listpack(
+                    map.len(),
+                    map.iter().flat_map(|(k, v)| [k, v]),
+                    limits.hash_max_listpack_entries,
+                    limits.hash_max_listpack_value,
+                ) =>
+            {
+                "listpack"
+            }
+            DatabaseValue::Map(_) => "hashtable",
+            DatabaseValue::SortedSet(members)
+                if is_listpack(
+                    members.len(),
+                    members.keys(),
+                    limits.zset_max_listpack_entries,
+                    limits.zset_max_listpack_value,
+                ) =>
+            {
+                "listpack"
+            }
+            DatabaseValue::SortedSet(_) => "skiplist",
+            _ => "int",
+        }
+    }
+}
+
+/// Whether an aggregate of `len` members is small enough to be a listpack.
+fn is_listpack<'a>(
+    len: usize,
+    mut members: impl Iterator<Item = &'a DatabaseValue>,
+    max_entries: usize,
+    max_value: usize,
+) -> bool {
+    len <= max_entries
+        && members.all(|member| {
+            member
+                .to_scalar_string()
+                .is_some_and(|s| s.len() <= max_value)
+        })
+}
+
+/// Whether a list fits into a single listpack node of a quicklist, whose size is
+/// limited by `max_size` like 'list-max-listpack-size'.
+fn is_list_listpack(values: &[DatabaseValue], max_size: i64) -> bool {
+    if max_size >= 0 {
+        return values.len() as u64 <= max_size as u64;
+    }
+    // NOTE: A listpack has a 7 byte header and terminator, and every entry at least
+    //       a byte each for its encoding and back-length next to its data.
+    let max_bytes = 4096_usize << ((-max_size).min(5) - 1);
+    let bytes: usize = values
+        .iter()
+        .map(|value| value.to_scalar_string().map_or(0, |s| s.len()) + 2)
+        .sum();
+    7 + bytes <= max_bytes
+}
+
+impl Eq for DatabaseValue {}
+
+impl PartialEq for DatabaseValue {
+    fn eq(&self, other: &DatabaseValue) -> bool {
+        match (self, other) {
+            (DatabaseValue::Null, DatabaseValue::Null) => true,
+            (DatabaseValue::Boolean(b1), DatabaseValue::Boolean(b2)) => b1 == b2,
+            (DatabaseValue::Integer(i1), DatabaseValue::Integer(i2)) => i1 == i2,
+            (DatabaseValue::Double(d1), DatabaseValue::Double(d2)) => d1 == d2,
+            (DatabaseValue::String(s1), DatabaseValue::String(s2)) => s1 == s2,
+            (DatabaseValue::Error(e1), DatabaseValue::Error(e2)) => e1 == e2,
+            (DatabaseValue::Array(arr1), DatabaseValue::Array(arr2)) => arr1 == arr2,
+            (DatabaseValue::Set(set1), DatabaseValue::Set(set2)) => set1 == set2,
+            (DatabaseValue::Map(map1), DatabaseValue::Map(map2)) => map1 == map2,
+            (DatabaseValue::SortedSet(zset1), DatabaseValue::SortedSet(zset2)) => zset1 == zset2,
+            _ => false,
+        }
+    }
+}
+
+impl std::hash::Hash for DatabaseValue {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: std::hash::Hasher,
+    {
+        match self {
+            DatabaseValue::Boolean(b) => b.hash(state),
+            DatabaseValue::Integer(i) => i.hash(state),
+            DatabaseValue::Double(d) => d.to_bits().hash(state),
+            DatabaseValue::String(s) => s.hash(state),
+            DatabaseValue::Error(e) => e.hash(state),
+            DatabaseValue::Array(vec) => Self::hash_slice(vec, state),
+            // TODO: Implement Set, Map and SortedSet Hash
+            _ => {}
+        }
+    }
 }
 
+#[derive(Debug)]
 pub enum DatabaseSlot {
     Simple(DatabaseValue),
     Timed {
@@ -21,6 +255,172 @@ pub enum DatabaseSlot {
     },
 }
 
+impl DatabaseSlot {
+    pub fn value(&self) -> &DatabaseValue {
+        match self {
+            DatabaseSlot::Simple(value) | DatabaseSlot::Timed { value, .. } => value,
+        }
+    }
+    pub fn expires(&self) -> Option<Instant> {
+        match self {
+            DatabaseSlot::Simple(_) => None,
+            DatabaseSlot::Timed { expires, .. } => Some(*expires),
+        }
+    }
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.expires().is_some_and(|expires| expires <= now)
+    }
+    /// Expiry as Unix time in milliseconds, which is how it is persisted and propagated.
+    pub fn expires_unix_ms(&self) -> Option<u64> {
+        let remaining = self.expires()?.saturating_duration_since(Instant::now());
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+        Some((now + remaining).as_millis() as u64)
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct Database {
-    values: HashMap<String, DatabaseSlot>,
+    /// Values by the position of their key in the iteration order of SCAN and the key,
+    /// see [`Database::scan`].
+    values: BTreeMap<(u64, String), DatabaseSlot>,
+    /// Keys with an expiry, soonest first, so that expiring keys are found without
+    /// scanning every key.
+    ///
+    /// Entries aren't removed when their key is deleted or its expiry changes, they are
+    /// skipped once they come up instead.
+    expiries: BinaryHeap<Reverse<(Instant, String)>>,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn insert(&mut self, key: String, slot: DatabaseSlot) -> Option<DatabaseSlot> {
+        if let Some(expires) = slot.expires() {
+            self.expiries.push(Reverse((expires, key.clone())));
+        }
+        self.values.insert((scan_position(&key), key), slot)
+    }
+    /// Looks up a key, hiding it once it expired.
+    ///
+    /// Expired keys are only removed by [`Database::remove_expired`], since a replica
+    /// has to keep them until the master propagates their deletion.
+    pub fn get(&self, key: &str) -> Option<&DatabaseSlot> {
+        self.get_including_expired(key)
+            .filter(|slot| !slot.is_expired(Instant::now()))
+    }
+    pub fn remove(&mut self, key: &str) -> Option<DatabaseSlot> {
+        self.values.remove(&(scan_position(key), key.to_string()))
+    }
+    fn get_including_expired(&self, key: &str) -> Option<&DatabaseSlot> {
+        // NOTE: Looked up as a range, so that the key isn't copied for the tuple.
+        let position = scan_position(key);
+        self.values
+            .range((position, String::new())..)
+            .take_while(|((other, _), _)| *other == position)
+            .find(|((_, other), _)| other == key)
+            .map(|(_, slot)| slot)
+    }
+    /// Removes every key that expired by `now` and returns their names, soonest first.
+    pub fn remove_expired(&mut self, now: Instant) -> Vec<String> {
+        let mut expired = Vec::new();
+        while let Some(Reverse((expires, _))) = self.expiries.peek() {
+            if *expires > now {
+                break;
+            }
+            let Some(Reverse((expires, key))) = self.expiries.pop() else {
+                break;
+            };
+            // NOTE: Skips stale entries of keys which were deleted or got a new expiry.
+            let current = self
+                .get_including_expired(&key)
+                .and_then(DatabaseSlot::expires);
+            if current == Some(expires) {
+                let entry = self.values.remove_entry(&(scan_position(&key), key));
+                expired.extend(entry.map(|((_, key), _)| key));
+            }
+        }
+        // NOTE: Rebuilding the index once mostly stale entries are left keeps it from
+        //       growing with keys whose expiry is set over and over again.
+        if self.expiries.len() > 2 * self.values.len() + EXPIRIES_SLACK {
+            self.expiries = self
+                .values
+                .iter()
+                .filter_map(|((_, key), slot)| Some(Reverse((slot.expires()?, key.clone()))))
+                .collect();
+        }
+        expired
+    }
+    /// Number of entries in the expiry index, including stale ones.
+    pub fn expiry_index_len(&self) -> usize {
+        self.expiries.len()
+    }
+    /// Returns about `count` keys starting at `cursor`, and the cursor to continue
+    /// from, which is zero once every key was returned.
+    ///
+    /// The cursor is a position in the order of a hash of the key names, which the
+    /// table of keys is sorted by, so every key which exists during the whole
+    /// iteration is returned exactly once, no matter how many keys are added or
+    /// removed in between. Keys with the same hash are always returned together, so
+    /// more than `count` are returned then.
+    ///
+    /// A call only visits the keys it returns and at most [`SCAN_MAX_VISITS_PER_KEY`]
+    /// times `count` expired ones, which may make it return fewer keys.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&String, &DatabaseSlot)>) {
+        let now = Instant::now();
+        let count = count.max(1);
+        let mut keys = Vec::new();
+        let mut previous = None;
+        let range = self.values.range((cursor, String::new())..);
+        for (visited, ((position, key), slot)) in range.enumerate() {
+            let full = keys.len() >= count || visited >= count * SCAN_MAX_VISITS_PER_KEY;
+            if full && previous != Some(*position) {
+                return (*position, keys);
+            }
+            previous = Some(*position);
+            if !slot.is_expired(now) {
+                keys.push((key, slot));
+            }
+        }
+        (0, keys)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &DatabaseSlot)> {
+        self.values.iter().map(|((_, key), slot)| (key, slot))
+    }
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+    /// Estimated number of bytes the keys and values occupy.
+    pub fn dataset_bytes(&self) -> usize {
+        self.values
+            .iter()
+            .map(|((_, key), slot)| key.capacity() + slot.value().memory_usage())
+            .sum()
+    }
+    /// Estimated number of bytes the table of the keys occupies itself.
+    pub fn hashtable_overhead(&self) -> usize {
+        self.values.len()
+            * (std::mem::size_of::<((u64, String), DatabaseSlot)>() + HASHTABLE_ENTRY_OVERHEAD)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// Position of a key in the iteration order of SCAN, see [`Database::scan`].
+fn scan_position(key: &str) -> u64 {
+    // NOTE: The default hasher uses fixed keys, unlike `RandomState`, so the positions
+    //       stay the same while the server runs.
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Converts an expiry in Unix milliseconds back to an [`Instant`].
+///
+/// Returns [`None`] if the expiry already lies in the past.
+pub fn unix_ms_to_instant(ms: u64) -> Option<Instant> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    let remaining = Duration::from_millis(ms).checked_sub(now)?;
+    Instant::now().checked_add(remaining)
 }
@@ -0,0 +1,327 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Write};
+
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped_transform, is_not, tag, take_while_m_n},
+    character::complete::{char, multispace0},
+    combinator::{all_consuming, map, map_opt, opt, value},
+    multi::separated_list0,
+    number::complete::double,
+    sequence::{delimited, preceded, separated_pair},
+    IResult,
+};
+use thiserror::Error;
+
+use crate::db::{unix_ms_to_instant, Database, DatabaseSlot, DatabaseValue};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum JsonError {
+    #[error("invalid json at offset {0}")]
+    InvalidJson(usize),
+    #[error("invalid entry for key {0:?}: {1}")]
+    InvalidEntry(String, &'static str),
+}
+
+/// Minimal JSON document model used by the dataset export.
+#[derive(Debug, PartialEq, Clone)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Members in document order, which keeps the output stable for diffing.
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl Display for JsonValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Bool(b) => write!(f, "{b}"),
+            // NOTE: JSON has no representation for infinity, which scores can be.
+            JsonValue::Number(n) if !n.is_finite() => write_escaped(f, &n.to_string()),
+            JsonValue::Number(n) => write!(f, "{n}"),
+            JsonValue::String(s) => write_escaped(f, s),
+            JsonValue::Array(values) => {
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            JsonValue::Object(members) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_escaped(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_escaped(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+fn parse_string(input: &str) -> IResult<&str, String> {
+    let unicode_escape = map_opt(
+        preceded(
+            char('u'),
+            take_while_m_n(4, 4, |c: char| c.is_ascii_hexdigit()),
+        ),
+        |hex| u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+    );
+    let escape = alt((
+        value('"', char('"')),
+        value('\\', char('\\')),
+        value('/', char('/')),
+        value('\n', char('n')),
+        value('\r', char('r')),
+        value('\t', char('t')),
+        value('\u{8}', char('b')),
+        value('\u{c}', char('f')),
+        unicode_escape,
+    ));
+    delimited(
+        char('"'),
+        map(
+            opt(escaped_transform(is_not("\"\\"), '\\', escape)),
+            Option::unwrap_or_default,
+        ),
+        char('"'),
+    )(input)
+}
+
+fn parse_value(input: &str) -> IResult<&str, JsonValue> {
+    let array = map(
+        delimited(
+            char('['),
+            separated_list0(char(','), parse_value),
+            preceded(multispace0, char(']')),
+        ),
+        JsonValue::Array,
+    );
+    let member = separated_pair(
+        preceded(multispace0, parse_string),
+        preceded(multispace0, char(':')),
+        parse_value,
+    );
+    let object = map(
+        delimited(
+            char('{'),
+            separated_list0(preceded(multispace0, char(',')), member),
+            preceded(multispace0, char('}')),
+        ),
+        JsonValue::Object,
+    );
+
+    delimited(
+        multispace0,
+        alt((
+            value(JsonValue::Null, tag("null")),
+            value(JsonValue::Bool(true), tag("true")),
+            value(JsonValue::Bool(false), tag("false")),
+            map(parse_string, JsonValue::String),
+            map(double, JsonValue::Number),
+            array,
+            object,
+        )),
+        multispace0,
+    )(input)
+}
+
+pub fn parse_json(input: &str) -> Result<JsonValue, JsonError> {
+    all_consuming(parse_value)(input)
+        .map(|(_, value)| value)
+        .map_err(|e| match e {
+            nom::Err::Error(e) | nom::Err::Failure(e) => {
+                JsonError::InvalidJson(input.len() - e.input.len())
+            }
+            nom::Err::Incomplete(_) => JsonError::InvalidJson(input.len()),
+        })
+}
+
+/// Exports the Database as JSON object mapping each key to its type, value and
+/// expiry in Unix milliseconds, e.g.
+///
+/// ```json
+/// {"key":{"type":"string","value":"bar","expires_at":1700000000000}}
+/// ```
+///
+/// Keys and unordered collections are sorted, so that the output of two equal
+/// datasets can be diffed.
+pub fn dump_json(db: &Database) -> String {
+    let mut keys: Vec<_> = db.iter().collect();
+    keys.sort_unstable_by_key(|(key, _)| *key);
+
+    let mut entries = Vec::with_capacity(keys.len());
+    for (key, slot) in keys {
+        let Some((value_type, value)) = value_to_json(slot.value()) else {
+            eprintln!("Skipping key {key:?} in JSON export: value has no JSON form");
+            continue;
+        };
+        let mut entry = vec![
+            (String::from("type"), JsonValue::String(value_type.into())),
+            (String::from("value"), value),
+        ];
+        if let Some(expires_ms) = slot.expires_unix_ms() {
+            entry.push((
+                String::from("expires_at"),
+                JsonValue::Number(expires_ms as f64),
+            ));
+        }
+        entries.push((key.clone(), JsonValue::Object(entry)));
+    }
+
+    JsonValue::Object(entries).to_string()
+}
+
+fn value_to_json(value: &DatabaseValue) -> Option<(&'static str, JsonValue)> {
+    let string = |v: &DatabaseValue| v.to_scalar_string().map(JsonValue::String);
+    let sorted = |mut values: Vec<JsonValue>| {
+        values.sort_unstable_by_key(|v| v.to_string());
+        values
+    };
+
+    Some(match value {
+        DatabaseValue::Array(list) => (
+            "list",
+            JsonValue::Array(list.iter().map(string).collect::<Option<_>>()?),
+        ),
+        DatabaseValue::Set(set) => (
+            "set",
+            JsonValue::Array(sorted(set.iter().map(string).collect::<Option<_>>()?)),
+        ),
+        DatabaseValue::Map(map) => {
+            let mut members = map
+                .iter()
+                .map(|(f, v)| Some((f.to_scalar_string()?, string(v)?)))
+                .collect::<Option<Vec<_>>>()?;
+            members.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+            ("hash", JsonValue::Object(members))
+        }
+        DatabaseValue::SortedSet(zset) => {
+            let mut members = zset
+                .iter()
+                .map(|(m, score)| Some((m.to_scalar_string()?, JsonValue::Number(*score))))
+                .collect::<Option<Vec<_>>>()?;
+            members.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+            ("zset", JsonValue::Object(members))
+        }
+        value => ("string", string(value)?),
+    })
+}
+
+/// Builds a Database from the output of [`dump_json`].
+///
+/// Keys whose expiry lies in the past are skipped.
+pub fn load_json(input: &str) -> Result<Database, JsonError> {
+    let JsonValue::Object(entries) = parse_json(input)? else {
+        return Err(JsonError::InvalidJson(0));
+    };
+
+    let mut db = Database::new();
+    for (key, entry) in entries {
+        let invalid = |reason| JsonError::InvalidEntry(key.clone(), reason);
+
+        let value_type = entry.get("type").and_then(JsonValue::as_str);
+        let value = entry.get("value").ok_or_else(|| invalid("missing value"))?;
+        let value = match (value_type, value) {
+            (Some("string"), JsonValue::String(s)) => DatabaseValue::String(s.clone()),
+            (Some("list"), JsonValue::Array(list)) => DatabaseValue::Array(
+                json_strings(list).ok_or_else(|| invalid("non string element"))?,
+            ),
+            (Some("set"), JsonValue::Array(set)) => DatabaseValue::Set(
+                json_strings(set)
+                    .ok_or_else(|| invalid("non string member"))?
+                    .into_iter()
+                    .collect::<HashSet<_>>(),
+            ),
+            (Some("hash"), JsonValue::Object(members)) => DatabaseValue::Map(
+                members
+                    .iter()
+                    .map(|(f, v)| {
+                        let v = v.as_str()?;
+                        Some((
+                            DatabaseValue::String(f.clone()),
+                            DatabaseValue::String(v.into()),
+                        ))
+                    })
+                    .collect::<Option<HashMap<_, _>>>()
+                    .ok_or_else(|| invalid("non string field value"))?,
+            ),
+            (Some("zset"), JsonValue::Object(members)) => DatabaseValue::SortedSet(
+                members
+                    .iter()
+                    .map(|(m, score)| {
+                        let score = match score {
+                            JsonValue::Number(n) => *n,
+                            JsonValue::String(s) => s.parse().ok()?,
+                            _ => return None,
+                        };
+                        Some((DatabaseValue::String(m.clone()), score))
+                    })
+                    .collect::<Option<HashMap<_, _>>>()
+                    .ok_or_else(|| invalid("invalid score"))?,
+            ),
+            (None, _) => return Err(invalid("missing type")),
+            _ => return Err(invalid("value does not match type")),
+        };
+
+        let slot = match entry.get("expires_at") {
+            None => DatabaseSlot::Simple(value),
+            Some(JsonValue::Number(ms)) if *ms >= 0.0 => match unix_ms_to_instant(*ms as u64) {
+                Some(expires) => DatabaseSlot::Timed { expires, value },
+                None => continue,
+            },
+            Some(_) => return Err(invalid("invalid expiry")),
+        };
+        db.insert(key, slot);
+    }
+
+    Ok(db)
+}
+
+fn json_strings(values: &[JsonValue]) -> Option<Vec<DatabaseValue>> {
+    values
+        .iter()
+        .map(|v| v.as_str().map(|s| DatabaseValue::String(s.into())))
+        .collect()
+}
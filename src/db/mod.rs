@@ -1,3 +1,5 @@
 mod database;
+mod json;
 
-pub use database::{Database, DatabaseSlot, DatabaseValue};
+pub use database::{unix_ms_to_instant, Database, DatabaseSlot, DatabaseValue, EncodingLimits};
+pub use json::{dump_json, load_json, parse_json, JsonError, JsonValue};
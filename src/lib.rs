@@ -8,15 +8,67 @@ mod types;
 use types::AsyncReader;
 
 mod resp;
-use resp::{parse_resp_value, RespDataType, RespReader, RespReaderError, RespValue, RespWriter};
+pub use resp::RespValue;
+use resp::{parse_resp_value, RespDataType, RespReader, RespReaderError, RespWriter};
 
 mod db;
 use db::Database;
 
+mod rdb;
+use rdb::{dump_database, RdbReader};
+pub use rdb::{inspect_rdb, RdbKey, RdbReaderError, RdbSummary};
+
+mod command;
+use command::Command;
+pub use command::{
+    reply_later, CommandHandler, HandlerFuture, PendingReply, RedisError, ReplyHandle,
+};
+
+mod server;
+use server::{info, ClientKind, ConnectionContext, ServerState};
+pub use server::{RedisServer, RedisServerBuilder, RedisServerHandle};
+
+mod replication;
+
+mod acl;
+
+mod cluster;
+
+mod aof;
+use aof::{load_aof, rewrite_commands, AofWriter};
+
+mod util;
+use util::{crc16, crc64};
+
+pub mod testing;
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Dispatches the command `args` as sent by the connection `ctx` and returns the reply.
+    fn request(
+        state: &std::sync::Arc<ServerState>,
+        ctx: &mut ConnectionContext,
+        args: &[&str],
+    ) -> String {
+        let args = args
+            .iter()
+            .map(|arg| RespValue::BulkString((*arg).into()))
+            .collect();
+        request_frame(state, ctx, RespValue::Array(args).to_string().as_bytes())
+    }
+
+    /// Dispatches the raw request `frame` as sent by the connection `ctx` and returns the reply.
+    fn request_frame(
+        state: &std::sync::Arc<ServerState>,
+        ctx: &mut ConnectionContext,
+        frame: &[u8],
+    ) -> String {
+        let (_, value) = parse_resp_value(frame).unwrap();
+        command::dispatch(state, ctx, value, frame).to_string()
+    }
+
     #[test]
     fn test_valid_parse_resp_simple_string1() {
         let input = b"+Test\r\n";
@@ -27,6 +79,16 @@ mod tests {
         );
     }
     #[test]
+    fn test_valid_parse_resp_null_bulk_string() {
+        let input = b"$-1\r\n";
+
+        assert_eq!(
+            (&b""[..], RespValue::NullBulkString),
+            parse_resp_value(input).unwrap()
+        );
+        assert_eq!(RespValue::NullBulkString.to_string().as_bytes(), input);
+    }
+    #[test]
     fn test_invalid_parse_resp_simple_string() {
         let inputs: Vec<&[u8]> = vec![b"+Test", b"+Test\r", b"+\r", b"+\r", b"Test\r\n"];
 
@@ -34,4 +96,2928 @@ mod tests {
             assert!(parse_resp_value(input).is_err(), "Failed on {:?}", input);
         }
     }
+
+    /// Deterministic xorshift generator for the property tests, so that a failing
+    /// case is reproduced by every run.
+    struct TestRng(u64);
+
+    impl TestRng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+        fn string(&mut self, line: bool) -> String {
+            const CHARS: &[char] = &['a', 'Z', '0', ' ', ':', '*', '$', 'ä', '€', '🦀'];
+            let len = self.below(12);
+            (0..len)
+                .map(|_| match self.below(8) {
+                    0 if !line => '\r',
+                    1 if !line => '\n',
+                    _ => CHARS[self.below(CHARS.len() as u64) as usize],
+                })
+                .collect()
+        }
+        /// Value of the types RESP2 has, or of all RESP3 types if `resp3` is set.
+        fn value(&mut self, depth: u32, resp3: bool) -> RespValue<'static> {
+            let kinds = if resp3 { 14 } else { 5 };
+            // NOTE: Aggregates are the last kinds, which leaves them out at the maximum depth.
+            let kinds = if depth == 0 { kinds - 4 } else { kinds };
+            let mut values = |rng: &mut Self| -> Vec<_> {
+                (0..rng.below(4))
+                    .map(|_| rng.value(depth - 1, resp3))
+                    .collect()
+            };
+            match (resp3, self.below(kinds)) {
+                (_, 0) => RespValue::SimpleString(self.string(true).into()),
+                (_, 1) => RespValue::SimpleError(self.string(true).into()),
+                (_, 2) => RespValue::Integer(self.next() as i64),
+                (_, 3) => RespValue::BulkString(self.string(false).into()),
+                (false, _) => RespValue::Array(values(self)),
+                (true, 4) => RespValue::Null,
+                (true, 5) => RespValue::Boolean(self.below(2) == 0),
+                (true, 6) => RespValue::Double(self.next() as i32 as f64 / 8.0),
+                (true, 7) => RespValue::BigNumber(format!("{}", self.next() as i64).into()),
+                (true, 8) => RespValue::BulkError(self.string(false).into()),
+                (true, 9) => RespValue::VerbatimString(("txt".into(), self.string(false).into())),
+                (true, 10) => RespValue::Array(values(self)),
+                (true, 11) => RespValue::Push(values(self)),
+                (true, 12) => RespValue::Set(values(self).into_iter().collect()),
+                _ => RespValue::Map(
+                    values(self)
+                        .into_iter()
+                        .map(|key| (key, self.value(depth - 1, resp3)))
+                        .collect(),
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_resp_round_trip() {
+        let mut rng = TestRng(0x2545_f491_4f6c_dd1d);
+        for resp3 in [false, true] {
+            for _ in 0..2000 {
+                let value = rng.value(3, resp3);
+                let encoded = value.to_string();
+                let (rest, parsed) = parse_resp_value(encoded.as_bytes())
+                    .unwrap_or_else(|e| panic!("Failed to parse {encoded:?}: {e:?}"));
+                assert!(rest.is_empty(), "Left {rest:?} of {encoded:?}");
+                assert_eq!(parsed, value, "Round trip of {encoded:?}");
+
+                // Every strict prefix has to be reported as incomplete, so that
+                // connections wait for the rest instead of failing.
+                let bytes = encoded.as_bytes();
+                let len = rng.below(bytes.len() as u64) as usize;
+                let result = parse_resp_value(&bytes[..len]);
+                assert!(
+                    result.as_ref().is_err_and(resp::is_incomplete),
+                    "Prefix {:?} parsed as {result:?}",
+                    &encoded[..len.min(encoded.len())]
+                );
+            }
+        }
+    }
+    #[test]
+    fn test_resp_parser_survives_noise() {
+        const BYTES: &[u8] = b"+-:$*_#,(!=%~>\r\n0123456789.tfe";
+        let mut rng = TestRng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..20000 {
+            let noise: Vec<u8> = (0..rng.below(48))
+                .map(|_| match rng.below(4) {
+                    0 => rng.next() as u8,
+                    _ => BYTES[rng.below(BYTES.len() as u64) as usize],
+                })
+                .collect();
+            let _ = parse_resp_value(&noise);
+        }
+        // Announcing a huge aggregate must not allocate room for all of its elements.
+        for frame in [
+            &b"*999999999999\r\n"[..],
+            b"~999999999999\r\n",
+            b"%999999999999\r\n",
+        ] {
+            assert!(parse_resp_value(frame).is_err_and(|e| resp::is_incomplete(&e)));
+        }
+    }
+    #[test]
+    fn test_rdb_reader_strings_and_expiry() {
+        let future_ms = (std::time::SystemTime::now() + std::time::Duration::from_secs(3600))
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        let mut input = b"REDIS0011".to_vec();
+        input.extend(b"\xFA\x09redis-ver\x057.2.0");
+        input.extend(b"\xFE\x00\xFB\x03\x02");
+        input.extend(b"\x00\x03foo\x03bar");
+        input.push(0xFC);
+        input.extend(future_ms.to_le_bytes());
+        input.extend(b"\x00\x03baz\x03qux");
+        input.push(0xFC);
+        input.extend(1u64.to_le_bytes());
+        input.extend(b"\x00\x03old\x01x");
+        input.extend(b"\xFF\x00\x00\x00\x00\x00\x00\x00\x00");
+
+        let rdb = RdbReader::new(&input).read().unwrap();
+        let db = &rdb.databases[&0];
+
+        assert_eq!(rdb.version, 11);
+        assert_eq!(rdb.aux_fields["redis-ver"], "7.2.0");
+        assert_eq!(db.len(), 2);
+        assert_eq!(
+            db.get("foo").unwrap().value(),
+            &db::DatabaseValue::String("bar".into())
+        );
+        assert!(db.get("baz").unwrap().expires().is_some());
+        assert!(db.get("old").is_none());
+    }
+    #[test]
+    fn test_rdb_reader_listpack_hash() {
+        let listpack = b"\x0C\x00\x00\x00\x02\x00\x81f\x02\x05\x01\xFF";
+
+        let mut input = b"REDIS0011\x10\x01h".to_vec();
+        input.push(listpack.len() as u8);
+        input.extend(listpack);
+        input.extend(b"\xFF\x00\x00\x00\x00\x00\x00\x00\x00");
+
+        let rdb = RdbReader::new(&input).read().unwrap();
+        let db::DatabaseValue::Map(map) = rdb.databases[&0].get("h").unwrap().value() else {
+            panic!("expected hash value");
+        };
+
+        assert_eq!(
+            map[&db::DatabaseValue::String("f".into())],
+            db::DatabaseValue::String("5".into())
+        );
+    }
+    #[test]
+    fn test_rdb_reader_invalid_header() {
+        let inputs: Vec<&[u8]> = vec![b"", b"REDIS", b"RESID0011\xFF", b"REDISabcd\xFF"];
+
+        for input in inputs {
+            assert!(
+                RdbReader::new(input).read().is_err(),
+                "Failed on {:?}",
+                input
+            );
+        }
+    }
+    #[test]
+    fn test_rdb_writer_round_trip() {
+        use db::{DatabaseSlot, DatabaseValue};
+
+        let string = |s: &str| DatabaseValue::String(s.into());
+        let expires = std::time::Instant::now() + std::time::Duration::from_secs(60);
+
+        let mut db = Database::new();
+        db.insert("str".into(), DatabaseSlot::Simple(string("value")));
+        db.insert(
+            "list".into(),
+            DatabaseSlot::Timed {
+                expires,
+                value: DatabaseValue::Array(vec![string("a"), string("b")]),
+            },
+        );
+        db.insert(
+            "set".into(),
+            DatabaseSlot::Simple(DatabaseValue::Set([string("x")].into())),
+        );
+        db.insert(
+            "hash".into(),
+            DatabaseSlot::Simple(DatabaseValue::Map([(string("f"), string("v"))].into())),
+        );
+        db.insert(
+            "zset".into(),
+            DatabaseSlot::Simple(DatabaseValue::SortedSet([(string("m"), 1.5)].into())),
+        );
+
+        let bytes = dump_database(&db).unwrap();
+        let rdb = RdbReader::new(&bytes).read().unwrap();
+        let loaded = &rdb.databases[&0];
+
+        assert_eq!(loaded.len(), db.len());
+        for (key, slot) in db.iter() {
+            let loaded_slot = loaded.get(key).unwrap();
+            assert_eq!(loaded_slot.value(), slot.value(), "Failed on {:?}", key);
+            assert_eq!(loaded_slot.expires().is_some(), slot.expires().is_some());
+        }
+    }
+    #[test]
+    fn test_crc64_checksum() {
+        assert_eq!(crc64(0, b"123456789"), 0xe9c6d914c4b8d9ca);
+        assert_eq!(crc64(crc64(0, b"1234"), b"56789"), 0xe9c6d914c4b8d9ca);
+
+        let mut bytes = dump_database(&Database::new()).unwrap();
+        assert!(RdbReader::new(&bytes).read().is_ok());
+
+        // Corrupt the 'redis-ver' aux value, which leaves the file parseable.
+        let position = bytes.windows(5).position(|w| w == b"7.2.0").unwrap();
+        bytes[position] = b'6';
+        assert!(matches!(
+            RdbReader::new(&bytes).read(),
+            Err(rdb::RdbReaderError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rdb_special_string_encodings() {
+        use db::{DatabaseSlot, DatabaseValue};
+
+        let mut input = b"REDIS0011".to_vec();
+        input.extend(b"\xFA\x0Aredis-bits\xC0\x40");
+        input.extend(b"\x00\x02i8\xC0\xF6");
+        input.extend(b"\x00\x03i16\xC1\x39\x30");
+        input.extend(b"\x00\x03i32\xC2\x87\xD6\x12\x00");
+        input.extend(b"\xFF\x00\x00\x00\x00\x00\x00\x00\x00");
+
+        let rdb = RdbReader::new(&input).read().unwrap();
+        let db = &rdb.databases[&0];
+
+        assert_eq!(rdb.aux_fields["redis-bits"], "64");
+        for (key, expected) in [("i8", "-10"), ("i16", "12345"), ("i32", "1234567")] {
+            assert_eq!(
+                db.get(key).unwrap().value(),
+                &DatabaseValue::String(expected.into())
+            );
+        }
+
+        let long = "abcdefgh".repeat(100);
+        let mut db = Database::new();
+        db.insert(
+            "long".into(),
+            DatabaseSlot::Simple(DatabaseValue::String(long.clone())),
+        );
+        db.insert(
+            "int".into(),
+            DatabaseSlot::Simple(DatabaseValue::String("-70000".into())),
+        );
+
+        let bytes = dump_database(&db).unwrap();
+        let loaded = RdbReader::new(&bytes).read().unwrap();
+
+        assert!(bytes.len() < long.len());
+        assert_eq!(
+            loaded.databases[&0].get("long").unwrap().value(),
+            &DatabaseValue::String(long)
+        );
+        assert_eq!(
+            loaded.databases[&0].get("int").unwrap().value(),
+            &DatabaseValue::String("-70000".into())
+        );
+    }
+    #[test]
+    fn test_aof_writer_appends_frames() {
+        let dir = std::env::temp_dir().join(format!("test-aof-append-{}", std::process::id()));
+        let config = Config {
+            dir: dir.clone(),
+            appendfsync: config::AppendFsync::Always,
+            ..Default::default()
+        };
+
+        let aof = AofWriter::open(&config, Default::default()).unwrap();
+        aof.append(b"*1\r\n$4\r\nPING\r\n").unwrap();
+        aof.append(b"*1\r\n$4\r\nSAVE\r\n").unwrap();
+
+        let contents = std::fs::read(config.aof_dir().join("appendonly.aof.1.incr.aof")).unwrap();
+        let manifest = std::fs::read_to_string(config.aof_dir().join("appendonly.aof.manifest"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(contents, b"*1\r\n$4\r\nPING\r\n*1\r\n$4\r\nSAVE\r\n");
+        assert_eq!(
+            manifest.unwrap(),
+            "file appendonly.aof.1.incr.aof seq 1 type i\n"
+        );
+    }
+    #[test]
+    fn test_aof_rewrite_replaces_files() {
+        use db::{DatabaseSlot, DatabaseValue};
+
+        let dir = std::env::temp_dir().join(format!("test-aof-rewrite-{}", std::process::id()));
+        let config = Config {
+            dir: dir.clone(),
+            appendonly: true,
+            appendfsync: config::AppendFsync::No,
+            ..Default::default()
+        };
+
+        let mut db = Database::new();
+        db.insert(
+            String::from("key"),
+            DatabaseSlot::Simple(DatabaseValue::String(String::from("value"))),
+        );
+        let aof = AofWriter::open(&config, Default::default()).unwrap();
+        aof.append(b"*1\r\n$4\r\nPING\r\n").unwrap();
+        aof.lock().start_rewrite().unwrap();
+        aof.append(b"*1\r\n$4\r\nPING\r\n").unwrap();
+        aof.finish_rewrite(&dump_database(&db).unwrap(), true, |_| {})
+            .unwrap();
+        drop(aof);
+
+        let manifest = std::fs::read_to_string(config.aof_dir().join("appendonly.aof.manifest"));
+        let old_incr_exists = config.aof_dir().join("appendonly.aof.1.incr.aof").exists();
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+        let num_commands = load_aof(&state).unwrap();
+        let expected = DatabaseValue::String(String::from("value"));
+        let loaded = state
+            .db
+            .lock()
+            .unwrap()
+            .get("key")
+            .map(|s| s.value() == &expected);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            manifest.unwrap(),
+            "file appendonly.aof.1.base.rdb seq 1 type b\n\
+             file appendonly.aof.2.incr.aof seq 2 type i\n"
+        );
+        assert!(!old_incr_exists);
+        assert_eq!(num_commands, 1);
+        assert_eq!(loaded, Some(true));
+    }
+    #[test]
+    fn test_aof_rewrite_without_preamble() {
+        use db::{DatabaseSlot, DatabaseValue};
+
+        let dir = std::env::temp_dir().join(format!("test-aof-commands-{}", std::process::id()));
+        let config = Config {
+            dir: dir.clone(),
+            appendonly: true,
+            appendfsync: config::AppendFsync::No,
+            ..Default::default()
+        };
+        let string = |s: &str| DatabaseValue::String(s.into());
+        let expires = std::time::Instant::now() + std::time::Duration::from_secs(60);
+
+        let mut db = Database::new();
+        db.insert("str".into(), DatabaseSlot::Simple(string("value")));
+        db.insert(
+            "list".into(),
+            DatabaseSlot::Timed {
+                expires,
+                value: DatabaseValue::Array(vec![string("a"), string("b")]),
+            },
+        );
+        let commands = rewrite_commands(&db).unwrap();
+
+        let aof = AofWriter::open(&config, Default::default()).unwrap();
+        aof.lock().start_rewrite().unwrap();
+        aof.finish_rewrite(&commands, false, |_| {}).unwrap();
+        drop(aof);
+
+        let state = std::sync::Arc::new(ServerState::new(config.clone(), Database::new()).unwrap());
+        let num_commands = load_aof(&state).unwrap();
+        let list = DatabaseValue::Array(vec![string("a"), string("b")]);
+        let (str_loaded, list_loaded) = {
+            let db = state.db.lock().unwrap();
+            (
+                db.get("str").map(|s| s.value() == &string("value")),
+                db.get("list")
+                    .map(|s| s.value() == &list && s.expires().is_some()),
+            )
+        };
+
+        // NOTE: A command failing to replay must not be skipped silently.
+        let incr_path = config.aof_dir().join("appendonly.aof.2.incr.aof");
+        std::fs::write(&incr_path, b"*1\r\n$7\r\nUNKNOWN\r\n").unwrap();
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+        let failed = load_aof(&state);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(num_commands, 2);
+        assert_eq!(str_loaded, Some(true));
+        assert_eq!(list_loaded, Some(true));
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn test_load_aof_truncated_tail() {
+        let dir = std::env::temp_dir().join(format!("test-aof-{}", std::process::id()));
+        let config = Config {
+            dir: dir.clone(),
+            appendonly: true,
+            ..Default::default()
+        };
+
+        let path = config.aof_dir().join("appendonly.aof.1.incr.aof");
+        std::fs::create_dir_all(config.aof_dir()).unwrap();
+        std::fs::write(&path, b"*1\r\n$4\r\nPING\r\n*1\r\n$4\r\nPI").unwrap();
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+        let num_commands = load_aof(&state).unwrap();
+        let aof = state.aof.as_ref().unwrap();
+        aof.append(b"*1\r\n$4\r\nSAVE\r\n").unwrap();
+        let current_size = aof.lock().current_size;
+        let contents = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(num_commands, 1);
+        assert_eq!(contents, b"*1\r\n$4\r\nPING\r\n*1\r\n$4\r\nSAVE\r\n");
+        assert_eq!(current_size, contents.len() as u64);
+    }
+    #[test]
+    fn test_failed_aof_write_is_reported() {
+        let dir = std::env::temp_dir().join(format!("test-aof-full-{}", std::process::id()));
+        let config = Config {
+            dir: dir.clone(),
+            appendonly: true,
+            ..Default::default()
+        };
+        // NOTE: Every write to /dev/full fails with ENOSPC, like on a full disk.
+        std::fs::create_dir_all(config.aof_dir()).unwrap();
+        let incr_path = config.aof_dir().join("appendonly.aof.1.incr.aof");
+        std::os::unix::fs::symlink("/dev/full", incr_path).unwrap();
+
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+        // NOTE: The write is applied, so only the following writes are refused.
+        let applied = request(&state, ctx, &["DEL", "a"]);
+        let refused = request(&state, ctx, &["DEL", "a"]);
+        let read = request(&state, ctx, &["DUMP", "missing"]);
+        let persistence = info(&state, &["persistence".into()]);
+        drop(state);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(applied, ":0\r\n");
+        assert!(
+            refused.starts_with("-MISCONF Errors writing to the AOF file: "),
+            "{refused}"
+        );
+        assert_eq!(read, "$-1\r\n");
+        assert!(persistence.contains("aof_last_write_status:err\r\n"));
+    }
+    #[test]
+    fn test_failed_bgsave_stops_writes() {
+        use std::sync::atomic::Ordering;
+
+        let state = ServerState::new(Config::default(), Database::new()).unwrap();
+        assert!(!state.writes_stopped_by_bgsave_error());
+        assert!(info(&state, &[]).contains("rdb_last_bgsave_status:ok\r\n"));
+
+        state.rdb_last_bgsave_ok.store(false, Ordering::Relaxed);
+        assert!(state.writes_stopped_by_bgsave_error());
+        assert!(info(&state, &["Persistence".into()]).contains("rdb_last_bgsave_status:err\r\n"));
+        assert!(info(&state, &["unknown".into()]).is_empty());
+    }
+    #[test]
+    fn test_json_dump_round_trip() {
+        use db::{dump_json, load_json, DatabaseSlot, DatabaseValue};
+
+        let string = |s: &str| DatabaseValue::String(s.into());
+        let expires = std::time::Instant::now() + std::time::Duration::from_secs(60);
+
+        let mut db = Database::new();
+        db.insert("str".into(), DatabaseSlot::Simple(string("a \"quoted\"\n")));
+        db.insert(
+            "set".into(),
+            DatabaseSlot::Timed {
+                expires,
+                value: DatabaseValue::Set([string("y"), string("x")].into()),
+            },
+        );
+        db.insert(
+            "zset".into(),
+            DatabaseSlot::Simple(DatabaseValue::SortedSet(
+                [(string("m"), 1.5), (string("n"), f64::INFINITY)].into(),
+            )),
+        );
+
+        let json = dump_json(&db);
+        assert!(json.starts_with(r#"{"set":{"type":"set","value":["x","y"],"expires_at":"#));
+        assert!(json.ends_with(r#""zset":{"type":"zset","value":{"m":1.5,"n":"inf"}}}"#));
+
+        let loaded = load_json(&json).unwrap();
+        assert_eq!(loaded.len(), db.len());
+        for (key, slot) in db.iter() {
+            let loaded_slot = loaded.get(key).unwrap();
+            assert_eq!(loaded_slot.value(), slot.value(), "Failed on {:?}", key);
+            assert_eq!(loaded_slot.expires().is_some(), slot.expires().is_some());
+        }
+        assert!(load_json(r#"{"k":{"type":"list","value":"x"}}"#).is_err());
+    }
+    #[test]
+    fn test_config_from_args() {
+        let args = ["--port", "6380", "--replicaof", "localhost 6379"];
+        let config = Config::from_args(args.map(String::from)).unwrap();
+        assert_eq!(config.port, 6380);
+        assert_eq!(config.replicaof, Some((String::from("localhost"), 6379)));
+        assert!(config.replica_read_only);
+
+        let args = ["--replica-read-only", "no"];
+        let config = Config::from_args(args.map(String::from)).unwrap();
+        assert!(!config.replica_read_only);
+
+        let args = ["--dir", "/tmp/redis", "--dbfilename", "db.rdb"];
+        let config = Config::from_args(args.map(String::from)).unwrap();
+        assert_eq!(config.rdb_path(), std::path::Path::new("/tmp/redis/db.rdb"));
+        assert!(!config.appendonly);
+        let args = ["--appendonly", "yes", "--appendfsync", "always"];
+        let config = Config::from_args(args.map(String::from)).unwrap();
+        assert!(config.appendonly);
+        assert_eq!(config.appendfsync, config::AppendFsync::Always);
+
+        let args = ["--repl-backlog-size", "2mb"];
+        let config = Config::from_args(args.map(String::from)).unwrap();
+        assert_eq!(config.repl_backlog_size, 2 * 1024 * 1024);
+        let args = ["--auto-aof-rewrite-min-size", "1G"];
+        let config = Config::from_args(args.map(String::from)).unwrap();
+        assert_eq!(config.auto_aof_rewrite_min_size, 1000 * 1000 * 1000);
+        let args = ["--timeout", "30", "--cluster-node-timeout", "500"];
+        let config = Config::from_args(args.map(String::from)).unwrap();
+        assert_eq!(config.timeout, std::time::Duration::from_secs(30));
+        assert_eq!(config.cluster_node_timeout.as_millis(), 500);
+
+        let error = Config::from_args(["--repl-backlog-size", "0"].map(String::from));
+        let expected = "Invalid --repl-backlog-size: argument must be between 1 and";
+        assert!(error.unwrap_err().to_string().starts_with(expected));
+        assert!(Config::from_args(["--repl-backlog-size", "1tb"].map(String::from)).is_err());
+        assert!(Config::from_args(["--port".to_string()]).is_err());
+        assert!(Config::from_args(["--replicaof", "localhost"].map(String::from)).is_err());
+        assert!(Config::from_args(["--replica-read-only", "maybe"].map(String::from)).is_err());
+        assert!(Config::from_args(["--appendfsync", "sometimes"].map(String::from)).is_err());
+        assert!(Config::from_args(["--unknown", "1"].map(String::from)).is_err());
+    }
+    #[test]
+    fn test_config_file() {
+        let dir = std::env::temp_dir().join(format!("test-config-file-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("redis.conf");
+        let included = dir.join("included.conf");
+        let contents = format!(
+            "# Comment\n\nport 6380\n  replicaof localhost 6379\n\
+             dbfilename \"my \\x64b.rdb\"\nappenddirname 'it\\'s'\ninclude {}\n",
+            included.display()
+        );
+        std::fs::write(&main, contents).unwrap();
+        std::fs::write(&included, "APPENDONLY yes\nport 6381\n").unwrap();
+
+        let args = [main.display().to_string(), "--port".into(), "6382".into()];
+        let config = Config::from_args(args);
+        let invalid = |contents: &str| {
+            std::fs::write(&main, contents).unwrap();
+            Config::from_args([main.display().to_string()]).is_err()
+        };
+        let errors = [
+            invalid("port"),
+            invalid("unknown 1"),
+            invalid("port \"6380"),
+            invalid("dbfilename \"a\"b"),
+            invalid("appendonly maybe"),
+        ];
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let config = config.unwrap();
+        assert_eq!(config.port, 6382);
+        assert_eq!(config.replicaof, Some((String::from("localhost"), 6379)));
+        assert_eq!(config.dbfilename, "my db.rdb");
+        assert_eq!(config.appenddirname, "it's");
+        assert!(config.appendonly);
+        assert_eq!(errors, [true; 5]);
+    }
+    #[test]
+    fn test_config_reload() {
+        let dir = std::env::temp_dir().join(format!("test-config-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("redis.conf");
+        std::fs::write(&path, "port 6390\ntimeout 10\n").unwrap();
+        let args = ["--appendfsync", "no"].map(String::from);
+        let config = Config::from_args([path.display().to_string()].into_iter().chain(args));
+        let config = config.unwrap();
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+
+        std::fs::write(&path, "port 6391\ntimeout 20\nappendfsync always\n").unwrap();
+        let reply = request(
+            &state,
+            &mut ConnectionContext::default(),
+            &["DEBUG", "CONFIG-RELOAD"],
+        );
+        std::fs::write(&path, "timeout \"20").unwrap();
+        let invalid = state.reload_config();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let expected = "*4\r\n$7\r\napplied\r\n*1\r\n$7\r\ntimeout\r\n\
+                        $7\r\nignored\r\n*1\r\n$4\r\nport\r\n";
+        assert_eq!(reply, expected);
+        let config = state.config();
+        assert_eq!(config.timeout, std::time::Duration::from_secs(20));
+        assert_eq!(config.port, 6390);
+        // NOTE: Command line arguments still override the config file.
+        assert_eq!(config.appendfsync, config::AppendFsync::No);
+        assert!(invalid.is_err());
+    }
+    #[test]
+    fn test_psync_starts_full_resync() {
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let mut ctx = ConnectionContext::default();
+
+        let reply = request_frame(
+            &state,
+            &mut ctx,
+            b"*3\r\n$8\r\nREPLCONF\r\n$14\r\nlistening-port\r\n$4\r\n6380\r\n",
+        );
+        assert_eq!(reply, "+OK\r\n");
+        // NOTE: The reply is sent once the snapshot is taken, see 'serve_replica'.
+        request_frame(
+            &state,
+            &mut ctx,
+            b"*3\r\n$5\r\nPSYNC\r\n$1\r\n?\r\n$2\r\n-1\r\n",
+        );
+        assert_eq!(state.replication.replid().len(), 40);
+        assert_eq!(ctx.kind, ClientKind::Replica);
+        assert_eq!(ctx.psync_offset, None);
+        assert_eq!(ctx.listening_port, Some(6380));
+    }
+    #[tokio::test]
+    async fn test_fullresync_offset_matches_snapshot() {
+        use testing::TestClient;
+
+        let server = RedisServer::builder().port(0).start().await.unwrap();
+        let mut client = TestClient::in_memory(&server);
+        client.send("DEL a").await.unwrap();
+        let info = match client.send("INFO replication").await.unwrap() {
+            RespValue::BulkString(info) => info.into_owned(),
+            reply => panic!("INFO replied {reply:?}"),
+        };
+        let offset = info
+            .lines()
+            .find_map(|line| line.strip_prefix("master_repl_offset:"))
+            .unwrap()
+            .to_string();
+        let replid = info
+            .lines()
+            .find_map(|line| line.strip_prefix("master_replid:"))
+            .unwrap()
+            .to_string();
+
+        let mut replica = TestClient::in_memory(&server);
+        let reply = replica.send("PSYNC ? -1").await.unwrap();
+        assert_ne!(offset, "0");
+        assert_eq!(
+            reply,
+            RespValue::SimpleString(format!("FULLRESYNC {replid} {offset}").into())
+        );
+    }
+    #[test]
+    fn test_propagate_to_replicas() {
+        use replication::{ReplicaInfo, ReplicationState};
+        use std::sync::atomic::Ordering;
+
+        let replication = ReplicationState::new(None, 1024);
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        replication.lock_replicas().push(ReplicaInfo {
+            id: replication.next_replica_id(),
+            ip: std::net::Ipv4Addr::LOCALHOST.into(),
+            listening_port: 6380,
+            sender,
+            queued: Default::default(),
+            ack_offset: 0,
+        });
+
+        let frame = b"*3\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbar\r\n";
+        replication.propagate(&replication.lock_replicas(), frame);
+        replication.propagate(&replication.lock_replicas(), frame);
+
+        assert_eq!(receiver.try_recv().unwrap(), &frame[..]);
+        assert_eq!(receiver.try_recv().unwrap(), &frame[..]);
+        assert_eq!(
+            replication.repl_offset.load(Ordering::Relaxed),
+            2 * frame.len() as u64
+        );
+
+        replication.request_acks();
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            &b"*3\r\n$8\r\nREPLCONF\r\n$6\r\nGETACK\r\n$1\r\n*\r\n"[..]
+        );
+        replication.record_ack(0, 74);
+        assert_eq!(replication.lock_replicas()[0].ack_offset, 74);
+    }
+    #[test]
+    fn test_replica_output_buffer_limit() {
+        use replication::{ReplicaInfo, ReplicationState};
+        use std::sync::atomic::Ordering;
+
+        let replication = ReplicationState::new(None, 1024);
+        replication.set_output_buffer_limit(100);
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let queued = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        replication.lock_replicas().push(ReplicaInfo {
+            id: replication.next_replica_id(),
+            ip: std::net::Ipv4Addr::LOCALHOST.into(),
+            listening_port: 6380,
+            sender,
+            queued: queued.clone(),
+            ack_offset: 0,
+        });
+
+        let frame = [b'x'; 60];
+        replication.propagate(&replication.lock_replicas(), &frame);
+        assert_eq!(receiver.try_recv().unwrap(), &frame[..]);
+        // NOTE: Frames the connection wrote no longer count towards the limit.
+        queued.fetch_sub(frame.len(), Ordering::AcqRel);
+        replication.propagate(&replication.lock_replicas(), &frame);
+        assert_eq!(receiver.try_recv().unwrap(), &frame[..]);
+        assert!(!replication.exceeds_output_buffer_limit(queued.load(Ordering::Acquire)));
+
+        // NOTE: The replica is told to disconnect once, later frames are dropped.
+        replication.propagate(&replication.lock_replicas(), &frame);
+        replication.propagate(&replication.lock_replicas(), &frame);
+        assert!(receiver.try_recv().unwrap().is_empty());
+        assert!(receiver.try_recv().is_err());
+        assert!(replication.exceeds_output_buffer_limit(queued.load(Ordering::Acquire)));
+        assert_eq!(
+            replication.repl_offset.load(Ordering::Relaxed),
+            4 * frame.len() as u64
+        );
+    }
+    #[tokio::test]
+    async fn test_wait_for_replica_acks() {
+        use replication::ReplicaInfo;
+        use std::sync::atomic::Ordering;
+        use std::time::Duration;
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        state.replication.lock_replicas().push(ReplicaInfo {
+            id: state.replication.next_replica_id(),
+            ip: std::net::Ipv4Addr::LOCALHOST.into(),
+            listening_port: 6380,
+            sender,
+            queued: Default::default(),
+            ack_offset: 0,
+        });
+        let wait = |frame: &[u8]| {
+            let mut ctx = ConnectionContext::default();
+            request_frame(&state, &mut ctx, frame);
+            ctx.deferred.take().unwrap()
+        };
+
+        let reply = wait(b"*3\r\n$4\r\nWAIT\r\n$1\r\n1\r\n$1\r\n0\r\n");
+        assert_eq!(reply.resolve(&state).await.to_string(), ":1\r\n");
+
+        let frame = b"*1\r\n$4\r\nPING\r\n";
+        state
+            .replication
+            .propagate(&state.replication.lock_replicas(), frame);
+        let reply = wait(b"*3\r\n$4\r\nWAIT\r\n$1\r\n1\r\n$2\r\n10\r\n");
+        assert_eq!(reply.resolve(&state).await.to_string(), ":0\r\n");
+
+        let reply = wait(b"*3\r\n$4\r\nWAIT\r\n$1\r\n1\r\n$1\r\n0\r\n");
+        // The GETACK sent by the previous WAIT also counts towards the offset.
+        let offset = state.replication.repl_offset.load(Ordering::Relaxed);
+        let acker = state.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            acker.replication.record_ack(0, offset);
+        });
+        assert_eq!(reply.resolve(&state).await.to_string(), ":1\r\n");
+        assert!(receiver.try_recv().is_ok());
+
+        let info = info(&state, &["replication".into()]);
+        assert!(info.contains("role:master\r\nconnected_slaves:1\r\n"));
+        assert!(info.contains(&format!(
+            "slave0:ip=127.0.0.1,port=6380,state=online,offset={offset}\r\n"
+        )));
+        assert!(info.contains(&format!(
+            "master_repl_offset:{}\r\n",
+            state.replication.repl_offset.load(Ordering::Relaxed)
+        )));
+    }
+    #[tokio::test]
+    async fn test_blocking_timers() {
+        use server::BlockingTimers;
+        use std::time::Duration;
+        use tokio::time::Instant;
+
+        let timers = std::sync::Arc::new(BlockingTimers::default());
+        let start = Instant::now();
+        let late = timers.timeout_at(start + Duration::from_millis(50));
+        let cancelled = timers.timeout_at(start + Duration::from_millis(20));
+        let early = timers.timeout_at(start + Duration::from_millis(10));
+        assert_eq!(timers.len(), 3);
+
+        drop(cancelled);
+        assert_eq!(timers.len(), 2);
+
+        early.await;
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        assert_eq!(timers.len(), 1);
+        late.await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(timers.is_empty());
+    }
+    #[test]
+    fn test_replica_stale_data() {
+        use replication::MasterLinkState;
+
+        let config = Config {
+            replicaof: Some((String::from("localhost"), 6379)),
+            replica_serve_stale_data: false,
+            ..Default::default()
+        };
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+        let dump = b"*2\r\n$4\r\nDUMP\r\n$1\r\na\r\n";
+
+        assert_eq!(
+            request_frame(&state, ctx, b"*1\r\n$4\r\nPING\r\n"),
+            "+PONG\r\n"
+        );
+        assert!(request_frame(&state, ctx, b"*1\r\n$8\r\nLASTSAVE\r\n").starts_with(':'));
+        assert!(request_frame(&state, ctx, dump).starts_with("-MASTERDOWN "));
+        state.replication.set_master_link(MasterLinkState::Syncing);
+        assert!(request_frame(&state, ctx, dump).starts_with("-MASTERDOWN "));
+        state
+            .replication
+            .set_master_link(MasterLinkState::Connected);
+        assert_eq!(request_frame(&state, ctx, dump), "$-1\r\n");
+    }
+    #[test]
+    fn test_expiry_index() {
+        use db::{DatabaseSlot, DatabaseValue};
+        use std::time::{Duration, Instant};
+
+        let now = Instant::now();
+        let timed = |secs: u64| DatabaseSlot::Timed {
+            expires: now + Duration::from_secs(secs),
+            value: DatabaseValue::String("x".into()),
+        };
+        let mut db = Database::new();
+        db.insert("b".into(), timed(2));
+        db.insert("a".into(), timed(1));
+        db.insert("persisted".into(), timed(1));
+        db.insert(
+            "persisted".into(),
+            DatabaseSlot::Simple(DatabaseValue::String("x".into())),
+        );
+        db.insert("deleted".into(), timed(1));
+        db.remove("deleted");
+        db.insert("extended".into(), timed(1));
+        db.insert("extended".into(), timed(10));
+
+        assert!(db.remove_expired(now).is_empty());
+        let expired = db.remove_expired(now + Duration::from_secs(5));
+        assert_eq!(expired, ["a", "b"]);
+        assert_eq!(db.len(), 2);
+        assert!(db.get("extended").is_some());
+
+        // NOTE: Stale entries of keys whose expiry keeps changing are dropped eventually.
+        for secs in 0..10_000 {
+            db.insert("extended".into(), timed(20 + secs));
+        }
+        db.remove_expired(now);
+        assert_eq!(db.expiry_index_len(), 1);
+    }
+    #[test]
+    fn test_expired_keys_are_deleted_by_master() {
+        use db::{DatabaseSlot, DatabaseValue};
+        use replication::ReplicaInfo;
+
+        let expired = || {
+            let mut db = Database::new();
+            db.insert(
+                "old".into(),
+                DatabaseSlot::Timed {
+                    expires: std::time::Instant::now(),
+                    value: DatabaseValue::String("x".into()),
+                },
+            );
+            db
+        };
+        let del = b"*2\r\n$3\r\nDEL\r\n$3\r\nold\r\n";
+
+        let master = ServerState::new(Config::default(), expired()).unwrap();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        master.replication.lock_replicas().push(ReplicaInfo {
+            id: master.replication.next_replica_id(),
+            ip: std::net::Ipv4Addr::LOCALHOST.into(),
+            listening_port: 6380,
+            sender,
+            queued: Default::default(),
+            ack_offset: 0,
+        });
+        assert!(master.db.lock().unwrap().get("old").is_none());
+        assert_eq!(master.expire_keys(), 1);
+        assert!(master.db.lock().unwrap().is_empty());
+        assert_eq!(receiver.try_recv().unwrap(), &del[..]);
+
+        let config = Config {
+            replicaof: Some((String::from("localhost"), 6379)),
+            ..Default::default()
+        };
+        let replica = std::sync::Arc::new(ServerState::new(config, expired()).unwrap());
+        assert_eq!(replica.expire_keys(), 0);
+        assert!(replica.db.lock().unwrap().get("old").is_none());
+        assert_eq!(replica.db.lock().unwrap().len(), 1);
+
+        let mut ctx = ConnectionContext::new(ClientKind::Master);
+        assert_eq!(request_frame(&replica, &mut ctx, del), ":0\r\n");
+        assert!(replica.db.lock().unwrap().is_empty());
+    }
+    #[test]
+    fn test_partial_resync_after_promotion() {
+        use replication::ReplicationState;
+
+        let master = Some((String::from("localhost"), 6379));
+        let replication = ReplicationState::new(master, 16);
+        let old_replid = "a".repeat(40);
+        replication.reset_history(old_replid.clone(), 100);
+        replication.propagate(&[], b"0123456789");
+
+        assert!(replication.can_continue(&old_replid, 101));
+        assert!(replication.can_continue(&old_replid, 111));
+        assert!(!replication.can_continue(&old_replid, 112));
+        assert!(!replication.can_continue(&"b".repeat(40), 101));
+        assert_eq!(replication.backlog_since(106).unwrap(), b"56789");
+
+        replication.propagate(&[], b"abcdefghij");
+        assert!(!replication.can_continue(&old_replid, 101));
+        assert_eq!(replication.backlog_since(105).unwrap(), b"456789abcdefghij");
+
+        replication.promote();
+        assert!(!replication.is_replica());
+        let ids = replication.ids();
+        assert_ne!(ids.replid, old_replid);
+        assert_eq!(ids.replid2, old_replid);
+        assert_eq!(ids.second_replid_offset, Some(121));
+        assert!(replication.can_continue(&old_replid, 121));
+        assert!(replication.can_continue(&ids.replid, 121));
+
+        replication.propagate(&[], b"klm");
+        assert!(!replication.can_continue(&old_replid, 124));
+        assert!(replication.can_continue(&ids.replid, 124));
+    }
+    #[test]
+    fn test_crc16_key_hash_slot() {
+        use cluster::key_hash_slot;
+
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+        assert_eq!(key_hash_slot(b"foo"), 12182);
+        assert_eq!(
+            key_hash_slot(b"{user1000}.following"),
+            key_hash_slot(b"user1000")
+        );
+        // An empty tag hashes the whole key.
+        assert_eq!(key_hash_slot(b"foo{}{bar}"), crc16(b"foo{}{bar}") % 16384);
+        assert_eq!(key_hash_slot(b"foo{{bar}}zap"), key_hash_slot(b"{bar"));
+        assert_eq!(key_hash_slot(b"foo{bar}{zap}"), key_hash_slot(b"bar"));
+    }
+    #[test]
+    fn test_cluster_info_and_myid() {
+        let cluster_info = b"*2\r\n$7\r\nCLUSTER\r\n$4\r\nINFO\r\n";
+        let cluster_myid = b"*2\r\n$7\r\nCLUSTER\r\n$4\r\nMYID\r\n";
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+        assert!(
+            request_frame(&state, ctx, cluster_info).starts_with("-ERR This instance has cluster")
+        );
+        assert!(info(&state, &["cluster".into()]).contains("cluster_enabled:0\r\n"));
+
+        let config = Config {
+            cluster_enabled: true,
+            ..Default::default()
+        };
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+        let myid = &state.cluster.as_ref().unwrap().myid;
+        assert_eq!(
+            request_frame(&state, ctx, cluster_myid),
+            format!("$40\r\n{myid}\r\n")
+        );
+        assert!(request_frame(&state, ctx, cluster_info).contains("cluster_state:fail\r\n"));
+        assert!(info(&state, &["cluster".into()]).contains("cluster_enabled:1\r\n"));
+    }
+    #[test]
+    fn test_cluster_redirection() {
+        use cluster::ClusterNode;
+
+        let config = Config {
+            cluster_enabled: true,
+            ..Default::default()
+        };
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+        let del_foo = b"*2\r\n$3\r\nDEL\r\n$3\r\nfoo\r\n";
+        let asking = b"*1\r\n$6\r\nASKING\r\n";
+
+        let cluster = state.cluster.as_ref().unwrap();
+        assert_eq!(
+            request_frame(&state, ctx, del_foo),
+            "-CLUSTERDOWN The cluster is down\r\n"
+        );
+
+        // Every slot but the one of 'foo' is served locally.
+        let other = ClusterNode::new("b".repeat(40), String::from("127.0.0.1"), 7001);
+        let slots: Vec<u16> = (0..16384).filter(|&slot| slot != 12182).collect();
+        cluster.add_slots(&slots).unwrap();
+        {
+            let mut topology = cluster.lock_topology();
+            topology.nodes.insert(other.id.clone(), other.clone());
+            topology.slots[12182] = Some(other.id.clone());
+        }
+        assert!(cluster.info().contains("cluster_state:ok\r\n"));
+        assert_eq!(
+            request_frame(&state, ctx, del_foo),
+            "-MOVED 12182 127.0.0.1:7001\r\n"
+        );
+        let del_foo_bar = b"*3\r\n$3\r\nDEL\r\n$3\r\nfoo\r\n$3\r\nbar\r\n";
+        assert!(request_frame(&state, ctx, del_foo_bar).starts_with("-CROSSSLOT "));
+
+        cluster
+            .lock_topology()
+            .importing
+            .insert(12182, other.id.clone());
+        assert_eq!(request_frame(&state, ctx, asking), "+OK\r\n");
+        assert_eq!(request_frame(&state, ctx, del_foo), ":0\r\n");
+        assert_eq!(
+            request_frame(&state, ctx, del_foo),
+            "-MOVED 12182 127.0.0.1:7001\r\n"
+        );
+
+        {
+            let mut topology = cluster.lock_topology();
+            topology.importing.clear();
+            topology.slots[12182] = Some(cluster.myid.clone());
+            topology.migrating.insert(12182, other.id.clone());
+        }
+        assert_eq!(
+            request_frame(&state, ctx, del_foo),
+            "-ASK 12182 127.0.0.1:7001\r\n"
+        );
+    }
+    #[test]
+    fn test_cross_slot_keys() {
+        use command::find_command;
+
+        let del = find_command("del").unwrap();
+        assert_eq!(del.key_positions(4).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(del.key_positions(1).count(), 0);
+        let migrate = find_command("migrate").unwrap();
+        assert_eq!(migrate.key_positions(6).collect::<Vec<_>>(), [3]);
+        assert_eq!(find_command("ping").unwrap().key_positions(2).count(), 0);
+
+        let config = Config {
+            cluster_enabled: true,
+            ..Default::default()
+        };
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+        assert!(request(&state, ctx, &["DEL", "foo", "bar"]).starts_with("-CROSSSLOT "));
+        assert!(request(&state, ctx, &["DEL", "{user}:1", "{user}:2"]).starts_with("-CLUSTERDOWN "));
+        assert!(request(&state, ctx, &["DEL"]).starts_with("-ERR wrong number of arguments"));
+    }
+    #[test]
+    fn test_asking_during_slot_migration() {
+        use cluster::ClusterNode;
+
+        let new_node = || {
+            let config = Config {
+                cluster_enabled: true,
+                ..Default::default()
+            };
+            std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap())
+        };
+
+        // The slot of 'foo' is being migrated from the source to the target.
+        let (source, target) = (new_node(), new_node());
+        let source_cluster = source.cluster.as_ref().unwrap();
+        let target_cluster = target.cluster.as_ref().unwrap();
+        let source_node =
+            ClusterNode::new(source_cluster.myid.clone(), String::from("127.0.0.1"), 7000);
+        let target_node =
+            ClusterNode::new(target_cluster.myid.clone(), String::from("127.0.0.1"), 7001);
+        let slots: Vec<u16> = (0..16384).collect();
+        source_cluster.add_slots(&slots).unwrap();
+        {
+            let mut topology = source_cluster.lock_topology();
+            topology
+                .nodes
+                .insert(target_node.id.clone(), target_node.clone());
+            topology.migrating.insert(12182, target_node.id.clone());
+        }
+        {
+            let mut topology = target_cluster.lock_topology();
+            topology
+                .nodes
+                .insert(source_node.id.clone(), source_node.clone());
+            topology.slots.fill(Some(source_node.id.clone()));
+            topology.importing.insert(12182, source_node.id.clone());
+        }
+
+        let ctx = &mut ConnectionContext::default();
+        let reply = request(&source, ctx, &["DEL", "foo"]);
+        assert_eq!(reply, "-ASK 12182 127.0.0.1:7001\r\n");
+
+        let ctx = &mut ConnectionContext::default();
+        let reply = request(&target, ctx, &["DEL", "foo"]);
+        assert_eq!(reply, "-MOVED 12182 127.0.0.1:7000\r\n");
+        assert_eq!(request(&target, ctx, &["ASKING"]), "+OK\r\n");
+        assert_eq!(request(&target, ctx, &["DEL", "foo"]), ":0\r\n");
+        // ASKING only applies to the next request, also if that one is refused.
+        let reply = request(&target, ctx, &["DEL", "foo"]);
+        assert_eq!(reply, "-MOVED 12182 127.0.0.1:7000\r\n");
+        assert_eq!(request(&target, ctx, &["ASKING"]), "+OK\r\n");
+        assert!(request(&target, ctx, &["DEL"]).starts_with("-ERR wrong number"));
+        let reply = request(&target, ctx, &["DEL", "foo"]);
+        assert_eq!(reply, "-MOVED 12182 127.0.0.1:7000\r\n");
+        // Slots which aren't being imported are redirected regardless.
+        assert_eq!(request(&target, ctx, &["ASKING"]), "+OK\r\n");
+        let reply = request(&target, ctx, &["DEL", "bar"]);
+        assert_eq!(reply, "-MOVED 5061 127.0.0.1:7000\r\n");
+    }
+    #[test]
+    fn test_cluster_slots_and_shards() {
+        use cluster::{cluster_shards, cluster_slots, ClusterNode, ClusterState};
+
+        let cluster = ClusterState::new(7000, std::time::Duration::from_secs(15));
+        let other = ClusterNode::new("b".repeat(40), String::from("127.0.0.1"), 7001);
+        let slots: Vec<u16> = (0..100).chain(200..300).collect();
+        cluster.add_slots(&slots).unwrap();
+        {
+            let mut topology = cluster.lock_topology();
+            topology.nodes.insert(other.id.clone(), other.clone());
+            for slot in 100..200 {
+                topology.slots[slot] = Some(other.id.clone());
+            }
+        }
+
+        let RespValue::Array(ranges) = cluster_slots(&cluster) else {
+            panic!("expected array");
+        };
+        let ranges: Vec<String> = ranges.iter().map(ToString::to_string).collect();
+        assert_eq!(ranges.len(), 3);
+        let node = |port| format!("*4\r\n$9\r\n127.0.0.1\r\n:{port}\r\n");
+        assert!(ranges[0].starts_with(&format!("*3\r\n:0\r\n:99\r\n{}", node(7000))));
+        assert!(ranges[1].starts_with(&format!("*3\r\n:100\r\n:199\r\n{}", node(7001))));
+        assert!(ranges[2].starts_with("*3\r\n:200\r\n:299\r\n"));
+
+        let RespValue::Array(shards) = cluster_shards(&cluster, 42) else {
+            panic!("expected array");
+        };
+        let shards: Vec<String> = shards.iter().map(ToString::to_string).collect();
+        assert_eq!(shards.len(), 2);
+        let slots = "*4\r\n$5\r\nslots\r\n";
+        assert!(shards[0].starts_with(&format!("{slots}*4\r\n:0\r\n:99\r\n:200\r\n:299\r\n")));
+        assert!(shards[0].contains(&format!("$2\r\nid\r\n$40\r\n{}\r\n", cluster.myid)));
+        assert!(shards[0].contains("$18\r\nreplication-offset\r\n:42\r\n"));
+        assert!(shards[1].starts_with(&format!("{slots}*2\r\n:100\r\n:199\r\n")));
+    }
+    #[test]
+    fn test_cluster_gossip() {
+        use cluster::{ClusterMessage, ClusterState, MessageType};
+        use std::time::Duration;
+
+        let roundtrip = |message: ClusterMessage| {
+            let frame = message.to_frame();
+            let (_, value) = parse_resp_value(frame.as_bytes()).unwrap();
+            let parsed = ClusterMessage::parse(value).unwrap();
+            assert_eq!(parsed, message);
+            parsed
+        };
+        let a = ClusterState::new(7000, Duration::ZERO);
+        let b = ClusterState::new(7001, Duration::from_secs(15));
+        b.add_slots(&(0..100).chain([200]).collect::<Vec<_>>())
+            .unwrap();
+
+        // A meets B under a temporary ID until B replies with its own.
+        a.meet(String::from("127.0.0.1"), 7001);
+        let handshake_id = {
+            let topology = a.lock_topology();
+            let node = topology.nodes.values().find(|node| node.handshake).unwrap();
+            node.id.clone()
+        };
+        let meet = roundtrip(a.build_message(MessageType::Meet));
+        let pong = roundtrip(b.process_message(&meet, "127.0.0.1").unwrap());
+        assert_eq!(pong.kind, MessageType::Pong);
+        assert_eq!(pong.slots, vec![(0, 99), (200, 200)]);
+        assert!(b.lock_topology().nodes.contains_key(&a.myid));
+
+        a.process_pong(&handshake_id, &pong);
+        {
+            let topology = a.lock_topology();
+            assert!(!topology.nodes.contains_key(&handshake_id));
+            assert!(!topology.nodes[&b.myid].handshake);
+            assert_eq!(topology.slots[200].as_deref(), Some(b.myid.as_str()));
+            assert_eq!(topology.slots[100], None);
+        }
+        let line = format!("{} 127.0.0.1:7001@17001 master", b.myid);
+        assert!(a.nodes().contains(&line));
+
+        // B is the only master serving slots, so A alone agrees on its failure.
+        assert!(a.check_failures().is_empty());
+        let now = std::time::Instant::now();
+        a.lock_topology().nodes.get_mut(&b.myid).unwrap().ping_sent = Some(now);
+        assert_eq!(a.check_failures(), vec![b.myid.clone()]);
+        assert!(a.info().contains("cluster_slots_fail:101\r\n"));
+        let fail = roundtrip(a.build_fail_message(&b.myid).unwrap());
+        assert_eq!(fail.gossip[0].id, b.myid);
+    }
+    #[test]
+    fn test_cluster_node_comes_back() {
+        use cluster::{ClusterState, MessageType};
+        use std::time::Duration;
+
+        let a = ClusterState::new(7000, Duration::ZERO);
+        let b = ClusterState::new(7001, Duration::from_secs(15));
+        b.add_slots(&(0..16384).collect::<Vec<_>>()).unwrap();
+        a.meet(String::from("127.0.0.1"), 7001);
+        let handshake_id = a.due_pings()[0].0.clone();
+        let meet = a.build_message(MessageType::Meet);
+        let pong = b.process_message(&meet, "127.0.0.1").unwrap();
+        a.process_pong(&handshake_id, &pong);
+        assert!(a.info().contains("cluster_state:ok\r\n"));
+
+        // B stops answering, so A flags it as failing but keeps pinging it.
+        let pings = a.due_pings();
+        assert_eq!(pings.len(), 1);
+        assert_eq!(pings[0].0, b.myid);
+        assert_eq!(a.check_failures(), vec![b.myid.clone()]);
+        assert!(a.info().contains("cluster_state:fail\r\n"));
+        let pings = a.due_pings();
+        assert_eq!(pings.len(), 1);
+        assert_eq!(pings[0].3, MessageType::Ping);
+
+        // B comes back and answers the repeated PING.
+        let ping = a.build_message(MessageType::Ping);
+        a.process_pong(&b.myid, &b.process_message(&ping, "127.0.0.1").unwrap());
+        assert!(a.info().contains("cluster_state:ok\r\n"));
+
+        // Any message of B clears its failure flags, not only a PONG.
+        a.due_pings();
+        assert_eq!(a.check_failures(), vec![b.myid.clone()]);
+        a.process_message(&b.build_message(MessageType::Ping), "127.0.0.1");
+        assert!(a.info().contains("cluster_state:ok\r\n"));
+        assert!(a.check_failures().is_empty());
+    }
+    #[test]
+    fn test_scan_returns_every_key() {
+        use db::{DatabaseSlot, DatabaseValue};
+        use std::collections::HashSet;
+
+        let string = || DatabaseSlot::Simple(DatabaseValue::String("x".into()));
+        let mut db = Database::new();
+        for i in 0..100 {
+            db.insert(format!("stable:{i}"), string());
+            db.insert(format!("removed:{i}"), string());
+        }
+
+        // NOTE: Keys are added and removed while the scan is running, which must not
+        //       make it miss or repeat keys that exist the whole time.
+        let (mut cursor, mut seen, mut round) = (0, Vec::new(), 0);
+        loop {
+            let (next, keys) = db.scan(cursor, 25);
+            assert!(keys.len() >= 25 || next == 0);
+            seen.extend(keys.into_iter().map(|(key, _)| key.clone()));
+            // NOTE: Every other round adds far more keys than there are and removes them
+            //       again, also removing some of the other keys.
+            if round % 2 == 0 {
+                for i in 0..4000 {
+                    db.insert(format!("added:{i}"), string());
+                }
+            } else {
+                for i in 0..4000 {
+                    db.remove(&format!("added:{i}"));
+                }
+                for i in 0..5 {
+                    db.remove(&format!("removed:{}", round * 5 + i));
+                }
+            }
+            round += 1;
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        let stable = seen.iter().filter(|key| key.starts_with("stable:"));
+        assert_eq!(stable.count(), 100);
+        let unique: HashSet<_> = seen.iter().collect();
+        assert_eq!(unique.len(), seen.len());
+
+        // NOTE: A call stops after visiting ten expired keys per requested key.
+        let mut expired = Database::new();
+        for i in 0..1000 {
+            let expires = std::time::Instant::now();
+            let value = DatabaseValue::String("x".into());
+            let slot = DatabaseSlot::Timed { expires, value };
+            expired.insert(format!("expired:{i}"), slot);
+        }
+        let (next, keys) = expired.scan(0, 10);
+        assert!(keys.is_empty());
+        assert_ne!(next, 0);
+
+        let state = std::sync::Arc::new(ServerState::new(Config::default(), db).unwrap());
+        state.db.lock().unwrap().insert(
+            "list".into(),
+            DatabaseSlot::Simple(DatabaseValue::Array(Vec::new())),
+        );
+        let ctx = &mut ConnectionContext::default();
+        let scan = "SCAN 0 COUNT 1000000";
+        let reply = request(&state, ctx, &scan.split(' ').collect::<Vec<_>>());
+        assert!(reply.starts_with("*2\r\n$1\r\n0\r\n*"));
+        let reply = request(
+            &state,
+            ctx,
+            &["SCAN", "0", "MATCH", "stable:9?", "COUNT", "100000"],
+        );
+        assert_eq!(reply.matches("stable:").count(), 10);
+        let reply = request(
+            &state,
+            ctx,
+            &["scan", "0", "type", "LIST", "count", "100000"],
+        );
+        assert_eq!(reply, "*2\r\n$1\r\n0\r\n*1\r\n$4\r\nlist\r\n");
+        assert!(request(&state, ctx, &["SCAN", "0", "COUNT", "0"]).starts_with('-'));
+        assert!(request(&state, ctx, &["SCAN", "x"]).starts_with('-'));
+        assert!(request(&state, ctx, &["SCAN", "0", "MATCH"]).starts_with('-'));
+    }
+    #[test]
+    fn test_dump_restore_and_slot_migration() {
+        use cluster::SetSlot;
+        use db::{DatabaseSlot, DatabaseValue};
+
+        let bulk = |reply: &str| reply.split("\r\n").nth(1).unwrap().to_string();
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+        let set: std::collections::HashSet<_> = ["a", "b", "12345"]
+            .into_iter()
+            .map(|member| DatabaseValue::String(member.into()))
+            .collect();
+        state
+            .db
+            .lock()
+            .unwrap()
+            .insert("s".into(), DatabaseSlot::Simple(DatabaseValue::Set(set)));
+        let payload = bulk(&request(&state, ctx, &["DUMP", "s"]));
+        assert_eq!(request(&state, ctx, &["DUMP", "missing"]), "$-1\r\n");
+
+        assert_eq!(
+            request(&state, ctx, &["RESTORE", "t", "0", &payload]),
+            "+OK\r\n"
+        );
+        {
+            let db = state.db.lock().unwrap();
+            assert_eq!(db.get("t").unwrap().value(), db.get("s").unwrap().value());
+        }
+        let busy = request(&state, ctx, &["RESTORE", "t", "0", &payload]);
+        assert!(busy.starts_with("-BUSYKEY"));
+        let replace = request(&state, ctx, &["RESTORE", "t", "5000", &payload, "REPLACE"]);
+        assert_eq!(replace, "+OK\r\n");
+        let expires = state.db.lock().unwrap().get("t").unwrap().expires();
+        assert!(expires.is_some());
+        let corrupt = format!("{}00", &payload[..payload.len() - 2]);
+        let reply = request(&state, ctx, &["RESTORE", "u", "0", &corrupt]);
+        assert!(reply.starts_with("-ERR DUMP payload version or checksum are wrong"));
+
+        let config = Config {
+            cluster_enabled: true,
+            ..Default::default()
+        };
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+        let cluster = state.cluster.as_ref().unwrap();
+        cluster.add_slots(&(0..16384).collect::<Vec<_>>()).unwrap();
+        let other = "b".repeat(40);
+        cluster.lock_topology().nodes.insert(
+            other.clone(),
+            cluster::ClusterNode::new(other.clone(), String::from("127.0.0.1"), 7001),
+        );
+        for key in ["foo", "{foo}bar"] {
+            let value = DatabaseSlot::Simple(DatabaseValue::String("1".into()));
+            state.db.lock().unwrap().insert(key.into(), value);
+        }
+
+        let count = request(&state, ctx, &["CLUSTER", "COUNTKEYSINSLOT", "12182"]);
+        assert_eq!(count, ":2\r\n");
+        let keys = request(&state, ctx, &["CLUSTER", "GETKEYSINSLOT", "12182", "1"]);
+        assert!(keys.starts_with("*1\r\n"));
+        let setslot = |args: &[&str]| {
+            let args = [&["CLUSTER", "SETSLOT", "12182"][..], args].concat();
+            request(&state, &mut ConnectionContext::default(), &args)
+        };
+        let reply = setslot(&["IMPORTING", &other]);
+        assert!(reply.starts_with("-ERR I'm already the owner of hash slot 12182"));
+        let reply = setslot(&["MIGRATING", "x"]);
+        assert!(reply.starts_with("-ERR I don't know about node x"));
+        let reply = setslot(&["MIGRATING", &other]);
+        assert_eq!(reply, "+OK\r\n");
+        assert!(cluster.lock_topology().migrating.contains_key(&12182));
+        let reply = setslot(&["NODE", &other]);
+        assert!(reply.starts_with("-ERR Can't assign hashslot 12182 to a different node"));
+
+        state.db.lock().unwrap().remove("foo");
+        state.db.lock().unwrap().remove("{foo}bar");
+        let reply = setslot(&["NODE", &other]);
+        assert_eq!(reply, "+OK\r\n");
+        assert!(cluster.lock_topology().migrating.is_empty());
+        let moved = request(&state, ctx, &["DUMP", "foo"]);
+        assert_eq!(moved, "-MOVED 12182 127.0.0.1:7001\r\n");
+
+        // Taking the slot back after importing it bumps the config epoch.
+        let myid = cluster.myid.clone();
+        cluster
+            .set_slot(12182, SetSlot::Importing(other), false)
+            .unwrap();
+        cluster.set_slot(12182, SetSlot::Node(myid), false).unwrap();
+        assert_eq!(cluster.my_epoch(), 1);
+        assert!(cluster.lock_topology().importing.is_empty());
+    }
+    #[tokio::test]
+    async fn test_migrate_keeps_overwritten_keys() {
+        use db::{DatabaseSlot, DatabaseValue};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        for key in ["a", "b"] {
+            let value = DatabaseSlot::Simple(DatabaseValue::String("old".into()));
+            state.db.lock().unwrap().insert(key.into(), value);
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+
+        // The target accepts every command, but 'a' is overwritten on the source while
+        // its RESTORE is in flight.
+        let source = state.clone();
+        let target = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = Vec::new();
+            while stream.read_buf(&mut buffer).await.unwrap() > 0 {
+                while let Ok((rest, RespValue::Array(args))) = parse_resp_value(&buffer) {
+                    let overwrite = matches!(
+                        args.as_slice(),
+                        [RespValue::BulkString(name), RespValue::BulkString(key), ..]
+                            if name == "RESTORE" && key == "a"
+                    );
+                    let consumed = buffer.len() - rest.len();
+                    buffer.drain(..consumed);
+                    if overwrite {
+                        let value = DatabaseSlot::Simple(DatabaseValue::String("new".into()));
+                        source.db.lock().unwrap().insert("a".into(), value);
+                    }
+                    stream.write_all(b"+OK\r\n").await.unwrap();
+                }
+            }
+        });
+
+        let keys = ["KEYS", "a", "b"];
+        let args = [&["MIGRATE", "127.0.0.1", &port, "", "0", "1000"][..], &keys].concat();
+        let mut ctx = ConnectionContext::default();
+        request(&state, &mut ctx, &args);
+        let reply = match ctx.deferred.take() {
+            Some(deferred) => deferred.resolve(&state).await,
+            None => panic!("MIGRATE did not defer its reply"),
+        };
+        target.await.unwrap();
+
+        assert_eq!(reply.to_string(), "+OK\r\n");
+        let db = state.db.lock().unwrap();
+        let new = DatabaseValue::String("new".into());
+        assert_eq!(db.get("a").map(|slot| slot.value() == &new), Some(true));
+        assert!(db.get("b").is_none());
+    }
+    #[test]
+    fn test_config_get_and_set() {
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        let reply = request(
+            &state,
+            ctx,
+            &["CONFIG", "GET", "*READ-only*", "port", "replica-read-only"],
+        );
+        let expected = "*4\r\n$4\r\nport\r\n$4\r\n6379\r\n\
+                        $17\r\nreplica-read-only\r\n$3\r\nyes\r\n";
+        assert_eq!(reply, expected);
+        assert_eq!(
+            request(&state, ctx, &["CONFIG", "GET", "nothing"]),
+            "*0\r\n"
+        );
+
+        let reply = request(
+            &state,
+            ctx,
+            &["config", "set", "appendfsync", "always", "timeout", "300"],
+        );
+        assert_eq!(reply, "+OK\r\n");
+        assert_eq!(state.config().appendfsync, config::AppendFsync::Always);
+        let reply = request(&state, ctx, &["CONFIG", "GET", "timeout"]);
+        assert_eq!(reply, "*2\r\n$7\r\ntimeout\r\n$3\r\n300\r\n");
+
+        // A failing parameter leaves all others unchanged.
+        let reply = request(
+            &state,
+            ctx,
+            &["CONFIG", "SET", "timeout", "10", "port", "6380"],
+        );
+        assert!(reply.starts_with("-ERR CONFIG SET failed (possibly related to argument 'port')"));
+        let reply = request(
+            &state,
+            ctx,
+            &["CONFIG", "SET", "dbfilename", "x", "timeout", "-1"],
+        );
+        assert!(reply.contains("argument couldn't be parsed into an integer"));
+        assert_eq!(state.config().dbfilename, "dump.rdb");
+        let reply = request(&state, ctx, &["CONFIG", "SET", "unknown", "1"]);
+        assert!(reply.starts_with("-ERR Unknown option or number of arguments"));
+        assert_eq!(state.config().timeout, std::time::Duration::from_secs(300));
+        assert_eq!(state.config().port, 6379);
+
+        assert!(util::glob_match(b"h?llo*", b"hello world", false));
+        assert!(util::glob_match(b"h[a-e]llo", b"HELLO", true));
+        assert!(!util::glob_match(b"h[^e]llo", b"hello", false));
+        assert!(util::glob_match(b"*\\*", b"a*", false));
+        assert!(!util::glob_match(b"*\\*", b"ab", false));
+        assert!(!util::glob_match(b"a*b", b"acd", false));
+    }
+    #[test]
+    fn test_requirepass_and_auth() {
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+
+        let ctx = &mut ConnectionContext::default();
+        assert_eq!(request(&state, ctx, &["PING"]), "+PONG\r\n");
+        let reply = request(&state, ctx, &["AUTH", "secret"]);
+        assert!(reply.starts_with("-ERR AUTH <password> called without"));
+        assert_eq!(
+            request(&state, ctx, &["AUTH", "default", "anything"]),
+            "+OK\r\n"
+        );
+        let reply = request(&state, ctx, &["CONFIG", "SET", "requirepass", "secret"]);
+        assert_eq!(reply, "+OK\r\n");
+
+        let ctx = &mut ConnectionContext::default();
+        let noauth = "-NOAUTH Authentication required.\r\n";
+        let wrongpass = "-WRONGPASS invalid username-password pair or user is disabled.\r\n";
+        assert_eq!(request(&state, ctx, &["PING"]), noauth);
+        assert!(request(&state, ctx, &["HELLO", "2"]).starts_with("-NOAUTH HELLO must be called"));
+        assert_eq!(request(&state, ctx, &["AUTH", "wrong"]), wrongpass);
+        assert_eq!(
+            request(&state, ctx, &["AUTH", "admin", "secret"]),
+            wrongpass
+        );
+        let reply = request(&state, ctx, &["HELLO", "2", "AUTH", "default", "wrong"]);
+        assert_eq!(reply, wrongpass);
+        assert_eq!(request(&state, ctx, &["PING"]), noauth);
+
+        let reply = request(&state, ctx, &["HELLO", "2", "AUTH", "default", "secret"]);
+        assert!(reply.starts_with("*14\r\n$6\r\nserver\r\n$5\r\nredis\r\n"));
+        assert_eq!(request(&state, ctx, &["PING"]), "+PONG\r\n");
+        assert!(request(&state, ctx, &["HELLO", "3"]).starts_with("-NOPROTO"));
+        assert!(request(&state, ctx, &["HELLO", "4"]).starts_with("-NOPROTO"));
+        assert_eq!(request(&state, ctx, &["QUIT"]), "+OK\r\n");
+        assert!(ctx.quit);
+    }
+    #[test]
+    fn test_acl_users_and_permissions() {
+        let digest = util::to_hex(&util::sha256(b"abc"));
+        assert_eq!(
+            digest,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+
+        let admin = &mut ConnectionContext::default();
+        let rules = "on >p1 ~cache:* +@keyspace -dump +config|get +acl|whoami";
+        let setuser: Vec<_> = ["ACL", "SETUSER", "alice"]
+            .into_iter()
+            .chain(rules.split(' '))
+            .collect();
+        assert_eq!(request(&state, admin, &setuser), "+OK\r\n");
+        let list = request(&state, admin, &["ACL", "LIST"]);
+        let alice = format!(
+            "user alice on #{} ~cache:* resetchannels -@all {}",
+            util::to_hex(&util::sha256(b"p1")),
+            "+@keyspace -dump +config|get +acl|whoami"
+        );
+        assert!(list.contains("user default on nopass ~* &* +@all"));
+        assert!(list.contains(&alice));
+        let getuser = request(&state, admin, &["ACL", "GETUSER", "alice"]);
+        assert!(getuser.starts_with("*12\r\n$5\r\nflags\r\n*1\r\n$2\r\non\r\n"));
+        assert_eq!(
+            request(&state, admin, &["ACL", "GETUSER", "bob"]),
+            "$-1\r\n"
+        );
+
+        let ctx = &mut ConnectionContext::default();
+        assert!(request(&state, ctx, &["AUTH", "alice", "wrong"]).starts_with("-WRONGPASS"));
+        assert_eq!(request(&state, ctx, &["AUTH", "alice", "p1"]), "+OK\r\n");
+        assert_eq!(request(&state, ctx, &["ACL", "WHOAMI"]), "$5\r\nalice\r\n");
+        assert_eq!(request(&state, ctx, &["DEL", "cache:1"]), ":0\r\n");
+        let reply = request(&state, ctx, &["DEL", "cache:1", "other"]);
+        assert_eq!(reply, "-NOPERM No permissions to access a key\r\n");
+        let reply = request(&state, ctx, &["DUMP", "cache:1"]);
+        assert!(reply.starts_with("-NOPERM User alice has no permissions to run the 'dump'"));
+        assert!(request(&state, ctx, &["CONFIG", "GET", "port"]).starts_with("*2\r\n"));
+        let reply = request(&state, ctx, &["CONFIG", "SET", "timeout", "1"]);
+        assert!(reply.contains("no permissions to run the 'config|set' command"));
+
+        // Invalid rules leave the user unchanged.
+        let reply = request(
+            &state,
+            admin,
+            &["ACL", "SETUSER", "alice", "off", "+unknown"],
+        );
+        assert!(reply.starts_with("-ERR Error in ACL SETUSER modifier '+unknown'"));
+        let reply = request(&state, admin, &["ACL", "SETUSER", "bob", "allkeys", "~x"]);
+        assert!(reply.contains("Try 'resetkeys' to start with an empty list of patterns"));
+        assert_eq!(
+            request(&state, admin, &["ACL", "GETUSER", "bob"]),
+            "$-1\r\n"
+        );
+        assert_eq!(request(&state, ctx, &["DEL", "cache:1"]), ":0\r\n");
+
+        let reply = request(&state, admin, &["ACL", "SETUSER", "alice", "off"]);
+        assert_eq!(reply, "+OK\r\n");
+        let reply = request(&state, ctx, &["DEL", "cache:1"]);
+        assert_eq!(reply, "-NOAUTH Authentication required.\r\n");
+        let reply = request(&state, admin, &["ACL", "DELUSER", "default"]);
+        assert_eq!(reply, "-ERR The 'default' user cannot be removed\r\n");
+        let reply = request(&state, admin, &["ACL", "DELUSER", "alice", "bob"]);
+        assert_eq!(reply, ":1\r\n");
+
+        assert!(request(&state, admin, &["ACL", "CAT"]).starts_with("*21\r\n$8\r\nkeyspace\r\n"));
+        let dangerous = request(&state, admin, &["ACL", "CAT", "dangerous"]);
+        assert!(dangerous.contains("$10\r\nconfig|set\r\n"));
+        assert!(!dangerous.contains("$5\r\nwhoami\r\n"));
+        assert!(
+            request(&state, admin, &["ACL", "CAT", "nope"]).starts_with("-ERR Unknown category")
+        );
+    }
+    #[test]
+    fn test_info_sections() {
+        use db::{DatabaseSlot, DatabaseValue};
+
+        let expires = std::time::Instant::now() + std::time::Duration::from_secs(60);
+        let mut db = Database::new();
+        db.insert(
+            "a".into(),
+            DatabaseSlot::Simple(DatabaseValue::String("1".into())),
+        );
+        db.insert(
+            "b".into(),
+            DatabaseSlot::Timed {
+                expires,
+                value: DatabaseValue::String("2".into()),
+            },
+        );
+        let state = std::sync::Arc::new(ServerState::new(Config::default(), db).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        let server = request(&state, ctx, &["INFO", "server"]);
+        assert!(server.contains("# Server\r\nredis_version:7.2.0\r\n"));
+        assert!(server.contains("\r\ntcp_port:6379\r\n"));
+        assert!(server.contains("\r\nredis_mode:standalone\r\n"));
+        assert!(!server.contains("# Clients"));
+
+        let keyspace = request(&state, ctx, &["INFO", "KEYSPACE"]);
+        assert!(keyspace.contains("# Keyspace\r\ndb0:keys=2,expires=1,avg_ttl="));
+
+        let default = request(&state, ctx, &["INFO"]);
+        for section in ["Server", "Clients", "Memory", "Stats", "CPU", "Keyspace"] {
+            assert!(default.contains(&format!("# {section}\r\n")));
+        }
+        let everything = request(&state, ctx, &["INFO", "everything"]);
+        assert!(everything.contains("# Replication\r\n"));
+
+        let stats = request(&state, ctx, &["INFO", "stats"]);
+        assert!(stats.contains("total_commands_processed:5\r\n"));
+        assert_eq!(request(&state, ctx, &["INFO", "nope"]), "$0\r\n\r\n");
+    }
+    #[test]
+    fn test_command_introspection() {
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        let count = command::COMMAND_TABLE.len();
+        assert_eq!(
+            request(&state, ctx, &["COMMAND", "COUNT"]),
+            format!(":{count}\r\n")
+        );
+        let all = request(&state, ctx, &["COMMAND"]);
+        assert!(all.starts_with(&format!("*{count}\r\n*10\r\n$3\r\nacl\r\n")));
+
+        let del = request(&state, ctx, &["COMMAND", "INFO", "del", "nope"]);
+        let expected = "*2\r\n*10\r\n$3\r\ndel\r\n:-2\r\n*1\r\n$5\r\nwrite\r\n:1\r\n:-1\r\n:1\r\n";
+        assert!(del.starts_with(expected));
+        assert!(del.contains("$7\r\nlastkey\r\n:-1\r\n$7\r\nkeystep\r\n:1\r\n"));
+        assert!(del.ends_with("*0\r\n$-1\r\n"));
+        let config_get = request(&state, ctx, &["COMMAND", "INFO", "CONFIG|GET"]);
+        assert!(config_get.starts_with("*1\r\n*10\r\n$10\r\nconfig|get\r\n:-3\r\n"));
+        let config = request(&state, ctx, &["COMMAND", "INFO", "config"]);
+        assert!(config.contains("$10\r\nconfig|set\r\n:-4\r\n"));
+
+        let docs = request(&state, ctx, &["COMMAND", "DOCS", "dump", "nope"]);
+        assert!(docs.starts_with("*2\r\n$4\r\ndump\r\n*6\r\n$7\r\nsummary\r\n"));
+        assert!(docs.ends_with("$5\r\ngroup\r\n$7\r\ngeneric\r\n"));
+        let docs = request(&state, ctx, &["COMMAND", "DOCS", "config"]);
+        assert!(docs.contains("$11\r\nsubcommands\r\n*6\r\n$10\r\nconfig|get\r\n"));
+        assert!(request(&state, ctx, &["COMMAND", "NOPE"]).starts_with("-ERR"));
+    }
+    #[test]
+    fn test_latency_monitor() {
+        use std::time::Duration;
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        // Nothing is recorded while monitoring is disabled.
+        state.latency.record("command", Duration::from_millis(500));
+        assert_eq!(request(&state, ctx, &["LATENCY", "LATEST"]), "*0\r\n");
+        assert!(
+            request(&state, ctx, &["LATENCY", "DOCTOR"]).contains("Latency monitoring is disabled")
+        );
+
+        let reply = request(
+            &state,
+            ctx,
+            &["CONFIG", "SET", "latency-monitor-threshold", "100"],
+        );
+        assert_eq!(reply, "+OK\r\n");
+        let doctor = request(&state, ctx, &["LATENCY", "DOCTOR"]);
+        assert!(doctor.contains("no latency spike was observed"));
+        let latency = &state.latency;
+        latency.record("command", Duration::from_millis(50));
+        latency.record("command", Duration::from_millis(300));
+        latency.record("expire-cycle", Duration::from_millis(150));
+
+        let latest = request(&state, ctx, &["LATENCY", "LATEST"]);
+        assert!(latest.starts_with("*2\r\n*4\r\n$7\r\ncommand\r\n:"));
+        assert!(latest.contains("\r\n:300\r\n:300\r\n*4\r\n$12\r\nexpire-cycle\r\n"));
+        let history = request(&state, ctx, &["LATENCY", "HISTORY", "command"]);
+        assert!(history.starts_with("*1\r\n*2\r\n:") && history.ends_with(":300\r\n"));
+        assert_eq!(
+            request(&state, ctx, &["LATENCY", "HISTORY", "nope"]),
+            "*0\r\n"
+        );
+        let doctor = request(&state, ctx, &["LATENCY", "DOCTOR"]);
+        assert!(doctor.contains("1. command: 1 latency spikes (average 300ms"));
+        assert!(doctor.contains("2. expire-cycle: 1 latency spikes"));
+
+        let reply = request(&state, ctx, &["LATENCY", "RESET", "command", "nope"]);
+        assert_eq!(reply, ":1\r\n");
+        assert_eq!(
+            request(&state, ctx, &["LATENCY", "HISTORY", "command"]),
+            "*0\r\n"
+        );
+        assert_eq!(request(&state, ctx, &["LATENCY", "RESET"]), ":1\r\n");
+        assert_eq!(request(&state, ctx, &["LATENCY", "LATEST"]), "*0\r\n");
+    }
+    #[tokio::test]
+    async fn test_debug_subcommands() {
+        use db::{DatabaseSlot, DatabaseValue};
+        use std::sync::atomic::Ordering;
+
+        let string = |s: &str| DatabaseValue::String(s.into());
+        let mut db = Database::new();
+        db.insert("int".into(), DatabaseSlot::Simple(string("12345")));
+        db.insert("str".into(), DatabaseSlot::Simple(string("value")));
+        db.insert("raw".into(), DatabaseSlot::Simple(string(&"x".repeat(45))));
+        db.insert(
+            "list".into(),
+            DatabaseSlot::Simple(DatabaseValue::Array(vec![string("a"), string("b")])),
+        );
+        let state = std::sync::Arc::new(ServerState::new(Config::default(), db).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        for (key, encoding) in [
+            ("int", "int"),
+            ("str", "embstr"),
+            ("raw", "raw"),
+            ("list", "listpack"),
+        ] {
+            let reply = request(&state, ctx, &["DEBUG", "OBJECT", key]);
+            assert!(reply.starts_with("+Value at:0x"));
+            assert!(reply.contains(&format!(" refcount:1 encoding:{encoding} ")));
+        }
+        let reply = request(&state, ctx, &["DEBUG", "OBJECT", "str"]);
+        assert!(reply.contains(" serializedlength:6 "));
+        let reply = request(&state, ctx, &["DEBUG", "OBJECT", "nope"]);
+        assert_eq!(reply, "-ERR no such key\r\n");
+
+        request(&state, ctx, &["DEBUG", "SLEEP", "0.01"]);
+        let sleep = ctx.deferred.take().unwrap();
+        assert_eq!(sleep.resolve(&state).await.to_string(), "+OK\r\n");
+        assert!(request(&state, ctx, &["DEBUG", "SLEEP", "soon"]).starts_with("-ERR"));
+
+        let reply = request(&state, ctx, &["DEBUG", "SET-ACTIVE-EXPIRE", "0"]);
+        assert_eq!(reply, "+OK\r\n");
+        assert!(!state.active_expire_enabled.load(Ordering::Relaxed));
+        let reply = request(&state, ctx, &["DEBUG", "SET-ACTIVE-EXPIRE", "1"]);
+        assert_eq!(reply, "+OK\r\n");
+        assert!(state.active_expire_enabled.load(Ordering::Relaxed));
+
+        let reply = request(&state, ctx, &["DEBUG", "STRINGMATCH-LEN"]);
+        assert_eq!(reply, "+Apparently Redis did not crash: test passed\r\n");
+    }
+    #[test]
+    fn test_latency_histogram() {
+        use server::LatencyHistogram;
+        use std::time::Duration;
+
+        let mut histogram = LatencyHistogram::default();
+        assert!(histogram.cumulative().is_empty());
+        for usec in [0, 1, 3, 4, 100] {
+            histogram.record(Duration::from_micros(usec));
+        }
+        assert_eq!(histogram.cumulative(), [(1, 2), (4, 4), (128, 5)]);
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+        request(&state, ctx, &["PING"]);
+        request(&state, ctx, &["PING"]);
+        request(&state, ctx, &["CONFIG", "GET", "port"]);
+
+        let reply = request(&state, ctx, &["LATENCY", "HISTOGRAM", "ping"]);
+        assert!(reply.starts_with(
+            "*2\r\n$4\r\nping\r\n*4\r\n$5\r\ncalls\r\n:2\r\n$14\r\nhistogram_usec\r\n"
+        ));
+        assert!(reply.ends_with(":2\r\n"));
+        let reply = request(&state, ctx, &["LATENCY", "HISTOGRAM", "CONFIG"]);
+        assert!(reply.starts_with("*2\r\n$10\r\nconfig|get\r\n"));
+        assert_eq!(
+            request(&state, ctx, &["LATENCY", "HISTOGRAM", "config|set"]),
+            "*0\r\n"
+        );
+        // Every command which ran, including the previous LATENCY HISTOGRAMs.
+        let reply = request(&state, ctx, &["LATENCY", "HISTOGRAM"]);
+        assert!(reply.starts_with("*6\r\n$10\r\nconfig|get\r\n"));
+        assert!(reply.contains("$17\r\nlatency|histogram\r\n*4\r\n$5\r\ncalls\r\n:3\r\n"));
+    }
+    #[tokio::test]
+    async fn test_persistence_progress() {
+        use server::SaveProgress;
+        use std::sync::atomic::Ordering;
+
+        let progress = SaveProgress::default();
+        assert_eq!(progress.current(), None);
+        assert_eq!(progress.last_duration_secs(), -1);
+        progress.start(100);
+        progress.advance(40);
+        assert_eq!(progress.current(), Some((40, 100)));
+        assert_eq!(progress.current_duration_secs(), 0);
+        progress.finish();
+        assert_eq!(progress.current(), None);
+        assert_eq!(progress.current_duration_secs(), -1);
+        assert_eq!(progress.last_duration_secs(), 0);
+
+        let dir = std::env::temp_dir().join(format!("test-save-progress-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = Config {
+            dir: dir.clone(),
+            ..Default::default()
+        };
+        let state = std::sync::Arc::new(ServerState::new(config, Database::new()).unwrap());
+        let persistence = || info(&state, &["persistence".into()]);
+        assert!(persistence().contains("current_fork_perc:0.00\r\n"));
+        assert!(persistence().contains("rdb_last_bgsave_time_sec:-1\r\n"));
+
+        assert!(state.bgsave().unwrap());
+        while state.rdb_bgsave_in_progress.load(Ordering::Acquire) {
+            tokio::task::yield_now().await;
+        }
+        let info = persistence();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let snapshot_size = state
+            .rdb_bgsave_progress
+            .last_snapshot_size
+            .load(Ordering::Relaxed);
+        assert!(snapshot_size > 0);
+        assert!(info.contains("rdb_last_bgsave_status:ok\r\nrdb_last_bgsave_time_sec:0\r\n"));
+        assert!(info.contains("rdb_current_bgsave_time_sec:-1\r\n"));
+        assert!(info.contains(&format!("rdb_last_cow_size:{snapshot_size}\r\n")));
+        assert!(info.contains("aof_last_rewrite_time_sec:-1\r\n"));
+        assert!(info.contains("aof_last_bgrewrite_status:ok\r\n"));
+    }
+    #[test]
+    fn test_diagnostics_report() {
+        use server::diagnostics_report;
+        use std::time::Duration;
+
+        let config = Config {
+            latency_monitor_threshold: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let state = ServerState::new(config, Database::new()).unwrap();
+        let addr = std::net::SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, 50000));
+        state.clients.register(addr, addr);
+        state.latency.record("command", Duration::from_millis(300));
+
+        let report = diagnostics_report(&state);
+        assert!(report.contains("# Clients\nconnected_clients:0\nblocked_clients:0\n"));
+        assert!(report.contains("# Memory\n"));
+        assert!(report.contains("blocked_clients_with_timeout:0\n"));
+        assert!(report.contains("# Clients list\nid=1 addr=127.0.0.1:50000 "));
+        assert!(report.contains("\ncommand:max=300ms,latest="));
+        assert!(!report.contains('\r'));
+    }
+    #[test]
+    fn test_memory_stats_and_doctor() {
+        use db::{DatabaseSlot, DatabaseValue};
+
+        let string = |s: &str| DatabaseValue::String(s.into());
+        assert!(string(&"x".repeat(100)).memory_usage() >= 100);
+
+        let mut db = Database::new();
+        db.insert("small".into(), DatabaseSlot::Simple(string("value")));
+        db.insert(
+            "list".into(),
+            DatabaseSlot::Timed {
+                expires: std::time::Instant::now() + std::time::Duration::from_secs(60),
+                value: DatabaseValue::Array(vec![string(&"y".repeat(1000))]),
+            },
+        );
+        let state = std::sync::Arc::new(ServerState::new(Config::default(), db).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        let memory = server::memory_stats(&state);
+        assert_eq!(memory.keys_count, 2);
+        assert!(memory.dataset_bytes > 1000);
+        assert_eq!(
+            memory.total_allocated,
+            memory.overhead_total + memory.dataset_bytes
+        );
+        assert!(memory.peak_allocated >= memory.total_allocated);
+        assert!(memory.db_hashtable_expires > 0);
+
+        let stats = request(&state, ctx, &["MEMORY", "STATS"]);
+        assert!(stats.starts_with("*34\r\n$14\r\npeak.allocated\r\n:"));
+        assert!(stats.contains("$10\r\nkeys.count\r\n:2\r\n"));
+        assert!(stats.contains("$4\r\ndb.0\r\n*4\r\n$23\r\noverhead.hashtable.main\r\n:"));
+        let info = request(&state, ctx, &["INFO", "memory"]);
+        let dataset = format!("\r\nused_memory_dataset:{}\r\n", memory.dataset_bytes);
+        assert!(info.contains(&dataset));
+
+        let doctor = request(&state, ctx, &["MEMORY", "DOCTOR"]);
+        assert!(doctor.contains("Hi Sam, this instance is empty or is using very little memory"));
+        assert!(request(&state, ctx, &["MEMORY", "NOPE"]).starts_with("-ERR"));
+    }
+    #[test]
+    fn test_prometheus_metrics() {
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        request(&state, ctx, &["PING"]);
+        request(&state, ctx, &["PING"]);
+        request(&state, ctx, &["CONFIG", "GET", "port"]);
+        assert_eq!(request(&state, ctx, &["DUMP", "missing"]), "$-1\r\n");
+
+        let metrics = server::render_metrics(&state);
+        assert!(metrics.contains("# TYPE redis_commands_total counter\n"));
+        assert!(metrics.contains("redis_commands_total{cmd=\"ping\"} 2\n"));
+        assert!(metrics.contains("redis_commands_total{cmd=\"config|get\"} 1\n"));
+        assert!(metrics.contains("redis_keyspace_misses_total 1\n"));
+        assert!(metrics.contains("redis_db_keys{db=\"db0\"} 0\n"));
+        assert!(metrics.contains("redis_connected_replicas 0\n"));
+        assert!(!metrics.contains("redis_master_link_up"));
+
+        let info = request(&state, ctx, &["INFO", "stats"]);
+        assert!(info.contains("keyspace_hits:0\r\nkeyspace_misses:1\r\n"));
+    }
+
+    #[test]
+    fn test_commandstats_and_errorstats() {
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        request(&state, ctx, &["CONFIG", "GET", "port"]);
+        assert!(request(&state, ctx, &["CONFIG", "SET", "port", "nan"]).starts_with("-ERR"));
+        assert!(request(&state, ctx, &["CONFIG", "GET"]).starts_with("-ERR"));
+        assert!(request(&state, ctx, &["NOSUCHCOMMAND"]).starts_with("-ERR"));
+
+        let info = request(&state, ctx, &["INFO", "commandstats"]);
+        assert!(info.contains("# Commandstats\r\n"));
+        assert!(info.contains("cmdstat_config|get:calls=1,"));
+        assert!(info.contains(",rejected_calls=1,failed_calls=0\r\n"));
+        assert!(info.contains(",rejected_calls=0,failed_calls=1\r\n"));
+        assert!(!request(&state, ctx, &["INFO"]).contains("# Commandstats"));
+        assert!(request(&state, ctx, &["INFO", "all"]).contains("# Commandstats"));
+
+        let info = request(&state, ctx, &["INFO", "errorstats"]);
+        assert!(info.contains("errorstat_ERR:count=3\r\n"));
+        let info = request(&state, ctx, &["INFO", "stats"]);
+        assert!(info.contains("total_error_replies:3\r\n"));
+    }
+
+    #[test]
+    fn test_client_info_and_list() {
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let laddr = "127.0.0.1:6379".parse().unwrap();
+        let first = &mut ConnectionContext::default();
+        first.id = state
+            .clients
+            .register("127.0.0.1:50000".parse().unwrap(), laddr);
+        let second = &mut ConnectionContext::default();
+        second.id = state
+            .clients
+            .register("127.0.0.1:50001".parse().unwrap(), laddr);
+
+        assert_eq!(request(&state, first, &["CLIENT", "ID"]), ":1\r\n");
+        assert_eq!(request(&state, first, &["CLIENT", "GETNAME"]), "$-1\r\n");
+        let reply = request(&state, first, &["CLIENT", "SETNAME", "bad name"]);
+        assert!(reply.starts_with("-ERR Client names cannot contain spaces"));
+        assert_eq!(
+            request(&state, first, &["CLIENT", "SETNAME", "worker"]),
+            "+OK\r\n"
+        );
+        assert_eq!(
+            request(&state, first, &["CLIENT", "GETNAME"]),
+            "$6\r\nworker\r\n"
+        );
+
+        let info = request(&state, first, &["CLIENT", "INFO"]);
+        assert!(info.contains(
+            "id=1 addr=127.0.0.1:50000 laddr=127.0.0.1:6379 name=worker age=0 idle=0 flags=N db=0 "
+        ));
+        assert!(info.contains(" sub=0 psub=0 ssub=0 multi=-1 "));
+        assert!(info.contains(" obl=0 oll=0 omem=0 cmd=client|info user=default resp=2\n"));
+
+        request(&state, second, &["PING"]);
+        let list = request(&state, first, &["CLIENT", "LIST"]);
+        let lines: Vec<_> = list
+            .split('\n')
+            .filter(|line| line.contains("id="))
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("cmd=client|list"));
+        assert!(lines[1].starts_with("id=2 addr=127.0.0.1:50001 "));
+        assert!(lines[1].contains("name= "));
+        assert!(lines[1].contains("cmd=ping"));
+
+        state.clients.unregister(second.id);
+        assert!(!request(&state, first, &["CLIENT", "LIST"]).contains("id=2"));
+    }
+
+    #[tokio::test]
+    async fn test_embedded_server() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpStream;
+
+        async fn request(client: &mut TcpStream, frame: &[u8]) -> String {
+            client.write_all(frame).await.unwrap();
+            let mut buf = vec![0; 1024];
+            let read = client.read(&mut buf).await.unwrap();
+            String::from_utf8(buf[..read].to_vec()).unwrap()
+        }
+
+        let result = RedisServer::builder()
+            .port(0)
+            .set("no-such-parameter", "1")
+            .start()
+            .await;
+        assert!(result.is_err());
+
+        let dir = std::env::temp_dir().join(format!("test-embedded-{}", std::process::id()));
+        let server = RedisServer::builder()
+            .port(0)
+            .dir(&dir)
+            .set("dbfilename", "embedded.rdb")
+            .start()
+            .await
+            .unwrap();
+        let addr = server.addr();
+        assert_ne!(addr.port(), 0);
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let reply = request(&mut client, b"*1\r\n$4\r\nPING\r\n").await;
+        assert_eq!(reply, "+PONG\r\n");
+        let frame = b"*3\r\n$6\r\nCONFIG\r\n$3\r\nGET\r\n$4\r\nport\r\n";
+        let reply = request(&mut client, frame).await;
+        assert!(reply.ends_with(&format!("\r\n{}\r\n", addr.port())));
+        let frame = b"*3\r\n$6\r\nCONFIG\r\n$3\r\nGET\r\n$10\r\ndbfilename\r\n";
+        let reply = request(&mut client, frame).await;
+        assert!(reply.ends_with("\r\nembedded.rdb\r\n"));
+
+        server.shutdown().await.unwrap();
+        let mut buf = [0; 16];
+        assert!(matches!(client.read(&mut buf).await, Ok(0) | Err(_)));
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_test_client() {
+        use testing::TestClient;
+
+        let server = RedisServer::builder().port(0).start().await.unwrap();
+        let mut tcp = TestClient::connect(server.addr()).await.unwrap();
+        let mut client = TestClient::in_memory(&server);
+
+        let reply = client.send("PING").await.unwrap();
+        assert_eq!(reply, RespValue::SimpleString("PONG".into()));
+        let reply = client.send_args(&["PING", "hello world"]).await.unwrap();
+        assert_eq!(reply, RespValue::BulkString("hello world".into()));
+        let reply = client.send("CLIENT INFO").await.unwrap();
+        // NOTE: The test macro doesn't parse let-else.
+        let info = match reply {
+            RespValue::BulkString(info) => info,
+            reply => panic!("CLIENT INFO replied {reply:?}"),
+        };
+        assert!(info.contains(" addr=127.0.0.1:0 "));
+
+        client
+            .send_raw(b"*1\r\n$4\r\nPING\r\n*2\r\n$4\r\nPING\r\n$1\r\nx\r\n")
+            .await
+            .unwrap();
+        let reply = client.read_reply().await.unwrap();
+        assert_eq!(reply, RespValue::SimpleString("PONG".into()));
+        let reply = client.read_reply().await.unwrap();
+        assert_eq!(reply, RespValue::BulkString("x".into()));
+
+        let reply = tcp.send("CLIENT LIST").await.unwrap();
+        let list = match reply {
+            RespValue::BulkString(list) => list,
+            reply => panic!("CLIENT LIST replied {reply:?}"),
+        };
+        assert_eq!(list.lines().count(), 2);
+
+        server.shutdown().await.unwrap();
+        assert!(client.send("PING").await.is_err());
+        assert!(tcp.send("PING").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_custom_command_handler() {
+        use testing::TestClient;
+
+        struct Upper;
+        impl CommandHandler for Upper {
+            fn name(&self) -> &'static str {
+                "upper"
+            }
+            fn arity(&self) -> i64 {
+                -2
+            }
+            fn flags(&self) -> &'static [&'static str] {
+                &["fast"]
+            }
+            fn call(&self, args: Vec<String>) -> HandlerFuture {
+                Box::pin(async move {
+                    tokio::task::yield_now().await;
+                    let args = args
+                        .into_iter()
+                        .map(|arg| RespValue::BulkString(arg.to_uppercase().into()))
+                        .collect();
+                    RespValue::Array(args)
+                })
+            }
+        }
+        struct Ping;
+        impl CommandHandler for Ping {
+            fn name(&self) -> &'static str {
+                "PING"
+            }
+            fn arity(&self) -> i64 {
+                1
+            }
+            fn call(&self, _: Vec<String>) -> HandlerFuture {
+                Box::pin(async { RespValue::SimpleString("custom".into()) })
+            }
+        }
+
+        let result = RedisServer::builder().port(0).command(Ping).start().await;
+        assert!(result.is_err());
+
+        let server = RedisServer::builder()
+            .port(0)
+            .command(Upper)
+            .start()
+            .await
+            .unwrap();
+        let mut client = TestClient::in_memory(&server);
+
+        let reply = client.send("UpPeR a bc").await.unwrap();
+        let expected = ["A", "BC"]
+            .into_iter()
+            .map(|arg| RespValue::BulkString(arg.into()))
+            .collect();
+        assert_eq!(reply, RespValue::Array(expected));
+        let reply = client.send("upper").await.unwrap();
+        let expected =
+            RespValue::SimpleError("ERR wrong number of arguments for 'upper' command".into());
+        assert_eq!(reply, expected);
+        let reply = client.send("PING").await.unwrap();
+        assert_eq!(reply, RespValue::SimpleString("PONG".into()));
+
+        let reply = client.send("INFO commandstats").await.unwrap();
+        let info = match reply {
+            RespValue::BulkString(info) => info,
+            reply => panic!("INFO replied {reply:?}"),
+        };
+        assert!(info.contains("cmdstat_upper:calls=1,"));
+        assert!(info.contains(",rejected_calls=1,failed_calls=0\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_reply_handle() {
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+        use testing::TestClient;
+
+        struct Block(Arc<Mutex<Vec<ReplyHandle>>>);
+        impl CommandHandler for Block {
+            fn name(&self) -> &'static str {
+                "block"
+            }
+            fn arity(&self) -> i64 {
+                1
+            }
+            fn call(&self, _: Vec<String>) -> HandlerFuture {
+                let (handle, reply) = reply_later();
+                self.0.lock().unwrap().push(handle);
+                Box::pin(reply)
+            }
+        }
+        async fn next_handle(handles: &Mutex<Vec<ReplyHandle>>) -> ReplyHandle {
+            loop {
+                if let Some(handle) = handles.lock().unwrap().pop() {
+                    return handle;
+                }
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }
+
+        let handles = Arc::new(Mutex::new(Vec::new()));
+        let server = RedisServer::builder()
+            .port(0)
+            .command(Block(handles.clone()))
+            .start()
+            .await
+            .unwrap();
+
+        let mut client = TestClient::in_memory(&server);
+        let blocked = tokio::spawn(async move { client.send("BLOCK").await.unwrap() });
+        let handle = next_handle(&handles).await;
+        assert!(!handle.is_closed());
+        handle.reply(RespValue::Integer(42)).unwrap();
+        assert_eq!(blocked.await.unwrap(), RespValue::Integer(42));
+
+        let mut client = TestClient::in_memory(&server);
+        let blocked = tokio::spawn(async move { client.send("BLOCK").await });
+        let mut handle = next_handle(&handles).await;
+        blocked.abort();
+        tokio::time::timeout(Duration::from_secs(5), handle.closed())
+            .await
+            .unwrap();
+        assert!(handle.reply(RespValue::Integer(42)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_buffer_limit() {
+        use testing::TestClient;
+
+        let server = RedisServer::builder()
+            .port(0)
+            .set("client-query-buffer-limit", "1mb")
+            .start()
+            .await
+            .unwrap();
+
+        // Only the incomplete request counts, not those pipelined before it.
+        let mut client = TestClient::in_memory(&server);
+        let value = "x".repeat(600 * 1024);
+        let del = format!("*2\r\n$3\r\nDEL\r\n${}\r\n{value}\r\n", value.len());
+        client.send_raw(del.repeat(2).as_bytes()).await.unwrap();
+        for _ in 0..2 {
+            assert_eq!(client.read_reply().await.unwrap(), RespValue::Integer(0));
+        }
+
+        client
+            .send_raw(b"*2\r\n$4\r\nECHO\r\n$2000000\r\n")
+            .await
+            .unwrap();
+        // Sending more than just exceeds the limit could fail once the server closed.
+        let value = "x".repeat(1024 * 1024);
+        client.send_raw(value.as_bytes()).await.unwrap();
+        let reply = client.read_reply().await.unwrap();
+        let expected = "ERR Protocol error: client query buffer limit exceeded";
+        assert_eq!(reply, RespValue::SimpleError(expected.into()));
+        assert!(client.read_reply().await.is_err());
+
+        // A blocked client gets the same error for the requests it pipelines.
+        let mut client = TestClient::in_memory(&server);
+        client
+            .send_raw(b"*3\r\n$4\r\nWAIT\r\n$1\r\n1\r\n$1\r\n0\r\n")
+            .await
+            .unwrap();
+        let value = "x".repeat(1024 * 1024 + 1);
+        client.send_raw(value.as_bytes()).await.unwrap();
+        let reply = client.read_reply().await.unwrap();
+        assert_eq!(reply, RespValue::SimpleError(expected.into()));
+        assert!(client.read_reply().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_buffer_pool() {
+        use server::BufferPool;
+        use testing::TestClient;
+
+        let pool = BufferPool::default();
+        let mut buffer = pool.take();
+        buffer.extend_from_slice(b"+OK\r\n");
+        let capacity = buffer.capacity();
+        pool.put(buffer);
+        assert_eq!(pool.len(), 1);
+        let buffer = pool.take();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.capacity(), capacity);
+        let mut large = pool.take();
+        large.reserve(1024 * 1024);
+        pool.put(large);
+        assert_eq!(pool.len(), 0);
+
+        // NOTE: Requests split across reads are kept in the reused buffer until complete.
+        let server = RedisServer::builder().port(0).start().await.unwrap();
+        let mut client = TestClient::in_memory(&server);
+        client.send_raw(b"*1\r\n$4\r\nPI").await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        client
+            .send_raw(b"NG\r\n*2\r\n$4\r\nPING\r\n$2\r\nhi\r\n")
+            .await
+            .unwrap();
+        let reply = client.read_reply().await.unwrap();
+        assert_eq!(reply, RespValue::SimpleString("PONG".into()));
+        let reply = client.read_reply().await.unwrap();
+        assert_eq!(reply, RespValue::BulkString("hi".into()));
+    }
+
+    #[tokio::test]
+    async fn test_vectored_reply() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let large = "x".repeat(40 * 1024);
+        let reply = RespValue::Array(vec![
+            RespValue::BulkString(large.as_str().into()),
+            RespValue::Integer(7),
+            RespValue::Array(vec![
+                RespValue::BulkString("".into()),
+                RespValue::BulkString(large.as_str().into()),
+            ]),
+            RespValue::SimpleString("OK".into()),
+        ]);
+        let expected = reply.to_string();
+
+        // NOTE: The small pipe forces partial writes, which have to resume mid-slice.
+        let (mut client, mut server) = tokio::io::duplex(1000);
+        let mut buffer = bytes::BytesMut::new();
+        let write = async {
+            resp::write_reply(&mut server, &reply, &mut buffer).await?;
+            server.shutdown().await
+        };
+        let mut received = Vec::new();
+        let (written, read) = tokio::join!(write, client.read_to_end(&mut received));
+        written.unwrap();
+        read.unwrap();
+        assert_eq!(String::from_utf8(received).unwrap(), expected);
+        // NOTE: Only the framing is copied, not the bulk strings.
+        assert!(buffer.len() < 64);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_reply() {
+        use std::io::IoSlice;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+
+        /// Records the size of every write.
+        #[derive(Default)]
+        struct Recorder {
+            written: Vec<u8>,
+            writes: Vec<usize>,
+        }
+        impl tokio::io::AsyncWrite for Recorder {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<std::io::Result<usize>> {
+                self.poll_write_vectored(cx, &[IoSlice::new(buf)])
+            }
+            fn poll_write_vectored(
+                mut self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                bufs: &[IoSlice<'_>],
+            ) -> Poll<std::io::Result<usize>> {
+                let len = bufs.iter().map(|buf| buf.len()).sum();
+                for buf in bufs {
+                    self.written.extend_from_slice(buf);
+                }
+                self.writes.push(len);
+                Poll::Ready(Ok(len))
+            }
+            fn is_write_vectored(&self) -> bool {
+                true
+            }
+            fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+            fn poll_shutdown(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        let large = "x".repeat(1024 * 1024);
+        let reply = RespValue::Array(vec![
+            RespValue::BulkString(large.as_str().into()),
+            RespValue::Array((0..300_000).map(RespValue::Integer).collect()),
+        ]);
+        let mut writer = Recorder::default();
+        let mut buffer = bytes::BytesMut::new();
+        resp::write_reply(&mut writer, &reply, &mut buffer)
+            .await
+            .unwrap();
+
+        assert_eq!(writer.written, reply.to_string().as_bytes());
+        assert!(writer.writes.len() > 4);
+        assert!(writer.writes.iter().all(|&len| len <= 256 * 1024));
+        // NOTE: The framing of a single chunk is buffered at a time, not of the whole reply.
+        assert!(buffer.capacity() < 1024 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        use server::{RateLimitKey, RateLimiter};
+        use std::time::{Duration, Instant};
+        use testing::TestClient;
+
+        let limiter = RateLimiter::default();
+        let key = RateLimitKey::Client(1);
+        let now = Instant::now();
+        assert!(limiter.try_acquire(key, 10, 2, now));
+        assert!(limiter.try_acquire(key, 10, 2, now));
+        assert!(!limiter.try_acquire(key, 10, 2, now));
+        assert!(limiter.try_acquire(RateLimitKey::Client(2), 10, 2, now));
+        let later = now + Duration::from_millis(100);
+        assert!(limiter.try_acquire(key, 10, 2, later));
+        assert!(!limiter.try_acquire(key, 10, 2, later));
+
+        let server = RedisServer::builder()
+            .port(0)
+            .set("rate-limit", "1")
+            .set("rate-limit-burst", "2")
+            .set("rate-limit-by", "ip")
+            .start()
+            .await
+            .unwrap();
+        let mut first = TestClient::in_memory(&server);
+        let mut second = TestClient::in_memory(&server);
+        let pong = RespValue::SimpleString("PONG".into());
+        assert_eq!(first.send("PING").await.unwrap(), pong);
+        assert_eq!(second.send("PING").await.unwrap(), pong);
+        // NOTE: Both connections come from the same address, so they share the limit.
+        match first.send("PING").await.unwrap() {
+            RespValue::SimpleError(e) => assert!(e.starts_with("ERR rate limit exceeded")),
+            reply => panic!("PING replied {reply:?}"),
+        }
+    }
+
+    #[test]
+    fn test_prepare_shutdown() {
+        use config::ShutdownMode;
+        use server::ShutdownSignal;
+
+        let dir = std::env::temp_dir().join(format!("test-shutdown-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = Config {
+            dir: dir.clone(),
+            ..Default::default()
+        };
+        let state = ServerState::new(config.clone(), Database::new()).unwrap();
+        let save = config::find_config_entry("save").unwrap();
+        assert_eq!((save.get)(&config), "3600 1 300 100 60 10000");
+
+        // NOTE: Without save points the default is not to save, unless overridden.
+        state
+            .config_set(&[(String::from("save"), String::new())])
+            .unwrap();
+        state.prepare_shutdown(ShutdownSignal::Terminate).unwrap();
+        let saved_without_points = config.rdb_path().exists();
+        state
+            .config_set(&[(String::from("shutdown-on-sigint"), String::from("save"))])
+            .unwrap();
+        state.prepare_shutdown(ShutdownSignal::Interrupt).unwrap();
+        let saved_on_sigint = config.rdb_path().exists();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut invalid = config.clone();
+        assert!((save.set)(&mut invalid, "3600").is_err());
+        assert_eq!(
+            "nosave".parse::<ShutdownMode>().ok(),
+            Some(ShutdownMode::NoSave)
+        );
+        assert!(!saved_without_points);
+        assert!(saved_on_sigint);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_systemd_notify() {
+        use config::Supervised;
+        use server::SystemdNotifier;
+        use std::os::unix::net::UnixDatagram;
+
+        let dir = std::env::temp_dir().join(format!("test-notify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let config = Config {
+            shutdown_on_sigterm: config::ShutdownMode::NoSave,
+            ..Default::default()
+        };
+        let mut state = ServerState::new(config, Database::new()).unwrap();
+        state.supervisor = Some(SystemdNotifier::new(path.clone()));
+        state.notify_supervisor("READY=1");
+        let shutdown = state.prepare_shutdown(server::ShutdownSignal::Terminate);
+        let mut buffer = [0; 64];
+        let ready = socket.recv(&mut buffer).map(|n| buffer[..n].to_vec());
+        let stopping = socket.recv(&mut buffer).map(|n| buffer[..n].to_vec());
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(shutdown.is_ok());
+        assert_eq!(ready.unwrap(), b"READY=1");
+        assert_eq!(stopping.unwrap(), b"STOPPING=1");
+        assert_eq!("SystemD".parse(), Ok(Supervised::Systemd));
+        assert!(SystemdNotifier::from_env(Supervised::No).is_none());
+    }
+
+    #[test]
+    fn test_inspect_rdb() {
+        use db::{DatabaseSlot, DatabaseValue};
+
+        let mut db = Database::new();
+        db.insert(
+            String::from("list"),
+            DatabaseSlot::Simple(DatabaseValue::Array(vec![
+                DatabaseValue::String(String::from("a")),
+                DatabaseValue::String(String::from("b")),
+            ])),
+        );
+        db.insert(
+            String::from("key"),
+            DatabaseSlot::Timed {
+                expires: std::time::Instant::now() + std::time::Duration::from_secs(60),
+                value: DatabaseValue::String(String::from("value")),
+            },
+        );
+        let summary = inspect_rdb(&dump_database(&db).unwrap()).unwrap();
+
+        let keys: Vec<_> = summary
+            .keys
+            .iter()
+            .map(|key| (key.name.as_str(), key.value_type, key.len))
+            .collect();
+        assert_eq!(keys, [("key", "string", 5), ("list", "list", 2)]);
+        assert!(summary.keys[0].expires_at.is_some());
+        assert_eq!(summary.keys[1].expires_at, None);
+        assert!(summary.to_json().contains(
+            r#"{"db":0,"key":"list","type":"list","encoding":"listpack","expires_at":null"#
+        ));
+        assert!(inspect_rdb(b"REDIS0011").is_err());
+    }
+
+    #[test]
+    fn test_lolwut() {
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        let art = request(&state, ctx, &["LOLWUT", "VERSION", "5", "10", "2", "3"]);
+        let lines: Vec<_> = art.split('\n').collect();
+        assert!(art.ends_with("schotter, plotter on paper, 1968. Redis ver. 7.2.0\n\r\n"));
+        // NOTE: Every line holds 10 Braille characters of 2x4 pixels, and the first rows
+        //       of squares are drawn in order.
+        assert_eq!(lines[1], "⢰⠒⠒⠒⢲⡖⠒⠒⠒⡆");
+        assert_eq!(
+            request(&state, ctx, &["LOLWUT", "VERSION", "6"]),
+            "$17\r\nRedis ver. 7.2.0\n\r\n"
+        );
+        assert!(request(&state, ctx, &["LOLWUT", "VERSION", "five"]).starts_with("-ERR"));
+    }
+
+    #[tokio::test]
+    async fn test_hello_setname() {
+        use testing::TestClient;
+
+        let server = RedisServer::builder()
+            .port(0)
+            .set("requirepass", "secret")
+            .start()
+            .await
+            .unwrap();
+        let mut client = TestClient::in_memory(&server);
+        let reply = client
+            .send("HELLO 2 AUTH default secret SETNAME worker")
+            .await
+            .unwrap();
+        let fields = match reply {
+            RespValue::Array(fields) => fields,
+            reply => panic!("HELLO replied {reply:?}"),
+        };
+        let id = client.send("CLIENT ID").await.unwrap();
+        assert_eq!(fields[6], RespValue::BulkString("id".into()));
+        assert_eq!(fields[7], id);
+        let name = client.send("CLIENT GETNAME").await.unwrap();
+        assert_eq!(name, RespValue::BulkString("worker".into()));
+
+        match client.send_args(&["HELLO", "2", "SETNAME", "a b"]).await {
+            Ok(RespValue::SimpleError(e)) => assert!(e.starts_with("ERR Client names")),
+            reply => panic!("HELLO replied {reply:?}"),
+        }
+        assert!(matches!(
+            client.send("HELLO 2 SETNAME").await.unwrap(),
+            RespValue::SimpleError(_)
+        ));
+    }
+
+    #[test]
+    fn test_container_help() {
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        assert_eq!(
+            request(&state, ctx, &["config", "help"]),
+            "*7\r\n\
+             +CONFIG <subcommand> [<arg> [value] [opt] ...]. Subcommands are:\r\n\
+             +GET\r\n+    Returns the effective values of configuration parameters.\r\n\
+             +HELP\r\n+    Returns helpful text about the different subcommands.\r\n\
+             +SET\r\n+    Sets configuration parameters in-flight.\r\n"
+        );
+        // NOTE: Every container documents its subcommands, including HELP itself.
+        for spec in command::COMMAND_TABLE {
+            if spec.subcommands.is_empty() {
+                continue;
+            }
+            let name = spec.name;
+            assert!(spec.subcommand("help").is_some(), "{name} has no HELP");
+            assert!(spec.subcommands.iter().all(|sub| !sub.summary.is_empty()));
+            let reply = request(&state, ctx, &[name, "HELP"]);
+            let expected = format!("*{}\r\n", 1 + spec.subcommands.len() * 2);
+            assert!(reply.starts_with(&expected), "{name} HELP replied {reply}");
+        }
+        assert_eq!(request(&state, ctx, &["PING", "HELP"]), "$4\r\nHELP\r\n");
+    }
+
+    #[test]
+    fn test_redis_error() {
+        assert_eq!(
+            RedisError::Moved(3999, "127.0.0.1:6381".into()).to_string(),
+            "MOVED 3999 127.0.0.1:6381"
+        );
+        assert_eq!(
+            RedisError::err("no such key").to_string(),
+            "ERR no such key"
+        );
+        assert_eq!(
+            RespValue::from(RedisError::NoAuth).to_string(),
+            "-NOAUTH Authentication required.\r\n"
+        );
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+        assert_eq!(
+            request(&state, ctx, &["CLUSTER", "KEYSLOT", "a"]),
+            "-ERR This instance has cluster support disabled\r\n"
+        );
+        assert_eq!(
+            request(&state, ctx, &["NOPE"]),
+            "-ERR command does not exist\r\n"
+        );
+        assert_eq!(
+            request(&state, ctx, &["RESTORE", "a", "0", "00"]),
+            "-ERR DUMP payload version or checksum are wrong\r\n"
+        );
+    }
+
+    #[test]
+    fn test_command_arity() {
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+
+        let wrong_arity =
+            |name: &str| format!("-ERR wrong number of arguments for '{name}' command\r\n");
+        assert_eq!(request(&state, ctx, &["SAVE", "now"]), wrong_arity("save"));
+        assert_eq!(request(&state, ctx, &["config"]), wrong_arity("config"));
+        assert_eq!(
+            request(&state, ctx, &["CONFIG", "GET"]),
+            wrong_arity("config|get")
+        );
+        assert_eq!(
+            request(&state, ctx, &["config", "help", "me"]),
+            wrong_arity("config|help")
+        );
+        assert_eq!(
+            request(&state, ctx, &["CONFIG", "NOPE"]),
+            "-ERR invalid arguments\r\n"
+        );
+        let stats = info(&state, &["errorstats".into()]);
+        assert!(stats.contains("errorstat_ERR:count=5\r\n"), "{stats}");
+
+        // NOTE: MIGRATE is a write, but only the deletion of its keys is propagated.
+        let args: Vec<_> = ["MIGRATE", "localhost", "6380", "a", "0", "100"]
+            .into_iter()
+            .map(|arg| RespValue::BulkString(arg.into()))
+            .collect();
+        let migrate = command::Command::try_from(args).unwrap();
+        assert!(migrate.is_write() && !migrate.is_propagated());
+    }
+
+    #[test]
+    fn test_find_command() {
+        use command::find_command;
+
+        assert_eq!(find_command("ConFig").map(|spec| spec.name), Some("config"));
+        assert_eq!(find_command("UNLINK").map(|spec| spec.name), Some("del"));
+        assert_eq!(
+            find_command("slaveof").map(|spec| spec.name),
+            Some("replicaof")
+        );
+        assert!(find_command("nope").is_none());
+        assert!(find_command(&"a".repeat(1000)).is_none());
+        assert!(find_command("ÄCL").is_none());
+        for spec in command::COMMAND_TABLE {
+            let name = spec.name.to_ascii_uppercase();
+            assert_eq!(find_command(&name).map(|found| found.name), Some(spec.name));
+        }
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let reply = request(&state, &mut ConnectionContext::default(), &["EcHo", "hi"]);
+        assert_eq!(reply, "$2\r\nhi\r\n");
+    }
+
+    #[test]
+    fn test_debug_reload() {
+        use db::{DatabaseSlot, DatabaseValue};
+
+        let dir = std::env::temp_dir().join(format!("test-debug-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = Config {
+            dir: dir.clone(),
+            ..Default::default()
+        };
+        let state = std::sync::Arc::new(ServerState::new(config.clone(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+        state.db.lock().unwrap().insert(
+            "a".into(),
+            DatabaseSlot::Simple(DatabaseValue::String("1".into())),
+        );
+
+        assert_eq!(request(&state, ctx, &["DEBUG", "RELOAD"]), "+OK\r\n");
+        let reloaded = state
+            .db
+            .lock()
+            .unwrap()
+            .get("a")
+            .is_some_and(|slot| slot.value() == &DatabaseValue::String("1".into()));
+        std::fs::remove_file(config.rdb_path()).unwrap();
+        assert_eq!(
+            request(&state, ctx, &["DEBUG", "RELOAD", "NOSAVE"]),
+            "+OK\r\n"
+        );
+        let emptied = state.db.lock().unwrap().get("a").is_none();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(reloaded && emptied);
+
+        let replid = state.replication.replid();
+        state.replication.shift_replid("b".repeat(40));
+        assert_eq!(
+            request(&state, ctx, &["DEBUG", "CHANGE-REPL-ID"]),
+            "+OK\r\n"
+        );
+        let ids = state.replication.ids();
+        assert_ne!(ids.replid, replid);
+        assert_eq!(ids.replid.len(), 40);
+        assert!(!state.replication.can_continue(&"b".repeat(40), 0));
+    }
+
+    #[test]
+    fn test_restore_absttl() {
+        use db::{DatabaseSlot, DatabaseValue};
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let encode = |args: &[&str]| {
+            let args = args
+                .iter()
+                .map(|arg| RespValue::BulkString((*arg).into()))
+                .collect();
+            RespValue::Array(args).to_string()
+        };
+        let ctx = &mut ConnectionContext::default();
+        let payload = util::to_hex(&rdb::dump_value(&DatabaseValue::String("v".into())).unwrap());
+
+        // NOTE: The relative TTL is propagated as the time the key expires at.
+        let frame = encode(&["RESTORE", "a", "60000", &payload, "REPLACE"]);
+        let (_, value) = parse_resp_value(frame.as_bytes()).unwrap();
+        let RespValue::Array(args) = value else {
+            panic!("{frame:?} is not an array")
+        };
+        let rewritten = command::Command::try_from(args)
+            .unwrap()
+            .propagated_frame()
+            .unwrap();
+        let (_, rewritten_value) = parse_resp_value(rewritten.as_bytes()).unwrap();
+        let RespValue::Array(rewritten_args) = rewritten_value else {
+            panic!("RESTORE was rewritten to {rewritten:?}")
+        };
+        let expires_ms: u64 = match &rewritten_args[2] {
+            RespValue::BulkString(ms) => ms.parse().unwrap(),
+            arg => panic!("TTL was rewritten to {arg:?}"),
+        };
+        assert!(rewritten.ends_with("$7\r\nREPLACE\r\n$6\r\nABSTTL\r\n"));
+
+        assert_eq!(request_frame(&state, ctx, rewritten.as_bytes()), "+OK\r\n");
+        let expires = state
+            .db
+            .lock()
+            .unwrap()
+            .get("a")
+            .and_then(DatabaseSlot::expires_unix_ms);
+        assert!(expires.is_some_and(|ms| ms.abs_diff(expires_ms) < 1000));
+
+        // NOTE: A key whose absolute TTL passed is not restored.
+        let expired = encode(&["RESTORE", "a", "1", &payload, "REPLACE", "ABSTTL"]);
+        assert_eq!(request_frame(&state, ctx, expired.as_bytes()), "+OK\r\n");
+        assert!(state.db.lock().unwrap().get("a").is_none());
+    }
+
+    #[test]
+    fn test_encoding_limits() {
+        use db::{DatabaseSlot, DatabaseValue, EncodingLimits};
+
+        let state =
+            std::sync::Arc::new(ServerState::new(Config::default(), Database::new()).unwrap());
+        let ctx = &mut ConnectionContext::default();
+        let list = (0..3)
+            .map(|i| DatabaseValue::String(i.to_string()))
+            .collect();
+        state.db.lock().unwrap().insert(
+            "list".into(),
+            DatabaseSlot::Simple(DatabaseValue::Array(list)),
+        );
+        let encoding = || {
+            let reply = request(
+                &state,
+                &mut ConnectionContext::default(),
+                &["DEBUG", "OBJECT", "list"],
+            );
+            let encoding = reply.split(' ').find_map(|s| s.strip_prefix("encoding:"));
+            encoding.unwrap_or_default().to_string()
+        };
+
+        assert_eq!(encoding(), "listpack");
+        let set = |name: &str, value: &str| {
+            request(
+                &state,
+                &mut ConnectionContext::default(),
+                &["CONFIG", "SET", name, value],
+            )
+        };
+        assert_eq!(set("list-max-listpack-size", "2"), "+OK\r\n");
+        assert_eq!(encoding(), "quicklist");
+        assert_eq!(set("list-max-listpack-size", "-1"), "+OK\r\n");
+        assert_eq!(encoding(), "listpack");
+        assert!(set("list-max-listpack-size", "-6").starts_with("-ERR CONFIG SET failed"));
+        assert_eq!(
+            request(&state, ctx, &["CONFIG", "GET", "set-max-intset-entries"]),
+            "*2\r\n$22\r\nset-max-intset-entries\r\n$3\r\n512\r\n"
+        );
+
+        let members = (0..3).map(DatabaseValue::Integer).collect();
+        let set = DatabaseValue::Set(members);
+        let limits = EncodingLimits {
+            set_max_intset_entries: 2,
+            ..Default::default()
+        };
+        assert_eq!(set.encoding(&EncodingLimits::default()), "intset");
+        assert_eq!(set.encoding(&limits), "listpack");
+        let limits = EncodingLimits {
+            set_max_listpack_entries: 2,
+            ..limits
+        };
+        assert_eq!(set.encoding(&limits), "hashtable");
+    }
 }
@@ -0,0 +1,107 @@
+// LZF compression as used by Redis for long strings in RDB files.
+// http://oldhome.schmorp.de/marc/liblzf.html
+
+const HASH_LOG: u32 = 14;
+const MAX_LITERAL: usize = 1 << 5;
+const MAX_OFFSET: usize = 1 << 13;
+const MAX_REFERENCE: usize = (1 << 8) + (1 << 3);
+
+/// Decompresses `input`, returning [`None`] if the data is corrupt or does not
+/// decompress to exactly `len` bytes.
+pub fn decompress(input: &[u8], len: usize) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(len);
+    let mut ip = 0;
+
+    while ip < input.len() {
+        let ctrl = usize::from(input[ip]);
+        ip += 1;
+
+        if ctrl < MAX_LITERAL {
+            let literal = input.get(ip..ip + ctrl + 1)?;
+            output.extend_from_slice(literal);
+            ip += ctrl + 1;
+        } else {
+            let mut ref_len = ctrl >> 5;
+            if ref_len == 7 {
+                ref_len += usize::from(*input.get(ip)?);
+                ip += 1;
+            }
+            let offset = ((ctrl & 0x1F) << 8) + usize::from(*input.get(ip)?) + 1;
+            ip += 1;
+
+            let start = output.len().checked_sub(offset)?;
+            // NOTE: The reference may overlap with the bytes it produces, so copy byte by byte.
+            for i in start..start + ref_len + 2 {
+                output.push(output[i]);
+            }
+        }
+    }
+
+    (output.len() == len).then_some(output)
+}
+
+/// Compresses `input`, which only pays off for inputs with repeated sequences.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut table = vec![usize::MAX; 1 << HASH_LOG];
+    let mut literals = Vec::with_capacity(MAX_LITERAL);
+    let mut ip = 0;
+
+    while ip + 2 < input.len() {
+        let hash = hash(&input[ip..ip + 3]);
+        let candidate = std::mem::replace(&mut table[hash], ip);
+
+        let is_match = candidate != usize::MAX
+            && ip - candidate <= MAX_OFFSET
+            && input[candidate..candidate + 3] == input[ip..ip + 3];
+        if !is_match {
+            literals.push(input[ip]);
+            if literals.len() == MAX_LITERAL {
+                flush_literals(&mut output, &mut literals);
+            }
+            ip += 1;
+            continue;
+        }
+
+        let max_len = std::cmp::min(MAX_REFERENCE, input.len() - ip);
+        let mut len = 3;
+        while len < max_len && input[candidate + len] == input[ip + len] {
+            len += 1;
+        }
+
+        flush_literals(&mut output, &mut literals);
+        let offset = ip - candidate - 1;
+        let ref_len = len - 2;
+        if ref_len < 7 {
+            output.push(((ref_len << 5) | (offset >> 8)) as u8);
+        } else {
+            output.push(((7 << 5) | (offset >> 8)) as u8);
+            output.push((ref_len - 7) as u8);
+        }
+        output.push(offset as u8);
+
+        ip += len;
+    }
+
+    for &b in &input[ip..] {
+        literals.push(b);
+        if literals.len() == MAX_LITERAL {
+            flush_literals(&mut output, &mut literals);
+        }
+    }
+    flush_literals(&mut output, &mut literals);
+
+    output
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let v = (u32::from(bytes[0]) << 16) | (u32::from(bytes[1]) << 8) | u32::from(bytes[2]);
+    (v.wrapping_mul(2_654_435_761) >> (32 - HASH_LOG)) as usize
+}
+
+fn flush_literals(output: &mut Vec<u8>, literals: &mut Vec<u8>) {
+    if !literals.is_empty() {
+        output.push((literals.len() - 1) as u8);
+        output.append(literals);
+    }
+}
@@ -0,0 +1,10 @@
+mod lzf;
+mod rdb_inspect;
+mod rdb_reader;
+mod rdb_type;
+mod rdb_writer;
+
+pub use rdb_inspect::{inspect_rdb, RdbKey, RdbSummary};
+pub use rdb_reader::{Rdb, RdbReader, RdbReaderError};
+pub use rdb_type::{RdbOpcode, RdbValueType};
+pub use rdb_writer::{dump_database, dump_value, write_rdb_file, RdbWriter, RdbWriterError};
@@ -0,0 +1,130 @@
+use crate::db::{DatabaseSlot, DatabaseValue, EncodingLimits, JsonValue};
+use crate::rdb::{RdbReader, RdbReaderError};
+
+/// Summary of an RDB file, which lists its keys without exposing their values.
+#[derive(Debug)]
+pub struct RdbSummary {
+    pub version: u32,
+    /// Auxiliary fields, e.g. "redis-ver", sorted by name.
+    pub aux_fields: Vec<(String, String)>,
+    /// Keys sorted by database and name.
+    pub keys: Vec<RdbKey>,
+}
+
+/// A key stored in an RDB file, see [`inspect_rdb`].
+#[derive(Debug)]
+pub struct RdbKey {
+    pub db: usize,
+    pub name: String,
+    /// Type as reported by TYPE, e.g. "hash".
+    pub value_type: &'static str,
+    /// Encoding as reported by OBJECT ENCODING with the default thresholds, e.g.
+    /// "listpack".
+    pub encoding: &'static str,
+    /// Unix time in milliseconds the key expires at.
+    pub expires_at: Option<u64>,
+    /// Number of members of aggregates, or the length of strings in bytes.
+    pub len: usize,
+    /// Estimated bytes the value would occupy once loaded.
+    pub memory: usize,
+}
+
+/// Parses an RDB file into a summary of its keys.
+///
+/// Like when the file is loaded, keys whose expiry lies in the past are skipped.
+///
+/// # Errors
+///
+/// Will return [`Err`] under the same conditions as [`RdbReader::read`].
+///
+/// [`Err`]: std::result::Result::Err
+pub fn inspect_rdb(input: &[u8]) -> Result<RdbSummary, RdbReaderError> {
+    let rdb = RdbReader::new(input).read()?;
+
+    let mut aux_fields: Vec<_> = rdb.aux_fields.into_iter().collect();
+    aux_fields.sort_unstable();
+    let mut keys = Vec::new();
+    for (&db, database) in &rdb.databases {
+        let start = keys.len();
+        keys.extend(
+            database
+                .iter()
+                .map(|(name, slot)| RdbKey::new(db, name, slot)),
+        );
+        keys[start..].sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    Ok(RdbSummary {
+        version: rdb.version,
+        aux_fields,
+        keys,
+    })
+}
+
+impl RdbKey {
+    fn new(db: usize, name: &str, slot: &DatabaseSlot) -> Self {
+        let value = slot.value();
+        let len = match value {
+            DatabaseValue::Array(values) => values.len(),
+            DatabaseValue::Set(members) => members.len(),
+            DatabaseValue::Map(map) => map.len(),
+            DatabaseValue::SortedSet(members) => members.len(),
+            value => value.to_scalar_string().map_or(0, |s| s.len()),
+        };
+        Self {
+            db,
+            name: name.to_string(),
+            value_type: value.type_name(),
+            encoding: value.encoding(&EncodingLimits::default()),
+            expires_at: slot.expires_unix_ms(),
+            len,
+            memory: value.memory_usage(),
+        }
+    }
+    fn to_json(&self) -> JsonValue {
+        let number = |n: usize| JsonValue::Number(n as f64);
+        JsonValue::Object(vec![
+            (String::from("db"), number(self.db)),
+            (String::from("key"), JsonValue::String(self.name.clone())),
+            (
+                String::from("type"),
+                JsonValue::String(self.value_type.into()),
+            ),
+            (
+                String::from("encoding"),
+                JsonValue::String(self.encoding.into()),
+            ),
+            (
+                String::from("expires_at"),
+                self.expires_at
+                    .map_or(JsonValue::Null, |ms| JsonValue::Number(ms as f64)),
+            ),
+            (String::from("len"), number(self.len)),
+            (String::from("memory"), number(self.memory)),
+        ])
+    }
+}
+
+impl RdbSummary {
+    /// Renders the summary as a JSON object with the version, the auxiliary fields
+    /// and an array of keys.
+    pub fn to_json(&self) -> String {
+        let aux_fields = self
+            .aux_fields
+            .iter()
+            .map(|(name, value)| (name.clone(), JsonValue::String(value.clone())))
+            .collect();
+        JsonValue::Object(vec![
+            (
+                String::from("version"),
+                JsonValue::Number(f64::from(self.version)),
+            ),
+            (String::from("aux"), JsonValue::Object(aux_fields)),
+            (
+                String::from("keys"),
+                JsonValue::Array(self.keys.iter().map(RdbKey::to_json).collect()),
+            ),
+        ])
+        .to_string()
+    }
+}
@@ -0,0 +1,596 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+use crate::db::{unix_ms_to_instant, Database, DatabaseSlot, DatabaseValue};
+use crate::rdb::{lzf, RdbOpcode, RdbValueType};
+use crate::util::crc64;
+
+/// Highest RDB version whose encodings are understood, which DUMP payloads are
+/// checked against.
+const RDB_MAX_VERSION: u16 = 11;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RdbReaderError {
+    #[error("unexpected end of file")]
+    UnexpectedEof,
+    #[error("invalid magic string")]
+    InvalidMagic,
+    #[error("invalid version: {0:?}")]
+    InvalidVersion(String),
+    #[error("unsupported string encoding: {0}")]
+    UnsupportedEncoding(u8),
+    #[error("unsupported value type: {0}")]
+    UnsupportedValueType(u8),
+    #[error("unsupported opcode: {0:#04x}")]
+    UnsupportedOpcode(u8),
+    #[error("invalid length encoding")]
+    InvalidLength,
+    #[error("invalid double: {0:?}")]
+    InvalidDouble(String),
+    #[error("corrupt {0} entry")]
+    CorruptEntry(&'static str),
+    #[error("non utf8 string")]
+    NonUtf8String,
+    #[error("checksum mismatch: expected {expected:#018x}, got {actual:#018x}")]
+    ChecksumMismatch { expected: u64, actual: u64 },
+}
+
+/// Contents of a parsed RDB file.
+#[derive(Debug, Default)]
+pub struct Rdb {
+    pub version: u32,
+    pub aux_fields: HashMap<String, String>,
+    pub databases: BTreeMap<usize, Database>,
+}
+
+/// Length as stored in front of strings and aggregates.
+///
+/// If the two most significant bits of the first byte are set, the remaining
+/// six bits describe a special string encoding instead of a length.
+enum RdbLength {
+    Length(usize),
+    Encoded(u8),
+}
+
+pub struct RdbReader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> RdbReader<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Self { input }
+    }
+    /// Parses a complete RDB file into its databases.
+    ///
+    /// Keys whose expiry lies in the past are skipped, the same way Redis does
+    /// when loading a file as a master.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if the file is truncated, is not an RDB file, fails the
+    /// checksum or contains encodings or value types that are not supported.
+    ///
+    /// [`Err`]: std::result::Result::Err
+    pub fn read(mut self) -> Result<Rdb, RdbReaderError> {
+        let file = self.input;
+        let mut rdb = Rdb {
+            version: self.read_header()?,
+            ..Default::default()
+        };
+
+        let mut db_index = 0;
+        let mut expires_ms = None;
+
+        loop {
+            let type_byte = self.read_u8()?;
+            match RdbOpcode::try_from(type_byte) {
+                Ok(RdbOpcode::Eof) => break,
+                Ok(RdbOpcode::SelectDb) => db_index = self.read_length()?,
+                Ok(RdbOpcode::ResizeDb) => {
+                    let _db_size = self.read_length()?;
+                    let _expires_size = self.read_length()?;
+                }
+                Ok(RdbOpcode::Aux) => {
+                    let key = self.read_utf8()?;
+                    let value = self.read_utf8()?;
+                    rdb.aux_fields.insert(key, value);
+                }
+                Ok(RdbOpcode::ExpireTimeMs) => {
+                    expires_ms = Some(u64::from_le_bytes(self.read_array()?));
+                }
+                Ok(RdbOpcode::ExpireTime) => {
+                    let secs = u32::from_le_bytes(self.read_array()?);
+                    expires_ms = Some(u64::from(secs) * 1000);
+                }
+                // NOTE: Eviction hints are irrelevant as long as there is no maxmemory.
+                Ok(RdbOpcode::Freq) => {
+                    let _ = self.read_u8()?;
+                }
+                Ok(RdbOpcode::Idle) => {
+                    let _ = self.read_length()?;
+                }
+                Ok(RdbOpcode::Function) => {
+                    let _ = self.read_string()?;
+                }
+                Ok(op) => return Err(RdbReaderError::UnsupportedOpcode(u8::from(op))),
+                Err(()) => {
+                    let key = self.read_utf8()?;
+                    let value = self.read_value(type_byte)?;
+
+                    let slot = match expires_ms.take() {
+                        None => Some(DatabaseSlot::Simple(value)),
+                        Some(ms) => unix_ms_to_instant(ms)
+                            .map(|expires| DatabaseSlot::Timed { expires, value }),
+                    };
+                    if let Some(slot) = slot {
+                        rdb.databases.entry(db_index).or_default().insert(key, slot);
+                    }
+                }
+            }
+        }
+
+        // NOTE: Version 5 and later append a CRC64 checksum, where zero means that
+        //       checksumming was disabled when the file was written.
+        if rdb.version >= 5 {
+            let actual = crc64(0, &file[..file.len() - self.input.len()]);
+            let expected = u64::from_le_bytes(self.read_array()?);
+            if expected != 0 && expected != actual {
+                return Err(RdbReaderError::ChecksumMismatch { expected, actual });
+            }
+        }
+
+        Ok(rdb)
+    }
+    /// Parses the payload of DUMP, which is a single value in RDB encoding followed
+    /// by the RDB version and a CRC64 checksum.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if the payload is truncated, was written by a newer RDB
+    /// version, fails the checksum or holds an unsupported value.
+    ///
+    /// [`Err`]: std::result::Result::Err
+    pub fn read_dump(mut self) -> Result<DatabaseValue, RdbReaderError> {
+        let payload = self.input;
+        if payload.len() < 10 {
+            return Err(RdbReaderError::UnexpectedEof);
+        }
+        let (value, footer) = payload.split_at(payload.len() - 10);
+
+        let version = u16::from_le_bytes([footer[0], footer[1]]);
+        if version > RDB_MAX_VERSION {
+            return Err(RdbReaderError::InvalidVersion(version.to_string()));
+        }
+        let mut expected = [0; 8];
+        expected.copy_from_slice(&footer[2..]);
+        let expected = u64::from_le_bytes(expected);
+        let actual = crc64(0, &payload[..payload.len() - 8]);
+        if expected != actual {
+            return Err(RdbReaderError::ChecksumMismatch { expected, actual });
+        }
+
+        self.input = value;
+        let type_byte = self.read_u8()?;
+        let value = self.read_value(type_byte)?;
+        if !self.input.is_empty() {
+            return Err(RdbReaderError::CorruptEntry("DUMP payload"));
+        }
+        Ok(value)
+    }
+    fn read_header(&mut self) -> Result<u32, RdbReaderError> {
+        if self.take(5)? != b"REDIS" {
+            return Err(RdbReaderError::InvalidMagic);
+        }
+        let version = self.take(4)?;
+        let version_str =
+            std::str::from_utf8(version).map_err(|_| RdbReaderError::NonUtf8String)?;
+        version_str
+            .parse()
+            .map_err(|_| RdbReaderError::InvalidVersion(version_str.to_string()))
+    }
+    fn take(&mut self, n: usize) -> Result<&'a [u8], RdbReaderError> {
+        if self.input.len() < n {
+            return Err(RdbReaderError::UnexpectedEof);
+        }
+        let (bytes, rest) = self.input.split_at(n);
+        self.input = rest;
+        Ok(bytes)
+    }
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], RdbReaderError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+    fn read_u8(&mut self) -> Result<u8, RdbReaderError> {
+        Ok(self.take(1)?[0])
+    }
+    /// Parses a length value using the RDB length encoding.
+    ///
+    /// See [`Length Encoding`] for the possible formats.
+    ///
+    /// [`Length Encoding`]: https://rdb.fnordig.de/file_format.html#length-encoding
+    fn read_length_encoding(&mut self) -> Result<RdbLength, RdbReaderError> {
+        let first = self.read_u8()?;
+        match first >> 6 {
+            0b00 => Ok(RdbLength::Length(usize::from(first & 0x3F))),
+            0b01 => {
+                let second = self.read_u8()?;
+                Ok(RdbLength::Length(
+                    (usize::from(first & 0x3F) << 8) | usize::from(second),
+                ))
+            }
+            0b10 if first == 0x80 => {
+                let len = u32::from_be_bytes(self.read_array()?);
+                usize::try_from(len)
+                    .map(RdbLength::Length)
+                    .map_err(|_| RdbReaderError::InvalidLength)
+            }
+            0b10 if first == 0x81 => {
+                let len = u64::from_be_bytes(self.read_array()?);
+                usize::try_from(len)
+                    .map(RdbLength::Length)
+                    .map_err(|_| RdbReaderError::InvalidLength)
+            }
+            0b10 => Err(RdbReaderError::InvalidLength),
+            _ => Ok(RdbLength::Encoded(first & 0x3F)),
+        }
+    }
+    fn read_length(&mut self) -> Result<usize, RdbReaderError> {
+        match self.read_length_encoding()? {
+            RdbLength::Length(len) => Ok(len),
+            RdbLength::Encoded(_) => Err(RdbReaderError::InvalidLength),
+        }
+    }
+    /// Reads a string, which is either length-prefixed, an integer or LZF-compressed.
+    ///
+    /// See [`String Encoding`] for the possible formats.
+    ///
+    /// [`String Encoding`]: https://rdb.fnordig.de/file_format.html#string-encoding
+    fn read_string(&mut self) -> Result<Vec<u8>, RdbReaderError> {
+        match self.read_length_encoding()? {
+            RdbLength::Length(len) => Ok(self.take(len)?.to_vec()),
+            RdbLength::Encoded(0) => Ok(i8::from_le_bytes(self.read_array()?)
+                .to_string()
+                .into_bytes()),
+            RdbLength::Encoded(1) => Ok(i16::from_le_bytes(self.read_array()?)
+                .to_string()
+                .into_bytes()),
+            RdbLength::Encoded(2) => Ok(i32::from_le_bytes(self.read_array()?)
+                .to_string()
+                .into_bytes()),
+            RdbLength::Encoded(3) => {
+                let compressed_len = self.read_length()?;
+                let len = self.read_length()?;
+                let compressed = self.take(compressed_len)?;
+                lzf::decompress(compressed, len).ok_or(RdbReaderError::CorruptEntry("lzf"))
+            }
+            RdbLength::Encoded(enc) => Err(RdbReaderError::UnsupportedEncoding(enc)),
+        }
+    }
+    fn read_utf8(&mut self) -> Result<String, RdbReaderError> {
+        bytes_to_string(self.read_string()?)
+    }
+    fn read_string_value(&mut self) -> Result<DatabaseValue, RdbReaderError> {
+        self.read_utf8().map(DatabaseValue::String)
+    }
+    fn read_value(&mut self, type_byte: u8) -> Result<DatabaseValue, RdbReaderError> {
+        let value_type = RdbValueType::try_from(type_byte)
+            .map_err(|_| RdbReaderError::UnsupportedValueType(type_byte))?;
+
+        match value_type {
+            RdbValueType::String => self.read_string_value(),
+            RdbValueType::List => {
+                let len = self.read_length()?;
+                let mut list = Vec::with_capacity(len);
+                for _ in 0..len {
+                    list.push(self.read_string_value()?);
+                }
+                Ok(DatabaseValue::Array(list))
+            }
+            RdbValueType::Set => {
+                let len = self.read_length()?;
+                let mut set = HashSet::with_capacity(len);
+                for _ in 0..len {
+                    set.insert(self.read_string_value()?);
+                }
+                Ok(DatabaseValue::Set(set))
+            }
+            RdbValueType::SortedSet | RdbValueType::SortedSet2 => {
+                let len = self.read_length()?;
+                let mut zset = HashMap::with_capacity(len);
+                for _ in 0..len {
+                    let member = self.read_string_value()?;
+                    let score = if value_type == RdbValueType::SortedSet {
+                        self.read_string_double()?
+                    } else {
+                        f64::from_le_bytes(self.read_array()?)
+                    };
+                    zset.insert(member, score);
+                }
+                Ok(DatabaseValue::SortedSet(zset))
+            }
+            RdbValueType::Hash => {
+                let len = self.read_length()?;
+                let mut map = HashMap::with_capacity(len);
+                for _ in 0..len {
+                    let field = self.read_string_value()?;
+                    let value = self.read_string_value()?;
+                    map.insert(field, value);
+                }
+                Ok(DatabaseValue::Map(map))
+            }
+            RdbValueType::ListZiplist => {
+                let entries = parse_ziplist(&self.read_string()?)?;
+                Ok(DatabaseValue::Array(strings_to_values(entries)?))
+            }
+            RdbValueType::ListQuicklist => {
+                let len = self.read_length()?;
+                let mut list = Vec::new();
+                for _ in 0..len {
+                    list.extend(strings_to_values(parse_ziplist(&self.read_string()?)?)?);
+                }
+                Ok(DatabaseValue::Array(list))
+            }
+            RdbValueType::ListQuicklist2 => {
+                const QUICKLIST_NODE_PLAIN: usize = 1;
+
+                let len = self.read_length()?;
+                let mut list = Vec::new();
+                for _ in 0..len {
+                    let container = self.read_length()?;
+                    let bytes = self.read_string()?;
+                    if container == QUICKLIST_NODE_PLAIN {
+                        list.push(DatabaseValue::String(bytes_to_string(bytes)?));
+                    } else {
+                        list.extend(strings_to_values(parse_listpack(&bytes)?)?);
+                    }
+                }
+                Ok(DatabaseValue::Array(list))
+            }
+            RdbValueType::SetIntset => {
+                let entries = parse_intset(&self.read_string()?)?;
+                Ok(DatabaseValue::Set(
+                    strings_to_values(entries)?.into_iter().collect(),
+                ))
+            }
+            RdbValueType::SetListpack => {
+                let entries = parse_listpack(&self.read_string()?)?;
+                Ok(DatabaseValue::Set(
+                    strings_to_values(entries)?.into_iter().collect(),
+                ))
+            }
+            RdbValueType::SortedSetZiplist | RdbValueType::SortedSetListpack => {
+                let bytes = self.read_string()?;
+                let entries = if value_type == RdbValueType::SortedSetZiplist {
+                    parse_ziplist(&bytes)?
+                } else {
+                    parse_listpack(&bytes)?
+                };
+                let mut entries = entries.into_iter();
+                let mut zset = HashMap::with_capacity(entries.len() / 2);
+                while let Some(member) = entries.next() {
+                    let score = entries
+                        .next()
+                        .ok_or(RdbReaderError::CorruptEntry("sorted set"))?;
+                    let score = bytes_to_string(score)?;
+                    let score = score
+                        .parse()
+                        .map_err(|_| RdbReaderError::InvalidDouble(score))?;
+                    zset.insert(DatabaseValue::String(bytes_to_string(member)?), score);
+                }
+                Ok(DatabaseValue::SortedSet(zset))
+            }
+            RdbValueType::HashZiplist | RdbValueType::HashListpack => {
+                let bytes = self.read_string()?;
+                let entries = if value_type == RdbValueType::HashZiplist {
+                    parse_ziplist(&bytes)?
+                } else {
+                    parse_listpack(&bytes)?
+                };
+                let mut values = strings_to_values(entries)?.into_iter();
+                let mut map = HashMap::with_capacity(values.len() / 2);
+                while let Some(field) = values.next() {
+                    let value = values.next().ok_or(RdbReaderError::CorruptEntry("hash"))?;
+                    map.insert(field, value);
+                }
+                Ok(DatabaseValue::Map(map))
+            }
+            RdbValueType::HashZipmap => Err(RdbReaderError::UnsupportedValueType(type_byte)),
+        }
+    }
+    /// Reads a double stored as a length-prefixed ASCII string, which is used by
+    /// the original sorted set encoding.
+    fn read_string_double(&mut self) -> Result<f64, RdbReaderError> {
+        match self.read_u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => {
+                let bytes = self.take(usize::from(len))?;
+                let string =
+                    std::str::from_utf8(bytes).map_err(|_| RdbReaderError::NonUtf8String)?;
+                string
+                    .parse()
+                    .map_err(|_| RdbReaderError::InvalidDouble(string.to_string()))
+            }
+        }
+    }
+}
+
+fn bytes_to_string(bytes: Vec<u8>) -> Result<String, RdbReaderError> {
+    String::from_utf8(bytes).map_err(|_| RdbReaderError::NonUtf8String)
+}
+
+fn strings_to_values(entries: Vec<Vec<u8>>) -> Result<Vec<DatabaseValue>, RdbReaderError> {
+    entries
+        .into_iter()
+        .map(|bytes| bytes_to_string(bytes).map(DatabaseValue::String))
+        .collect()
+}
+
+fn take_entry<'a>(
+    input: &mut &'a [u8],
+    n: usize,
+    name: &'static str,
+) -> Result<&'a [u8], RdbReaderError> {
+    if input.len() < n {
+        return Err(RdbReaderError::CorruptEntry(name));
+    }
+    let (bytes, rest) = input.split_at(n);
+    *input = rest;
+    Ok(bytes)
+}
+
+/// Sign-extends the lowest `bits` bits of `value`.
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+fn le_int(bytes: &[u8]) -> i64 {
+    let value = bytes
+        .iter()
+        .rev()
+        .fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+    sign_extend(value, 8 * bytes.len() as u32)
+}
+
+/// Decodes a ziplist into its entries, with integers converted to their string form.
+///
+/// See [`Ziplist Encoding`] for the layout.
+///
+/// [`Ziplist Encoding`]: https://rdb.fnordig.de/file_format.html#ziplist-encoding
+fn parse_ziplist(mut input: &[u8]) -> Result<Vec<Vec<u8>>, RdbReaderError> {
+    const NAME: &str = "ziplist";
+
+    let header = take_entry(&mut input, 10, NAME)?;
+    let len = u16::from_le_bytes([header[8], header[9]]);
+
+    let mut entries = Vec::with_capacity(usize::from(len));
+    loop {
+        let prev_len = take_entry(&mut input, 1, NAME)?[0];
+        if prev_len == 0xFF {
+            break;
+        }
+        if prev_len == 0xFE {
+            take_entry(&mut input, 4, NAME)?;
+        }
+        let encoding = take_entry(&mut input, 1, NAME)?[0];
+        let entry = match encoding >> 6 {
+            0b00 => take_entry(&mut input, usize::from(encoding & 0x3F), NAME)?.to_vec(),
+            0b01 => {
+                let second = take_entry(&mut input, 1, NAME)?[0];
+                let len = (usize::from(encoding & 0x3F) << 8) | usize::from(second);
+                take_entry(&mut input, len, NAME)?.to_vec()
+            }
+            0b10 => {
+                let len_bytes = take_entry(&mut input, 4, NAME)?;
+                let len =
+                    u32::from_be_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]);
+                take_entry(&mut input, len as usize, NAME)?.to_vec()
+            }
+            _ => {
+                let int = match encoding {
+                    0xC0 => le_int(take_entry(&mut input, 2, NAME)?),
+                    0xD0 => le_int(take_entry(&mut input, 4, NAME)?),
+                    0xE0 => le_int(take_entry(&mut input, 8, NAME)?),
+                    0xF0 => le_int(take_entry(&mut input, 3, NAME)?),
+                    0xFE => le_int(take_entry(&mut input, 1, NAME)?),
+                    0xF1..=0xFD => i64::from(encoding & 0x0F) - 1,
+                    _ => return Err(RdbReaderError::CorruptEntry(NAME)),
+                };
+                int.to_string().into_bytes()
+            }
+        };
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Decodes a listpack into its entries, with integers converted to their string form.
+///
+/// See [`Listpack Specification`] for the layout.
+///
+/// [`Listpack Specification`]: https://github.com/antirez/listpack/blob/master/listpack.md
+fn parse_listpack(mut input: &[u8]) -> Result<Vec<Vec<u8>>, RdbReaderError> {
+    const NAME: &str = "listpack";
+
+    let header = take_entry(&mut input, 6, NAME)?;
+    let len = u16::from_le_bytes([header[4], header[5]]);
+
+    let mut entries = Vec::with_capacity(usize::from(len));
+    loop {
+        let encoding = take_entry(&mut input, 1, NAME)?[0];
+        let (entry, entry_len) = if encoding == 0xFF {
+            break;
+        } else if encoding >> 7 == 0 {
+            (i64::from(encoding).to_string().into_bytes(), 1)
+        } else if encoding >> 6 == 0b10 {
+            let len = usize::from(encoding & 0x3F);
+            (take_entry(&mut input, len, NAME)?.to_vec(), 1 + len)
+        } else if encoding >> 5 == 0b110 {
+            let second = take_entry(&mut input, 1, NAME)?[0];
+            let value = (u64::from(encoding & 0x1F) << 8) | u64::from(second);
+            (sign_extend(value, 13).to_string().into_bytes(), 2)
+        } else if encoding >> 4 == 0b1110 {
+            let second = take_entry(&mut input, 1, NAME)?[0];
+            let len = (usize::from(encoding & 0x0F) << 8) | usize::from(second);
+            (take_entry(&mut input, len, NAME)?.to_vec(), 2 + len)
+        } else {
+            let (int_len, str_len) = match encoding {
+                0xF0 => {
+                    let len_bytes = take_entry(&mut input, 4, NAME)?;
+                    (0, le_int(len_bytes) as u32 as usize)
+                }
+                0xF1 => (2, 0),
+                0xF2 => (3, 0),
+                0xF3 => (4, 0),
+                0xF4 => (8, 0),
+                _ => return Err(RdbReaderError::CorruptEntry(NAME)),
+            };
+            if int_len > 0 {
+                let int = le_int(take_entry(&mut input, int_len, NAME)?);
+                (int.to_string().into_bytes(), 1 + int_len)
+            } else {
+                (take_entry(&mut input, str_len, NAME)?.to_vec(), 5 + str_len)
+            }
+        };
+        take_entry(&mut input, listpack_backlen_size(entry_len), NAME)?;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Number of bytes used to store the back-length of a listpack entry.
+fn listpack_backlen_size(entry_len: usize) -> usize {
+    match entry_len {
+        0..=127 => 1,
+        128..=16_383 => 2,
+        16_384..=2_097_151 => 3,
+        2_097_152..=268_435_455 => 4,
+        _ => 5,
+    }
+}
+
+/// Decodes an intset into its members in string form.
+fn parse_intset(mut input: &[u8]) -> Result<Vec<Vec<u8>>, RdbReaderError> {
+    const NAME: &str = "intset";
+
+    let header = take_entry(&mut input, 8, NAME)?;
+    let encoding = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if !matches!(encoding, 2 | 4 | 8) {
+        return Err(RdbReaderError::CorruptEntry(NAME));
+    }
+
+    (0..len)
+        .map(|_| {
+            let int = le_int(take_entry(&mut input, encoding, NAME)?);
+            Ok(int.to_string().into_bytes())
+        })
+        .collect()
+}
@@ -0,0 +1,125 @@
+/// Opcodes that can appear in place of a value type in an RDB file.
+///
+/// See [`RDB File Format`] for a description of each opcode.
+///
+/// [`RDB File Format`]: https://rdb.fnordig.de/file_format.html
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum RdbOpcode {
+    Function,
+    ModuleAux,
+    Idle,
+    Freq,
+    Aux,
+    ResizeDb,
+    ExpireTimeMs,
+    ExpireTime,
+    SelectDb,
+    Eof,
+}
+
+impl From<RdbOpcode> for u8 {
+    fn from(op: RdbOpcode) -> u8 {
+        match op {
+            RdbOpcode::Function => 0xF5,
+            RdbOpcode::ModuleAux => 0xF7,
+            RdbOpcode::Idle => 0xF8,
+            RdbOpcode::Freq => 0xF9,
+            RdbOpcode::Aux => 0xFA,
+            RdbOpcode::ResizeDb => 0xFB,
+            RdbOpcode::ExpireTimeMs => 0xFC,
+            RdbOpcode::ExpireTime => 0xFD,
+            RdbOpcode::SelectDb => 0xFE,
+            RdbOpcode::Eof => 0xFF,
+        }
+    }
+}
+
+impl TryFrom<u8> for RdbOpcode {
+    type Error = ();
+
+    fn try_from(b: u8) -> Result<Self, Self::Error> {
+        match b {
+            0xF5 => Ok(RdbOpcode::Function),
+            0xF7 => Ok(RdbOpcode::ModuleAux),
+            0xF8 => Ok(RdbOpcode::Idle),
+            0xF9 => Ok(RdbOpcode::Freq),
+            0xFA => Ok(RdbOpcode::Aux),
+            0xFB => Ok(RdbOpcode::ResizeDb),
+            0xFC => Ok(RdbOpcode::ExpireTimeMs),
+            0xFD => Ok(RdbOpcode::ExpireTime),
+            0xFE => Ok(RdbOpcode::SelectDb),
+            0xFF => Ok(RdbOpcode::Eof),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The type byte written in front of every key-value pair.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum RdbValueType {
+    String,
+    List,
+    Set,
+    SortedSet,
+    Hash,
+    SortedSet2,
+    HashZipmap,
+    ListZiplist,
+    SetIntset,
+    SortedSetZiplist,
+    HashZiplist,
+    ListQuicklist,
+    HashListpack,
+    SortedSetListpack,
+    ListQuicklist2,
+    SetListpack,
+}
+
+impl From<RdbValueType> for u8 {
+    fn from(t: RdbValueType) -> u8 {
+        match t {
+            RdbValueType::String => 0,
+            RdbValueType::List => 1,
+            RdbValueType::Set => 2,
+            RdbValueType::SortedSet => 3,
+            RdbValueType::Hash => 4,
+            RdbValueType::SortedSet2 => 5,
+            RdbValueType::HashZipmap => 9,
+            RdbValueType::ListZiplist => 10,
+            RdbValueType::SetIntset => 11,
+            RdbValueType::SortedSetZiplist => 12,
+            RdbValueType::HashZiplist => 13,
+            RdbValueType::ListQuicklist => 14,
+            RdbValueType::HashListpack => 16,
+            RdbValueType::SortedSetListpack => 17,
+            RdbValueType::ListQuicklist2 => 18,
+            RdbValueType::SetListpack => 20,
+        }
+    }
+}
+
+impl TryFrom<u8> for RdbValueType {
+    type Error = ();
+
+    fn try_from(b: u8) -> Result<Self, Self::Error> {
+        match b {
+            0 => Ok(RdbValueType::String),
+            1 => Ok(RdbValueType::List),
+            2 => Ok(RdbValueType::Set),
+            3 => Ok(RdbValueType::SortedSet),
+            4 => Ok(RdbValueType::Hash),
+            5 => Ok(RdbValueType::SortedSet2),
+            9 => Ok(RdbValueType::HashZipmap),
+            10 => Ok(RdbValueType::ListZiplist),
+            11 => Ok(RdbValueType::SetIntset),
+            12 => Ok(RdbValueType::SortedSetZiplist),
+            13 => Ok(RdbValueType::HashZiplist),
+            14 => Ok(RdbValueType::ListQuicklist),
+            16 => Ok(RdbValueType::HashListpack),
+            17 => Ok(RdbValueType::SortedSetListpack),
+            18 => Ok(RdbValueType::ListQuicklist2),
+            20 => Ok(RdbValueType::SetListpack),
+            _ => Err(()),
+        }
+    }
+}
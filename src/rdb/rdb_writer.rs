@@ -0,0 +1,265 @@
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+use crate::db::{Database, DatabaseSlot, DatabaseValue};
+use crate::rdb::{lzf, RdbOpcode, RdbValueType};
+use crate::util::Crc64Writer;
+
+const RDB_VERSION: &[u8] = b"0011";
+/// [`RDB_VERSION`] as stored in DUMP payloads.
+const DUMP_RDB_VERSION: u16 = 11;
+const REDIS_VERSION: &str = "7.2.0";
+/// Bytes written at once by [`write_rdb_file`], after each of which the progress is
+/// reported.
+const WRITE_CHUNK_SIZE: usize = 1024 * 1024;
+/// Strings up to this length are never compressed, same as in Redis.
+const LZF_MIN_LENGTH: usize = 20;
+
+#[derive(Error, Debug)]
+pub enum RdbWriterError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("value can not be stored in an RDB file: {0:?}")]
+    UnsupportedValue(&'static str),
+}
+
+pub struct RdbWriter<W>
+where
+    W: Write,
+{
+    writer: Crc64Writer<W>,
+}
+
+impl<W> RdbWriter<W>
+where
+    W: Write,
+{
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Crc64Writer::new(writer),
+        }
+    }
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+    /// Serializes the complete Database as RDB file with the Database stored as 'db0'.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if writing fails or the Database contains a value that has
+    /// no RDB representation, e.g. a nested aggregate.
+    ///
+    /// [`Err`]: std::result::Result::Err
+    pub fn write_database(&mut self, db: &Database) -> Result<(), RdbWriterError> {
+        self.writer.write_all(b"REDIS")?;
+        self.writer.write_all(RDB_VERSION)?;
+
+        let ctime = unix_time_ms() / 1000;
+        self.write_aux("redis-ver", REDIS_VERSION)?;
+        self.write_aux("redis-bits", &(usize::BITS).to_string())?;
+        self.write_aux("ctime", &ctime.to_string())?;
+
+        if !db.is_empty() {
+            let num_expires = db.iter().filter(|(_, s)| s.expires().is_some()).count();
+
+            self.write_opcode(RdbOpcode::SelectDb)?;
+            self.write_length(0)?;
+            self.write_opcode(RdbOpcode::ResizeDb)?;
+            self.write_length(db.len())?;
+            self.write_length(num_expires)?;
+
+            for (key, slot) in db.iter() {
+                self.write_entry(key, slot)?;
+            }
+        }
+
+        self.write_opcode(RdbOpcode::Eof)?;
+        let checksum = self.writer.checksum();
+        self.writer.write_all(&checksum.to_le_bytes())?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+    fn write_opcode(&mut self, op: RdbOpcode) -> Result<(), RdbWriterError> {
+        self.writer.write_all(&[u8::from(op)])?;
+        Ok(())
+    }
+    fn write_aux(&mut self, key: &str, value: &str) -> Result<(), RdbWriterError> {
+        self.write_opcode(RdbOpcode::Aux)?;
+        self.write_string(key.as_bytes())?;
+        self.write_string(value.as_bytes())
+    }
+    fn write_entry(&mut self, key: &str, slot: &DatabaseSlot) -> Result<(), RdbWriterError> {
+        if let Some(expires_ms) = slot.expires_unix_ms() {
+            self.write_opcode(RdbOpcode::ExpireTimeMs)?;
+            self.writer.write_all(&expires_ms.to_le_bytes())?;
+        }
+
+        let value = slot.value();
+        self.write_value_type(value)?;
+        self.write_string(key.as_bytes())?;
+        self.write_value(value)
+    }
+    fn write_value_type(&mut self, value: &DatabaseValue) -> Result<(), RdbWriterError> {
+        let value_type = match value {
+            DatabaseValue::Array(_) => RdbValueType::List,
+            DatabaseValue::Set(_) => RdbValueType::Set,
+            DatabaseValue::Map(_) => RdbValueType::Hash,
+            DatabaseValue::SortedSet(_) => RdbValueType::SortedSet2,
+            _ => RdbValueType::String,
+        };
+        self.writer.write_all(&[u8::from(value_type)])?;
+        Ok(())
+    }
+    fn write_value(&mut self, value: &DatabaseValue) -> Result<(), RdbWriterError> {
+        match value {
+            DatabaseValue::Array(list) => {
+                self.write_length(list.len())?;
+                for element in list {
+                    self.write_string_value(element)?;
+                }
+            }
+            DatabaseValue::Set(set) => {
+                self.write_length(set.len())?;
+                for member in set {
+                    self.write_string_value(member)?;
+                }
+            }
+            DatabaseValue::Map(map) => {
+                self.write_length(map.len())?;
+                for (field, value) in map {
+                    self.write_string_value(field)?;
+                    self.write_string_value(value)?;
+                }
+            }
+            DatabaseValue::SortedSet(zset) => {
+                self.write_length(zset.len())?;
+                for (member, score) in zset {
+                    self.write_string_value(member)?;
+                    self.writer.write_all(&score.to_le_bytes())?;
+                }
+            }
+            value => self.write_string_value(value)?,
+        }
+
+        Ok(())
+    }
+    /// Writes a length using the smallest possible RDB length encoding.
+    fn write_length(&mut self, len: usize) -> Result<(), RdbWriterError> {
+        if len < 1 << 6 {
+            self.writer.write_all(&[len as u8])?;
+        } else if len < 1 << 14 {
+            self.writer
+                .write_all(&[0x40 | (len >> 8) as u8, len as u8])?;
+        } else if let Ok(len) = u32::try_from(len) {
+            self.writer.write_all(&[0x80])?;
+            self.writer.write_all(&len.to_be_bytes())?;
+        } else {
+            self.writer.write_all(&[0x81])?;
+            self.writer.write_all(&(len as u64).to_be_bytes())?;
+        }
+        Ok(())
+    }
+    /// Writes a string as integer if it is the canonical representation of one,
+    /// LZF-compressed if that saves space or length-prefixed otherwise.
+    fn write_string(&mut self, bytes: &[u8]) -> Result<(), RdbWriterError> {
+        if let Some(int) = parse_canonical_int(bytes) {
+            if let Ok(int) = i8::try_from(int) {
+                self.writer.write_all(&[0xC0])?;
+                self.writer.write_all(&int.to_le_bytes())?;
+                return Ok(());
+            } else if let Ok(int) = i16::try_from(int) {
+                self.writer.write_all(&[0xC1])?;
+                self.writer.write_all(&int.to_le_bytes())?;
+                return Ok(());
+            } else if let Ok(int) = i32::try_from(int) {
+                self.writer.write_all(&[0xC2])?;
+                self.writer.write_all(&int.to_le_bytes())?;
+                return Ok(());
+            }
+        }
+
+        if bytes.len() > LZF_MIN_LENGTH {
+            let compressed = lzf::compress(bytes);
+            if compressed.len() < bytes.len() {
+                self.writer.write_all(&[0xC3])?;
+                self.write_length(compressed.len())?;
+                self.write_length(bytes.len())?;
+                self.writer.write_all(&compressed)?;
+                return Ok(());
+            }
+        }
+
+        self.write_length(bytes.len())?;
+        self.writer.write_all(bytes)?;
+        Ok(())
+    }
+    fn write_string_value(&mut self, value: &DatabaseValue) -> Result<(), RdbWriterError> {
+        match value.to_scalar_string() {
+            Some(s) => self.write_string(s.as_bytes()),
+            None => Err(RdbWriterError::UnsupportedValue("non-scalar value")),
+        }
+    }
+}
+
+/// Serializes a single value as payload of DUMP, which is its RDB encoding followed
+/// by the RDB version and a CRC64 checksum.
+pub fn dump_value(value: &DatabaseValue) -> Result<Vec<u8>, RdbWriterError> {
+    let mut writer = RdbWriter::new(Vec::new());
+    writer.write_value_type(value)?;
+    writer.write_value(value)?;
+    writer.writer.write_all(&DUMP_RDB_VERSION.to_le_bytes())?;
+    let checksum = writer.writer.checksum();
+    writer.writer.write_all(&checksum.to_le_bytes())?;
+    Ok(writer.into_inner())
+}
+
+/// Serializes the Database into an in-memory RDB file.
+pub fn dump_database(db: &Database) -> Result<Vec<u8>, RdbWriterError> {
+    let mut writer = RdbWriter::new(Vec::new());
+    writer.write_database(db)?;
+    Ok(writer.into_inner())
+}
+
+/// Writes an RDB file to a temporary file in the same directory and renames it to
+/// `path` afterwards, so a crash mid-write never leaves a truncated file behind.
+///
+/// `progress` is called with the number of bytes whenever a part was written.
+pub fn write_rdb_file(
+    path: &Path,
+    bytes: &[u8],
+    mut progress: impl FnMut(u64),
+) -> std::io::Result<()> {
+    let temp_path = path.with_file_name(format!("temp-{}.rdb", std::process::id()));
+
+    let result = std::fs::File::create(&temp_path).and_then(|mut file| {
+        for chunk in bytes.chunks(WRITE_CHUNK_SIZE) {
+            file.write_all(chunk)?;
+            progress(chunk.len() as u64);
+        }
+        file.sync_all()
+    });
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    std::fs::rename(&temp_path, path)
+}
+
+/// Parses `bytes` as integer if formatting the result yields the exact same bytes,
+/// so that e.g. "007" or "+1" are kept as strings.
+fn parse_canonical_int(bytes: &[u8]) -> Option<i64> {
+    let string = std::str::from_utf8(bytes).ok()?;
+    let int: i64 = string.parse().ok()?;
+    (int.to_string() == string).then_some(int)
+}
+
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
@@ -0,0 +1,63 @@
+use std::collections::VecDeque;
+
+/// Most recent part of the replication stream, which a replica that reconnects can
+/// continue from instead of requiring a full resynchronization.
+#[derive(Debug)]
+pub struct ReplicationBacklog {
+    data: VecDeque<u8>,
+    capacity: usize,
+}
+
+impl ReplicationBacklog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Number of bytes currently held.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+    /// Changes the capacity, discarding the oldest bytes that no longer fit.
+    pub fn resize(&mut self, capacity: usize) {
+        let overflow = self.data.len().saturating_sub(capacity);
+        self.data.drain(..overflow);
+        self.capacity = capacity;
+    }
+    /// Appends `frame`, discarding the oldest bytes once the capacity is exceeded.
+    pub fn feed(&mut self, frame: &[u8]) {
+        let frame = &frame[frame.len().saturating_sub(self.capacity)..];
+        let overflow = (self.data.len() + frame.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(frame);
+    }
+    /// Offset of the first byte held, given that the last byte is at `end_offset`.
+    ///
+    /// Offsets count from 1, so a replica which processed `n` bytes continues at `n + 1`.
+    pub fn first_byte_offset(&self, end_offset: u64) -> u64 {
+        end_offset + 1 - self.data.len() as u64
+    }
+    /// Bytes from `offset` up to `end_offset`, or [`None`] if they are no longer held.
+    pub fn since(&self, offset: u64, end_offset: u64) -> Option<Vec<u8>> {
+        let first = self.first_byte_offset(end_offset);
+        if offset < first || offset > end_offset + 1 {
+            return None;
+        }
+        Some(
+            self.data
+                .range((offset - first) as usize..)
+                .copied()
+                .collect(),
+        )
+    }
+}
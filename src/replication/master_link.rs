@@ -0,0 +1,152 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use bytes::{Buf, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use crate::command::Command;
+use crate::rdb::dump_database;
+use crate::replication::ReplicaInfo;
+use crate::resp::{is_incomplete, parse_resp_value, write_all_vectored, RespValue};
+use crate::server::{ConnectionContext, ServerState};
+
+/// Serves a connection which completed PSYNC as replica.
+///
+/// Replies to PSYNC with FULLRESYNC followed by the RDB snapshot in the form
+/// `$<len>\r\n<bytes>`, without a trailing CRLF, or with CONTINUE followed by the part
+/// of the backlog the replica is missing. That is followed by the replication stream
+/// until the connection is closed.
+pub async fn serve_replica(
+    read_half: &mut (impl AsyncRead + Unpin),
+    write_half: &mut (impl AsyncWrite + Unpin),
+    addr: SocketAddr,
+    ctx: &ConnectionContext,
+    state: &Arc<ServerState>,
+) -> anyhow::Result<()> {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let queued = Arc::new(AtomicUsize::new(0));
+    let listening_port = ctx.listening_port.unwrap_or(addr.port());
+    let id = state.replication.next_replica_id();
+
+    // NOTE: Registering the replica while the snapshot or backlog is taken ensures that
+    //       every write is either part of it or of the replication stream. The offset
+    //       sent with FULLRESYNC is read under the same lock, so it matches the snapshot.
+    let (header, body) = {
+        let mut replicas = state.replication.lock_replicas();
+        let replication = &state.replication;
+        let replid = replication.replid();
+        // NOTE: The backlog may have moved past the offset since PSYNC, which falls
+        //       back to a full resynchronization.
+        let backlog = ctx
+            .psync_offset
+            .and_then(|offset| replication.backlog_since(offset));
+        let preamble = match backlog {
+            Some(backlog) => (format!("+CONTINUE {replid}\r\n").into_bytes(), backlog),
+            None => {
+                let offset = replication.repl_offset.load(Ordering::Relaxed);
+                let snapshot = dump_database(&state.db.lock().unwrap())?;
+                let header = format!("+FULLRESYNC {replid} {offset}\r\n${}\r\n", snapshot.len());
+                (header.into_bytes(), snapshot)
+            }
+        };
+        replicas.push(ReplicaInfo {
+            id,
+            ip: addr.ip(),
+            listening_port,
+            sender,
+            queued: queued.clone(),
+            ack_offset: 0,
+        });
+        preamble
+    };
+    println!("Replica {}:{listening_port} synchronized", addr.ip());
+
+    // NOTE: The header and the snapshot are written together without copying them into
+    //       one buffer first.
+    let preamble = [header.as_slice(), body.as_slice()];
+    let result = stream_to_replica(
+        read_half,
+        write_half,
+        &preamble,
+        &mut receiver,
+        &queued,
+        |queued| state.replication.exceeds_output_buffer_limit(queued),
+        |offset| state.replication.record_ack(id, offset),
+    )
+    .await;
+
+    state.replication.remove_replica(id);
+    println!(
+        "Connection with replica {}:{listening_port} lost",
+        addr.ip()
+    );
+    result
+}
+
+async fn stream_to_replica(
+    read_half: &mut (impl AsyncRead + Unpin),
+    write_half: &mut (impl AsyncWrite + Unpin),
+    preamble: &[&[u8]],
+    receiver: &mut UnboundedReceiver<Bytes>,
+    queued: &AtomicUsize,
+    exceeds_limit: impl Fn(usize) -> bool,
+    on_ack: impl Fn(u64),
+) -> anyhow::Result<()> {
+    write_all_vectored(write_half, preamble).await?;
+
+    let mut buffer = BytesMut::new();
+    loop {
+        tokio::select! {
+            frame = receiver.recv() => {
+                let Some(frame) = frame else {
+                    return Ok(());
+                };
+                // NOTE: An empty frame is sent once the replica exceeded the limit.
+                if frame.is_empty() || exceeds_limit(queued.load(Ordering::Acquire)) {
+                    return Err(anyhow!("Replica exceeded the output buffer limit"));
+                }
+                write_half.write_all(&frame).await?;
+                queued.fetch_sub(frame.len(), Ordering::AcqRel);
+            }
+            read = read_half.read_buf(&mut buffer) => {
+                if read? == 0 {
+                    return Ok(());
+                }
+                let consumed = handle_acks(&buffer, &on_ack)?;
+                buffer.advance(consumed);
+            }
+        }
+    }
+}
+
+/// Handles the 'REPLCONF ACK <offset>' commands sent by the replica.
+///
+/// Returns the number of consumed bytes.
+fn handle_acks(buffer: &[u8], on_ack: impl Fn(u64)) -> anyhow::Result<usize> {
+    let mut input = buffer;
+    while !input.is_empty() {
+        let value;
+        (input, value) = match parse_resp_value(input) {
+            Ok(x) => x,
+            Err(e) if is_incomplete(&e) => break,
+            Err(e) => return Err(anyhow!("Invalid command from replica: {e}")),
+        };
+        let RespValue::Array(args) = value else {
+            continue;
+        };
+        if let Ok(Command::ReplConf(options)) = Command::try_from(args) {
+            for (option, value) in options {
+                if !option.eq_ignore_ascii_case("ack") {
+                    continue;
+                }
+                if let Ok(offset) = value.parse() {
+                    on_ack(offset);
+                }
+            }
+        }
+    }
+    Ok(buffer.len() - input.len())
+}
@@ -0,0 +1,9 @@
+mod backlog;
+mod master_link;
+mod replica_link;
+mod replication_state;
+
+pub use backlog::ReplicationBacklog;
+pub use master_link::serve_replica;
+pub use replica_link::run_replica_link;
+pub use replication_state::{MasterLinkState, ReplicaInfo, ReplicationIds, ReplicationState};
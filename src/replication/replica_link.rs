@@ -0,0 +1,229 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::command::dispatch;
+use crate::rdb::RdbReader;
+use crate::replication::MasterLinkState;
+use crate::resp::{is_incomplete, parse_resp_value, RespValue};
+use crate::server::{ClientKind, ConnectionContext, ServerState};
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keeps the connection to the master alive, reconnecting whenever it is lost.
+///
+/// Stops once the master is changed, which starts a link with a new `generation`.
+pub async fn run_replica_link(state: Arc<ServerState>, generation: u64) {
+    let changed = state.replication.link_changed.notified();
+    tokio::pin!(changed);
+    // NOTE: Registering for the notification before checking the generation ensures
+    //       that a change in between is not missed.
+    changed.as_mut().enable();
+
+    while let Some((host, port)) = state.replication.master_for_link(generation) {
+        println!("Connecting to MASTER {host}:{port}");
+        tokio::select! {
+            result = replicate(&state, &host, port, generation) => {
+                if let Err(e) = result {
+                    eprintln!("Error replicating from MASTER {host}:{port}: {e}");
+                }
+            }
+            _ = &mut changed => break,
+        }
+        if state.replication.master_for_link(generation).is_none() {
+            break;
+        }
+        state
+            .replication
+            .set_master_link(MasterLinkState::Connecting);
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+async fn replicate(
+    state: &Arc<ServerState>,
+    host: &str,
+    port: u16,
+    generation: u64,
+) -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect((host, port)).await?;
+    let mut buffer = BytesMut::new();
+
+    let (listening_port, masterauth) = {
+        let config = state.config();
+        (config.port.to_string(), config.masterauth.clone())
+    };
+    let auth = ["AUTH", masterauth.as_str()];
+    let handshake: [(&[&str], &str); 4] = [
+        (&auth, "OK"),
+        (&["PING"], "PONG"),
+        (&["REPLCONF", "listening-port", &listening_port], "OK"),
+        (&["REPLCONF", "capa", "psync2"], "OK"),
+    ];
+    // NOTE: AUTH is only sent if the master requires a password.
+    let handshake = handshake
+        .into_iter()
+        .skip(usize::from(masterauth.is_empty()));
+    for (command, expected) in handshake {
+        let reply = request(&mut stream, &mut buffer, command).await?;
+        if !reply.eq_ignore_ascii_case(expected) {
+            return Err(anyhow!("Unexpected reply to {}: {reply:?}", command[0]));
+        }
+    }
+
+    // NOTE: Without any history there is nothing to continue from, so a full
+    //       resynchronization is requested right away.
+    let replication = &state.replication;
+    let (replid, offset) = match replication.repl_offset.load(Ordering::Relaxed) {
+        0 => (String::from("?"), String::from("-1")),
+        offset => (replication.replid(), (offset + 1).to_string()),
+    };
+    let reply = request(&mut stream, &mut buffer, &["PSYNC", &replid, &offset]).await?;
+    let mut parts = reply.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("FULLRESYNC"), Some(replid), Some(offset)) => {
+            let offset: u64 = offset
+                .parse()
+                .map_err(|_| anyhow!("Invalid offset in reply to PSYNC: {reply:?}"))?;
+            println!("Full resync from MASTER with replication ID {replid} at offset {offset}");
+            replication.set_master_link(MasterLinkState::Syncing);
+
+            let snapshot = read_snapshot(&mut stream, &mut buffer).await?;
+            let mut rdb = RdbReader::new(&snapshot).read()?;
+
+            // NOTE: Own replicas have to resynchronize as well, since the history changed.
+            let mut replicas = replication.lock_replicas();
+            if replication.master_for_link(generation).is_none() {
+                return Ok(());
+            }
+            // NOTE: Only a single logical database is supported, so everything but 'db0' is dropped.
+            *state.db.lock().unwrap() = rdb.databases.remove(&0).unwrap_or_default();
+            replication.reset_history(replid.to_string(), offset);
+            replicas.clear();
+            println!(
+                "Loaded {} bytes of RDB snapshot from MASTER",
+                snapshot.len()
+            );
+        }
+        (Some("CONTINUE"), replid, None) => {
+            println!("Partial resync from MASTER at offset {offset}");
+            if let Some(replid) = replid.filter(|&id| id != replication.replid()) {
+                replication.shift_replid(replid.to_string());
+            }
+        }
+        _ => return Err(anyhow!("Unexpected reply to PSYNC: {reply:?}")),
+    }
+    replication.set_master_link(MasterLinkState::Connected);
+
+    apply_commands(state, &mut stream, &mut buffer, generation).await
+}
+
+/// Reads the RDB snapshot the master sends as `$<len>\r\n<bytes>`, without trailing CRLF.
+async fn read_snapshot(stream: &mut TcpStream, buffer: &mut BytesMut) -> anyhow::Result<Vec<u8>> {
+    loop {
+        if let Some(end) = buffer.windows(2).position(|w| w == b"\r\n") {
+            let len: usize = std::str::from_utf8(&buffer[..end])
+                .ok()
+                .and_then(|header| header.strip_prefix('$'))
+                .and_then(|len| len.parse().ok())
+                .ok_or_else(|| anyhow!("Invalid RDB snapshot header from MASTER"))?;
+
+            let start = end + 2;
+            if buffer.len() >= start + len {
+                buffer.advance(start);
+                return Ok(buffer.split_to(len).to_vec());
+            }
+        }
+        if stream.read_buf(buffer).await? == 0 {
+            return Err(anyhow!("MASTER closed the connection"));
+        }
+    }
+}
+
+/// Applies the commands streamed by the master and forwards them to own replicas,
+/// counting every processed byte in the replication offset.
+async fn apply_commands(
+    state: &Arc<ServerState>,
+    stream: &mut TcpStream,
+    buffer: &mut BytesMut,
+    generation: u64,
+) -> anyhow::Result<()> {
+    let mut ctx = ConnectionContext::new(ClientKind::Master);
+    let replication = &state.replication;
+
+    loop {
+        while !buffer.is_empty() {
+            // NOTE: Holding the replica lock from applying a command until it is forwarded
+            //       keeps new replicas consistent, and checking the generation under it
+            //       ensures that nothing is applied once the master was changed.
+            let (consumed, response) = {
+                let replicas = replication.lock_replicas();
+                if replication.master_for_link(generation).is_none() {
+                    return Ok(());
+                }
+                let (consumed, response) = match parse_resp_value(buffer) {
+                    Ok((rest, value)) => {
+                        let consumed = buffer.len() - rest.len();
+                        let response = dispatch(state, &mut ctx, value, &buffer[..consumed]);
+                        (consumed, response.to_string())
+                    }
+                    Err(e) if is_incomplete(&e) => break,
+                    Err(e) => return Err(anyhow!("Invalid command from MASTER: {e}")),
+                };
+                replication.propagate(&replicas, &buffer[..consumed]);
+                (consumed, response)
+            };
+            // NOTE: The master does not expect replies to the commands it sends, except
+            //       for the ACKs it requests.
+            if std::mem::take(&mut ctx.force_reply) {
+                stream.write_all(response.as_bytes()).await?;
+            }
+            buffer.advance(consumed);
+        }
+
+        if stream.read_buf(buffer).await? == 0 {
+            return Err(anyhow!("MASTER closed the connection"));
+        }
+    }
+}
+
+/// Sends `command` to the master and waits for its simple string reply.
+async fn request(
+    stream: &mut TcpStream,
+    buffer: &mut BytesMut,
+    command: &[&str],
+) -> anyhow::Result<String> {
+    let args = command
+        .iter()
+        .map(|arg| RespValue::BulkString((*arg).into()))
+        .collect();
+    stream
+        .write_all(RespValue::Array(args).to_string().as_bytes())
+        .await?;
+
+    loop {
+        match parse_resp_value(buffer) {
+            Ok((rest, value)) => {
+                let consumed = buffer.len() - rest.len();
+                let reply = match value {
+                    RespValue::SimpleString(reply) => Ok(reply.to_string()),
+                    RespValue::SimpleError(e) => Err(anyhow!("MASTER replied with error: {e}")),
+                    value => Err(anyhow!("Unexpected reply from MASTER: {value:?}")),
+                };
+                buffer.advance(consumed);
+                return reply;
+            }
+            Err(e) if is_incomplete(&e) => {
+                if stream.read_buf(buffer).await? == 0 {
+                    return Err(anyhow!("MASTER closed the connection"));
+                }
+            }
+            Err(e) => return Err(anyhow!("Invalid reply from MASTER: {e}")),
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bytes::Bytes;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::Notify;
+
+use crate::replication::ReplicationBacklog;
+use crate::resp::RespValue;
+
+use crate::util::random_hex_id;
+
+/// Replication ID reported when there is no secondary one.
+const NO_REPLID: &str = "0000000000000000000000000000000000000000";
+
+/// Replica connected to this server.
+#[derive(Debug)]
+pub struct ReplicaInfo {
+    pub id: u64,
+    pub ip: IpAddr,
+    pub listening_port: u16,
+    /// Replication stream of the replica, buffered until its connection can take it.
+    pub sender: UnboundedSender<Bytes>,
+    /// Bytes sent to `sender` which were not written to the connection yet.
+    pub queued: Arc<AtomicUsize>,
+    /// Offset of the replication stream the replica last acknowledged.
+    pub ack_offset: u64,
+}
+
+/// State of the link from a replica to its master.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum MasterLinkState {
+    /// Not connected, or the handshake has not completed yet.
+    #[default]
+    Connecting,
+    /// The master accepted PSYNC and the snapshot is being transferred.
+    Syncing,
+    /// The snapshot is loaded and the command stream is applied.
+    Connected,
+}
+
+/// Identifies the history of the dataset, together with the replication offset.
+#[derive(Debug, Clone)]
+pub struct ReplicationIds {
+    pub replid: String,
+    /// ID of the master this server replicated from before it was promoted, which is
+    /// still accepted for partial resynchronizations up to `second_replid_offset`.
+    pub replid2: String,
+    pub second_replid_offset: Option<u64>,
+}
+
+/// Replication role of the server and the state of its link to the master.
+#[derive(Debug)]
+pub struct ReplicationState {
+    /// Host and port of the master if this server is a replica.
+    master: Mutex<Option<(String, u16)>>,
+    /// Incremented whenever the master changes, which stops the link to the previous one.
+    link_generation: AtomicU64,
+    /// Notified whenever the master changes.
+    pub link_changed: Notify,
+    master_link: Mutex<MasterLinkState>,
+    ids: Mutex<ReplicationIds>,
+    /// Number of bytes of the replication stream produced so far, or on a replica
+    /// received from the master.
+    pub repl_offset: AtomicU64,
+    backlog: Mutex<ReplicationBacklog>,
+    pub replicas: Mutex<Vec<ReplicaInfo>>,
+    /// Notified whenever a replica acknowledges an offset.
+    pub ack_notify: Notify,
+    next_replica_id: AtomicU64,
+    /// Bytes which may be queued for a replica, see [`ReplicationState::propagate`].
+    output_buffer_limit: AtomicUsize,
+}
+
+impl ReplicationState {
+    pub fn new(master: Option<(String, u16)>, backlog_size: usize) -> Self {
+        Self {
+            master: Mutex::new(master),
+            link_generation: AtomicU64::new(0),
+            link_changed: Notify::new(),
+            master_link: Mutex::default(),
+            ids: Mutex::new(ReplicationIds {
+                replid: random_hex_id(40),
+                replid2: String::from(NO_REPLID),
+                second_replid_offset: None,
+            }),
+            repl_offset: AtomicU64::new(0),
+            backlog: Mutex::new(ReplicationBacklog::new(backlog_size)),
+            replicas: Mutex::new(Vec::new()),
+            ack_notify: Notify::new(),
+            next_replica_id: AtomicU64::new(0),
+            output_buffer_limit: AtomicUsize::new(0),
+        }
+    }
+    pub fn master(&self) -> Option<(String, u16)> {
+        self.master.lock().unwrap().clone()
+    }
+    pub fn is_replica(&self) -> bool {
+        self.master.lock().unwrap().is_some()
+    }
+    pub fn role(&self) -> &'static str {
+        if self.is_replica() {
+            "slave"
+        } else {
+            "master"
+        }
+    }
+    pub fn link_generation(&self) -> u64 {
+        self.link_generation.load(Ordering::Acquire)
+    }
+    /// Master the link started at `generation` replicates from, or [`None`] once the
+    /// master was changed and the link has to stop.
+    pub fn master_for_link(&self, generation: u64) -> Option<(String, u16)> {
+        let master = self.master.lock().unwrap();
+        if self.link_generation() != generation {
+            return None;
+        }
+        master.clone()
+    }
+    /// Starts replicating from `host` and `port`, returning the generation of the new link.
+    ///
+    /// Connected replicas are dropped, since the history they replicate is replaced.
+    pub fn set_master(&self, host: String, port: u16) -> u64 {
+        let mut replicas = self.lock_replicas();
+        replicas.clear();
+        *self.master.lock().unwrap() = Some((host, port));
+        self.set_master_link(MasterLinkState::Connecting);
+        let generation = self.link_generation.fetch_add(1, Ordering::AcqRel) + 1;
+        self.link_changed.notify_waiters();
+        generation
+    }
+    /// Turns a replica into a master which continues the history of its former master.
+    ///
+    /// The ID of the former master is kept as secondary ID, so that its other replicas
+    /// can continue with a partial resynchronization.
+    pub fn promote(&self) {
+        let mut replicas = self.lock_replicas();
+        replicas.clear();
+        *self.master.lock().unwrap() = None;
+        self.link_generation.fetch_add(1, Ordering::AcqRel);
+        self.shift_replid(random_hex_id(40));
+        self.link_changed.notify_waiters();
+    }
+    pub fn master_link(&self) -> MasterLinkState {
+        *self.master_link.lock().unwrap()
+    }
+    pub fn set_master_link(&self, link: MasterLinkState) {
+        *self.master_link.lock().unwrap() = link;
+    }
+    pub fn ids(&self) -> ReplicationIds {
+        self.ids.lock().unwrap().clone()
+    }
+    pub fn replid(&self) -> String {
+        self.ids.lock().unwrap().replid.clone()
+    }
+    /// Adopts the history of a master after a full resynchronization at `offset`.
+    pub fn reset_history(&self, replid: String, offset: u64) {
+        let mut backlog = self.backlog.lock().unwrap();
+        *self.ids.lock().unwrap() = ReplicationIds {
+            replid,
+            replid2: String::from(NO_REPLID),
+            second_replid_offset: None,
+        };
+        backlog.clear();
+        self.repl_offset.store(offset, Ordering::Relaxed);
+    }
+    /// Switches to a new replication ID and forgets the previous ones, so that no
+    /// replica can continue its stream with a partial resynchronization.
+    pub fn change_replid(&self) {
+        *self.ids.lock().unwrap() = ReplicationIds {
+            replid: random_hex_id(40),
+            replid2: String::from(NO_REPLID),
+            second_replid_offset: None,
+        };
+    }
+    /// Switches to a new replication ID, keeping the current one as secondary ID for
+    /// the history up to the current offset.
+    pub fn shift_replid(&self, replid: String) {
+        let offset = self.repl_offset.load(Ordering::Relaxed);
+        let mut ids = self.ids.lock().unwrap();
+        ids.replid2 = std::mem::replace(&mut ids.replid, replid);
+        ids.second_replid_offset = Some(offset + 1);
+    }
+    /// Whether a replica which last replicated `replid` can continue the stream at
+    /// `offset` from the backlog.
+    pub fn can_continue(&self, replid: &str, offset: u64) -> bool {
+        let backlog = self.backlog.lock().unwrap();
+        let ids = self.ids.lock().unwrap();
+        let end_offset = self.repl_offset.load(Ordering::Relaxed);
+
+        let known_history = replid == ids.replid
+            || (replid == ids.replid2
+                && ids
+                    .second_replid_offset
+                    .is_some_and(|second| offset <= second));
+        known_history && backlog.since(offset, end_offset).is_some()
+    }
+    /// Part of the replication stream starting at `offset`, if it is still in the backlog.
+    pub fn backlog_since(&self, offset: u64) -> Option<Vec<u8>> {
+        let backlog = self.backlog.lock().unwrap();
+        backlog.since(offset, self.repl_offset.load(Ordering::Relaxed))
+    }
+    /// Size, first byte offset and length of the backlog.
+    pub fn backlog_info(&self) -> (usize, u64, usize) {
+        let backlog = self.backlog.lock().unwrap();
+        let end_offset = self.repl_offset.load(Ordering::Relaxed);
+        (
+            backlog.capacity(),
+            backlog.first_byte_offset(end_offset),
+            backlog.len(),
+        )
+    }
+    pub fn resize_backlog(&self, capacity: usize) {
+        self.backlog.lock().unwrap().resize(capacity);
+    }
+    /// Sets the bytes which may be queued for a replica, zero disables the limit.
+    pub fn set_output_buffer_limit(&self, limit: usize) {
+        self.output_buffer_limit.store(limit, Ordering::Relaxed);
+    }
+    /// Whether more of the stream was queued for the replica than the limit allows.
+    pub fn exceeds_output_buffer_limit(&self, queued: usize) -> bool {
+        let limit = self.output_buffer_limit.load(Ordering::Relaxed);
+        limit != 0 && queued > limit
+    }
+    /// Locks the replica list, which also keeps writes from being propagated until
+    /// the guard is dropped.
+    pub fn lock_replicas(&self) -> MutexGuard<'_, Vec<ReplicaInfo>> {
+        self.replicas.lock().unwrap()
+    }
+    pub fn next_replica_id(&self) -> u64 {
+        self.next_replica_id.fetch_add(1, Ordering::Relaxed)
+    }
+    pub fn remove_replica(&self, id: u64) {
+        self.lock_replicas().retain(|replica| replica.id != id);
+    }
+    pub fn record_ack(&self, id: u64, offset: u64) {
+        let mut replicas = self.lock_replicas();
+        if let Some(replica) = replicas.iter_mut().find(|replica| replica.id == id) {
+            replica.ack_offset = offset;
+        }
+        self.ack_notify.notify_waiters();
+    }
+    /// Asks every replica to acknowledge the offset it has processed.
+    pub fn request_acks(&self) {
+        let frame = RespValue::Array(vec![
+            RespValue::BulkString("REPLCONF".into()),
+            RespValue::BulkString("GETACK".into()),
+            RespValue::BulkString("*".into()),
+        ]);
+        self.propagate(&self.lock_replicas(), frame.to_string().as_bytes());
+    }
+    /// Appends `frame` to the replication stream, which feeds the backlog and every
+    /// replica.
+    ///
+    /// On replicas this is the stream received from the master, which is forwarded as is.
+    ///
+    /// Replicas which can't keep up are not sent more than the output buffer limit.
+    /// Once they exceed it only an empty frame is sent, which tells their connection to
+    /// close, since the stream they would continue is incomplete.
+    pub fn propagate(&self, replicas: &[ReplicaInfo], frame: &[u8]) {
+        if frame.is_empty() {
+            return;
+        }
+        let mut backlog = self.backlog.lock().unwrap();
+        backlog.feed(frame);
+        self.repl_offset
+            .fetch_add(frame.len() as u64, Ordering::Relaxed);
+        drop(backlog);
+
+        let frame = Bytes::copy_from_slice(frame);
+        for replica in replicas {
+            let queued = replica.queued.fetch_add(frame.len(), Ordering::AcqRel);
+            let frame = if !self.exceeds_output_buffer_limit(queued + frame.len()) {
+                frame.clone()
+            } else if !self.exceeds_output_buffer_limit(queued) {
+                Bytes::new()
+            } else {
+                continue;
+            };
+            // NOTE: Sending only fails if the connection is gone, which removes the replica.
+            let _ = replica.sender.send(frame);
+        }
+    }
+}
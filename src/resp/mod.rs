@@ -4,8 +4,8 @@ mod resp_reader;
 mod resp_value;
 mod resp_writer;
 
-pub use parser::{parse_resp_value, ParseError};
+pub use parser::{is_incomplete, parse_resp_value, ParseError};
 pub use resp_data_type::RespDataType;
 pub use resp_reader::{RespReader, RespReaderError};
 pub use resp_value::RespValue;
-pub use resp_writer::RespWriter;
+pub use resp_writer::{write_all_vectored, write_reply, RespWriter};
@@ -5,7 +5,7 @@ use std::collections::{HashMap, HashSet};
 
 use nom::{
     branch::alt,
-    bytes::streaming::{is_not, tag, take},
+    bytes::streaming::{tag, take, take_till},
     character::streaming::{char, crlf, digit1, one_of},
     combinator::{map, map_res, opt, recognize, rest},
     multi::length_value,
@@ -39,6 +39,14 @@ impl<I> ParseError<I> {
     }
 }
 
+/// Whether parsing failed only because the input ends in the middle of a value.
+pub fn is_incomplete<I>(e: &nom::Err<ParseError<I>>) -> bool {
+    match e {
+        nom::Err::Incomplete(_) => true,
+        nom::Err::Error(e) | nom::Err::Failure(e) => e.incomplete(),
+    }
+}
+
 impl<I> From<ParseError<I>> for nom::Err<ParseError<I>> {
     fn from(e: ParseError<I>) -> Self {
         nom::Err::Error(e)
@@ -47,7 +55,10 @@ impl<I> From<ParseError<I>> for nom::Err<ParseError<I>> {
 
 impl<I> From<nom::Err<ParseError<I>>> for ParseError<I> {
     fn from(e: nom::Err<ParseError<I>>) -> Self {
-        e.into()
+        match e {
+            nom::Err::Error(e) | nom::Err::Failure(e) => e,
+            nom::Err::Incomplete(needed) => ParseError::Nom(nom::Err::Incomplete(needed)),
+        }
     }
 }
 
@@ -63,7 +74,7 @@ impl<I> nom::error::ParseError<I> for ParseError<I> {
 type ParseResult<I, O> = IResult<I, O, ParseError<I>>;
 
 fn line(input: &[u8]) -> ParseResult<&[u8], &[u8]> {
-    terminated(is_not("\r\n"), crlf)(input)
+    terminated(take_till(|b| b == b'\r' || b == b'\n'), crlf)(input)
 }
 fn length_bytes(input: &[u8]) -> ParseResult<&[u8], &[u8]> {
     terminated(length_value(parse_usize, rest), crlf)(input)
@@ -93,30 +104,33 @@ where
     }
 }
 
-fn parse_null(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_null(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     let (input, _) = crlf(input)?;
 
     Ok((input, RespValue::Null))
 }
-fn parse_boolean(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_boolean(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     let (input, b) = terminated(one_of("tf"), crlf)(input)?;
 
     Ok((input, RespValue::Boolean(b == 't')))
 }
 
-fn parse_simple_string(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_simple_string(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     map(map_cow(line), RespValue::SimpleString)(input)
 }
-fn parse_simple_error(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_simple_error(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     map(map_cow(line), RespValue::SimpleError)(input)
 }
-fn parse_bulk_string(input: &[u8]) -> ParseResult<&[u8], RespValue> {
-    map(map_cow(length_bytes), RespValue::BulkString)(input)
+fn parse_bulk_string(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
+    alt((
+        map(tag("-1\r\n"), |_| RespValue::NullBulkString),
+        map(map_cow(length_bytes), RespValue::BulkString),
+    ))(input)
 }
-fn parse_bulk_error(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_bulk_error(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     map(map_cow(length_bytes), RespValue::BulkError)(input)
 }
-fn parse_verbatim_string(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_verbatim_string(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     let (input, bytes) = length_bytes(input)?;
 
     let (_, (bytes_enc, _, bytes_string)) = tuple((take(3u8), char(':'), rest))(bytes)?;
@@ -130,7 +144,7 @@ fn parse_verbatim_string(input: &[u8]) -> ParseResult<&[u8], RespValue> {
     ))
 }
 
-fn parse_integer(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_integer(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     map(parse_i64, RespValue::Integer)(input)
 }
 
@@ -171,7 +185,7 @@ fn parse_i64(input: &[u8]) -> ParseResult<&[u8], i64> {
     Ok((input, int))
 }
 
-fn parse_big_number(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_big_number(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     let (input, big_number_bytes) = recognize(pair(opt(one_of("+-")), digit1))(input)?;
     let (input, _) = crlf(input)?;
 
@@ -181,7 +195,7 @@ fn parse_big_number(input: &[u8]) -> ParseResult<&[u8], RespValue> {
     Ok((input, RespValue::BigNumber(big_number.into())))
 }
 
-fn parse_double(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_double(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     let (input, double_bytes) = recognize(tuple((
         opt(one_of("+-")),
         digit1,
@@ -200,10 +214,17 @@ fn parse_double(input: &[u8]) -> ParseResult<&[u8], RespValue> {
     Ok((input, RespValue::Double(double)))
 }
 
-fn parse_array_internal(input: &[u8]) -> ParseResult<&[u8], Vec<RespValue>> {
+/// Number of elements to allocate room for upfront, which is bounded by the input
+/// since every element takes at least three bytes, so that a huge announced length
+/// can't exhaust the memory.
+fn initial_capacity(len: usize, input: &[u8]) -> usize {
+    len.min(input.len() / 3)
+}
+
+fn parse_array_internal(input: &[u8]) -> ParseResult<&[u8], Vec<RespValue<'_>>> {
     let (mut input, len) = parse_usize(input)?;
 
-    let mut vec = Vec::with_capacity(len);
+    let mut vec = Vec::with_capacity(initial_capacity(len, input));
     for _ in 0..len {
         let value;
         (input, value) = parse_resp_value(input)?;
@@ -213,20 +234,20 @@ fn parse_array_internal(input: &[u8]) -> ParseResult<&[u8], Vec<RespValue>> {
     Ok((input, vec))
 }
 
-fn parse_array(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_array(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     let (input, vec) = parse_array_internal(input)?;
     Ok((input, RespValue::Array(vec)))
 }
 
-fn parse_push(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_push(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     let (input, vec) = parse_array_internal(input)?;
     Ok((input, RespValue::Push(vec)))
 }
 
-fn parse_set(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_set(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     let (mut input, len) = parse_usize(input)?;
 
-    let mut set = HashSet::with_capacity(len);
+    let mut set = HashSet::with_capacity(initial_capacity(len, input));
     for _ in 0..len {
         let value;
         (input, value) = parse_resp_value(input)?;
@@ -236,10 +257,10 @@ fn parse_set(input: &[u8]) -> ParseResult<&[u8], RespValue> {
     Ok((input, RespValue::Set(set)))
 }
 
-fn parse_map(input: &[u8]) -> ParseResult<&[u8], RespValue> {
+fn parse_map(input: &[u8]) -> ParseResult<&[u8], RespValue<'_>> {
     let (mut input, len) = parse_usize(input)?;
 
-    let mut map = HashMap::with_capacity(len);
+    let mut map = HashMap::with_capacity(initial_capacity(len, input) / 2);
     for _ in 0..len {
         let (key, value);
         (input, key) = parse_resp_value(input)?;
@@ -270,4 +291,3 @@ pub fn parse_resp_value<'b, 'a: 'b>(input: &'a [u8]) -> ParseResult<&'b [u8], Re
         _ => unreachable!(),
     }
 }
-
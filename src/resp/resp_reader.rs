@@ -85,10 +85,10 @@ where
     }
     fn next_boxed(
         &mut self,
-    ) -> Pin<Box<dyn Future<Output = Result<RespValue, RespReaderError>> + Send + '_>> {
+    ) -> Pin<Box<dyn Future<Output = Result<RespValue<'_>, RespReaderError>> + Send + '_>> {
         Box::pin(async move { self.next().await })
     }
-    pub async fn next(&mut self) -> Result<RespValue, RespReaderError> {
+    pub async fn next(&mut self) -> Result<RespValue<'_>, RespReaderError> {
         let first_byte = self
             .buffer
             .next()
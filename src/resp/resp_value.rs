@@ -12,6 +12,8 @@ pub enum RespValue<'a> {
     BigNumber(Cow<'a, str>),
     SimpleString(Cow<'a, str>),
     BulkString(Cow<'a, str>),
+    /// Nil of RESP2, which is encoded as a bulk string of length -1.
+    NullBulkString,
     VerbatimString((Cow<'a, str>, Cow<'a, str>)),
     SimpleError(Cow<'a, str>),
     BulkError(Cow<'a, str>),
@@ -21,6 +23,43 @@ pub enum RespValue<'a> {
     Push(Vec<RespValue<'a>>),
 }
 
+impl<'a> RespValue<'a> {
+    /// Copies the borrowed strings, so that the value outlives the buffer it was
+    /// parsed from.
+    pub fn into_owned(self) -> RespValue<'static> {
+        let owned = |s: Cow<'a, str>| Cow::Owned(s.into_owned());
+        match self {
+            RespValue::Null => RespValue::Null,
+            RespValue::Boolean(b) => RespValue::Boolean(b),
+            RespValue::Integer(i) => RespValue::Integer(i),
+            RespValue::Double(d) => RespValue::Double(d),
+            RespValue::BigNumber(i) => RespValue::BigNumber(owned(i)),
+            RespValue::SimpleString(s) => RespValue::SimpleString(owned(s)),
+            RespValue::BulkString(s) => RespValue::BulkString(owned(s)),
+            RespValue::NullBulkString => RespValue::NullBulkString,
+            RespValue::VerbatimString((enc, s)) => {
+                RespValue::VerbatimString((owned(enc), owned(s)))
+            }
+            RespValue::SimpleError(e) => RespValue::SimpleError(owned(e)),
+            RespValue::BulkError(e) => RespValue::BulkError(owned(e)),
+            RespValue::Array(arr) => {
+                RespValue::Array(arr.into_iter().map(RespValue::into_owned).collect())
+            }
+            RespValue::Map(map) => RespValue::Map(
+                map.into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect(),
+            ),
+            RespValue::Set(set) => {
+                RespValue::Set(set.into_iter().map(RespValue::into_owned).collect())
+            }
+            RespValue::Push(arr) => {
+                RespValue::Push(arr.into_iter().map(RespValue::into_owned).collect())
+            }
+        }
+    }
+}
+
 impl<'a> From<&RespValue<'a>> for RespDataType {
     fn from(v: &RespValue<'a>) -> Self {
         match v {
@@ -31,7 +70,7 @@ impl<'a> From<&RespValue<'a>> for RespDataType {
             RespValue::BigNumber(_) => RespDataType::BigNumber,
             RespValue::SimpleString(_) => RespDataType::SimpleString,
             RespValue::SimpleError(_) => RespDataType::SimpleError,
-            RespValue::BulkString(_) => RespDataType::BulkString,
+            RespValue::BulkString(_) | RespValue::NullBulkString => RespDataType::BulkString,
             RespValue::BulkError(_) => RespDataType::BulkError,
             RespValue::VerbatimString(_) => RespDataType::VerbatimString,
             RespValue::Array(_) => RespDataType::Array,
@@ -55,15 +94,18 @@ impl<'a> PartialEq for RespValue<'a> {
             // TODO: Should different strings also be comparable?
             (RespValue::SimpleString(s1), RespValue::SimpleString(s2)) => s1 == s2,
             (RespValue::BulkString(s1), RespValue::BulkString(s2)) => s1 == s2,
+            (RespValue::NullBulkString, RespValue::NullBulkString) => true,
             (RespValue::VerbatimString((e1, s1)), RespValue::VerbatimString((e2, s2))) => {
                 e1 == e2 && s1 == s2
             }
             (RespValue::SimpleError(e1), RespValue::SimpleError(e2)) => e1 == e2,
             (RespValue::BulkError(e1), RespValue::BulkError(e2)) => e1 == e2,
-            (RespValue::Array(arr1), RespValue::Array(arr2)) => {
+            (RespValue::Array(arr1), RespValue::Array(arr2))
+            | (RespValue::Push(arr1), RespValue::Push(arr2)) => {
                 (arr1.len() == arr2.len()) && arr1.iter().zip(arr2.iter()).all(|(e1, e2)| e1 == e2)
             }
-            // TODO: Implement Set and Map Equals
+            (RespValue::Set(set1), RespValue::Set(set2)) => set1 == set2,
+            (RespValue::Map(map1), RespValue::Map(map2)) => map1 == map2,
             _ => false,
         }
     }
@@ -84,8 +126,9 @@ impl<'a> std::hash::Hash for RespValue<'a> {
             RespValue::VerbatimString(s) => s.hash(state),
             RespValue::SimpleError(e) => e.hash(state),
             RespValue::BulkError(e) => e.hash(state),
-            RespValue::Array(vec) => Self::hash_slice(vec, state),
-            // TODO: Implement Set and Map Equals
+            RespValue::Array(vec) | RespValue::Push(vec) => Self::hash_slice(vec, state),
+            // NOTE: Sets and maps are unordered, so their elements can't be hashed in
+            //       order, and hashing nothing is consistent with any equality.
             _ => {}
         }
     }
@@ -109,6 +152,7 @@ impl<'a> std::fmt::Display for RespValue<'a> {
             RespValue::BulkString(s) | RespValue::BulkError(s) => {
                 write!(f, "{first_byte}{}\r\n{s}\r\n", s.len())
             }
+            RespValue::NullBulkString => write!(f, "{first_byte}-1\r\n"),
             RespValue::VerbatimString((enc, s)) => {
                 write!(f, "{first_byte}{}\r\n{}:{s}\r\n", 3 + 1 + s.len(), enc)
             }
@@ -1,8 +1,12 @@
+use std::fmt::Write;
+use std::io::IoSlice;
 use std::marker::Unpin;
+use std::ops::Range;
 
-use tokio::io::AsyncWriteExt;
+use bytes::BytesMut;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
-use crate::RespValue;
+use crate::{RespDataType, RespValue};
 
 // TODO: Use BytesMut as underlying Buffer, eliminating the allocation on each write?
 //       What would happen if multiple 'write's are interleaved by different tasks.
@@ -26,3 +30,181 @@ where
         Ok(())
     }
 }
+
+/// Estimated size a reply has to have to be written with [`write_vectored`], below
+/// that copying it into one buffer is cheaper than the extra bookkeeping.
+///
+/// [`write_vectored`]: AsyncWriteExt::write_vectored
+const VECTORED_WRITE_THRESHOLD: usize = 64 * 1024;
+
+/// Bytes written at once, larger replies are written in several chunks so that their
+/// framing doesn't have to be held in memory as a whole.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Slices passed to a single write, which is the minimum of IOV_MAX POSIX allows.
+const MAX_SLICES: usize = 1024;
+
+/// Part of a serialized value, either written into the header buffer or borrowed from
+/// the value itself.
+enum Segment<'v> {
+    Header(Range<usize>),
+    Payload(&'v [u8]),
+}
+
+/// Values of a reply which are still to be serialized, as a stack of the aggregates
+/// being walked.
+type Pending<'v, 'a> = Vec<Box<dyn Iterator<Item = &'v RespValue<'a>> + Send + 'v>>;
+
+/// Writes a reply, serializing it into `buffer` unless it is large.
+///
+/// Large replies are written in chunks of about [`CHUNK_SIZE`] bytes with vectored
+/// writes. Bulk strings are written from the value itself, while only the type bytes,
+/// lengths and CRLFs around them go into `buffer`, which is reused for every chunk.
+pub async fn write_reply(
+    writer: &mut (impl AsyncWrite + Unpin),
+    value: &RespValue<'_>,
+    buffer: &mut BytesMut,
+) -> std::io::Result<()> {
+    buffer.clear();
+    if estimated_len(value) < VECTORED_WRITE_THRESHOLD {
+        let _ = write!(buffer, "{value}");
+        return writer.write_all(buffer).await;
+    }
+
+    let mut pending: Pending = vec![Box::new(std::iter::once(value))];
+    let mut segments = Vec::new();
+    while !pending.is_empty() {
+        buffer.clear();
+        segments.clear();
+        next_chunk(&mut pending, buffer, &mut segments);
+        let parts: Vec<&[u8]> = segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Header(range) => &buffer[range.clone()],
+                Segment::Payload(payload) => payload,
+            })
+            .collect();
+        write_all_vectored(writer, &parts).await?;
+    }
+    Ok(())
+}
+
+/// Writes all parts in order, with as few writes as the writer allows.
+pub async fn write_all_vectored(
+    writer: &mut (impl AsyncWrite + Unpin),
+    parts: &[&[u8]],
+) -> std::io::Result<()> {
+    let mut parts: Vec<&[u8]> = parts.iter().copied().filter(|p| !p.is_empty()).collect();
+    let mut first = 0;
+    while first < parts.len() {
+        // NOTE: Operating systems limit the number of slices of a single write, and
+        //       large strings are written in chunks, each of which is awaited.
+        let mut remaining = CHUNK_SIZE;
+        let slices: Vec<IoSlice> = parts[first..]
+            .iter()
+            .take(MAX_SLICES)
+            .map_while(|part| {
+                let len = part.len().min(remaining);
+                remaining -= len;
+                (len > 0).then(|| IoSlice::new(&part[..len]))
+            })
+            .collect();
+        let mut written = writer.write_vectored(&slices).await?;
+        if written == 0 {
+            return Err(std::io::ErrorKind::WriteZero.into());
+        }
+        while first < parts.len() && written >= parts[first].len() {
+            written -= parts[first].len();
+            first += 1;
+        }
+        if written > 0 {
+            parts[first] = &parts[first][written..];
+        }
+    }
+    Ok(())
+}
+
+/// Rough size of the serialized value, which only has to tell small replies apart.
+fn estimated_len(value: &RespValue<'_>) -> usize {
+    /// Type byte, length and CRLFs, which mostly take less than this.
+    const FRAMING: usize = 16;
+    FRAMING
+        + match value {
+            RespValue::BulkString(s)
+            | RespValue::BulkError(s)
+            | RespValue::SimpleString(s)
+            | RespValue::SimpleError(s) => s.len(),
+            RespValue::Array(arr) | RespValue::Push(arr) => arr.iter().map(estimated_len).sum(),
+            RespValue::Set(set) => set.iter().map(estimated_len).sum(),
+            RespValue::Map(map) => map
+                .iter()
+                .map(|(k, v)| estimated_len(k) + estimated_len(v))
+                .sum(),
+            _ => 0,
+        }
+}
+
+/// Serializes the pending values until about [`CHUNK_SIZE`] bytes or [`MAX_SLICES`]
+/// segments are collected, borrowing the bulk strings instead of copying them.
+fn next_chunk<'v>(
+    pending: &mut Pending<'v, '_>,
+    headers: &mut BytesMut,
+    segments: &mut Vec<Segment<'v>>,
+) {
+    let mut payload_len = 0;
+    // NOTE: A bulk string takes up to three segments, which have to fit.
+    while headers.len() + payload_len < CHUNK_SIZE && segments.len() + 3 <= MAX_SLICES {
+        let Some(values) = pending.last_mut() else {
+            return;
+        };
+        let Some(value) = values.next() else {
+            pending.pop();
+            continue;
+        };
+
+        let start = headers.len();
+        let first_byte = char::from(RespDataType::from(value));
+        match value {
+            RespValue::BulkString(s) | RespValue::BulkError(s) => {
+                let _ = write!(headers, "{first_byte}{}\r\n", s.len());
+                push_header(segments, start..headers.len());
+                let payload = s.as_bytes();
+                segments.push(Segment::Payload(payload));
+                payload_len += payload.len();
+                let start = headers.len();
+                headers.extend_from_slice(b"\r\n");
+                push_header(segments, start..headers.len());
+            }
+            RespValue::Array(arr) | RespValue::Push(arr) => {
+                let _ = write!(headers, "{first_byte}{}\r\n", arr.len());
+                push_header(segments, start..headers.len());
+                pending.push(Box::new(arr.iter()));
+            }
+            RespValue::Set(set) => {
+                let _ = write!(headers, "{first_byte}{}\r\n", set.len());
+                push_header(segments, start..headers.len());
+                pending.push(Box::new(set.iter()));
+            }
+            RespValue::Map(map) => {
+                let _ = write!(headers, "{first_byte}{}\r\n", map.len());
+                push_header(segments, start..headers.len());
+                pending.push(Box::new(map.iter().flat_map(|(k, v)| [k, v])));
+            }
+            _ => {
+                let _ = write!(headers, "{value}");
+                push_header(segments, start..headers.len());
+            }
+        }
+    }
+}
+
+/// Adds a header, merging it with the previous one if they are adjacent.
+fn push_header(segments: &mut Vec<Segment<'_>>, range: Range<usize>) {
+    if let Some(Segment::Header(last)) = segments.last_mut() {
+        if last.end == range.start {
+            last.end = range.end;
+            return;
+        }
+    }
+    segments.push(Segment::Header(range));
+}
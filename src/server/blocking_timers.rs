@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use tokio::sync::{oneshot, Notify};
+use tokio::time::Instant;
+
+/// Timeouts of blocked clients, e.g. of WAIT, which a single task waits for instead of
+/// every blocked client sleeping on its own.
+///
+/// The task is spawned when the first timeout is registered.
+#[derive(Debug, Default)]
+pub struct BlockingTimers {
+    queue: Mutex<TimerQueue>,
+    /// Wakes the task when a timeout expiring before all others is registered.
+    changed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct TimerQueue {
+    /// Clients to wake by deadline, where the ID tells apart equal deadlines.
+    timers: BTreeMap<(Instant, u64), oneshot::Sender<()>>,
+    next_id: u64,
+    running: bool,
+}
+
+/// Timeout of a blocked client, which completes once its deadline passed and is
+/// cancelled when dropped, e.g. because the client was woken up early.
+#[derive(Debug)]
+pub struct BlockingTimeout {
+    timers: Arc<BlockingTimers>,
+    key: (Instant, u64),
+    expired: oneshot::Receiver<()>,
+}
+
+impl BlockingTimers {
+    fn lock_queue(&self) -> MutexGuard<'_, TimerQueue> {
+        self.queue.lock().unwrap()
+    }
+    /// Registers a timeout expiring at `deadline`.
+    pub fn timeout_at(self: &Arc<Self>, deadline: Instant) -> BlockingTimeout {
+        let (sender, expired) = oneshot::channel();
+        let mut queue = self.lock_queue();
+        let key = (deadline, queue.next_id);
+        queue.next_id += 1;
+        queue.timers.insert(key, sender);
+
+        if !std::mem::replace(&mut queue.running, true) {
+            tokio::spawn(run_blocking_timers(self.clone()));
+        } else if queue.timers.keys().next() == Some(&key) {
+            self.changed.notify_one();
+        }
+        BlockingTimeout {
+            timers: self.clone(),
+            key,
+            expired,
+        }
+    }
+    /// Number of timeouts which neither expired nor were cancelled yet.
+    pub fn len(&self) -> usize {
+        self.lock_queue().timers.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Wakes the clients whose deadline passed, returning the next deadline.
+    fn expire(&self, now: Instant) -> Option<Instant> {
+        let mut queue = self.lock_queue();
+        while let Some(entry) = queue.timers.first_entry() {
+            if entry.key().0 > now {
+                return Some(entry.key().0);
+            }
+            // NOTE: The client may have given up waiting in the meantime.
+            let _ = entry.remove().send(());
+        }
+        None
+    }
+}
+
+impl Future for BlockingTimeout {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // NOTE: The sender is only dropped without sending if the task stopped, in
+        //       which case the server shuts down anyway.
+        Pin::new(&mut self.expired).poll(cx).map(|_| ())
+    }
+}
+
+impl Drop for BlockingTimeout {
+    fn drop(&mut self) {
+        self.timers.lock_queue().timers.remove(&self.key);
+    }
+}
+
+/// Marks the task as stopped once it is dropped, e.g. by a shutting down runtime, so
+/// that the next timeout spawns a new one.
+struct RunningGuard(Arc<BlockingTimers>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.lock_queue().running = false;
+    }
+}
+
+async fn run_blocking_timers(timers: Arc<BlockingTimers>) {
+    let _guard = RunningGuard(timers.clone());
+    loop {
+        // NOTE: Registering for the notification before looking at the deadlines
+        //       ensures that no sooner timeout registered in between is missed.
+        let changed = timers.changed.notified();
+        tokio::pin!(changed);
+        changed.as_mut().enable();
+
+        match timers.expire(Instant::now()) {
+            Some(deadline) => {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(deadline) => {}
+                    _ = changed => {}
+                }
+            }
+            None => changed.await,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::Mutex;
+
+use bytes::BytesMut;
+
+/// Capacity of newly allocated buffers, which fits most requests and replies.
+const INITIAL_CAPACITY: usize = 4 * 1024;
+/// Largest buffer kept for reuse, bigger ones are freed so that a single large
+/// request doesn't pin its memory for the lifetime of the server.
+const MAX_POOLED_CAPACITY: usize = 64 * 1024;
+/// Number of buffers kept for reuse, the rest is freed.
+const MAX_POOLED_BUFFERS: usize = 256;
+
+/// Read and reply buffers of closed connections, which new connections reuse instead
+/// of allocating their own.
+#[derive(Debug, Default)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<BytesMut>>,
+}
+
+impl BufferPool {
+    /// Takes an empty buffer from the pool, or allocates one if it is empty.
+    pub fn take(&self) -> BytesMut {
+        self.buffers
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| BytesMut::with_capacity(INITIAL_CAPACITY))
+    }
+    /// Returns a buffer to the pool, if it isn't too large and the pool isn't full.
+    pub fn put(&self, mut buffer: BytesMut) {
+        if buffer.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+        buffer.clear();
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < MAX_POOLED_BUFFERS {
+            buffers.push(buffer);
+        }
+    }
+    /// Number of buffers ready for reuse.
+    pub fn len(&self) -> usize {
+        self.buffers.lock().unwrap().len()
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+use crate::acl::DEFAULT_USER;
+use crate::server::{ClientKind, CommandName, ConnectionContext};
+
+/// A connection as listed by CLIENT LIST and CLIENT INFO.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub id: u64,
+    /// Address of the peer.
+    pub addr: SocketAddr,
+    /// Address of the server the peer connected to.
+    pub laddr: SocketAddr,
+    /// Set by CLIENT SETNAME, empty if none was set.
+    pub name: String,
+    pub kind: ClientKind,
+    /// ACL user the connection authenticated as.
+    pub user: Option<String>,
+    pub created: Instant,
+    /// When the last command was received.
+    pub last_interaction: Instant,
+    pub last_command: Option<CommandName>,
+    /// Bytes of requests which were received but not run yet.
+    pub query_buffer: usize,
+}
+
+impl ClientInfo {
+    /// Renders the client as a line of CLIENT LIST, without the line break.
+    pub fn render(&self) -> String {
+        let now = Instant::now();
+        let flags = match self.kind {
+            ClientKind::Normal | ClientKind::AofLoader => "N",
+            ClientKind::Replica => "S",
+            ClientKind::Master => "M",
+        };
+        let command = match self.last_command {
+            Some((name, Some(subcommand))) => format!("{name}|{subcommand}"),
+            Some((name, None)) => name.to_string(),
+            None => String::from("NULL"),
+        };
+
+        let mut line = String::new();
+        let _ = write!(
+            line,
+            "id={} addr={} laddr={} name={} age={} idle={} flags={flags} db=0 ",
+            self.id,
+            self.addr,
+            self.laddr,
+            self.name,
+            now.duration_since(self.created).as_secs(),
+            now.duration_since(self.last_interaction).as_secs()
+        );
+        // NOTE: There is no Pub/Sub or MULTI, so connections never subscribe to channels
+        //       nor queue commands.
+        line.push_str("sub=0 psub=0 ssub=0 multi=-1 ");
+        // NOTE: Replies are written to the socket right away, so the output buffers
+        //       are always empty.
+        let _ = write!(
+            line,
+            "qbuf={} obl=0 oll=0 omem=0 cmd={command} user={} resp=2",
+            self.query_buffer,
+            self.user.as_deref().unwrap_or(DEFAULT_USER)
+        );
+        line
+    }
+}
+
+/// Connections of clients, see [`ClientInfo`].
+#[derive(Debug)]
+pub struct ClientRegistry {
+    next_id: AtomicU64,
+    clients: Mutex<BTreeMap<u64, ClientInfo>>,
+}
+
+impl Default for ClientRegistry {
+    fn default() -> Self {
+        Self {
+            // NOTE: Zero is the ID of connections which are not registered.
+            next_id: AtomicU64::new(1),
+            clients: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl ClientRegistry {
+    /// Clients sorted by their ID, which is the order they connected in.
+    pub fn lock_clients(&self) -> MutexGuard<'_, BTreeMap<u64, ClientInfo>> {
+        self.clients.lock().unwrap()
+    }
+    /// Adds a newly accepted connection, returning its ID.
+    pub fn register(&self, addr: SocketAddr, laddr: SocketAddr) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+        let client = ClientInfo {
+            id,
+            addr,
+            laddr,
+            name: String::new(),
+            kind: ClientKind::Normal,
+            user: None,
+            created: now,
+            last_interaction: now,
+            last_command: None,
+            query_buffer: 0,
+        };
+        self.lock_clients().insert(id, client);
+        id
+    }
+    /// Address of the peer, if the client is registered.
+    pub fn addr(&self, id: u64) -> Option<SocketAddr> {
+        self.lock_clients().get(&id).map(|client| client.addr)
+    }
+    pub fn unregister(&self, id: u64) {
+        self.lock_clients().remove(&id);
+    }
+    /// Modifies the client, if it is registered.
+    pub fn update(&self, id: u64, f: impl FnOnce(&mut ClientInfo)) {
+        if let Some(client) = self.lock_clients().get_mut(&id) {
+            f(client);
+        }
+    }
+    /// Records that the connection is about to run the command.
+    pub fn record_command(&self, ctx: &ConnectionContext, name: CommandName) {
+        self.update(ctx.id, |client| {
+            client.last_interaction = Instant::now();
+            client.last_command = Some(name);
+            client.kind = ctx.kind;
+            if client.user != ctx.user {
+                client.user = ctx.user.clone();
+            }
+        });
+    }
+}
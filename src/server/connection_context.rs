@@ -0,0 +1,47 @@
+use crate::command::DeferredReply;
+
+/// Who is on the other end of a connection.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum ClientKind {
+    #[default]
+    Normal,
+    /// Fake client replaying the AOF at startup, whose writes must not be appended again.
+    AofLoader,
+    /// Replica which completed PSYNC and now receives the replication stream.
+    Replica,
+    /// Link to the master, whose commands are applied without replying.
+    Master,
+}
+
+/// Per-connection state that commands can read and modify.
+#[derive(Debug, Default)]
+pub struct ConnectionContext {
+    /// ID in the [`ClientRegistry`](super::ClientRegistry), or zero if the connection
+    /// isn't registered, like the AOF loader.
+    pub id: u64,
+    pub kind: ClientKind,
+    /// Port a replica announced with 'REPLCONF listening-port'.
+    pub listening_port: Option<u16>,
+    /// Set by commands whose reply has to be sent even to the master.
+    pub force_reply: bool,
+    /// Offset a replica asked to continue the replication stream at with PSYNC, if the
+    /// backlog held it.
+    pub psync_offset: Option<u64>,
+    /// Set by ASKING, which allows the next command to access a slot being imported.
+    pub asking: bool,
+    /// ACL user the connection authenticated as with AUTH or HELLO.
+    pub user: Option<String>,
+    /// Set by QUIT, which closes the connection once the reply was sent.
+    pub quit: bool,
+    /// Reply the connection has to wait for before handling the next request.
+    pub deferred: Option<DeferredReply>,
+}
+
+impl ConnectionContext {
+    pub fn new(kind: ClientKind) -> Self {
+        Self {
+            kind,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt::Write;
+
+use crate::server::{info, ServerState};
+
+/// INFO sections included in the report.
+const INFO_SECTIONS: &[&str] = &["server", "clients", "memory", "persistence", "replication"];
+
+/// Latency samples listed per event.
+const LATENCY_TAIL_LEN: usize = 5;
+
+/// Renders a report of what the server is busy with, which is logged on SIGUSR1 to
+/// inspect a server that seems to hang.
+pub fn diagnostics_report(state: &ServerState) -> String {
+    let sections: Vec<String> = INFO_SECTIONS.iter().map(|s| s.to_string()).collect();
+    let mut report = info(state, &sections).replace("\r\n", "\n");
+
+    report.push_str("\n# Blocked\n");
+    let _ = writeln!(
+        report,
+        "blocked_clients_with_timeout:{}",
+        state.blocking_timers.len()
+    );
+
+    report.push_str("\n# Clients list\n");
+    for client in state.clients.lock_clients().values() {
+        let _ = writeln!(report, "{}", client.render());
+    }
+
+    // NOTE: There is no SLOWLOG, the latency spikes are the closest record of slow
+    //       commands.
+    report.push_str("\n# Latency\n");
+    for (name, event) in state.latency.lock_events().iter() {
+        let skip = event.samples.len().saturating_sub(LATENCY_TAIL_LEN);
+        let samples: Vec<String> = event
+            .samples
+            .iter()
+            .skip(skip)
+            .map(|sample| format!("{}:{}ms", sample.time, sample.latency_ms))
+            .collect();
+        let _ = writeln!(
+            report,
+            "{name}:max={}ms,latest={}",
+            event.max_ms,
+            samples.join(",")
+        );
+    }
+    report
+}
@@ -0,0 +1,414 @@
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::replication::MasterLinkState;
+use crate::server::{memory_stats, ServerState};
+
+/// Version of Redis whose behavior the server follows.
+pub const REDIS_VERSION: &str = "7.2.0";
+
+/// Sections in the order they are listed by INFO.
+const SECTIONS: &[&str] = &[
+    "server",
+    "clients",
+    "memory",
+    "persistence",
+    "stats",
+    "replication",
+    "cpu",
+    "commandstats",
+    "errorstats",
+    "cluster",
+    "keyspace",
+];
+
+/// Sections only listed by INFO if requested by name, "all" or "everything".
+const NON_DEFAULT_SECTIONS: &[&str] = &["commandstats"];
+
+/// Renders the requested INFO sections, or the default ones if `sections` is empty.
+///
+/// Unknown section names are ignored, same as in Redis.
+pub fn info(state: &ServerState, sections: &[String]) -> String {
+    let requested = |name: &str| sections.iter().any(|s| s.eq_ignore_ascii_case(name));
+    let all = requested("all") || requested("everything");
+    let default = sections.is_empty() || requested("default");
+
+    let mut output = String::new();
+    for &name in SECTIONS {
+        let included = all || (default && !NON_DEFAULT_SECTIONS.contains(&name));
+        if !included && !requested(name) {
+            continue;
+        }
+        if !output.is_empty() {
+            output.push_str("\r\n");
+        }
+        match name {
+            "server" => server(state, &mut output),
+            "clients" => clients(state, &mut output),
+            "memory" => memory(state, &mut output),
+            "persistence" => persistence(state, &mut output),
+            "stats" => stats(state, &mut output),
+            "replication" => replication(state, &mut output),
+            "cpu" => cpu(&mut output),
+            "commandstats" => commandstats(state, &mut output),
+            "errorstats" => errorstats(state, &mut output),
+            "cluster" => cluster(state, &mut output),
+            "keyspace" => keyspace(state, &mut output),
+            _ => unreachable!(),
+        }
+    }
+
+    output
+}
+
+fn server(state: &ServerState, output: &mut String) {
+    let uptime = state.stats.start_time.elapsed().as_secs();
+    let mode = if state.cluster.is_some() {
+        "cluster"
+    } else {
+        "standalone"
+    };
+    let (port, config_file) = {
+        let config = state.config();
+        let config_file = config
+            .config_file
+            .as_ref()
+            .map(|path| path.display().to_string());
+        (config.port, config_file.unwrap_or_default())
+    };
+    let now_usec = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros());
+    let executable = std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default();
+
+    output.push_str("# Server\r\n");
+    let _ = write!(output, "redis_version:{REDIS_VERSION}\r\n");
+    let _ = write!(output, "redis_mode:{mode}\r\n");
+    let _ = write!(
+        output,
+        "os:{} {}\r\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = write!(output, "arch_bits:{}\r\n", usize::BITS);
+    let _ = write!(output, "process_id:{}\r\n", std::process::id());
+    let _ = write!(output, "run_id:{}\r\n", state.stats.run_id);
+    let _ = write!(output, "tcp_port:{port}\r\n");
+    let _ = write!(output, "server_time_usec:{now_usec}\r\n");
+    let _ = write!(output, "uptime_in_seconds:{uptime}\r\n");
+    let _ = write!(output, "uptime_in_days:{}\r\n", uptime / (24 * 60 * 60));
+    let _ = write!(output, "executable:{executable}\r\n");
+    let _ = write!(output, "config_file:{config_file}\r\n");
+}
+
+fn clients(state: &ServerState, output: &mut String) {
+    let connected = state.stats.connected_clients.load(Ordering::Relaxed);
+    let blocked = state.stats.blocked_clients.load(Ordering::Relaxed);
+
+    output.push_str("# Clients\r\n");
+    let _ = write!(output, "connected_clients:{connected}\r\n");
+    let _ = write!(output, "blocked_clients:{blocked}\r\n");
+}
+
+fn memory(state: &ServerState, output: &mut String) {
+    let memory = memory_stats(state);
+
+    output.push_str("# Memory\r\n");
+    let _ = write!(output, "used_memory:{}\r\n", memory.total_allocated);
+    let _ = write!(
+        output,
+        "used_memory_human:{}\r\n",
+        human_bytes(memory.total_allocated)
+    );
+    let _ = write!(output, "used_memory_rss:{}\r\n", memory.rss);
+    let _ = write!(output, "used_memory_peak:{}\r\n", memory.peak_allocated);
+    let _ = write!(
+        output,
+        "used_memory_startup:{}\r\n",
+        memory.startup_allocated
+    );
+    let _ = write!(output, "used_memory_dataset:{}\r\n", memory.dataset_bytes);
+    let _ = write!(
+        output,
+        "mem_fragmentation_ratio:{:.2}\r\n",
+        memory.fragmentation()
+    );
+    let _ = write!(
+        output,
+        "mem_replication_backlog:{}\r\n",
+        memory.replication_backlog
+    );
+    output.push_str("maxmemory:0\r\n");
+    output.push_str("maxmemory_policy:noeviction\r\n");
+}
+
+fn persistence(state: &ServerState, output: &mut String) {
+    let bgsave_in_progress = state.rdb_bgsave_in_progress.load(Ordering::Relaxed);
+    let last_save_time = state.rdb_last_save_time.load(Ordering::Relaxed);
+    let last_bgsave_ok = state.rdb_last_bgsave_ok.load(Ordering::Relaxed);
+    let rewrite_in_progress = state.aof_rewrite_in_progress.load(Ordering::Relaxed);
+    let last_bgrewrite_ok = state.aof_last_bgrewrite_ok.load(Ordering::Relaxed);
+    let (rdb, aof) = (&state.rdb_bgsave_progress, &state.aof_rewrite_progress);
+    // NOTE: A background save and an AOF rewrite may run at the same time, unlike the
+    //       forks in Redis, so the progress covers the snapshots of both.
+    let (processed, total) = [rdb.current(), aof.current()]
+        .into_iter()
+        .flatten()
+        .fold((0, 0), |(processed, total), (written, size)| {
+            (processed + written, total + size)
+        });
+    let percentage = if total == 0 {
+        0.0
+    } else {
+        processed as f64 * 100.0 / total as f64
+    };
+
+    output.push_str("# Persistence\r\n");
+    output.push_str("loading:0\r\n");
+    let _ = write!(output, "current_fork_perc:{percentage:.2}\r\n");
+    let _ = write!(output, "current_save_bytes_processed:{processed}\r\n");
+    let _ = write!(output, "current_save_bytes_total:{total}\r\n");
+    let _ = write!(
+        output,
+        "rdb_bgsave_in_progress:{}\r\n",
+        u8::from(bgsave_in_progress)
+    );
+    let _ = write!(output, "rdb_last_save_time:{last_save_time}\r\n");
+    let _ = write!(
+        output,
+        "rdb_last_bgsave_status:{}\r\n",
+        status(last_bgsave_ok)
+    );
+    let _ = write!(
+        output,
+        "rdb_last_bgsave_time_sec:{}\r\n",
+        rdb.last_duration_secs()
+    );
+    let _ = write!(
+        output,
+        "rdb_current_bgsave_time_sec:{}\r\n",
+        rdb.current_duration_secs()
+    );
+    let _ = write!(
+        output,
+        "rdb_last_cow_size:{}\r\n",
+        rdb.last_snapshot_size.load(Ordering::Relaxed)
+    );
+    let _ = write!(output, "aof_enabled:{}\r\n", u8::from(state.aof.is_some()));
+    let _ = write!(
+        output,
+        "aof_rewrite_in_progress:{}\r\n",
+        u8::from(rewrite_in_progress)
+    );
+    let _ = write!(
+        output,
+        "aof_last_rewrite_time_sec:{}\r\n",
+        aof.last_duration_secs()
+    );
+    let _ = write!(
+        output,
+        "aof_current_rewrite_time_sec:{}\r\n",
+        aof.current_duration_secs()
+    );
+    let _ = write!(
+        output,
+        "aof_last_bgrewrite_status:{}\r\n",
+        status(last_bgrewrite_ok)
+    );
+    let _ = write!(
+        output,
+        "aof_last_cow_size:{}\r\n",
+        aof.last_snapshot_size.load(Ordering::Relaxed)
+    );
+    if let Some(aof) = &state.aof {
+        let aof = aof.lock();
+        let _ = write!(
+            output,
+            "aof_last_write_status:{}\r\n",
+            status(aof.last_write_error.is_none())
+        );
+        let _ = write!(output, "aof_current_size:{}\r\n", aof.current_size);
+        let _ = write!(output, "aof_base_size:{}\r\n", aof.base_size);
+    }
+}
+
+fn stats(state: &ServerState, output: &mut String) {
+    let stats = &state.stats;
+    let connections = stats.total_connections_received.load(Ordering::Relaxed);
+    let commands = stats.total_commands_processed.load(Ordering::Relaxed);
+    let expired_keys = stats.expired_keys.load(Ordering::Relaxed);
+
+    output.push_str("# Stats\r\n");
+    let _ = write!(output, "total_connections_received:{connections}\r\n");
+    let _ = write!(output, "total_commands_processed:{commands}\r\n");
+    let _ = write!(output, "expired_keys:{expired_keys}\r\n");
+    let _ = write!(
+        output,
+        "keyspace_hits:{}\r\n",
+        stats.keyspace_hits.load(Ordering::Relaxed)
+    );
+    let _ = write!(
+        output,
+        "keyspace_misses:{}\r\n",
+        stats.keyspace_misses.load(Ordering::Relaxed)
+    );
+    let _ = write!(
+        output,
+        "total_error_replies:{}\r\n",
+        stats.total_error_replies.load(Ordering::Relaxed)
+    );
+}
+
+fn replication(state: &ServerState, output: &mut String) {
+    let replication = &state.replication;
+    let offset = replication.repl_offset.load(Ordering::Relaxed);
+
+    output.push_str("# Replication\r\n");
+    let _ = write!(output, "role:{}\r\n", replication.role());
+    if let Some((host, port)) = replication.master() {
+        let link = replication.master_link();
+        let link_up = link == MasterLinkState::Connected;
+        let _ = write!(output, "master_host:{host}\r\n");
+        let _ = write!(output, "master_port:{port}\r\n");
+        let _ = write!(
+            output,
+            "master_link_status:{}\r\n",
+            if link_up { "up" } else { "down" }
+        );
+        let _ = write!(
+            output,
+            "master_sync_in_progress:{}\r\n",
+            u8::from(link == MasterLinkState::Syncing)
+        );
+        let _ = write!(output, "slave_repl_offset:{offset}\r\n");
+    }
+
+    let replicas = replication.lock_replicas();
+    let _ = write!(output, "connected_slaves:{}\r\n", replicas.len());
+    for (index, replica) in replicas.iter().enumerate() {
+        let _ = write!(
+            output,
+            "slave{index}:ip={},port={},state=online,offset={}\r\n",
+            replica.ip, replica.listening_port, replica.ack_offset
+        );
+    }
+    drop(replicas);
+
+    let ids = replication.ids();
+    let (backlog_size, backlog_first_byte_offset, backlog_len) = replication.backlog_info();
+    let _ = write!(output, "master_replid:{}\r\n", ids.replid);
+    let _ = write!(output, "master_replid2:{}\r\n", ids.replid2);
+    let _ = write!(output, "master_repl_offset:{offset}\r\n");
+    let _ = write!(
+        output,
+        "second_repl_offset:{}\r\n",
+        ids.second_replid_offset.map_or(-1, |offset| offset as i64)
+    );
+    output.push_str("repl_backlog_active:1\r\n");
+    let _ = write!(output, "repl_backlog_size:{backlog_size}\r\n");
+    let _ = write!(
+        output,
+        "repl_backlog_first_byte_offset:{backlog_first_byte_offset}\r\n"
+    );
+    let _ = write!(output, "repl_backlog_histlen:{backlog_len}\r\n");
+}
+
+fn cluster(state: &ServerState, output: &mut String) {
+    output.push_str("# Cluster\r\n");
+    let _ = write!(
+        output,
+        "cluster_enabled:{}\r\n",
+        u8::from(state.cluster.is_some())
+    );
+}
+
+fn cpu(output: &mut String) {
+    let (user, sys) = process_cpu_times().unwrap_or((0.0, 0.0));
+
+    output.push_str("# CPU\r\n");
+    let _ = write!(output, "used_cpu_sys:{sys:.6}\r\n");
+    let _ = write!(output, "used_cpu_user:{user:.6}\r\n");
+}
+
+fn commandstats(state: &ServerState, output: &mut String) {
+    output.push_str("# Commandstats\r\n");
+    for (&(name, subcommand), command) in state.stats.lock_commands().iter() {
+        let name = match subcommand {
+            Some(subcommand) => format!("{name}|{subcommand}"),
+            None => name.to_string(),
+        };
+        let usec_per_call = command.usec as f64 / command.calls.max(1) as f64;
+        let _ = write!(
+            output,
+            "cmdstat_{name}:calls={},usec={},usec_per_call={usec_per_call:.2},\
+             rejected_calls={},failed_calls={}\r\n",
+            command.calls, command.usec, command.rejected_calls, command.failed_calls
+        );
+    }
+}
+
+fn errorstats(state: &ServerState, output: &mut String) {
+    output.push_str("# Errorstats\r\n");
+    for (prefix, count) in state.stats.lock_errors().iter() {
+        let _ = write!(output, "errorstat_{prefix}:count={count}\r\n");
+    }
+}
+
+fn keyspace(state: &ServerState, output: &mut String) {
+    let now = Instant::now();
+    let db = state.db.lock().unwrap();
+    let ttls: Vec<_> = db
+        .iter()
+        .filter_map(|(_, slot)| slot.expires())
+        .map(|expires| expires.saturating_duration_since(now).as_millis())
+        .collect();
+
+    output.push_str("# Keyspace\r\n");
+    if !db.is_empty() {
+        let avg_ttl = ttls.iter().sum::<u128>() / ttls.len().max(1) as u128;
+        let _ = write!(
+            output,
+            "db0:keys={},expires={},avg_ttl={avg_ttl}\r\n",
+            db.len(),
+            ttls.len()
+        );
+    }
+}
+
+/// User and system CPU time of the process in seconds, only known on Linux.
+fn process_cpu_times() -> Option<(f64, f64)> {
+    // NOTE: The fields after the parenthesized command name start with the state, which
+    //       makes user and system time in clock ticks of 1/100s the 12th and 13th.
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    let (_, fields) = stat.rsplit_once(')')?;
+    let mut fields = fields.split_whitespace().skip(11);
+    let user: u64 = fields.next()?.parse().ok()?;
+    let sys: u64 = fields.next()?.parse().ok()?;
+    Some((user as f64 / 100.0, sys as f64 / 100.0))
+}
+
+fn human_bytes(bytes: u64) -> String {
+    let units = ["B", "K", "M", "G", "T"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes}B")
+    } else {
+        format!("{value:.2}{}", units[unit])
+    }
+}
+
+fn status(ok: bool) -> &'static str {
+    if ok {
+        "ok"
+    } else {
+        "err"
+    }
+}
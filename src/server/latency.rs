@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Number of samples kept per event, same as in Redis.
+const HISTORY_LEN: usize = 160;
+
+/// Slowest occurrence of an event within one second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySample {
+    /// Unix time in seconds.
+    pub time: u64,
+    pub latency_ms: u64,
+}
+
+/// Recorded latency spikes of one event class, e.g. "command" or "expire-cycle".
+#[derive(Debug, Default)]
+pub struct LatencyEvent {
+    /// The last [`HISTORY_LEN`] samples, oldest first.
+    pub samples: VecDeque<LatencySample>,
+    /// Slowest sample since the event was last reset.
+    pub max_ms: u64,
+}
+
+/// Distribution of the execution times of a command, see LATENCY HISTOGRAM.
+///
+/// Calls are counted in buckets of powers of two microseconds, where a bucket holds the
+/// calls taking longer than the previous one, but at most its own bound.
+#[derive(Debug, Default, Clone)]
+pub struct LatencyHistogram {
+    buckets: Vec<u64>,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let usec = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        let bucket = match usec {
+            0 | 1 => 0,
+            usec => (u64::BITS - (usec - 1).leading_zeros()) as usize,
+        };
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+    }
+    /// Bounds of the buckets in microseconds with the number of calls taking at most
+    /// that long, leaving out empty buckets.
+    pub fn cumulative(&self) -> Vec<(u64, u64)> {
+        let mut total = 0;
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(bucket, &count)| {
+                total += count;
+                (1u64.checked_shl(bucket as u32).unwrap_or(u64::MAX), total)
+            })
+            .collect()
+    }
+}
+
+/// Records events taking at least `latency-monitor-threshold`, see LATENCY.
+#[derive(Debug, Default)]
+pub struct LatencyMonitor {
+    /// Copy of the configured threshold in milliseconds, since the AOF records
+    /// latencies while holding its lock, which must not wait for the config.
+    threshold_ms: AtomicU64,
+    events: Mutex<BTreeMap<&'static str, LatencyEvent>>,
+}
+
+impl LatencyMonitor {
+    pub fn new(threshold: Duration) -> Self {
+        let monitor = Self::default();
+        monitor.set_threshold(threshold);
+        monitor
+    }
+    pub fn set_threshold(&self, threshold: Duration) {
+        let threshold_ms = u64::try_from(threshold.as_millis()).unwrap_or(u64::MAX);
+        self.threshold_ms.store(threshold_ms, Ordering::Relaxed);
+    }
+    pub fn lock_events(&self) -> MutexGuard<'_, BTreeMap<&'static str, LatencyEvent>> {
+        self.events.lock().unwrap()
+    }
+    /// Records that `event` took `latency`, if monitoring is enabled and it reached
+    /// the threshold. Spikes within the same second are merged into one sample.
+    pub fn record(&self, event: &'static str, latency: Duration) {
+        let threshold_ms = self.threshold_ms.load(Ordering::Relaxed);
+        let latency_ms = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+        if threshold_ms == 0 || latency_ms < threshold_ms {
+            return;
+        }
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        let mut events = self.lock_events();
+        let event = events.entry(event).or_default();
+        event.max_ms = event.max_ms.max(latency_ms);
+        match event.samples.back_mut() {
+            Some(last) if last.time == time => last.latency_ms = last.latency_ms.max(latency_ms),
+            _ => {
+                if event.samples.len() == HISTORY_LEN {
+                    event.samples.pop_front();
+                }
+                event.samples.push_back(LatencySample { time, latency_ms });
+            }
+        }
+    }
+    /// Runs `f` and records how long it took as `event`.
+    pub fn time<T>(&self, event: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(event, start.elapsed());
+        result
+    }
+    /// Deletes the samples of the given events, or of all events if none are given,
+    /// returning the number of events which had samples.
+    pub fn reset(&self, events: &[String]) -> usize {
+        let mut recorded = self.lock_events();
+        if events.is_empty() {
+            let count = recorded.len();
+            recorded.clear();
+            return count;
+        }
+        let names: Vec<_> = recorded
+            .keys()
+            .copied()
+            .filter(|name| events.iter().any(|event| event.eq_ignore_ascii_case(name)))
+            .collect();
+        for name in &names {
+            recorded.remove(name);
+        }
+        names.len()
+    }
+    /// Human readable analysis of the recorded events, as returned by LATENCY DOCTOR.
+    pub fn doctor(&self) -> String {
+        if self.threshold_ms.load(Ordering::Relaxed) == 0 {
+            return String::from(
+                "I'm sorry, Dave, I can't do that. Latency monitoring is disabled in this \
+                 Redis instance. You may use \"CONFIG SET latency-monitor-threshold \
+                 <milliseconds>.\" in order to enable it.\n",
+            );
+        }
+        let events = self.lock_events();
+        if events.is_empty() {
+            return String::from(
+                "Dave, no latency spike was observed during the lifetime of this Redis \
+                 instance, not in the slightest bit. I honestly think you ought to sleep \
+                 tonight.\n",
+            );
+        }
+
+        let mut report = String::from(
+            "Dave, I have observed latency spikes in this Redis instance. You don't mind \
+             talking about it, do you Dave?\n\n",
+        );
+        for (i, (name, event)) in events.iter().enumerate() {
+            let samples = &event.samples;
+            let count = samples.len() as u64;
+            let average = samples.iter().map(|s| s.latency_ms).sum::<u64>() / count.max(1);
+            let deviation = samples
+                .iter()
+                .map(|s| s.latency_ms.abs_diff(average))
+                .sum::<u64>()
+                / count.max(1);
+            let period = match (samples.front(), samples.back()) {
+                (Some(first), Some(last)) if count > 1 => {
+                    (last.time - first.time) as f64 / (count - 1) as f64
+                }
+                _ => 0.0,
+            };
+            let _ = writeln!(
+                report,
+                "{}. {name}: {count} latency spikes (average {average}ms, mean deviation \
+                 {deviation}ms, period {period:.2} sec). Worst all time event {}ms.",
+                i + 1,
+                event.max_ms
+            );
+        }
+
+        report.push_str("\nI have a few advices for you:\n\n");
+        for name in events.keys() {
+            let advice = match *name {
+                "command" | "fast-command" => {
+                    "Check which commands are slow, commands working on many keys or large \
+                     values block all other clients while they run."
+                }
+                "fork" => {
+                    "Snapshots of the dataset for SAVE, BGSAVE and AOF rewrites block the \
+                     server while it is serialized, consider saving less often."
+                }
+                "expire-cycle" => {
+                    "Many keys expire at the same time, consider spreading their expiry \
+                     over a longer time span."
+                }
+                "aof-fsync" => {
+                    "Syncing the AOF to disk is slow, consider 'appendfsync everysec' or a \
+                     faster disk."
+                }
+                _ => continue,
+            };
+            let _ = writeln!(report, "- {advice}");
+        }
+        report
+    }
+}
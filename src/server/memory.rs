@@ -0,0 +1,168 @@
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+
+use crate::server::ServerState;
+
+/// Memory usage of the server, as reported by MEMORY STATS and INFO.
+///
+/// Without allocator statistics the usage is estimated from the dataset and the
+/// buffers the server holds, on top of the memory the process used at startup.
+#[derive(Debug, Clone)]
+pub struct MemoryStats {
+    /// Highest `total_allocated` seen so far.
+    pub peak_allocated: u64,
+    pub total_allocated: u64,
+    /// Resident set size of the process at startup, before any data was loaded.
+    pub startup_allocated: u64,
+    pub replication_backlog: u64,
+    /// Output buffers of replicas and normal clients.
+    pub clients_replicas: u64,
+    pub clients_normal: u64,
+    pub aof_buffer: u64,
+    /// Hash table of the keys itself.
+    pub db_hashtable_main: u64,
+    /// Expiry times stored alongside the keys.
+    pub db_hashtable_expires: u64,
+    /// Everything but the dataset, including `startup_allocated`.
+    pub overhead_total: u64,
+    pub keys_count: u64,
+    pub dataset_bytes: u64,
+    /// Resident set size of the process, as reported by the OS.
+    pub rss: u64,
+}
+
+impl MemoryStats {
+    /// Share of the memory above `startup_allocated` holding the dataset.
+    pub fn dataset_percentage(&self) -> f64 {
+        let net = self.total_allocated.saturating_sub(self.startup_allocated);
+        percentage(self.dataset_bytes, net)
+    }
+    /// Current usage relative to the peak.
+    pub fn peak_percentage(&self) -> f64 {
+        percentage(self.total_allocated, self.peak_allocated)
+    }
+    /// Resident set size relative to the estimated usage.
+    pub fn fragmentation(&self) -> f64 {
+        self.rss as f64 / self.total_allocated.max(1) as f64
+    }
+    pub fn bytes_per_key(&self) -> u64 {
+        let net = self.total_allocated.saturating_sub(self.startup_allocated);
+        net / self.keys_count.max(1)
+    }
+    /// Human readable analysis of the memory usage, as returned by MEMORY DOCTOR.
+    pub fn doctor(&self) -> String {
+        const EMPTY_THRESHOLD: u64 = 5 * 1024 * 1024;
+
+        if self.total_allocated.saturating_sub(self.startup_allocated) < EMPTY_THRESHOLD {
+            return String::from(
+                "Hi Sam, this instance is empty or is using very little memory, my issues \
+                 detector can't be used in these conditions. Please, leave for your mission \
+                 on Earth and fill it with some data. The new Sam and I will be back to our \
+                 programming as soon as I finished rebooting.",
+            );
+        }
+
+        let mut issues = Vec::new();
+        if self.peak_percentage() < 66.0 {
+            issues.push(
+                " * Peak memory: In the past this instance used more than 150% the memory \
+                 that is currently using. The allocator is normally not able to release \
+                 memory after a peak, so you can expect to see a big fragmentation ratio.",
+            );
+        }
+        if self.fragmentation() > 1.4 {
+            issues.push(
+                " * High fragmentation: This instance has a memory fragmentation greater \
+                 than 1.4 (this means that the Resident Set Size of the Redis process is \
+                 much larger than the sum of the logical allocations Redis performed).",
+            );
+        }
+        if self.replication_backlog > self.dataset_bytes && self.dataset_bytes > 0 {
+            issues.push(
+                " * Big replication backlog: The replication backlog is larger than the \
+                 dataset, consider a smaller 'repl-backlog-size'.",
+            );
+        }
+        if issues.is_empty() {
+            return String::from(
+                "Hi Sam, I can't find any memory issue in your instance. I can only account \
+                 for what occurs on this base.",
+            );
+        }
+
+        let mut report = String::from(
+            "Sam, I detected a few issues in this Redis instance memory implants:\n\n",
+        );
+        for issue in issues {
+            let _ = writeln!(report, "{issue}\n");
+        }
+        report.push_str("I'm here to keep you safe, Sam. I want to help you.\n");
+        report
+    }
+}
+
+/// Measures the memory usage of the server, updating its peak.
+pub fn memory_stats(state: &ServerState) -> MemoryStats {
+    let (keys_count, dataset_bytes, db_hashtable_main, expires) = {
+        let db = state.db.lock().unwrap();
+        let expires = db
+            .iter()
+            .filter(|(_, slot)| slot.expires().is_some())
+            .count();
+        (
+            db.len(),
+            db.dataset_bytes(),
+            db.hashtable_overhead(),
+            expires,
+        )
+    };
+    let (_, _, backlog_len) = state.replication.backlog_info();
+
+    // NOTE: Replies are written to the socket right away and commands go straight to
+    //       the AOF file, so neither clients nor the AOF have buffers to account for.
+    let mut stats = MemoryStats {
+        peak_allocated: 0,
+        total_allocated: 0,
+        startup_allocated: state.stats.startup_rss,
+        replication_backlog: backlog_len as u64,
+        clients_replicas: 0,
+        clients_normal: 0,
+        aof_buffer: 0,
+        db_hashtable_main: db_hashtable_main as u64,
+        db_hashtable_expires: (expires * std::mem::size_of::<std::time::Instant>()) as u64,
+        overhead_total: 0,
+        keys_count: keys_count as u64,
+        dataset_bytes: dataset_bytes as u64,
+        rss: process_rss().unwrap_or(0),
+    };
+    stats.overhead_total = stats.startup_allocated
+        + stats.replication_backlog
+        + stats.clients_replicas
+        + stats.clients_normal
+        + stats.aof_buffer
+        + stats.db_hashtable_main
+        + stats.db_hashtable_expires;
+    stats.total_allocated = stats.overhead_total + stats.dataset_bytes;
+    let peak = state
+        .stats
+        .peak_allocated
+        .fetch_max(stats.total_allocated, Ordering::Relaxed);
+    stats.peak_allocated = peak.max(stats.total_allocated);
+    stats
+}
+
+/// Resident set size of the process in bytes, only known on Linux.
+pub fn process_rss() -> Option<u64> {
+    // NOTE: The second field is the number of resident pages, which are 4 KiB on
+    //       every common Linux platform.
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+fn percentage(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 * 100.0 / total as f64
+}
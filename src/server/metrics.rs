@@ -0,0 +1,208 @@
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::replication::MasterLinkState;
+use crate::server::{memory_stats, ServerState};
+
+/// Largest HTTP request head accepted, which is plenty for a scrape.
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+/// Serves the metrics of [`render_metrics`] over HTTP on `metrics-port`, so that
+/// Prometheus can scrape them from `/metrics`.
+pub async fn run_metrics_exporter(state: Arc<ServerState>) -> anyhow::Result<()> {
+    let port = state.config().metrics_port;
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    println!("Serving metrics on port {port}");
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_scrape(stream, &state).await {
+                eprintln!("Error serving metrics: {e}");
+            }
+        });
+    }
+}
+
+/// Answers a single HTTP request and closes the connection.
+async fn serve_scrape(mut stream: TcpStream, state: &ServerState) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_LEN {
+            return Ok(());
+        }
+        let mut buf = [0; 1024];
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let request_line = request.split(|&b| b == b'\r').next().unwrap_or_default();
+    let mut parts = request_line.split(|&b| b == b' ');
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(b"/metrics")) => ("200 OK", render_metrics(state)),
+        _ => ("404 Not Found", String::from("Not Found\n")),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Renders the metrics of the server in the Prometheus text exposition format.
+pub fn render_metrics(state: &ServerState) -> String {
+    let stats = &state.stats;
+    let mut output = String::new();
+
+    let uptime = stats.start_time.elapsed().as_secs();
+    family(
+        &mut output,
+        "redis_uptime_in_seconds",
+        "gauge",
+        "Time since the server started.",
+    );
+    let _ = writeln!(output, "redis_uptime_in_seconds {uptime}");
+
+    let connected = stats.connected_clients.load(Ordering::Relaxed);
+    family(
+        &mut output,
+        "redis_connected_clients",
+        "gauge",
+        "Number of client connections.",
+    );
+    let _ = writeln!(output, "redis_connected_clients {connected}");
+    let connections = stats.total_connections_received.load(Ordering::Relaxed);
+    family(
+        &mut output,
+        "redis_connections_received_total",
+        "counter",
+        "Connections accepted by the server.",
+    );
+    let _ = writeln!(output, "redis_connections_received_total {connections}");
+
+    family(
+        &mut output,
+        "redis_commands_total",
+        "counter",
+        "Calls of each command, whose rate is the commands per second.",
+    );
+    for (&(name, subcommand), command) in stats.lock_commands().iter() {
+        let name = match subcommand {
+            Some(subcommand) => format!("{name}|{subcommand}"),
+            None => name.to_string(),
+        };
+        let _ = writeln!(
+            output,
+            "redis_commands_total{{cmd=\"{name}\"}} {}",
+            command.calls
+        );
+    }
+
+    let hits = stats.keyspace_hits.load(Ordering::Relaxed);
+    let misses = stats.keyspace_misses.load(Ordering::Relaxed);
+    family(
+        &mut output,
+        "redis_keyspace_hits_total",
+        "counter",
+        "Lookups of existing keys, the hit ratio is hits / (hits + misses).",
+    );
+    let _ = writeln!(output, "redis_keyspace_hits_total {hits}");
+    family(
+        &mut output,
+        "redis_keyspace_misses_total",
+        "counter",
+        "Lookups of missing keys.",
+    );
+    let _ = writeln!(output, "redis_keyspace_misses_total {misses}");
+    let expired = stats.expired_keys.load(Ordering::Relaxed);
+    family(
+        &mut output,
+        "redis_expired_keys_total",
+        "counter",
+        "Keys deleted on expiry.",
+    );
+    let _ = writeln!(output, "redis_expired_keys_total {expired}");
+
+    let memory = memory_stats(state);
+    family(
+        &mut output,
+        "redis_db_keys",
+        "gauge",
+        "Number of keys in the database.",
+    );
+    let _ = writeln!(output, "redis_db_keys{{db=\"db0\"}} {}", memory.keys_count);
+    family(
+        &mut output,
+        "redis_memory_used_bytes",
+        "gauge",
+        "Estimated memory used by the server.",
+    );
+    let _ = writeln!(output, "redis_memory_used_bytes {}", memory.total_allocated);
+    family(
+        &mut output,
+        "redis_memory_rss_bytes",
+        "gauge",
+        "Resident set size of the process.",
+    );
+    let _ = writeln!(output, "redis_memory_rss_bytes {}", memory.rss);
+
+    let replication = &state.replication;
+    let offset = replication.repl_offset.load(Ordering::Relaxed);
+    family(
+        &mut output,
+        "redis_repl_offset",
+        "gauge",
+        "Offset of the replication stream.",
+    );
+    let _ = writeln!(output, "redis_repl_offset {offset}");
+    if replication.is_replica() {
+        let link_up = replication.master_link() == MasterLinkState::Connected;
+        family(
+            &mut output,
+            "redis_master_link_up",
+            "gauge",
+            "Whether the link to the master is up.",
+        );
+        let _ = writeln!(output, "redis_master_link_up {}", u8::from(link_up));
+    }
+    let replicas = replication.lock_replicas();
+    family(
+        &mut output,
+        "redis_connected_replicas",
+        "gauge",
+        "Number of connected replicas.",
+    );
+    let _ = writeln!(output, "redis_connected_replicas {}", replicas.len());
+    family(
+        &mut output,
+        "redis_replica_lag_bytes",
+        "gauge",
+        "Bytes of the replication stream a replica did not acknowledge yet.",
+    );
+    for replica in replicas.iter() {
+        let _ = writeln!(
+            output,
+            "redis_replica_lag_bytes{{replica=\"{}:{}\"}} {}",
+            replica.ip,
+            replica.listening_port,
+            offset.saturating_sub(replica.ack_offset)
+        );
+    }
+
+    output
+}
+
+fn family(output: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(output, "# HELP {name} {help}");
+    let _ = writeln!(output, "# TYPE {name} {kind}");
+}
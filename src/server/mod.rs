@@ -0,0 +1,35 @@
+mod blocking_timers;
+mod buffer_pool;
+mod clients;
+mod connection_context;
+mod diagnostics;
+mod info;
+mod latency;
+mod memory;
+mod metrics;
+mod rate_limiter;
+mod redis_server;
+mod save_progress;
+mod server_state;
+mod stats;
+mod supervisor;
+
+pub use blocking_timers::{BlockingTimeout, BlockingTimers};
+pub use buffer_pool::BufferPool;
+pub use clients::{ClientInfo, ClientRegistry};
+pub use connection_context::{ClientKind, ConnectionContext};
+pub use diagnostics::diagnostics_report;
+pub use info::{info, REDIS_VERSION};
+pub use latency::{LatencyEvent, LatencyHistogram, LatencyMonitor, LatencySample};
+pub use memory::{memory_stats, MemoryStats};
+pub use metrics::{render_metrics, run_metrics_exporter};
+pub use rate_limiter::{RateLimitKey, RateLimiter};
+pub use redis_server::{RedisServer, RedisServerBuilder, RedisServerHandle};
+pub use save_progress::SaveProgress;
+#[cfg(unix)]
+pub use server_state::{dump_diagnostics_on_sigusr1, reload_config_on_sighup};
+pub use server_state::{
+    load_database, run_active_expire, shutdown_signal, ConfigReload, ServerState, ShutdownSignal,
+};
+pub use stats::{CommandName, CommandStats, ServerStats};
+pub use supervisor::SystemdNotifier;
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::config::RateLimitBy;
+
+/// Buckets kept before those which are full again are dropped, since they behave
+/// the same as a new one.
+const MAX_IDLE_BUCKETS: usize = 1024;
+
+/// What a [`RateLimiter`] counts the commands of.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum RateLimitKey {
+    Client(u64),
+    Ip(IpAddr),
+}
+
+impl RateLimitKey {
+    pub fn new(by: RateLimitBy, id: u64, ip: IpAddr) -> Self {
+        match by {
+            RateLimitBy::Client => RateLimitKey::Client(id),
+            RateLimitBy::Ip => RateLimitKey::Ip(ip),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets limiting the commands per second of clients or source addresses, see
+/// `rate-limit`.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<RateLimitKey, TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// Takes a token from the bucket of `key`, which holds up to `burst` tokens and
+    /// gains `rate` per second, returning whether there was one.
+    pub fn try_acquire(&self, key: RateLimitKey, rate: u64, burst: u64, now: Instant) -> bool {
+        let (rate, burst) = (rate as f64, burst.max(1) as f64);
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_IDLE_BUCKETS && !buckets.contains_key(&key) {
+            buckets.retain(|_, bucket| refill(bucket, rate, burst, now) < burst);
+        }
+        let bucket = buckets.entry(key).or_insert(TokenBucket {
+            tokens: burst,
+            updated: now,
+        });
+        if refill(bucket, rate, burst, now) < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+    /// Drops the bucket of a client which disconnected.
+    pub fn forget(&self, key: RateLimitKey) {
+        self.buckets.lock().unwrap().remove(&key);
+    }
+    pub fn clear(&self) {
+        self.buckets.lock().unwrap().clear();
+    }
+}
+
+/// Adds the tokens gained since the last update, returning the new number of tokens.
+fn refill(bucket: &mut TokenBucket, rate: f64, burst: f64, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+    bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+    bucket.updated = now;
+    bucket.tokens
+}
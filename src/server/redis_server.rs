@@ -0,0 +1,493 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, DuplexStream};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::aof::load_aof;
+use crate::cluster::run_cluster_bus;
+use crate::command::{dispatch, find_command, CommandHandler, CustomCommand, RedisError};
+use crate::config::{find_config_entry, Config};
+use crate::db::Database;
+use crate::replication::{run_replica_link, serve_replica};
+use crate::resp::{parse_resp_value, write_reply, ParseError, RespValue};
+use crate::server::{
+    load_database, run_active_expire, shutdown_signal, ClientKind, ConnectionContext, RateLimitKey,
+    ServerState,
+};
+
+/// Entry point for running the server in-process, see [`RedisServer::builder`].
+pub struct RedisServer;
+
+impl RedisServer {
+    /// Starts configuring a server, which listens on port 6379 by default, e.g.
+    /// `RedisServer::builder().port(0).dir(tmp).start().await?` for one on a free port.
+    pub fn builder() -> RedisServerBuilder {
+        RedisServerBuilder::default()
+    }
+}
+
+/// Bytes an in-memory connection buffers in each direction.
+const IN_MEMORY_BUFFER_SIZE: usize = 64 * 1024;
+
+const QUERY_BUFFER_LIMIT_ERROR: &str = "Protocol error: client query buffer limit exceeded";
+
+/// Configuration of a server to start, see [`RedisServer::builder`].
+#[derive(Debug, Default)]
+pub struct RedisServerBuilder {
+    config: Config,
+    /// Parameters set by name, applied on top of `config` when starting.
+    parameters: Vec<(String, String)>,
+    reload_config_on_sighup: bool,
+    dump_diagnostics_on_sigusr1: bool,
+    shutdown_on_signals: bool,
+    commands: Vec<Arc<dyn CommandHandler>>,
+}
+
+impl RedisServerBuilder {
+    /// Replaces the whole configuration, e.g. one parsed from the command line.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+    /// Port to listen on, or zero to let the OS pick a free one.
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+    /// Directory of the RDB file and the AOF.
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.dir = dir.into();
+        self
+    }
+    /// Sets a parameter by its name in the config file, e.g. "appendonly". Invalid
+    /// parameters are reported by [`start`](Self::start).
+    pub fn set(mut self, name: &str, value: &str) -> Self {
+        self.parameters.push((name.to_string(), value.to_string()));
+        self
+    }
+    /// Adds a custom command, which must not be named like a built-in one. Conflicting
+    /// names are reported by [`start`](Self::start).
+    pub fn command(mut self, handler: impl CommandHandler + 'static) -> Self {
+        self.commands.push(Arc::new(handler));
+        self
+    }
+    /// Reloads the config file whenever the process receives SIGHUP, which only the
+    /// server binary should do, since the handler is installed for the whole process.
+    pub fn reload_config_on_sighup(mut self, enabled: bool) -> Self {
+        self.reload_config_on_sighup = enabled;
+        self
+    }
+    /// Logs a diagnostics report whenever the process receives SIGUSR1, see
+    /// [`diagnostics_report`](crate::server::diagnostics_report). Like
+    /// [`reload_config_on_sighup`](Self::reload_config_on_sighup) this is meant for the
+    /// server binary only.
+    pub fn dump_diagnostics_on_sigusr1(mut self, enabled: bool) -> Self {
+        self.dump_diagnostics_on_sigusr1 = enabled;
+        self
+    }
+    /// Shuts down once the process receives SIGTERM or SIGINT, after saving a final
+    /// snapshot as configured by `shutdown-on-sigterm` and `shutdown-on-sigint`. Like
+    /// [`reload_config_on_sighup`](Self::reload_config_on_sighup) this is meant for the
+    /// server binary only.
+    pub fn shutdown_on_signals(mut self, enabled: bool) -> Self {
+        self.shutdown_on_signals = enabled;
+        self
+    }
+    /// Loads the dataset and starts accepting connections on the tokio runtime it is
+    /// called from.
+    pub async fn start(self) -> anyhow::Result<RedisServerHandle> {
+        let mut config = self.config;
+        for (name, value) in &self.parameters {
+            let entry =
+                find_config_entry(name).ok_or_else(|| anyhow!("Unknown parameter {name:?}"))?;
+            (entry.set)(&mut config, value).map_err(|e| anyhow!("Invalid {name}: {e}"))?;
+        }
+
+        let listener = TcpListener::bind(("127.0.0.1", config.port)).await?;
+        let addr = listener.local_addr()?;
+        // NOTE: The port is announced to replicas and cluster nodes, so it has to be the
+        //       one the OS picked if it was zero.
+        config.port = addr.port();
+
+        // NOTE: The AOF is always at least as up to date as the RDB file, so it takes precedence.
+        let db = if config.appendonly {
+            Database::new()
+        } else {
+            load_database(&config.rdb_path())?
+        };
+        let mut state = ServerState::new(config, db)?;
+        for handler in self.commands {
+            let name = handler.name().to_ascii_lowercase();
+            if find_command(&name).is_some() || state.custom_commands.contains_key(&name) {
+                return Err(anyhow!("Command {name:?} already exists"));
+            }
+            let custom = CustomCommand::new(handler);
+            state.custom_commands.insert(name, Arc::new(custom));
+        }
+        let state = Arc::new(state);
+        if state.config().appendonly {
+            let num_commands = load_aof(&state)?;
+            println!("Replayed {num_commands} commands from the AOF");
+        }
+        // NOTE: The listener is bound already, so connections queue up until serve runs.
+        state.notify_supervisor("STATUS=Ready to accept connections\nREADY=1");
+
+        let (shutdown, shutdown_received) = oneshot::channel();
+        let (in_memory, in_memory_received) = mpsc::unbounded_channel();
+        let task = tokio::spawn(serve(
+            listener,
+            state,
+            self.reload_config_on_sighup,
+            self.dump_diagnostics_on_sigusr1,
+            self.shutdown_on_signals,
+            in_memory_received,
+            shutdown_received,
+        ));
+        Ok(RedisServerHandle {
+            addr,
+            in_memory,
+            shutdown,
+            task,
+        })
+    }
+}
+
+/// A running server, which is shut down once the handle is dropped.
+#[derive(Debug)]
+pub struct RedisServerHandle {
+    addr: SocketAddr,
+    /// Server ends of the connections opened by [`connect_in_memory`](Self::connect_in_memory).
+    in_memory: mpsc::UnboundedSender<DuplexStream>,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<anyhow::Result<()>>,
+}
+
+impl RedisServerHandle {
+    /// Address the server accepts connections on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+    /// Opens a connection which doesn't go through the network stack, but is served
+    /// the same as one accepted on [`addr`](Self::addr).
+    pub fn connect_in_memory(&self) -> DuplexStream {
+        let (client, server) = tokio::io::duplex(IN_MEMORY_BUFFER_SIZE);
+        // NOTE: If the server shut down already the client reads EOF right away.
+        let _ = self.in_memory.send(server);
+        client
+    }
+    /// Stops accepting connections, closes the open ones and stops all background tasks.
+    pub async fn shutdown(self) -> anyhow::Result<()> {
+        let _ = self.shutdown.send(());
+        self.task.await?
+    }
+    /// Runs the server until it fails to accept connections.
+    pub async fn wait(self) -> anyhow::Result<()> {
+        let Self {
+            in_memory,
+            shutdown,
+            task,
+            ..
+        } = self;
+        let result = task.await;
+        drop((in_memory, shutdown));
+        result?
+    }
+}
+
+/// Accepts connections until `shutdown` fires, which aborts every task of the server.
+async fn serve(
+    listener: TcpListener,
+    state: Arc<ServerState>,
+    reload_config_on_sighup: bool,
+    dump_diagnostics_on_sigusr1: bool,
+    shutdown_on_signals: bool,
+    mut in_memory: mpsc::UnboundedReceiver<DuplexStream>,
+    mut shutdown: oneshot::Receiver<()>,
+) -> anyhow::Result<()> {
+    let laddr = listener.local_addr()?;
+    let mut tasks = JoinSet::new();
+    let mut background_tasks = state
+        .take_background_tasks()
+        .ok_or_else(|| anyhow!("The server state is already served"))?;
+    tasks.spawn(run_active_expire(state.clone()));
+    #[cfg(unix)]
+    if reload_config_on_sighup {
+        let state = state.clone();
+        tasks.spawn(async move {
+            if let Err(e) = super::reload_config_on_sighup(state).await {
+                eprintln!("Config reloading on SIGHUP stopped with Error: {e:?}");
+            }
+        });
+    }
+    #[cfg(unix)]
+    if dump_diagnostics_on_sigusr1 {
+        let state = state.clone();
+        tasks.spawn(async move {
+            if let Err(e) = super::dump_diagnostics_on_sigusr1(state).await {
+                eprintln!("Diagnostics on SIGUSR1 stopped with Error: {e:?}");
+            }
+        });
+    }
+    if state.cluster.is_some() {
+        let state = state.clone();
+        tasks.spawn(async move {
+            if let Err(e) = run_cluster_bus(state).await {
+                eprintln!("Cluster bus stopped with Error: {e:?}");
+            }
+        });
+    }
+    if state.config().metrics_port != 0 {
+        let state = state.clone();
+        tasks.spawn(async move {
+            if let Err(e) = super::run_metrics_exporter(state).await {
+                eprintln!("Metrics exporter stopped with Error: {e:?}");
+            }
+        });
+    }
+    if state.config().replicaof.is_some() {
+        let generation = state.replication.link_generation();
+        tasks.spawn(run_replica_link(state.clone(), generation));
+    }
+
+    // NOTE: Created once, so that no signal is missed between iterations of the loop.
+    let signal = async {
+        if shutdown_on_signals {
+            shutdown_signal().await
+        } else {
+            std::future::pending().await
+        }
+    };
+    tokio::pin!(signal);
+
+    loop {
+        tokio::select! {
+            signal = &mut signal => {
+                let signal = signal?;
+                println!("Received {signal:?}, shutting down");
+                // NOTE: Returning stops accepting connections and aborts the tasks.
+                return state.prepare_shutdown(signal);
+            }
+            accepted = listener.accept() => {
+                let (stream, addr) = accepted?;
+                // NOTE: Unlike the halves of `tokio::io::split`, these support vectored writes.
+                let (read_half, write_half) = stream.into_split();
+                spawn_connection(&mut tasks, &state, read_half, write_half, addr, laddr);
+            }
+            Some(stream) = in_memory.recv() => {
+                let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+                let (read_half, write_half) = tokio::io::split(stream);
+                spawn_connection(&mut tasks, &state, read_half, write_half, addr, laddr);
+            }
+            Some(task) = background_tasks.recv() => {
+                tasks.spawn(task);
+            }
+            // NOTE: Finished tasks are reaped, so that their results don't pile up.
+            Some(_) = tasks.join_next(), if !tasks.is_empty() => {}
+            _ = &mut shutdown => return Ok(()),
+        }
+    }
+}
+
+/// Registers the connection of a client and serves it on a task of the server.
+fn spawn_connection(
+    tasks: &mut JoinSet<()>,
+    state: &Arc<ServerState>,
+    read_half: impl AsyncRead + Unpin + Send + 'static,
+    write_half: impl AsyncWrite + Unpin + Send + 'static,
+    addr: SocketAddr,
+    laddr: SocketAddr,
+) {
+    let id = state.clients.register(addr, laddr);
+    let stats = &state.stats;
+    stats.connected_clients.fetch_add(1, Ordering::Relaxed);
+    stats
+        .total_connections_received
+        .fetch_add(1, Ordering::Relaxed);
+
+    let state = state.clone();
+    tasks.spawn(async move {
+        let mut buffers = Buffers {
+            request: state.buffers.take(),
+            reply: state.buffers.take(),
+        };
+        let result =
+            handle_connection(read_half, write_half, &mut buffers, addr, id, state.clone()).await;
+        if let Err(e) = result {
+            eprintln!("Shutdown with Error: {:?}", e);
+        }
+        state.buffers.put(buffers.request);
+        state.buffers.put(buffers.reply);
+        state.clients.unregister(id);
+        state.rate_limiter.forget(RateLimitKey::Client(id));
+        state
+            .stats
+            .connected_clients
+            .fetch_sub(1, Ordering::Relaxed);
+    });
+}
+
+/// Buffers of a connection, which are taken from and returned to the [`BufferPool`].
+///
+/// [`BufferPool`]: crate::server::BufferPool
+struct Buffers {
+    /// Requests which were received but not parsed yet.
+    request: BytesMut,
+    /// Serialized reply which is being written.
+    reply: BytesMut,
+}
+
+/// Waits for the reply of a blocked client, returning `None` if it disconnected in the
+/// meantime. The reply is dropped then, which e.g. cancels its timeout and closes its
+/// [`ReplyHandle`](crate::command::ReplyHandle).
+///
+/// Requests the client pipelines while it is blocked are read into `pipelined`, and
+/// waiting stops as well once they exceed `limit` bytes, which is what is left of the
+/// query buffer limit, so that the client can be disconnected with its error.
+///
+/// NOTE: The limit applies to the requests that were buffered but not handled yet, like
+///       the query buffer limit of Redis. Outside of this that is at most one incomplete
+///       request, while a blocked client handles none, so every pipelined request counts
+///       and a blocked client can't pile up more than `limit` bytes.
+async fn resolve_or_disconnect(
+    reply: impl Future<Output = RespValue<'static>>,
+    read_half: &mut (impl AsyncRead + Unpin),
+    pipelined: &mut BytesMut,
+    limit: usize,
+) -> Option<RespValue<'static>> {
+    tokio::pin!(reply);
+    loop {
+        tokio::select! {
+            reply = &mut reply => return Some(reply),
+            result = read_half.read_buf(pipelined) => match result {
+                Ok(0) | Err(_) => return None,
+                Ok(_) if pipelined.len() > limit => return None,
+                Ok(_) => {}
+            },
+        }
+    }
+}
+
+async fn handle_connection(
+    mut read_half: impl AsyncRead + Unpin,
+    mut write_half: impl AsyncWrite + Unpin,
+    buffers: &mut Buffers,
+    addr: SocketAddr,
+    id: u64,
+    state: Arc<ServerState>,
+) -> anyhow::Result<()> {
+    let Buffers {
+        request: buffer,
+        reply,
+    } = buffers;
+    let mut ctx = ConnectionContext {
+        id,
+        ..Default::default()
+    };
+    // Requests that arrived while the client was blocked.
+    let mut pipelined = BytesMut::new();
+
+    loop {
+        if pipelined.is_empty() {
+            // NOTE: Idle clients are disconnected after the configured timeout, zero
+            //       disables it.
+            let timeout = state.config().timeout;
+            let read = read_half.read_buf(buffer);
+            let result = if timeout.is_zero() {
+                read.await
+            } else {
+                match tokio::time::timeout(timeout, read).await {
+                    Ok(result) => result,
+                    Err(_) => break,
+                }
+            };
+            match result {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+        } else {
+            buffer.extend_from_slice(&pipelined);
+            pipelined.clear();
+        }
+        state
+            .clients
+            .update(id, |client| client.query_buffer = buffer.len());
+        let mut input = buffer.as_ref();
+        loop {
+            if input.is_empty() {
+                break;
+            }
+            let frame = input;
+            let value;
+            (input, value) = match parse_resp_value(input) {
+                Ok(x) => x,
+                Err(nom::Err::Incomplete(_)) => break,
+                Err(nom::Err::Error(ParseError::Nom(nom::Err::Incomplete(_)))) => break,
+                Err(nom::Err::Failure(ParseError::Nom(nom::Err::Incomplete(_)))) => break,
+                Err(e) => return Err(anyhow!("{}", e)),
+            };
+
+            let frame = &frame[..frame.len() - input.len()];
+            let mut response = dispatch(&state, &mut ctx, value, frame);
+            if let Some(deferred) = ctx.deferred.take() {
+                let resolve = deferred.resolve(&state);
+                let blocked = &state.stats.blocked_clients;
+                blocked.fetch_add(1, Ordering::Relaxed);
+                // NOTE: Requests read along with the blocking one count towards the limit.
+                let limit = state
+                    .config()
+                    .client_query_buffer_limit
+                    .saturating_sub(input.len());
+                let resolved =
+                    resolve_or_disconnect(resolve, &mut read_half, &mut pipelined, limit).await;
+                blocked.fetch_sub(1, Ordering::Relaxed);
+                match resolved {
+                    Some(resolved) => response = resolved,
+                    None if pipelined.len() > limit => {
+                        return Ok(reject_query_buffer(&mut write_half, addr, reply).await?);
+                    }
+                    None => return Ok(()),
+                }
+            }
+            // NOTE: PSYNC is answered once the snapshot was taken, see 'serve_replica'.
+            if ctx.kind == ClientKind::Replica {
+                state.clients.update(id, |client| client.kind = ctx.kind);
+                return serve_replica(&mut read_half, &mut write_half, addr, &ctx, &state).await;
+            }
+            write_reply(&mut write_half, &response, reply).await?;
+            if ctx.quit {
+                return Ok(());
+            }
+        }
+        // NOTE: Advancing past the parsed requests keeps the allocation for the next read.
+        let consumed = buffer.len() - input.len();
+        buffer.advance(consumed);
+
+        // NOTE: What is left is a single incomplete request, the only one not handled
+        //       yet, see 'resolve_or_disconnect' for blocked clients.
+        if buffer.len() > state.config().client_query_buffer_limit {
+            return Ok(reject_query_buffer(&mut write_half, addr, reply).await?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Replies with the error for exceeding 'client-query-buffer-limit', after which the
+/// connection is closed.
+async fn reject_query_buffer(
+    write_half: &mut (impl AsyncWrite + Unpin),
+    addr: SocketAddr,
+    reply: &mut BytesMut,
+) -> std::io::Result<()> {
+    eprintln!("Closing client {addr} that reached the max query buffer length");
+    let error = RedisError::err(QUERY_BUFFER_LIMIT_ERROR).into();
+    write_reply(write_half, &error, reply).await
+}
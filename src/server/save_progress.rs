@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Progress of a background save or AOF rewrite writing its snapshot to disk, as
+/// reported by INFO persistence.
+#[derive(Debug)]
+pub struct SaveProgress {
+    /// When the running save started, if one runs.
+    started: Mutex<Option<Instant>>,
+    /// Bytes of the snapshot written so far, and in total.
+    written: AtomicU64,
+    total: AtomicU64,
+    /// Duration of the last save in seconds, or -1 if none finished yet.
+    last_duration_secs: AtomicI64,
+    /// Size of the snapshot of the last save, which was held in memory in addition to
+    /// the dataset like the pages copied on write by the fork in Redis.
+    pub last_snapshot_size: AtomicU64,
+}
+
+impl Default for SaveProgress {
+    fn default() -> Self {
+        Self {
+            started: Mutex::new(None),
+            written: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            last_duration_secs: AtomicI64::new(-1),
+            last_snapshot_size: AtomicU64::new(0),
+        }
+    }
+}
+
+impl SaveProgress {
+    /// Starts tracking a save writing a snapshot of `total` bytes.
+    pub fn start(&self, total: u64) {
+        *self.started.lock().unwrap() = Some(Instant::now());
+        self.written.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+        self.last_snapshot_size.store(total, Ordering::Relaxed);
+    }
+    /// Records that `bytes` more of the snapshot were written.
+    pub fn advance(&self, bytes: u64) {
+        self.written.fetch_add(bytes, Ordering::Relaxed);
+    }
+    /// Ends tracking the running save, successful or not.
+    pub fn finish(&self) {
+        if let Some(started) = self.started.lock().unwrap().take() {
+            let secs = started.elapsed().as_secs() as i64;
+            self.last_duration_secs.store(secs, Ordering::Relaxed);
+        }
+    }
+    /// Bytes written and in total of the running save, if one runs.
+    pub fn current(&self) -> Option<(u64, u64)> {
+        self.started.lock().unwrap().map(|_| {
+            (
+                self.written.load(Ordering::Relaxed),
+                self.total.load(Ordering::Relaxed),
+            )
+        })
+    }
+    /// Seconds the running save takes so far, or -1 if none runs.
+    pub fn current_duration_secs(&self) -> i64 {
+        self.started
+            .lock()
+            .unwrap()
+            .map_or(-1, |started| started.elapsed().as_secs() as i64)
+    }
+    pub fn last_duration_secs(&self) -> i64 {
+        self.last_duration_secs.load(Ordering::Relaxed)
+    }
+}
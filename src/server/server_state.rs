@@ -0,0 +1,602 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::mpsc;
+
+use crate::acl::AclState;
+use crate::aof::{rewrite_commands, AofWriter};
+use crate::cluster::ClusterState;
+use crate::command::{CustomCommand, RedisError};
+use crate::config::{find_config_entry, Config, ShutdownMode, CONFIG_ENTRIES};
+use crate::db::{Database, DatabaseSlot};
+use crate::rdb::{dump_database, write_rdb_file, RdbReader};
+use crate::replication::ReplicationState;
+use crate::resp::RespValue;
+use crate::server::{
+    BlockingTimers, BufferPool, ClientKind, ClientRegistry, ConnectionContext, LatencyMonitor,
+    RateLimiter, SaveProgress, ServerStats, SystemdNotifier,
+};
+
+const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Task started by a command which runs until the server shuts down.
+pub type BackgroundTask = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Parameters which changed when the config file was reloaded.
+#[derive(Debug, Default)]
+pub struct ConfigReload {
+    pub applied: Vec<&'static str>,
+    /// Immutable parameters, which keep their value until the server is restarted.
+    pub ignored: Vec<&'static str>,
+}
+
+/// State shared between all connections.
+pub struct ServerState {
+    /// Changed at runtime through CONFIG SET, see [`ServerState::config_set`].
+    config: RwLock<Config>,
+    pub db: Mutex<Database>,
+    /// Unix time in seconds of the last successful save.
+    pub rdb_last_save_time: AtomicU64,
+    pub rdb_bgsave_in_progress: AtomicBool,
+    /// Whether the last save succeeded, which write commands are refused on if not.
+    pub rdb_last_bgsave_ok: AtomicBool,
+    pub rdb_bgsave_progress: SaveProgress,
+    pub aof: Option<AofWriter>,
+    pub aof_rewrite_in_progress: AtomicBool,
+    pub aof_last_bgrewrite_ok: AtomicBool,
+    pub aof_rewrite_progress: SaveProgress,
+    /// Whether expired keys are deleted periodically, see DEBUG SET-ACTIVE-EXPIRE.
+    pub active_expire_enabled: AtomicBool,
+    pub replication: ReplicationState,
+    /// Set if the server runs in cluster mode.
+    pub cluster: Option<ClusterState>,
+    pub acl: AclState,
+    pub stats: ServerStats,
+    pub clients: ClientRegistry,
+    /// Read and reply buffers for reuse by new connections.
+    pub buffers: BufferPool,
+    pub rate_limiter: RateLimiter,
+    pub latency: Arc<LatencyMonitor>,
+    /// Timeouts of clients blocked by e.g. WAIT.
+    pub blocking_timers: Arc<BlockingTimers>,
+    /// Set if the server runs as a systemd unit, see `supervised`.
+    pub supervisor: Option<SystemdNotifier>,
+    /// Commands registered through [`CommandHandler`](crate::command::CommandHandler),
+    /// by their lowercase name.
+    pub custom_commands: BTreeMap<String, Arc<CustomCommand>>,
+    /// Tasks passed to [`ServerState::spawn`], which the server runs alongside its own.
+    background_tasks: mpsc::UnboundedSender<BackgroundTask>,
+    background_tasks_received: Mutex<Option<mpsc::UnboundedReceiver<BackgroundTask>>>,
+}
+
+impl ServerState {
+    pub fn new(config: Config, db: Database) -> anyhow::Result<Self> {
+        let latency = Arc::new(LatencyMonitor::new(config.latency_monitor_threshold));
+        let aof = if config.appendonly {
+            Some(AofWriter::open(&config, latency.clone())?)
+        } else {
+            None
+        };
+
+        let replication = ReplicationState::new(config.replicaof.clone(), config.repl_backlog_size);
+        replication.set_output_buffer_limit(config.replica_output_buffer_limit);
+        let cluster = config
+            .cluster_enabled
+            .then(|| ClusterState::new(config.port, config.cluster_node_timeout));
+
+        let acl = AclState::new(&config.requirepass);
+        let supervisor = SystemdNotifier::from_env(config.supervised);
+        let (background_tasks, background_tasks_received) = mpsc::unbounded_channel();
+
+        Ok(Self {
+            config: RwLock::new(config),
+            db: Mutex::new(db),
+            rdb_last_save_time: AtomicU64::new(unix_time_secs()),
+            rdb_bgsave_in_progress: AtomicBool::new(false),
+            rdb_last_bgsave_ok: AtomicBool::new(true),
+            rdb_bgsave_progress: SaveProgress::default(),
+            aof,
+            aof_rewrite_in_progress: AtomicBool::new(false),
+            aof_last_bgrewrite_ok: AtomicBool::new(true),
+            aof_rewrite_progress: SaveProgress::default(),
+            active_expire_enabled: AtomicBool::new(true),
+            replication,
+            cluster,
+            acl,
+            stats: ServerStats::default(),
+            clients: ClientRegistry::default(),
+            buffers: BufferPool::default(),
+            rate_limiter: RateLimiter::default(),
+            latency,
+            blocking_timers: Arc::default(),
+            supervisor,
+            custom_commands: BTreeMap::new(),
+            background_tasks,
+            background_tasks_received: Mutex::new(Some(background_tasks_received)),
+        })
+    }
+    /// Runs `task` on the tasks of the server, so that it is aborted once the server
+    /// shuts down.
+    pub fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        // NOTE: Once the server shut down, the task is dropped without running.
+        let _ = self.background_tasks.send(Box::pin(task));
+    }
+    /// Receiver of the tasks passed to [`ServerState::spawn`], which only the first
+    /// caller gets.
+    pub fn take_background_tasks(&self) -> Option<mpsc::UnboundedReceiver<BackgroundTask>> {
+        self.background_tasks_received.lock().unwrap().take()
+    }
+    /// Custom command called `name`, in any case.
+    pub fn find_custom_command(&self, name: &str) -> Option<&Arc<CustomCommand>> {
+        // NOTE: Every request is looked up, so nothing is allocated unless there are any.
+        if self.custom_commands.is_empty() {
+            return None;
+        }
+        self.custom_commands.get(&name.to_ascii_lowercase())
+    }
+    /// Reports the status of the server to systemd, if it is supervised by it.
+    pub fn notify_supervisor(&self, state: &str) {
+        if let Some(supervisor) = &self.supervisor {
+            if let Err(e) = supervisor.notify(state) {
+                eprintln!("Failed to notify systemd: {e}");
+            }
+        }
+    }
+    /// Current configuration, which must not be held while acquiring other locks
+    /// that CONFIG SET takes, e.g. the AOF.
+    pub fn config(&self) -> RwLockReadGuard<'_, Config> {
+        self.config.read().unwrap()
+    }
+    /// Sets every parameter of `params` to its value, or none if any is unknown,
+    /// immutable or given an invalid value.
+    pub fn config_set(&self, params: &[(String, String)]) -> Result<(), RedisError> {
+        let mut entries = Vec::with_capacity(params.len());
+        {
+            let mut config = self.config.write().unwrap();
+            let mut updated = config.clone();
+            for (name, value) in params {
+                let Some(entry) = find_config_entry(name) else {
+                    return Err(RedisError::Err(format!(
+                        "Unknown option or number of arguments for CONFIG SET - '{name}'"
+                    )));
+                };
+                let failed = |e: &str| {
+                    RedisError::Err(format!(
+                        "CONFIG SET failed (possibly related to argument '{name}') - {e}"
+                    ))
+                };
+                if !entry.mutable {
+                    return Err(failed("can't set immutable config"));
+                }
+                (entry.set)(&mut updated, value).map_err(|e| failed(&e))?;
+                entries.push(entry.name);
+            }
+            *config = updated;
+        }
+
+        // NOTE: Applied after releasing the config, since writers read it while
+        //       holding the AOF lock.
+        let config = self.config().clone();
+        for name in entries {
+            match name {
+                "appendfsync" => {
+                    if let Some(aof) = &self.aof {
+                        aof.lock().set_fsync(config.appendfsync);
+                    }
+                }
+                "repl-backlog-size" => self.replication.resize_backlog(config.repl_backlog_size),
+                "replica-output-buffer-limit" => self
+                    .replication
+                    .set_output_buffer_limit(config.replica_output_buffer_limit),
+                "requirepass" => self.acl.set_default_password(&config.requirepass),
+                // NOTE: Buckets of the previous kind of key would never be used again.
+                "rate-limit-by" => self.rate_limiter.clear(),
+                "latency-monitor-threshold" => {
+                    self.latency.set_threshold(config.latency_monitor_threshold)
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    /// Reads the config file again, applying the command line arguments on top, and
+    /// sets every mutable parameter whose value changed.
+    pub fn reload_config(&self) -> anyhow::Result<ConfigReload> {
+        let current = self.config().clone();
+        if current.config_file.is_none() {
+            return Err(anyhow::anyhow!(
+                "The server was started without a config file"
+            ));
+        }
+        let reloaded = Config::from_args(current.args.clone())?;
+
+        let mut reload = ConfigReload::default();
+        let mut params = Vec::new();
+        for entry in CONFIG_ENTRIES {
+            let value = (entry.get)(&reloaded);
+            if value == (entry.get)(&current) {
+                continue;
+            }
+            if entry.mutable {
+                reload.applied.push(entry.name);
+                params.push((entry.name.to_string(), value));
+            } else {
+                reload.ignored.push(entry.name);
+            }
+        }
+        self.config_set(&params).map_err(anyhow::Error::msg)?;
+        Ok(reload)
+    }
+    /// Whether the connection has to authenticate before running commands.
+    pub fn requires_auth(&self, ctx: &ConnectionContext) -> bool {
+        ctx.kind == ClientKind::Normal && self.acl.requires_auth(ctx.user.as_deref())
+    }
+    /// Synchronously writes the Database to the configured RDB file.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let bytes = self.latency.time("fork", || {
+            let db = self.db.lock().unwrap();
+            dump_database(&db)
+        })?;
+        self.write_snapshot(&bytes)
+    }
+    /// Writes the serialized Database to the RDB file and records the successful save.
+    fn write_snapshot(&self, bytes: &[u8]) -> anyhow::Result<()> {
+        write_rdb_file(&self.config().rdb_path(), bytes, |_| {})?;
+        self.rdb_last_save_time
+            .store(unix_time_secs(), Ordering::Relaxed);
+        self.rdb_last_bgsave_ok.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+    /// Replaces the dataset with the one read back from the RDB file, after saving a
+    /// snapshot to it if `save` is set.
+    ///
+    /// The Database stays locked throughout, since a write accepted in between would be
+    /// lost in memory while it is already in the AOF and on the replicas.
+    pub fn reload_rdb(&self, save: bool) -> anyhow::Result<()> {
+        let mut db = self.db.lock().unwrap();
+        if save {
+            let bytes = self.latency.time("fork", || dump_database(&db))?;
+            self.write_snapshot(&bytes)?;
+        }
+        *db = load_database(&self.config().rdb_path())?;
+        Ok(())
+    }
+    /// Prepares the server to exit because of `signal`, which means syncing the AOF
+    /// and, depending on `shutdown-on-sigterm` or `shutdown-on-sigint`, saving a final
+    /// snapshot.
+    pub fn prepare_shutdown(&self, signal: ShutdownSignal) -> anyhow::Result<()> {
+        self.notify_supervisor("STOPPING=1");
+        let save = {
+            let config = self.config();
+            let mode = match signal {
+                ShutdownSignal::Terminate => config.shutdown_on_sigterm,
+                ShutdownSignal::Interrupt => config.shutdown_on_sigint,
+            };
+            match mode {
+                ShutdownMode::Default => !config.save.is_empty(),
+                ShutdownMode::Save => true,
+                ShutdownMode::NoSave => false,
+            }
+        };
+        if let Some(aof) = &self.aof {
+            aof.lock().sync()?;
+        }
+        if save {
+            println!("Saving the final RDB snapshot before exiting");
+            self.save()?;
+        }
+        Ok(())
+    }
+    /// Serializes the Database and writes it to the RDB file on a background task.
+    ///
+    /// Returns `false` if another background save is still running.
+    pub fn bgsave(self: &Arc<Self>) -> anyhow::Result<bool> {
+        if self.rdb_bgsave_in_progress.swap(true, Ordering::AcqRel) {
+            return Ok(false);
+        }
+
+        // NOTE: Serializing while holding the lock is the snapshot, only the slow disk
+        //       write happens in the background. It is recorded as 'fork', the latency
+        //       event Redis uses for the part of a snapshot blocking the server.
+        let bytes = self.latency.time("fork", || {
+            let db = self.db.lock().unwrap();
+            dump_database(&db)
+        });
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.rdb_bgsave_in_progress.store(false, Ordering::Release);
+                return Err(e.into());
+            }
+        };
+
+        self.rdb_bgsave_progress.start(bytes.len() as u64);
+        let state = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let progress = &state.rdb_bgsave_progress;
+            let rdb_path = state.config().rdb_path();
+            let result = write_rdb_file(&rdb_path, &bytes, |written| progress.advance(written));
+            progress.finish();
+            match &result {
+                Ok(()) => state
+                    .rdb_last_save_time
+                    .store(unix_time_secs(), Ordering::Relaxed),
+                Err(e) => eprintln!("Background saving error: {e}"),
+            }
+            state
+                .rdb_last_bgsave_ok
+                .store(result.is_ok(), Ordering::Relaxed);
+            state.rdb_bgsave_in_progress.store(false, Ordering::Release);
+        });
+
+        Ok(true)
+    }
+}
+
+impl ServerState {
+    /// Rewrites the AOF from a snapshot of the Database on a background task.
+    ///
+    /// With 'aof-use-rdb-preamble' the new base file is an RDB file, otherwise it
+    /// contains the commands recreating the Database.
+    ///
+    /// Returns `false` if another rewrite is still running.
+    pub fn bgrewriteaof(self: &Arc<Self>) -> anyhow::Result<bool> {
+        if self.aof_rewrite_in_progress.swap(true, Ordering::AcqRel) {
+            return Ok(false);
+        }
+
+        let rdb_preamble = self.config().aof_use_rdb_preamble;
+        let base = match self.snapshot_aof_base(rdb_preamble) {
+            Ok(base) => base,
+            Err(e) => {
+                self.aof_rewrite_in_progress.store(false, Ordering::Release);
+                return Err(e);
+            }
+        };
+
+        self.aof_rewrite_progress.start(base.len() as u64);
+        let state = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let progress = |written| state.aof_rewrite_progress.advance(written);
+            let result = match &state.aof {
+                Some(aof) => aof
+                    .finish_rewrite(&base, rdb_preamble, progress)
+                    .map_err(Into::into),
+                // NOTE: Without AOF the rewrite creates a fresh AOF holding just the base.
+                None => AofWriter::open(&state.config().clone(), state.latency.clone())
+                    .map_err(anyhow::Error::from)
+                    .and_then(|aof| {
+                        aof.lock().start_rewrite()?;
+                        Ok(aof.finish_rewrite(&base, rdb_preamble, progress)?)
+                    }),
+            };
+            state.aof_rewrite_progress.finish();
+            if let Err(e) = &result {
+                eprintln!("Background AOF rewrite error: {e}");
+            }
+            state
+                .aof_last_bgrewrite_ok
+                .store(result.is_ok(), Ordering::Relaxed);
+            state
+                .aof_rewrite_in_progress
+                .store(false, Ordering::Release);
+        });
+
+        Ok(true)
+    }
+    fn snapshot_aof_base(&self, rdb_preamble: bool) -> anyhow::Result<Vec<u8>> {
+        // NOTE: Holding the AOF lock while switching to a new incremental file and taking
+        //       the snapshot guarantees that every write is either part of the snapshot
+        //       or of the new file, and never both.
+        let mut aof = self.aof.as_ref().map(AofWriter::lock);
+        if let Some(aof) = &mut aof {
+            aof.start_rewrite()?;
+        }
+        self.latency.time("fork", || {
+            let db = self.db.lock().unwrap();
+            if rdb_preamble {
+                Ok(dump_database(&db)?)
+            } else {
+                Ok(rewrite_commands(&db)?)
+            }
+        })
+    }
+    /// Whether write commands have to be refused because the last save failed and
+    /// 'stop-writes-on-bgsave-error' is enabled.
+    pub fn writes_stopped_by_bgsave_error(&self) -> bool {
+        self.config().stop_writes_on_bgsave_error
+            && !self.rdb_last_bgsave_ok.load(Ordering::Relaxed)
+    }
+    /// Error of the last write to the AOF, unless a later attempt succeeded, while
+    /// which write commands of clients are refused.
+    pub fn aof_write_error(&self) -> Option<String> {
+        self.aof.as_ref()?.lock().last_write_error.clone()
+    }
+    /// Starts a rewrite once the AOF has grown by 'auto-aof-rewrite-percentage' since
+    /// the last rewrite and is larger than 'auto-aof-rewrite-min-size'.
+    pub fn rewrite_aof_if_grown(self: &Arc<Self>) {
+        let percentage = self.config().auto_aof_rewrite_percentage;
+        let Some(aof) = &self.aof else {
+            return;
+        };
+        if percentage == 0 || self.aof_rewrite_in_progress.load(Ordering::Acquire) {
+            return;
+        }
+
+        let (base_size, current_size) = {
+            let file = aof.lock();
+            (file.base_size.max(1), file.current_size)
+        };
+        let growth = current_size.saturating_sub(base_size) * 100 / base_size;
+        if current_size >= self.config().auto_aof_rewrite_min_size && growth >= percentage {
+            println!("Starting automatic rewriting of AOF on {growth}% growth");
+            if let Err(e) = self.bgrewriteaof() {
+                eprintln!("Automatic AOF rewrite error: {e}");
+            }
+        }
+    }
+    /// Deletes expired keys and propagates a DEL for each of them, so that replicas
+    /// and the AOF see the same deletions. Replicas leave expiry to their master.
+    pub fn expire_keys(&self) -> usize {
+        if self.replication.is_replica() {
+            return 0;
+        }
+        let expired = self.latency.time("expire-cycle", || {
+            self.delete_and_propagate(|db| db.remove_expired(Instant::now()))
+        });
+        self.stats
+            .expired_keys
+            .fetch_add(expired as u64, Ordering::Relaxed);
+        expired
+    }
+    /// Deletes those of `keys` whose slot satisfies `condition` outside of a DEL
+    /// command, e.g. after MIGRATE moved them, and propagates a DEL for each deleted
+    /// key. Returns the number of deleted keys.
+    pub fn delete_keys_if<'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k str>,
+        condition: impl Fn(&str, &DatabaseSlot) -> bool,
+    ) -> usize {
+        self.delete_and_propagate(|db| {
+            keys.into_iter()
+                .filter(|key| {
+                    db.get(key).is_some_and(|slot| condition(key, slot)) && db.remove(key).is_some()
+                })
+                .map(String::from)
+                .collect()
+        })
+    }
+    /// Runs `delete`, which returns the keys it deleted, and propagates their deletion.
+    fn delete_and_propagate(&self, delete: impl FnOnce(&mut Database) -> Vec<String>) -> usize {
+        // NOTE: Same lock order as write commands, see 'dispatch'.
+        let mut aof = self.aof.as_ref().map(|aof| aof.lock());
+        let replicas = (!self.replication.is_replica()).then(|| self.replication.lock_replicas());
+        let deleted = delete(&mut self.db.lock().unwrap());
+
+        for key in &deleted {
+            let frame = RespValue::Array(vec![
+                RespValue::BulkString("DEL".into()),
+                RespValue::BulkString(key.as_str().into()),
+            ])
+            .to_string();
+            if let Some(aof) = &mut aof {
+                if let Err(e) = aof.append(frame.as_bytes()) {
+                    eprintln!("Error writing to the AOF: {e}");
+                }
+            }
+            if let Some(replicas) = &replicas {
+                self.replication.propagate(replicas, frame.as_bytes());
+            }
+        }
+
+        deleted.len()
+    }
+}
+
+/// Periodically deletes expired keys, see [`ServerState::expire_keys`].
+pub async fn run_active_expire(state: Arc<ServerState>) {
+    let mut interval = tokio::time::interval(ACTIVE_EXPIRE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if state.active_expire_enabled.load(Ordering::Relaxed) {
+            state.expire_keys();
+        }
+    }
+}
+
+/// Signal asking the server to exit.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ShutdownSignal {
+    /// SIGTERM, e.g. sent by a service manager.
+    Terminate,
+    /// SIGINT, e.g. sent by Ctrl-C.
+    Interrupt,
+}
+
+/// Waits for SIGTERM or SIGINT.
+#[cfg(unix)]
+pub async fn shutdown_signal() -> std::io::Result<ShutdownSignal> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    tokio::select! {
+        _ = terminate.recv() => Ok(ShutdownSignal::Terminate),
+        _ = interrupt.recv() => Ok(ShutdownSignal::Interrupt),
+    }
+}
+
+/// Waits for Ctrl-C, the only shutdown signal outside of Unix.
+#[cfg(not(unix))]
+pub async fn shutdown_signal() -> std::io::Result<ShutdownSignal> {
+    tokio::signal::ctrl_c().await?;
+    Ok(ShutdownSignal::Interrupt)
+}
+
+/// Reloads the config file whenever the process receives SIGHUP.
+#[cfg(unix)]
+pub async fn reload_config_on_sighup(state: Arc<ServerState>) -> anyhow::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    while hangup.recv().await.is_some() {
+        match state.reload_config() {
+            Ok(reload) => {
+                println!("Reloaded the config file, applied {:?}", reload.applied);
+                if !reload.ignored.is_empty() {
+                    println!("Ignored {:?}, which require a restart", reload.ignored);
+                }
+            }
+            Err(e) => eprintln!("Failed to reload the config file: {e}"),
+        }
+    }
+    Ok(())
+}
+
+/// Logs a [`diagnostics_report`](super::diagnostics_report) whenever the process
+/// receives SIGUSR1, and starts a background save afterwards if `sigusr1-bgsave` is set.
+#[cfg(unix)]
+pub async fn dump_diagnostics_on_sigusr1(state: Arc<ServerState>) -> anyhow::Result<()> {
+    use super::diagnostics_report;
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut user_defined = signal(SignalKind::user_defined1())?;
+    while user_defined.recv().await.is_some() {
+        println!(
+            "Received SIGUSR1, diagnostics report:\n{}",
+            diagnostics_report(&state)
+        );
+        if state.config().sigusr1_bgsave {
+            match state.bgsave() {
+                Ok(true) => println!("Background saving started"),
+                Ok(false) => println!("Background save already in progress"),
+                Err(e) => eprintln!("Failed to start a background save: {e}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the dataset from the RDB file at `path`, which is empty if there is none.
+pub fn load_database(path: &Path) -> anyhow::Result<Database> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Database::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut rdb = RdbReader::new(&bytes).read()?;
+
+    // NOTE: Only a single logical database is supported, so everything but 'db0' is dropped.
+    Ok(rdb.databases.remove(&0).unwrap_or_default())
+}
+
+pub fn unix_time_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::server::memory::process_rss;
+use crate::server::LatencyHistogram;
+use crate::util::random_hex_id;
+
+/// Name and subcommand of a command, see [`Command::name`](crate::command::Command::name).
+pub type CommandName = (&'static str, Option<&'static str>);
+
+/// Counters of a single command.
+#[derive(Debug, Default, Clone)]
+pub struct CommandStats {
+    pub calls: u64,
+    /// Total time spent executing the command.
+    pub usec: u64,
+    /// Calls refused before running, e.g. for invalid arguments or missing permissions.
+    pub rejected_calls: u64,
+    /// Calls which ran but replied with an error.
+    pub failed_calls: u64,
+    pub histogram: LatencyHistogram,
+}
+
+/// Counters reported by INFO.
+#[derive(Debug)]
+pub struct ServerStats {
+    pub start_time: Instant,
+    /// Random ID of this run of the server, which changes on every restart.
+    pub run_id: String,
+    pub connected_clients: AtomicUsize,
+    /// Clients waiting for a deferred reply, e.g. of WAIT.
+    pub blocked_clients: AtomicUsize,
+    pub total_connections_received: AtomicU64,
+    pub total_commands_processed: AtomicU64,
+    /// Keys deleted by the active expire cycle.
+    pub expired_keys: AtomicU64,
+    /// Lookups of keys by read commands which found the key, or not.
+    pub keyspace_hits: AtomicU64,
+    pub keyspace_misses: AtomicU64,
+    commands: Mutex<BTreeMap<CommandName, CommandStats>>,
+    /// Number of error replies by their prefix, e.g. "ERR" or "NOPERM".
+    errors: Mutex<BTreeMap<String, u64>>,
+    pub total_error_replies: AtomicU64,
+    /// Resident set size of the process when the server started.
+    pub startup_rss: u64,
+    /// Highest memory usage measured so far, see [`memory_stats`](super::memory_stats).
+    pub peak_allocated: AtomicU64,
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        Self {
+            start_time: Instant::now(),
+            run_id: random_hex_id(40),
+            connected_clients: AtomicUsize::new(0),
+            blocked_clients: AtomicUsize::new(0),
+            total_connections_received: AtomicU64::new(0),
+            total_commands_processed: AtomicU64::new(0),
+            expired_keys: AtomicU64::new(0),
+            keyspace_hits: AtomicU64::new(0),
+            keyspace_misses: AtomicU64::new(0),
+            commands: Mutex::new(BTreeMap::new()),
+            errors: Mutex::new(BTreeMap::new()),
+            total_error_replies: AtomicU64::new(0),
+            startup_rss: process_rss().unwrap_or(0),
+            peak_allocated: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ServerStats {
+    /// Counters of every command which ran at least once, sorted by name.
+    pub fn lock_commands(&self) -> MutexGuard<'_, BTreeMap<CommandName, CommandStats>> {
+        self.commands.lock().unwrap()
+    }
+    /// Counts a call of the command which ran for `duration`, and whether it failed.
+    pub fn record_call(&self, name: CommandName, duration: Duration, failed: bool) {
+        let mut commands = self.lock_commands();
+        let command = commands.entry(name).or_default();
+        command.calls += 1;
+        command.usec += u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        command.failed_calls += u64::from(failed);
+        command.histogram.record(duration);
+    }
+    /// Counts a call of the command refused before it ran.
+    pub fn record_rejected_call(&self, name: CommandName) {
+        self.lock_commands().entry(name).or_default().rejected_calls += 1;
+    }
+    /// Number of error replies by their prefix, sorted by it.
+    pub fn lock_errors(&self) -> MutexGuard<'_, BTreeMap<String, u64>> {
+        self.errors.lock().unwrap()
+    }
+    /// Counts an error reply by its prefix, the first word of `message`.
+    pub fn record_error(&self, message: &str) {
+        let prefix = message.split(' ').next().unwrap_or_default();
+        *self.lock_errors().entry(prefix.to_string()).or_default() += 1;
+        self.total_error_replies.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Counts a lookup of a key by a read command.
+    pub fn record_lookup(&self, hit: bool) {
+        let counter = if hit {
+            &self.keyspace_hits
+        } else {
+            &self.keyspace_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
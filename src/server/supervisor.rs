@@ -0,0 +1,67 @@
+use std::ffi::OsString;
+use std::io;
+
+use crate::config::Supervised;
+
+/// Reports the status of the server to systemd through the socket passed in
+/// `NOTIFY_SOCKET`, like `sd_notify(3)`.
+#[derive(Debug)]
+pub struct SystemdNotifier {
+    /// Path of the socket, or its name in the abstract namespace if it starts with '@'.
+    socket: OsString,
+}
+
+impl SystemdNotifier {
+    pub fn new(socket: impl Into<OsString>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+    /// Notifier of the systemd unit running the server, if it is to be notified
+    /// according to `supervised`.
+    pub fn from_env(supervised: Supervised) -> Option<Self> {
+        let socket = std::env::var_os("NOTIFY_SOCKET").filter(|socket| !socket.is_empty());
+        match (supervised, socket) {
+            (Supervised::No, _) => None,
+            (Supervised::Systemd, None) => {
+                eprintln!("systemd supervision requested, but NOTIFY_SOCKET not found");
+                None
+            }
+            (_, socket) => socket.map(Self::new),
+        }
+    }
+    /// Sends newline separated variable assignments, e.g. "READY=1".
+    #[cfg(unix)]
+    pub fn notify(&self, state: &str) -> io::Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::net::UnixDatagram;
+
+        let socket = UnixDatagram::unbound()?;
+        match self.socket.as_bytes().strip_prefix(b"@") {
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                socket.send_to_addr(state.as_bytes(), &addr)?;
+            }
+            #[cfg(not(target_os = "linux"))]
+            Some(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "abstract sockets are only supported on Linux",
+                ))
+            }
+            None => {
+                socket.send_to(state.as_bytes(), &self.socket)?;
+            }
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    pub fn notify(&self, _state: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "systemd notifications are only supported on Unix",
+        ))
+    }
+}
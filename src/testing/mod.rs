@@ -0,0 +1,3 @@
+mod test_client;
+
+pub use test_client::TestClient;
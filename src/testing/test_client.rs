@@ -0,0 +1,77 @@
+use std::net::SocketAddr;
+
+use anyhow::anyhow;
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::resp::{is_incomplete, parse_resp_value, RespValue};
+use crate::server::RedisServerHandle;
+
+/// Minimal client for end-to-end tests, which sends one command at a time and waits
+/// for its reply.
+pub struct TestClient {
+    /// Halves of the stream, boxed so that TCP and in-memory streams work alike.
+    reader: Box<dyn AsyncRead + Unpin + Send>,
+    writer: Box<dyn AsyncWrite + Unpin + Send>,
+    /// Bytes received but not yet parsed into a reply.
+    buffer: BytesMut,
+}
+
+impl TestClient {
+    /// Connects over TCP, e.g. to [`RedisServerHandle::addr`].
+    pub async fn connect(addr: SocketAddr) -> std::io::Result<Self> {
+        Ok(Self::new(TcpStream::connect(addr).await?))
+    }
+    /// Connects through an in-memory stream, which skips the network stack and
+    /// therefore can't fail or be slowed down by it.
+    pub fn in_memory(server: &RedisServerHandle) -> Self {
+        Self::new(server.connect_in_memory())
+    }
+    pub fn new(stream: impl AsyncRead + AsyncWrite + Send + 'static) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            buffer: BytesMut::new(),
+        }
+    }
+    /// Sends the command, split into arguments at whitespace like redis-cli does, and
+    /// returns its reply.
+    pub async fn send(&mut self, command: &str) -> anyhow::Result<RespValue<'static>> {
+        let args: Vec<_> = command.split_whitespace().collect();
+        self.send_args(&args).await
+    }
+    /// Sends the command with exactly the given arguments and returns its reply.
+    pub async fn send_args(&mut self, args: &[&str]) -> anyhow::Result<RespValue<'static>> {
+        let args = args
+            .iter()
+            .map(|arg| RespValue::BulkString((*arg).into()))
+            .collect();
+        let frame = RespValue::Array(args).to_string();
+        self.writer.write_all(frame.as_bytes()).await?;
+        self.read_reply().await
+    }
+    /// Waits for the next reply, e.g. of a command sent with [`send_raw`](Self::send_raw).
+    pub async fn read_reply(&mut self) -> anyhow::Result<RespValue<'static>> {
+        loop {
+            match parse_resp_value(&self.buffer) {
+                Ok((rest, value)) => {
+                    let value = value.into_owned();
+                    let consumed = self.buffer.len() - rest.len();
+                    self.buffer.advance(consumed);
+                    return Ok(value);
+                }
+                Err(e) if is_incomplete(&e) => {}
+                Err(e) => return Err(anyhow!("Invalid reply: {e}")),
+            }
+            if self.reader.read_buf(&mut self.buffer).await? == 0 {
+                return Err(anyhow!("Connection closed by the server"));
+            }
+        }
+    }
+    /// Sends the bytes as they are, e.g. to pipeline several commands.
+    pub async fn send_raw(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(bytes).await
+    }
+}
@@ -64,7 +64,7 @@ where
             buffer: BytesMut::new(),
         }
     }
-    pub async fn checkpoint(&mut self) -> Checkpoint<T> {
+    pub async fn checkpoint(&mut self) -> Checkpoint<'_, T> {
         Checkpoint::new(self)
     }
     async fn fill_buf(&mut self) -> bool {
@@ -0,0 +1,31 @@
+/// CCITT polynomial of the XMODEM variant used by Redis Cluster.
+const POLY: u16 = 0x1021;
+
+const TABLE: [u16; 256] = build_table();
+
+const fn build_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ POLY
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// CRC16 checksum of `data`, same as `crc16()` in Redis.
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        TABLE[(((crc >> 8) ^ u16::from(byte)) & 0xff) as usize] ^ (crc << 8)
+    })
+}
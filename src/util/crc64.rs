@@ -0,0 +1,73 @@
+use std::io::Write;
+
+/// Reflected form of the Jones polynomial `0xad93d23594c935a9` used by Redis.
+const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+
+const TABLE: [u64; 256] = build_table();
+
+const fn build_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Continues the CRC64 checksum `crc` over `data`, same as `crc64()` in Redis.
+///
+/// Start with a `crc` of 0 to checksum a complete buffer.
+pub fn crc64(crc: u64, data: &[u8]) -> u64 {
+    data.iter().fold(crc, |crc, &byte| {
+        TABLE[((crc ^ u64::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Writer that keeps a running CRC64 checksum of everything written through it.
+pub struct Crc64Writer<W>
+where
+    W: Write,
+{
+    writer: W,
+    crc: u64,
+}
+
+impl<W> Crc64Writer<W>
+where
+    W: Write,
+{
+    pub fn new(writer: W) -> Self {
+        Self { writer, crc: 0 }
+    }
+    pub fn checksum(&self) -> u64 {
+        self.crc
+    }
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W> Write for Crc64Writer<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.crc = crc64(self.crc, &buf[..written]);
+        Ok(written)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,122 @@
+use crate::util::random_hex_id;
+
+/// Matches `s` against a glob-style `pattern` the same way Redis does for KEYS and
+/// CONFIG GET.
+///
+/// Supports `*`, `?`, character classes like `[a-z]` or `[^abc]`, and `\` to escape
+/// the next character.
+pub fn glob_match(pattern: &[u8], s: &[u8], nocase: bool) -> bool {
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+
+    let (mut p, mut i) = (0, 0);
+    // NOTE: Position after the last '*' and the input it matched up to, which is
+    //       where matching resumes with one more character consumed by the '*'.
+    let mut backtrack: Option<(usize, usize)> = None;
+    loop {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    backtrack = Some((p + 1, i));
+                    p += 1;
+                    continue;
+                }
+                b'?' if i < s.len() => {
+                    p += 1;
+                    i += 1;
+                    continue;
+                }
+                b'[' if i < s.len() => {
+                    if let Some(end) = match_class(&pattern[p + 1..], s[i], nocase) {
+                        p += 1 + end;
+                        i += 1;
+                        continue;
+                    }
+                }
+                b'\\' if p + 1 < pattern.len() && i < s.len() && eq(pattern[p + 1], s[i]) => {
+                    p += 2;
+                    i += 1;
+                    continue;
+                }
+                // NOTE: A trailing backslash matches itself.
+                c if (c != b'\\' || p + 1 == pattern.len()) && i < s.len() && eq(c, s[i]) => {
+                    p += 1;
+                    i += 1;
+                    continue;
+                }
+                _ => {}
+            }
+        } else if i == s.len() {
+            return true;
+        }
+
+        match backtrack {
+            Some((star, matched)) if matched < s.len() => {
+                backtrack = Some((star, matched + 1));
+                p = star;
+                i = matched + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Matches `c` against the character class starting after its `[`, returning the
+/// length of the class including the closing `]` if it matches.
+fn match_class(class: &[u8], c: u8, nocase: bool) -> Option<usize> {
+    let fold = |c: u8| if nocase { c.to_ascii_lowercase() } else { c };
+    let c = fold(c);
+    let negate = class.first() == Some(&b'^');
+    let mut j = usize::from(negate);
+    let mut matched = false;
+
+    while j < class.len() && class[j] != b']' {
+        if class[j] == b'\\' && j + 1 < class.len() {
+            matched |= fold(class[j + 1]) == c;
+            j += 2;
+        } else if j + 2 < class.len() && class[j + 1] == b'-' && class[j + 2] != b']' {
+            let (start, end) = (fold(class[j]), fold(class[j + 2]));
+            let (start, end) = (start.min(end), start.max(end));
+            matched |= (start..=end).contains(&c);
+            j += 3;
+        } else {
+            matched |= fold(class[j]) == c;
+            j += 1;
+        }
+    }
+
+    // NOTE: An unterminated class extends to the end of the pattern, as in Redis.
+    let len = (j + 1).min(class.len());
+    (matched != negate).then_some(len)
+}
+
+/// Matches random patterns against random strings, which must neither panic nor hang,
+/// as DEBUG STRINGMATCH-LEN does in Redis.
+pub fn glob_match_fuzz(iterations: usize) {
+    const ALPHABET: &[u8] = b"ab*?[]^-\\";
+
+    // NOTE: A xorshift generator is plenty for picking characters.
+    let mut seed = u64::from_str_radix(&random_hex_id(16), 16).unwrap_or(1) | 1;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    for _ in 0..iterations {
+        let mut random_bytes = |max_len: u64| -> Vec<u8> {
+            let len = next() % max_len;
+            (0..len)
+                .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                .collect()
+        };
+        let pattern = random_bytes(32);
+        let s = random_bytes(32);
+        glob_match(&pattern, &s, next() % 2 == 0);
+    }
+}
@@ -0,0 +1,15 @@
+/// Lowercase hex encoding of `bytes`.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decodes a hex string, or returns [`None`] if it is not valid hex.
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    let digits = s.as_bytes().chunks_exact(2);
+    if !digits.remainder().is_empty() || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    digits
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,13 @@
+mod crc16;
+mod crc64;
+mod glob;
+mod hex;
+mod random;
+mod sha256;
+
+pub use crc16::crc16;
+pub use crc64::{crc64, Crc64Writer};
+pub use glob::{glob_match, glob_match_fuzz};
+pub use hex::{from_hex, to_hex};
+pub use random::{random_f32, random_hex_id};
+pub use sha256::sha256;
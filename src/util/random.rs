@@ -0,0 +1,30 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Random hex string of `len` characters, as used for replication and node IDs.
+///
+/// NOTE: Seeded from the standard library's per-process hash keys, which is enough
+///       for unique IDs but must not be used for anything security related.
+pub fn random_hex_id(len: usize) -> String {
+    let mut id = String::with_capacity(len + 16);
+    while id.len() < len {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos()),
+        );
+        hasher.write_usize(id.len());
+        id.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    id.truncate(len);
+    id
+}
+
+/// Random number in `0.0..1.0`, with the same caveats as [`random_hex_id`].
+pub fn random_f32() -> f32 {
+    let hasher = RandomState::new().build_hasher();
+    // NOTE: The 24 bits of the mantissa are spread evenly over the range.
+    (hasher.finish() >> 40) as f32 / (1 << 24) as f32
+}